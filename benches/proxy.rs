@@ -0,0 +1,84 @@
+use std::time::Duration;
+
+use axum::{body::Body, http::Request, Router};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use http_body_util::BodyExt;
+use serde_json::Value;
+use tokio::runtime::Runtime;
+use tower::ServiceExt;
+use wunderground_cache::{cache::forecast_key, mock};
+use wunderground_proxy_core::cache::{CachedEntry, MemoryCache, PayloadCache};
+
+const CURRENT_FIXTURE: &str = include_str!("../fixtures/current.json");
+
+/// The proxy in front of the mock upstream, with a cache duration long
+/// enough that every measured request is a hit.
+fn router(runtime: &Runtime) -> Router {
+    runtime.block_on(async {
+        let addr = mock::spawn().await.expect("mock upstream could not start");
+        std::env::set_var("CACHE_DURATION_SECS", "3600");
+        wunderground_cache::build_router(wunderground_cache::load_config_with_provider(
+            mock::provider_config(addr),
+        ))
+    })
+}
+
+async fn get(app: &Router, uri: &str) -> usize {
+    let request = Request::get(uri).body(Body::empty()).unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert!(
+        response.status().is_success(),
+        "{uri}: {}",
+        response.status()
+    );
+    response
+        .into_body()
+        .collect()
+        .await
+        .unwrap()
+        .to_bytes()
+        .len()
+}
+
+fn cache_hit(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let cache = MemoryCache::default();
+    let value: Value = serde_json::from_str(CURRENT_FIXTURE).unwrap();
+    runtime.block_on(async {
+        for i in 0..1000 {
+            let geocode = format!("{i}.0,19.94");
+            let key = forecast_key(&geocode, "en-US").to_owned_key();
+            cache.insert(key, CachedEntry::new(value.clone())).await;
+        }
+    });
+    let key = forecast_key("500.0,19.94", "en-US");
+
+    c.bench_function("cache_hit", |b| {
+        b.to_async(&runtime)
+            .iter(|| async { black_box(cache.fresh(key, Duration::from_secs(3600)).await) })
+    });
+}
+
+fn cache_key(c: &mut Criterion) {
+    c.bench_function("cache_key", |b| {
+        b.iter(|| forecast_key(black_box("50.06,19.94"), black_box("en-US")))
+    });
+}
+
+fn json_passthrough(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let app = router(&runtime);
+    let mut group = c.benchmark_group("json_passthrough");
+    for uri in [
+        "/v1/current",
+        "/v1/forecast?geocode=50.06,19.94&language=en-US",
+    ] {
+        // Fills the cache and the encoded representation first.
+        runtime.block_on(get(&app, uri));
+        group.bench_function(uri, |b| b.to_async(&runtime).iter(|| get(&app, uri)));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, cache_hit, cache_key, json_passthrough);
+criterion_main!(benches);
@@ -0,0 +1,184 @@
+use std::{
+    borrow::Borrow,
+    fmt::{self, Debug, Display},
+    hash::{DefaultHasher, Hash, Hasher},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use dashmap::DashMap;
+use serde_json::Value;
+
+/// A cached payload with the time it was fetched and a hash of its content,
+/// used to detect changes and derive validators.
+#[derive(Debug, Clone)]
+pub struct CachedEntry {
+    pub value: Value,
+    pub fetched_at: Instant,
+    pub content_hash: u64,
+}
+
+impl CachedEntry {
+    pub fn new(value: Value) -> CachedEntry {
+        let mut hasher = DefaultHasher::new();
+        value.to_string().hash(&mut hasher);
+
+        CachedEntry {
+            value,
+            fetched_at: Instant::now(),
+            content_hash: hasher.finish(),
+        }
+    }
+}
+
+/// Identifies a cached payload. Lookups take a borrowed [`KeyRef`], so a
+/// cache hit doesn't allocate; the owned form is only built on insert.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CacheKey {
+    Current,
+    Forecast { geocode: String, language: String },
+    Alerts { geocode: String, language: String },
+}
+
+/// Borrowed form of [`CacheKey`], hashing and comparing equal to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyRef<'a> {
+    Current,
+    Forecast { geocode: &'a str, language: &'a str },
+    Alerts { geocode: &'a str, language: &'a str },
+}
+
+impl KeyRef<'_> {
+    pub fn to_owned_key(self) -> CacheKey {
+        match self {
+            KeyRef::Current => CacheKey::Current,
+            KeyRef::Forecast { geocode, language } => CacheKey::Forecast {
+                geocode: geocode.to_string(),
+                language: language.to_string(),
+            },
+            KeyRef::Alerts { geocode, language } => CacheKey::Alerts {
+                geocode: geocode.to_string(),
+                language: language.to_string(),
+            },
+        }
+    }
+}
+
+/// The string form used in logs, metrics labels and `/debug/cache`, e.g.
+/// `forecast_50.06,19.94_en-US`.
+impl Display for KeyRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyRef::Current => write!(f, "current"),
+            KeyRef::Forecast { geocode, language } => write!(f, "forecast_{geocode}_{language}"),
+            KeyRef::Alerts { geocode, language } => write!(f, "alerts_{geocode}_{language}"),
+        }
+    }
+}
+
+impl Display for CacheKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.key_ref(), f)
+    }
+}
+
+impl Hash for CacheKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key_ref().hash(state);
+    }
+}
+
+/// Lets maps keyed by [`CacheKey`] be queried with a [`KeyRef`]; `Borrow`
+/// can't hand out a `KeyRef` directly, as it has to return a reference.
+pub trait AsKeyRef {
+    fn key_ref(&self) -> KeyRef<'_>;
+}
+
+impl AsKeyRef for CacheKey {
+    fn key_ref(&self) -> KeyRef<'_> {
+        match self {
+            CacheKey::Current => KeyRef::Current,
+            CacheKey::Forecast { geocode, language } => KeyRef::Forecast { geocode, language },
+            CacheKey::Alerts { geocode, language } => KeyRef::Alerts { geocode, language },
+        }
+    }
+}
+
+impl AsKeyRef for KeyRef<'_> {
+    fn key_ref(&self) -> KeyRef<'_> {
+        *self
+    }
+}
+
+impl<'a> Borrow<dyn AsKeyRef + 'a> for CacheKey {
+    fn borrow(&self) -> &(dyn AsKeyRef + 'a) {
+        self
+    }
+}
+
+impl Hash for dyn AsKeyRef + '_ {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key_ref().hash(state);
+    }
+}
+
+impl PartialEq for dyn AsKeyRef + '_ {
+    fn eq(&self, other: &Self) -> bool {
+        self.key_ref() == other.key_ref()
+    }
+}
+
+impl Eq for dyn AsKeyRef + '_ {}
+
+/// Storage for fetched payloads by cache key. Entries are never evicted by
+/// the store itself; callers decide freshness with [`PayloadCache::fresh`].
+#[async_trait]
+pub trait PayloadCache: Debug + Send + Sync {
+    async fn get(&self, key: KeyRef<'_>) -> Option<CachedEntry>;
+
+    async fn insert(&self, key: CacheKey, entry: CachedEntry);
+
+    /// Every stored entry with its key, in no particular order.
+    async fn entries(&self) -> Vec<(CacheKey, CachedEntry)>;
+
+    /// Drops every entry, so the next request for each key goes upstream.
+    async fn clear(&self);
+
+    /// The entry for `key` if it is younger than `max_age`.
+    async fn fresh(&self, key: KeyRef<'_>, max_age: Duration) -> Option<CachedEntry> {
+        self.get(key)
+            .await
+            .filter(|entry| entry.fetched_at.elapsed() < max_age)
+    }
+}
+
+/// An in-process [`PayloadCache`]. The map is sharded, so inserts for
+/// different keys (e.g. forecasts for many geocodes) don't wait on each other.
+#[derive(Debug, Default)]
+pub struct MemoryCache {
+    entries: DashMap<CacheKey, CachedEntry>,
+}
+
+#[async_trait]
+impl PayloadCache for MemoryCache {
+    async fn get(&self, key: KeyRef<'_>) -> Option<CachedEntry> {
+        self.entries
+            .get(&key as &dyn AsKeyRef)
+            .map(|entry| entry.clone())
+    }
+
+    async fn insert(&self, key: CacheKey, entry: CachedEntry) {
+        self.entries.insert(key, entry);
+    }
+
+    async fn entries(&self) -> Vec<(CacheKey, CachedEntry)> {
+        self.entries
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect()
+    }
+
+    async fn clear(&self) {
+        self.entries.clear();
+    }
+}
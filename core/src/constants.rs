@@ -0,0 +1,3 @@
+pub const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/111.0.0.0 Safari/537.36";
+
+pub const WEATHER_COM_URL: &str = "https://api.weather.com";
@@ -0,0 +1,29 @@
+/// Errors from fetching and interpreting upstream payloads.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The request failed or upstream answered with an error status. The URL
+    /// is stripped on conversion since it may carry an API key.
+    #[error("upstream request failed: {0}")]
+    Upstream(reqwest::Error),
+    /// Upstream answered, but without the data the proxy needs.
+    #[error("unexpected upstream payload: {0}")]
+    UpstreamPayload(&'static str),
+    /// Upstream answered with a payload that doesn't match the typed models.
+    #[error("upstream payload doesn't match the expected schema: {0}")]
+    InvalidPayload(serde_json::Error),
+    /// Upstream answered with a body that isn't JSON.
+    #[cfg(feature = "simd-json")]
+    #[error("upstream payload isn't valid JSON: {0}")]
+    MalformedPayload(simd_json::Error),
+    /// Replay mode has no saved payload for the request.
+    #[error("no recorded response at {0}")]
+    MissingRecording(String),
+    #[error("invalid geocode {0:?}, expected `lat,lon`")]
+    InvalidGeocode(String),
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Error {
+        Error::Upstream(err.without_url())
+    }
+}
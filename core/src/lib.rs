@@ -0,0 +1,15 @@
+#![deny(warnings)]
+#![warn(rust_2018_idioms)]
+
+//! Weather providers, payload models and the cache store behind
+//! wunderground-cache, without any web framework.
+
+pub mod cache;
+pub mod constants;
+mod error;
+pub mod models;
+pub mod upstream;
+
+pub use error::Error;
+
+pub type Result<A> = std::result::Result<A, Error>;
@@ -0,0 +1,136 @@
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Number, Value};
+
+use crate::{Error, Result};
+
+/// A `/v2/pws/observations/current` payload. Numbers stay [`Number`]s so
+/// integers round-trip unchanged; fields the proxy doesn't know about are
+/// kept in `extra`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CurrentConditions {
+    #[serde(default)]
+    pub observations: Vec<Observation>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Observation {
+    #[serde(rename = "stationID")]
+    pub station_id: Option<String>,
+    pub obs_time_utc: Option<String>,
+    pub obs_time_local: Option<String>,
+    pub neighborhood: Option<String>,
+    pub epoch: Option<i64>,
+    pub lat: Option<Number>,
+    pub lon: Option<Number>,
+    pub humidity: Option<Number>,
+    #[serde(rename = "winddir")]
+    pub wind_direction: Option<Number>,
+    pub solar_radiation: Option<Number>,
+    pub uv: Option<Number>,
+    /// Upstream is always queried in metric; the other unit objects only
+    /// appear after conversion.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metric: Option<Measurements>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub imperial: Option<Measurements>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uk_hybrid: Option<Measurements>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// The unit-dependent part of an observation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Measurements {
+    pub temp: Option<Number>,
+    pub heat_index: Option<Number>,
+    pub dewpt: Option<Number>,
+    pub wind_chill: Option<Number>,
+    /// Only present in `/current/derived`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub apparent_temp: Option<Number>,
+    pub wind_speed: Option<Number>,
+    pub wind_gust: Option<Number>,
+    pub pressure: Option<Number>,
+    pub precip_rate: Option<Number>,
+    pub precip_total: Option<Number>,
+    pub elev: Option<Number>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// A `/v3/wx/forecast/daily` payload: parallel arrays with one entry per day.
+/// Arrays a provider doesn't supply are left out rather than sent empty.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyForecast {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub day_of_week: Vec<Option<String>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub valid_time_local: Vec<Option<String>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub valid_time_utc: Vec<Option<i64>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub narrative: Vec<Option<String>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub temperature_max: Vec<Option<Number>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub temperature_min: Vec<Option<Number>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub qpf: Vec<Option<Number>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub qpf_snow: Vec<Option<Number>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub daypart: Vec<Daypart>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Day and night parts, interleaved with two entries per day.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Daypart {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub temperature: Vec<Option<Number>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub temperature_heat_index: Vec<Option<Number>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub temperature_wind_chill: Vec<Option<Number>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub wind_speed: Vec<Option<Number>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub qpf: Vec<Option<Number>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub qpf_snow: Vec<Option<Number>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// Checks an upstream observations payload against [`CurrentConditions`] and
+/// returns it in normalized form. `null` (upstream's `204 No Content` for an
+/// offline station) becomes an empty observation list.
+pub fn validate_current(value: Value) -> Result<Value> {
+    if value.is_null() {
+        return to_value(&CurrentConditions::default());
+    }
+    normalize::<CurrentConditions>(value)
+}
+
+/// Checks an upstream forecast payload against [`DailyForecast`] and returns
+/// it in normalized form.
+pub fn validate_forecast(value: Value) -> Result<Value> {
+    normalize::<DailyForecast>(value)
+}
+
+fn normalize<T: Serialize + DeserializeOwned>(value: Value) -> Result<Value> {
+    let typed: T = serde_json::from_value(value).map_err(Error::InvalidPayload)?;
+    to_value(&typed)
+}
+
+fn to_value(typed: &impl Serialize) -> Result<Value> {
+    serde_json::to_value(typed).map_err(Error::InvalidPayload)
+}
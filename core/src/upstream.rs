@@ -0,0 +1,232 @@
+use std::{fmt::Debug, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use chrono::{DateTime, FixedOffset, Utc};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+    constants::{USER_AGENT, WEATHER_COM_URL},
+    Error, Result,
+};
+
+mod failover;
+mod hedged;
+mod merged;
+mod met_no;
+mod open_meteo;
+mod openweathermap;
+mod recording;
+mod wunderground;
+
+pub use failover::Failover;
+pub use hedged::Hedged;
+pub use merged::{FieldGroup, Merged, PRIMARY};
+pub use met_no::MetNo;
+pub use open_meteo::OpenMeteo;
+pub use openweathermap::OpenWeatherMap;
+pub use recording::{Recording, RecordingMode};
+pub use wunderground::Wunderground;
+
+/// Which upstream weather service backs the proxy. Deserializes from a
+/// table whose `provider` is named as in `PROVIDER`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "provider", deny_unknown_fields)]
+pub enum ProviderConfig {
+    #[serde(rename = "wunderground")]
+    Wunderground {
+        pws_id: String,
+        api_key: String,
+        #[serde(default = "weather_com_url")]
+        base_url: String,
+    },
+    /// Open-Meteo has no stations; `location` is the `lat,lon` reported by `/current`.
+    #[serde(rename = "open-meteo")]
+    OpenMeteo { location: String },
+    /// OpenWeatherMap has no stations either; `location` works as for Open-Meteo.
+    #[serde(rename = "openweathermap")]
+    OpenWeatherMap { location: String, api_key: String },
+    /// MET Norway's Locationforecast; `user_agent` must identify the
+    /// application and a contact as its terms of service require.
+    #[serde(rename = "met.no")]
+    MetNo {
+        location: String,
+        user_agent: String,
+    },
+}
+
+impl ProviderConfig {
+    /// The PWS id, or the location for providers without stations.
+    pub fn station(&self) -> &str {
+        match self {
+            ProviderConfig::Wunderground { pws_id, .. } => pws_id,
+            ProviderConfig::OpenMeteo { location }
+            | ProviderConfig::OpenWeatherMap { location, .. }
+            | ProviderConfig::MetNo { location, .. } => location,
+        }
+    }
+
+    /// Scheme and host every request goes to, for warming up a connection.
+    pub fn origin(&self) -> &str {
+        match self {
+            ProviderConfig::Wunderground { base_url, .. } => base_url,
+            ProviderConfig::OpenMeteo { .. } => "https://api.open-meteo.com",
+            ProviderConfig::OpenWeatherMap { .. } => "https://api.openweathermap.org",
+            ProviderConfig::MetNo { .. } => "https://api.met.no",
+        }
+    }
+
+    /// The `PROVIDER` value selecting this provider.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProviderConfig::Wunderground { .. } => "wunderground",
+            ProviderConfig::OpenMeteo { .. } => "open-meteo",
+            ProviderConfig::OpenWeatherMap { .. } => "openweathermap",
+            ProviderConfig::MetNo { .. } => "met.no",
+        }
+    }
+
+    /// The key upstream requests are authenticated with, if any.
+    pub fn api_key(&self) -> Option<&String> {
+        match self {
+            ProviderConfig::Wunderground { api_key, .. }
+            | ProviderConfig::OpenWeatherMap { api_key, .. } => Some(api_key),
+            ProviderConfig::OpenMeteo { .. } | ProviderConfig::MetNo { .. } => None,
+        }
+    }
+}
+
+fn weather_com_url() -> String {
+    WEATHER_COM_URL.to_string()
+}
+
+/// An upstream weather service. Every provider returns payloads in the
+/// Wunderground shapes (PWS observations, v3 daily forecast, alert headlines,
+/// metric units) so all endpoints work regardless of the backend.
+#[async_trait]
+pub trait WeatherProvider: Debug + Send + Sync {
+    async fn current(&self, client: &Client) -> Result<Value>;
+
+    async fn forecast(&self, client: &Client, geocode: &str, language: &str) -> Result<Value>;
+
+    /// Active alert headlines; `null` when there are none or the provider has no alerts.
+    async fn alerts(&self, _client: &Client, _geocode: &str, _language: &str) -> Result<Value> {
+        Ok(Value::Null)
+    }
+
+    /// The place at `geocode` in the weather.com location point shape
+    /// (`location.city`, `location.countryCode`, ...); `null` when the
+    /// provider can't name places.
+    async fn location(&self, _client: &Client, _geocode: &str, _language: &str) -> Result<Value> {
+        Ok(Value::Null)
+    }
+}
+
+pub fn from_config(config: &ProviderConfig) -> Arc<dyn WeatherProvider> {
+    match config {
+        ProviderConfig::Wunderground {
+            pws_id,
+            api_key,
+            base_url,
+        } => Arc::new(Wunderground {
+            pws_id: pws_id.clone(),
+            api_key: api_key.clone(),
+            base_url: base_url.clone(),
+        }),
+        ProviderConfig::OpenMeteo { location } => Arc::new(OpenMeteo {
+            location: location.clone(),
+        }),
+        ProviderConfig::OpenWeatherMap { location, api_key } => Arc::new(OpenWeatherMap {
+            location: location.clone(),
+            api_key: api_key.clone(),
+        }),
+        ProviderConfig::MetNo {
+            location,
+            user_agent,
+        } => Arc::new(MetNo::new(location.clone(), user_agent.clone())),
+    }
+}
+
+/// Fetches and parses a JSON body; `204 No Content` yields `null` and an
+/// error status fails with the status kept.
+async fn fetch_json(client: &Client, url: &str, query: &[(&str, &str)]) -> Result<Value> {
+    let res = client
+        .get(url)
+        .query(query)
+        .header(reqwest::header::ACCEPT_ENCODING, "gzip")
+        .header(reqwest::header::USER_AGENT, USER_AGENT)
+        .timeout(Duration::from_secs(5))
+        .send()
+        .await?;
+
+    if res.status() == reqwest::StatusCode::NO_CONTENT {
+        return Ok(Value::Null);
+    }
+
+    parse_json(res.error_for_status()?).await
+}
+
+/// Reads a response body into the cached representation.
+#[cfg(not(feature = "simd-json"))]
+async fn parse_json(res: reqwest::Response) -> Result<Value> {
+    Ok(res.json::<Value>().await?)
+}
+
+/// Reads a response body into the cached representation. simd-json parses in
+/// place, so the body is copied into a mutable buffer first.
+#[cfg(feature = "simd-json")]
+async fn parse_json(res: reqwest::Response) -> Result<Value> {
+    let mut body = res.bytes().await?.to_vec();
+    simd_json::serde::from_slice(&mut body).map_err(Error::MalformedPayload)
+}
+
+// Wunderground formats of `obsTimeUtc`, `obsTimeLocal` and forecast `…TimeLocal`.
+const OBS_TIME_UTC: &str = "%Y-%m-%dT%H:%M:%SZ";
+const OBS_TIME_LOCAL: &str = "%Y-%m-%d %H:%M:%S";
+const FORECAST_TIME_LOCAL: &str = "%Y-%m-%dT%H:%M:%S%z";
+
+fn coordinates(geocode: &str) -> Result<(&str, &str)> {
+    geocode
+        .split_once(',')
+        .map(|(lat, lon)| (lat.trim(), lon.trim()))
+        .ok_or_else(|| Error::InvalidGeocode(geocode.to_string()))
+}
+
+fn fixed_offset(secs: Option<i64>) -> FixedOffset {
+    secs.and_then(|secs| FixedOffset::east_opt(i32::try_from(secs).ok()?))
+        .unwrap_or_else(|| FixedOffset::east_opt(0).expect("zero offset is valid"))
+}
+
+fn utc_time(epoch: i64) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(epoch, 0)
+}
+
+fn local_time(epoch: i64, offset: FixedOffset) -> Option<DateTime<FixedOffset>> {
+    utc_time(epoch).map(|time| time.with_timezone(&offset))
+}
+
+/// A short day narrative for providers without one, e.g. `Rain. High 17°C, low 9°C.`
+fn narrative(description: &str, high: Option<f64>, low: Option<f64>) -> String {
+    let mut narrative = description.to_string();
+    if let Some(high) = high {
+        narrative.push_str(&format!(". High {high:.0}°C"));
+    }
+    if let Some(low) = low {
+        narrative.push_str(&format!(", low {low:.0}°C"));
+    }
+    narrative.push('.');
+    narrative
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
@@ -0,0 +1,142 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde_json::Value;
+
+use super::WeatherProvider;
+use crate::{models, Error, Result};
+
+/// Statuses meaning the key is rejected or out of calls; retrying before the
+/// cooldown is over only burns requests.
+const KEY_EXHAUSTED: [StatusCode; 3] = [
+    StatusCode::UNAUTHORIZED,
+    StatusCode::FORBIDDEN,
+    StatusCode::TOO_MANY_REQUESTS,
+];
+
+/// Serves forecasts from `secondary` while `primary` is failing. After
+/// `threshold` failures in a row, or one answer saying the key is exhausted,
+/// the circuit opens and `primary` isn't asked again until `cooldown` has
+/// passed. Each forecast carries the name of the provider it came from in
+/// `source`. Observations, alerts and place names always come from
+/// `primary`, since another provider has neither the station nor the same
+/// warnings.
+#[derive(Debug)]
+pub struct Failover {
+    primary: Arc<dyn WeatherProvider>,
+    primary_name: &'static str,
+    secondary: Arc<dyn WeatherProvider>,
+    secondary_name: &'static str,
+    threshold: u32,
+    cooldown: Duration,
+    circuit: Mutex<Circuit>,
+}
+
+#[derive(Debug, Default)]
+struct Circuit {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl Failover {
+    pub fn new(
+        primary: Arc<dyn WeatherProvider>,
+        primary_name: &'static str,
+        secondary: Arc<dyn WeatherProvider>,
+        secondary_name: &'static str,
+        threshold: u32,
+        cooldown: Duration,
+    ) -> Failover {
+        Failover {
+            primary,
+            primary_name,
+            secondary,
+            secondary_name,
+            threshold: threshold.max(1),
+            cooldown,
+            circuit: Mutex::new(Circuit::default()),
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        let circuit = self.circuit.lock().unwrap_or_else(|err| err.into_inner());
+        circuit
+            .open_until
+            .is_some_and(|open_until| Instant::now() < open_until)
+    }
+
+    fn record(&self, result: &Result<Value>) {
+        let mut circuit = self.circuit.lock().unwrap_or_else(|err| err.into_inner());
+        let Err(err) = result else {
+            *circuit = Circuit::default();
+            return;
+        };
+        circuit.failures += 1;
+        let exhausted = matches!(err, Error::Upstream(err)
+            if err.status().is_some_and(|status| KEY_EXHAUSTED.contains(&status)));
+        if exhausted || circuit.failures >= self.threshold {
+            tracing::warn!(
+                "{} failing ({err}), serving forecasts from {} for {:?}",
+                self.primary_name,
+                self.secondary_name,
+                self.cooldown
+            );
+            circuit.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for Failover {
+    async fn current(&self, client: &Client) -> Result<Value> {
+        self.primary.current(client).await
+    }
+
+    async fn forecast(&self, client: &Client, geocode: &str, language: &str) -> Result<Value> {
+        if !self.is_open() {
+            // An error page parsed as JSON counts as a failure too.
+            let result = self
+                .primary
+                .forecast(client, geocode, language)
+                .await
+                .and_then(models::validate_forecast);
+            self.record(&result);
+            let err = match result {
+                Ok(forecast) => return Ok(with_source(forecast, self.primary_name)),
+                Err(err) => err,
+            };
+            tracing::info!(
+                "{} forecast failed, trying {}: {err}",
+                self.primary_name,
+                self.secondary_name
+            );
+            return match self.secondary.forecast(client, geocode, language).await {
+                Ok(forecast) => Ok(with_source(forecast, self.secondary_name)),
+                Err(_) => Err(err),
+            };
+        }
+        self.secondary
+            .forecast(client, geocode, language)
+            .await
+            .map(|forecast| with_source(forecast, self.secondary_name))
+    }
+
+    async fn alerts(&self, client: &Client, geocode: &str, language: &str) -> Result<Value> {
+        self.primary.alerts(client, geocode, language).await
+    }
+
+    async fn location(&self, client: &Client, geocode: &str, language: &str) -> Result<Value> {
+        self.primary.location(client, geocode, language).await
+    }
+}
+
+fn with_source(mut forecast: Value, source: &str) -> Value {
+    if let Some(forecast) = forecast.as_object_mut() {
+        forecast.insert("source".to_string(), source.into());
+    }
+    forecast
+}
@@ -0,0 +1,174 @@
+use std::{
+    collections::VecDeque,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::Value;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use super::WeatherProvider;
+use crate::Result;
+
+/// Fetch times the hedging delay is derived from.
+const SAMPLES: usize = 100;
+
+/// Fewer samples than this say little about the tail; `min_delay` is used
+/// until then.
+const MIN_SAMPLES: usize = 20;
+
+/// Sends a second, identical request when the first one is slower than the
+/// `percentile` of recent fetches (never sooner than `min_delay`), and
+/// returns whichever succeeds first. Each hedge is a real upstream request
+/// that counts against the provider's rate limits and quota, so only
+/// `budget_percent` of fetches may hedge, and a hedge needs a free slot of
+/// `permits`, the semaphore bounding concurrent upstream fetches. When
+/// either is exhausted, as during an upstream slowdown, the first request
+/// is simply waited out.
+#[derive(Debug)]
+pub struct Hedged {
+    inner: Arc<dyn WeatherProvider>,
+    percentile: f64,
+    min_delay: Duration,
+    budget_percent: f64,
+    permits: Arc<Semaphore>,
+    latencies: Mutex<VecDeque<Duration>>,
+    /// Hedges that may still be sent, in percent of one: each fetch adds
+    /// `budget_percent`, each hedge takes 100, and at most [`SAMPLES`]
+    /// fetches' worth is saved up.
+    budget: Mutex<f64>,
+}
+
+impl Hedged {
+    /// `percentile` and `budget_percent` are in `0.0..=100.0`.
+    pub fn new(
+        inner: Arc<dyn WeatherProvider>,
+        percentile: f64,
+        min_delay: Duration,
+        budget_percent: f64,
+        permits: Arc<Semaphore>,
+    ) -> Hedged {
+        Hedged {
+            inner,
+            percentile,
+            min_delay,
+            budget_percent,
+            permits,
+            latencies: Mutex::new(VecDeque::with_capacity(SAMPLES)),
+            budget: Mutex::new(0.0),
+        }
+    }
+
+    fn delay(&self) -> Duration {
+        let latencies = self.latencies.lock().unwrap_or_else(|err| err.into_inner());
+        if latencies.len() < MIN_SAMPLES {
+            return self.min_delay;
+        }
+        let mut sorted: Vec<Duration> = latencies.iter().copied().collect();
+        sorted.sort();
+        let index = ((sorted.len() - 1) as f64 * self.percentile / 100.0).round() as usize;
+        sorted[index].max(self.min_delay)
+    }
+
+    fn record(&self, latency: Duration) {
+        let mut latencies = self.latencies.lock().unwrap_or_else(|err| err.into_inner());
+        if latencies.len() == SAMPLES {
+            latencies.pop_front();
+        }
+        latencies.push_back(latency);
+    }
+
+    /// Credits the budget with one fetch.
+    fn deposit(&self) {
+        let mut budget = self.budget.lock().unwrap_or_else(|err| err.into_inner());
+        let saved = (self.budget_percent * SAMPLES as f64).max(100.0);
+        *budget = (*budget + self.budget_percent).min(saved);
+    }
+
+    /// An upstream slot for a hedge, when one is free and the budget allows
+    /// another hedge.
+    fn hedge_permit(&self) -> Option<SemaphorePermit<'_>> {
+        let permit = self.permits.try_acquire().ok()?;
+        let mut budget = self.budget.lock().unwrap_or_else(|err| err.into_inner());
+        if *budget < 100.0 {
+            return None;
+        }
+        *budget -= 100.0;
+        Some(permit)
+    }
+
+    async fn hedge<F, Fut>(&self, what: &str, fetch: F) -> Result<Value>
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Result<Value>>,
+    {
+        self.deposit();
+        let delay = self.delay();
+        let started = Instant::now();
+        let first = fetch();
+        tokio::pin!(first);
+
+        let result = tokio::select! {
+            result = &mut first => result,
+            () = tokio::time::sleep(delay) => {
+                let Some(_permit) = self.hedge_permit() else {
+                    tracing::debug!("{what} slower than {delay:?}, but no hedge is available");
+                    return self.finish(started, first.await);
+                };
+                tracing::debug!("{what} slower than {delay:?}, sending a hedged request");
+                let second = fetch();
+                tokio::pin!(second);
+                // A failure of either request still leaves the other one.
+                tokio::select! {
+                    result = &mut first => match result {
+                        Ok(value) => Ok(value),
+                        Err(_) => second.await,
+                    },
+                    result = &mut second => match result {
+                        Ok(value) => Ok(value),
+                        Err(_) => first.await,
+                    },
+                }
+            }
+        };
+        self.finish(started, result)
+    }
+
+    /// Times a successful fetch started at `started`.
+    fn finish(&self, started: Instant, result: Result<Value>) -> Result<Value> {
+        if result.is_ok() {
+            self.record(started.elapsed());
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for Hedged {
+    async fn current(&self, client: &Client) -> Result<Value> {
+        self.hedge("current observations", || self.inner.current(client))
+            .await
+    }
+
+    async fn forecast(&self, client: &Client, geocode: &str, language: &str) -> Result<Value> {
+        self.hedge("forecast", || {
+            self.inner.forecast(client, geocode, language)
+        })
+        .await
+    }
+
+    async fn alerts(&self, client: &Client, geocode: &str, language: &str) -> Result<Value> {
+        self.hedge("alerts", || self.inner.alerts(client, geocode, language))
+            .await
+    }
+
+    async fn location(&self, client: &Client, geocode: &str, language: &str) -> Result<Value> {
+        self.hedge("location", || {
+            self.inner.location(client, geocode, language)
+        })
+        .await
+    }
+}
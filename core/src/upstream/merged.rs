@@ -0,0 +1,276 @@
+use std::{collections::HashMap, future::Future, sync::Arc};
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::task::JoinSet;
+
+use super::WeatherProvider;
+use crate::{Error, Result};
+
+/// Name the main provider (`PROVIDER`) goes by in a group's priority order.
+pub const PRIMARY: &str = "primary";
+
+/// Fields of the Wunderground payload shapes that are taken from one
+/// provider together, so e.g. the temperature and its wind chill never come
+/// from different sources.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldGroup {
+    Temperature,
+    Humidity,
+    Wind,
+    Pressure,
+    Precipitation,
+    Solar,
+    ForecastTemperature,
+    ForecastPrecipitation,
+    ForecastWind,
+    ForecastConditions,
+    /// The alert headlines, taken whole.
+    Alerts,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Payload {
+    Current,
+    Forecast,
+    Alerts,
+}
+
+impl FieldGroup {
+    fn payload(self) -> Payload {
+        match self {
+            FieldGroup::Temperature
+            | FieldGroup::Humidity
+            | FieldGroup::Wind
+            | FieldGroup::Pressure
+            | FieldGroup::Precipitation
+            | FieldGroup::Solar => Payload::Current,
+            FieldGroup::ForecastTemperature
+            | FieldGroup::ForecastPrecipitation
+            | FieldGroup::ForecastWind
+            | FieldGroup::ForecastConditions => Payload::Forecast,
+            FieldGroup::Alerts => Payload::Alerts,
+        }
+    }
+
+    fn pointers(self) -> &'static [&'static str] {
+        match self {
+            FieldGroup::Temperature => &[
+                "/observations/0/metric/temp",
+                "/observations/0/metric/heatIndex",
+                "/observations/0/metric/windChill",
+            ],
+            FieldGroup::Humidity => &["/observations/0/humidity", "/observations/0/metric/dewpt"],
+            FieldGroup::Wind => &[
+                "/observations/0/winddir",
+                "/observations/0/metric/windSpeed",
+                "/observations/0/metric/windGust",
+            ],
+            FieldGroup::Pressure => &["/observations/0/metric/pressure"],
+            FieldGroup::Precipitation => &[
+                "/observations/0/metric/precipRate",
+                "/observations/0/metric/precipTotal",
+            ],
+            FieldGroup::Solar => &["/observations/0/solarRadiation", "/observations/0/uv"],
+            FieldGroup::ForecastTemperature => &[
+                "/temperatureMax",
+                "/temperatureMin",
+                "/daypart/0/temperature",
+                "/daypart/0/temperatureHeatIndex",
+                "/daypart/0/temperatureWindChill",
+            ],
+            FieldGroup::ForecastPrecipitation => &[
+                "/qpf",
+                "/qpfSnow",
+                "/daypart/0/precipChance",
+                "/daypart/0/precipType",
+                "/daypart/0/qpf",
+                "/daypart/0/qpfSnow",
+            ],
+            FieldGroup::ForecastWind => &[
+                "/daypart/0/windSpeed",
+                "/daypart/0/windDirection",
+                "/daypart/0/windDirectionCardinal",
+                "/daypart/0/windPhrase",
+            ],
+            FieldGroup::ForecastConditions => &[
+                "/narrative",
+                "/daypart/0/narrative",
+                "/daypart/0/wxPhraseLong",
+                "/daypart/0/wxPhraseShort",
+                "/daypart/0/iconCode",
+                "/daypart/0/cloudCover",
+            ],
+            FieldGroup::Alerts => &[],
+        }
+    }
+}
+
+/// Asks several providers at once and combines their payloads. The primary
+/// provider's payload is the base, or the first other one that succeeded
+/// when it failed; each configured group then takes all its fields from the
+/// first provider in its priority order that has any of them, with null for
+/// those it lacks. Providers no group of a payload names aren't asked for it.
+///
+/// Arrays are taken whole, so forecasts are only combined correctly when the
+/// providers start their days and day parts at the same time.
+#[derive(Debug)]
+pub struct Merged {
+    /// The primary provider first, under [`PRIMARY`].
+    providers: Vec<(String, Arc<dyn WeatherProvider>)>,
+    groups: HashMap<FieldGroup, Vec<String>>,
+}
+
+impl Merged {
+    /// Every name in `groups` is [`PRIMARY`] or one of `others`.
+    pub fn new(
+        primary: Arc<dyn WeatherProvider>,
+        others: Vec<(String, Arc<dyn WeatherProvider>)>,
+        groups: HashMap<FieldGroup, Vec<String>>,
+    ) -> Merged {
+        let mut providers = vec![(PRIMARY.to_string(), primary)];
+        providers.extend(others);
+        Merged { providers, groups }
+    }
+
+    /// Fetches from the primary provider and every provider named by a group
+    /// of `payload`, in provider order.
+    async fn fetch_all<F, Fut>(&self, payload: Payload, fetch: F) -> Vec<(String, Result<Value>)>
+    where
+        F: Fn(Arc<dyn WeatherProvider>) -> Fut,
+        Fut: Future<Output = Result<Value>> + Send + 'static,
+    {
+        let mut tasks = JoinSet::new();
+        for (index, (name, provider)) in self.providers.iter().enumerate() {
+            let named = self
+                .groups
+                .iter()
+                .any(|(group, order)| group.payload() == payload && order.contains(name));
+            if name == PRIMARY || named {
+                let fetch = fetch(provider.clone());
+                tasks.spawn(async move { (index, fetch.await) });
+            }
+        }
+        let mut results = Vec::new();
+        while let Some(joined) = tasks.join_next().await {
+            match joined {
+                Ok(result) => results.push(result),
+                Err(err) => tracing::warn!("merged provider fetch panicked: {err}"),
+            }
+        }
+        results.sort_by_key(|(index, _)| *index);
+        results
+            .into_iter()
+            .map(|(index, result)| {
+                let name = &self.providers[index].0;
+                if let Err(err) = &result {
+                    if name != PRIMARY {
+                        tracing::warn!("provider {name} failed, merging without it: {err}");
+                    }
+                }
+                (name.clone(), result)
+            })
+            .collect()
+    }
+
+    fn merge(&self, payload: Payload, results: Vec<(String, Result<Value>)>) -> Result<Value> {
+        let found = |results: &[(String, Result<Value>)], name: &str, pointer: &str| {
+            results
+                .iter()
+                .find(|(provider, _)| provider == name)
+                .and_then(|(_, result)| result.as_ref().ok())
+                .and_then(|value| value.pointer(pointer))
+                .filter(|value| !value.is_null())
+                .cloned()
+        };
+
+        let Some(base) = results
+            .iter()
+            .position(|(_, result)| result.as_ref().is_ok_and(|value| !value.is_null()))
+        else {
+            // Nothing to merge: the primary's answer, `null` or an error.
+            return results
+                .into_iter()
+                .next()
+                .map(|(_, result)| result)
+                .unwrap_or(Err(Error::UpstreamPayload("no provider answered")));
+        };
+        let mut merged = results[base].1.as_ref().cloned().unwrap_or_default();
+
+        for (group, order) in &self.groups {
+            if group.payload() != payload {
+                continue;
+            }
+            if payload == Payload::Alerts {
+                if let Some(alerts) = order.iter().find_map(|name| found(&results, name, "")) {
+                    merged = alerts;
+                }
+                continue;
+            }
+            let pointers = group.pointers();
+            let Some(source) = order.iter().find(|name| {
+                pointers
+                    .iter()
+                    .any(|pointer| found(&results, name, pointer).is_some())
+            }) else {
+                continue;
+            };
+            for pointer in pointers {
+                let Some((parent, key)) = pointer.rsplit_once('/') else {
+                    continue;
+                };
+                let value = found(&results, source, pointer).unwrap_or_default();
+                if let Some(parent) = merged.pointer_mut(parent).and_then(Value::as_object_mut) {
+                    parent.insert(key.to_string(), value);
+                }
+            }
+        }
+        Ok(merged)
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for Merged {
+    async fn current(&self, client: &Client) -> Result<Value> {
+        let results = self
+            .fetch_all(Payload::Current, |provider| {
+                let client = client.clone();
+                async move { provider.current(&client).await }
+            })
+            .await;
+        self.merge(Payload::Current, results)
+    }
+
+    async fn forecast(&self, client: &Client, geocode: &str, language: &str) -> Result<Value> {
+        let results = self
+            .fetch_all(Payload::Forecast, |provider| {
+                let (client, geocode, language) =
+                    (client.clone(), geocode.to_string(), language.to_string());
+                async move { provider.forecast(&client, &geocode, &language).await }
+            })
+            .await;
+        self.merge(Payload::Forecast, results)
+    }
+
+    async fn alerts(&self, client: &Client, geocode: &str, language: &str) -> Result<Value> {
+        let results = self
+            .fetch_all(Payload::Alerts, |provider| {
+                let (client, geocode, language) =
+                    (client.clone(), geocode.to_string(), language.to_string());
+                async move { provider.alerts(&client, &geocode, &language).await }
+            })
+            .await;
+        self.merge(Payload::Alerts, results)
+    }
+
+    /// Places are named by the primary provider alone.
+    async fn location(&self, client: &Client, geocode: &str, language: &str) -> Result<Value> {
+        self.providers[0]
+            .1
+            .location(client, geocode, language)
+            .await
+    }
+}
@@ -0,0 +1,330 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, SystemTime},
+};
+
+use async_trait::async_trait;
+use chrono::{DateTime, FixedOffset, NaiveDate, TimeZone, Timelike};
+use reqwest::{header, Client, StatusCode};
+use serde_json::{json, Value};
+use tokio::sync::Mutex;
+
+use super::{
+    capitalize, coordinates, fixed_offset, local_time, narrative, parse_json, round, utc_time,
+    WeatherProvider, FORECAST_TIME_LOCAL, OBS_TIME_LOCAL, OBS_TIME_UTC,
+};
+use crate::{Error, Result};
+
+const KMH_PER_MS: f64 = 3.6;
+const HOUR_SECS: i64 = 3600;
+/// Local hours counted as the day part of a forecast day; the rest is night.
+const DAYTIME_HOURS: std::ops::Range<u32> = 6..18;
+/// Responses kept for `Expires` and `Last-Modified`; every new location adds
+/// one.
+const MAX_RESPONSES: usize = 512;
+
+/// MET Norway's Locationforecast. Its terms require an identifying
+/// `User-Agent` and no re-requests before a response's `Expires`; after that
+/// requests are conditional on `Last-Modified`. Times are UTC only, so local
+/// days use the offset implied by the longitude (15° per hour).
+#[derive(Debug)]
+pub struct MetNo {
+    pub location: String,
+    /// Application name and contact, e.g. `weather-proxy/1.0 me@example.com`.
+    pub user_agent: String,
+    responses: Mutex<HashMap<String, Response>>,
+}
+
+#[derive(Debug, Clone)]
+struct Response {
+    body: Value,
+    expires: SystemTime,
+    last_modified: Option<String>,
+}
+
+impl MetNo {
+    pub fn new(location: String, user_agent: String) -> MetNo {
+        MetNo {
+            location,
+            user_agent,
+            responses: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn locationforecast(&self, client: &Client, geocode: &str) -> Result<Value> {
+        let (lat, lon) = coordinates(geocode)?;
+        // Coordinates with more than four decimals are rejected upstream.
+        let invalid = |_| Error::InvalidGeocode(geocode.to_string());
+        let (lat, lon): (f64, f64) = (lat.parse().map_err(invalid)?, lon.parse().map_err(invalid)?);
+        let url = format!(
+            "https://api.met.no/weatherapi/locationforecast/2.0/complete?lat={lat:.4}&lon={lon:.4}"
+        );
+
+        let cached = self.responses.lock().await.get(&url).cloned();
+        if let Some(cached) = cached
+            .as_ref()
+            .filter(|cached| cached.expires > SystemTime::now())
+        {
+            return Ok(cached.body.clone());
+        }
+
+        let mut request = client
+            .get(&url)
+            .header(header::USER_AGENT, &self.user_agent)
+            .timeout(Duration::from_secs(5));
+        if let Some(last_modified) = cached
+            .as_ref()
+            .and_then(|cached| cached.last_modified.clone())
+        {
+            request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+        }
+        let res = request.send().await?;
+
+        let header = |name: header::HeaderName| {
+            res.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(String::from)
+        };
+        let expires = header(header::EXPIRES)
+            .and_then(|expires| DateTime::parse_from_rfc2822(&expires).ok())
+            .map_or_else(SystemTime::now, SystemTime::from);
+        let last_modified = header(header::LAST_MODIFIED);
+
+        let body = if res.status() == StatusCode::NOT_MODIFIED {
+            cached
+                .map(|cached| cached.body)
+                .ok_or(Error::UpstreamPayload(
+                    "304 Not Modified without a cached response",
+                ))?
+        } else {
+            parse_json(res.error_for_status()?).await?
+        };
+
+        let mut responses = self.responses.lock().await;
+        if responses.len() >= MAX_RESPONSES && !responses.contains_key(&url) {
+            // Expired responses only save a conditional request; drop them
+            // first, and start over when that isn't enough.
+            let now = SystemTime::now();
+            responses.retain(|_, response| response.expires > now);
+            if responses.len() >= MAX_RESPONSES {
+                responses.clear();
+            }
+        }
+        responses.insert(
+            url,
+            Response {
+                body: body.clone(),
+                expires,
+                last_modified,
+            },
+        );
+        Ok(body)
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for MetNo {
+    async fn current(&self, client: &Client) -> Result<Value> {
+        let json = self.locationforecast(client, &self.location).await?;
+
+        observations(&json)
+    }
+
+    async fn forecast(&self, client: &Client, geocode: &str, _language: &str) -> Result<Value> {
+        let json = self.locationforecast(client, geocode).await?;
+
+        daily_forecast(&json)
+    }
+}
+
+/// Time series entries with their epoch, oldest first.
+fn timeseries(json: &Value) -> Vec<(i64, &Value)> {
+    json.pointer("/properties/timeseries")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let time = entry.get("time")?.as_str()?;
+            Some((DateTime::parse_from_rfc3339(time).ok()?.timestamp(), entry))
+        })
+        .collect()
+}
+
+fn longitude_offset(json: &Value) -> FixedOffset {
+    let lon = json
+        .pointer("/geometry/coordinates/0")
+        .and_then(Value::as_f64)
+        .unwrap_or_default();
+    fixed_offset(Some((lon / 15.0).round() as i64 * HOUR_SECS))
+}
+
+/// Maps the latest forecast step that has started to a PWS observations payload.
+fn observations(json: &Value) -> Result<Value> {
+    let now = chrono::Utc::now().timestamp();
+    let series = timeseries(json);
+    let (epoch, entry) = series
+        .iter()
+        .rev()
+        .find(|(epoch, _)| *epoch <= now)
+        .or(series.first())
+        .ok_or(Error::UpstreamPayload("no forecast steps"))?;
+    let offset = longitude_offset(json);
+    let instant = |key: &str| {
+        entry
+            .pointer(&format!("/data/instant/details/{key}"))?
+            .as_f64()
+    };
+    let kmh = |key: &str| instant(key).map(|speed| round(speed * KMH_PER_MS));
+
+    Ok(json!({
+        "observations": [{
+            "stationID": "met.no",
+            "obsTimeUtc": utc_time(*epoch).map(|time| time.format(OBS_TIME_UTC).to_string()),
+            "obsTimeLocal": local_time(*epoch, offset).map(|time| time.format(OBS_TIME_LOCAL).to_string()),
+            "epoch": epoch,
+            "lat": json.pointer("/geometry/coordinates/1"),
+            "lon": json.pointer("/geometry/coordinates/0"),
+            "humidity": instant("relative_humidity"),
+            "winddir": instant("wind_from_direction"),
+            "uv": instant("ultraviolet_index_clear_sky"),
+            "metric": {
+                "temp": instant("air_temperature"),
+                "dewpt": instant("dew_point_temperature"),
+                "windSpeed": kmh("wind_speed"),
+                "windGust": kmh("wind_speed_of_gust"),
+                "pressure": instant("air_pressure_at_sea_level"),
+                "precipRate": entry.pointer("/data/next_1_hours/details/precipitation_amount"),
+                "elev": json.pointer("/geometry/coordinates/2"),
+            },
+        }],
+    }))
+}
+
+#[derive(Default)]
+struct Day {
+    max: Option<f64>,
+    min: Option<f64>,
+    rain: f64,
+    day_chance: Option<f64>,
+    night_chance: Option<f64>,
+    /// Symbol of the step closest to local noon.
+    symbol: Option<(u32, String)>,
+}
+
+/// Folds the hourly and later 6-hourly steps into the parallel per-day arrays
+/// of the v3 daily forecast. Each step's precipitation is counted once, from
+/// its shortest period that doesn't overlap one already counted.
+fn daily_forecast(json: &Value) -> Result<Value> {
+    let series = timeseries(json);
+    if series.is_empty() {
+        return Err(Error::UpstreamPayload("no forecast steps"));
+    }
+    let offset = longitude_offset(json);
+
+    let mut days: BTreeMap<NaiveDate, Day> = BTreeMap::new();
+    let mut counted_until = i64::MIN;
+    for (epoch, entry) in &series {
+        let Some(time) = local_time(*epoch, offset) else {
+            continue;
+        };
+        let day = days.entry(time.date_naive()).or_default();
+        let data = |path: &str| entry.pointer(&format!("/data/{path}"));
+
+        if let Some(temp) = data("instant/details/air_temperature").and_then(Value::as_f64) {
+            day.max = Some(day.max.map_or(temp, |max| max.max(temp)));
+            day.min = Some(day.min.map_or(temp, |min| min.min(temp)));
+        }
+
+        let (period, hours) = match data("next_1_hours") {
+            Some(period) => (Some(period), 1),
+            None => (data("next_6_hours"), 6),
+        };
+        let Some(period) = period else {
+            continue;
+        };
+        if *epoch >= counted_until {
+            let amount = period.pointer("/details/precipitation_amount");
+            day.rain += amount.and_then(Value::as_f64).unwrap_or_default();
+            counted_until = epoch + hours * HOUR_SECS;
+        }
+        if let Some(chance) = period
+            .pointer("/details/probability_of_precipitation")
+            .and_then(Value::as_f64)
+        {
+            let part = if DAYTIME_HOURS.contains(&time.hour()) {
+                &mut day.day_chance
+            } else {
+                &mut day.night_chance
+            };
+            *part = Some(part.map_or(chance, |current| current.max(chance)));
+        }
+        let distance = time.hour().abs_diff(12);
+        if let Some(symbol) = period
+            .pointer("/summary/symbol_code")
+            .and_then(Value::as_str)
+        {
+            if day
+                .symbol
+                .as_ref()
+                .is_none_or(|(closest, _)| distance < *closest)
+            {
+                day.symbol = Some((distance, symbol.to_string()));
+            }
+        }
+    }
+
+    let days: Vec<(NaiveDate, Day)> = days.into_iter().take(5).collect();
+    let midnight = |date: &NaiveDate| {
+        offset
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+            .single()
+    };
+
+    Ok(json!({
+        "dayOfWeek": days.iter().map(|(date, _)| date.format("%A").to_string()).collect::<Vec<_>>(),
+        "validTimeLocal": days.iter().map(|(date, _)| midnight(date).map(|time| time.format(FORECAST_TIME_LOCAL).to_string())).collect::<Vec<_>>(),
+        "validTimeUtc": days.iter().map(|(date, _)| midnight(date).map(|time| time.timestamp())).collect::<Vec<_>>(),
+        "narrative": days.iter().map(|(_, day)| day.symbol.as_ref().map(|(_, symbol)| narrative(&describe(symbol), day.max, day.min))).collect::<Vec<_>>(),
+        "temperatureMax": days.iter().map(|(_, day)| day.max).collect::<Vec<_>>(),
+        "temperatureMin": days.iter().map(|(_, day)| day.min).collect::<Vec<_>>(),
+        "qpf": days.iter().map(|(_, day)| round(day.rain)).collect::<Vec<_>>(),
+        "daypart": [{
+            "dayOrNight": days.iter().flat_map(|_| ["D", "N"]).collect::<Vec<_>>(),
+            "precipChance": days.iter().flat_map(|(_, day)| [day.day_chance, day.night_chance]).collect::<Vec<_>>(),
+        }],
+    }))
+}
+
+/// Turns a symbol code such as `lightrainshowersandthunder_day` into
+/// `Light rain showers and thunder`.
+fn describe(symbol: &str) -> String {
+    let symbol = symbol.split('_').next().unwrap_or(symbol);
+    let (symbol, thunder) = match symbol.strip_suffix("andthunder") {
+        Some(symbol) => (symbol, " and thunder"),
+        None => (symbol, ""),
+    };
+    let (intensity, symbol) = if let Some(symbol) = symbol.strip_prefix("light") {
+        ("Light ", symbol)
+    } else if let Some(symbol) = symbol.strip_prefix("heavy") {
+        ("Heavy ", symbol)
+    } else {
+        ("", symbol)
+    };
+    let weather = match symbol {
+        "clearsky" => "clear sky",
+        "fair" => "fair",
+        "partlycloudy" => "partly cloudy",
+        "cloudy" => "cloudy",
+        "fog" => "fog",
+        "rain" => "rain",
+        "rainshowers" => "rain showers",
+        "sleet" => "sleet",
+        "sleetshowers" => "sleet showers",
+        "snow" => "snow",
+        "snowshowers" => "snow showers",
+        other => other,
+    };
+
+    capitalize(&format!("{intensity}{weather}{thunder}"))
+}
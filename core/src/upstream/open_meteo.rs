@@ -0,0 +1,219 @@
+use async_trait::async_trait;
+use chrono::FixedOffset;
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use super::{
+    coordinates, fetch_json, fixed_offset, local_time, narrative, round, utc_time, WeatherProvider,
+    FORECAST_TIME_LOCAL, OBS_TIME_LOCAL, OBS_TIME_UTC,
+};
+use crate::{Error, Result};
+
+const FORECAST_URL: &str = "https://api.open-meteo.com/v1/forecast";
+const CURRENT_VARIABLES: &str = "temperature_2m,relative_humidity_2m,dew_point_2m,precipitation,pressure_msl,wind_speed_10m,wind_direction_10m,wind_gusts_10m,uv_index,shortwave_radiation";
+const DAILY_VARIABLES: &str = "weather_code,temperature_2m_max,temperature_2m_min,precipitation_sum,snowfall_sum,precipitation_probability_max,sunrise,sunset";
+
+/// The keyless Open-Meteo API. Observations are modelled conditions at
+/// `location` rather than station readings, and narratives are English only.
+#[derive(Debug)]
+pub struct OpenMeteo {
+    pub location: String,
+}
+
+#[async_trait]
+impl WeatherProvider for OpenMeteo {
+    async fn current(&self, client: &Client) -> Result<Value> {
+        let (lat, lon) = coordinates(&self.location)?;
+        let json = fetch_json(
+            client,
+            FORECAST_URL,
+            &[
+                ("latitude", lat),
+                ("longitude", lon),
+                ("current", CURRENT_VARIABLES),
+                ("hourly", "precipitation"),
+                ("forecast_days", "1"),
+                ("timezone", "auto"),
+                ("timeformat", "unixtime"),
+            ],
+        )
+        .await?;
+
+        observations(&json)
+    }
+
+    async fn forecast(&self, client: &Client, geocode: &str, _language: &str) -> Result<Value> {
+        let (lat, lon) = coordinates(geocode)?;
+        let json = fetch_json(
+            client,
+            FORECAST_URL,
+            &[
+                ("latitude", lat),
+                ("longitude", lon),
+                ("daily", DAILY_VARIABLES),
+                ("forecast_days", "5"),
+                ("timezone", "auto"),
+                ("timeformat", "unixtime"),
+            ],
+        )
+        .await?;
+
+        daily_forecast(&json)
+    }
+}
+
+/// Maps the `current` block to a PWS observations payload. `precipTotal` sums
+/// the hourly precipitation since local midnight.
+fn observations(json: &Value) -> Result<Value> {
+    let current = json
+        .get("current")
+        .ok_or(Error::UpstreamPayload("no current conditions"))?;
+    let epoch = current
+        .get("time")
+        .and_then(Value::as_i64)
+        .ok_or(Error::UpstreamPayload("no observation time"))?;
+    let offset = utc_offset(json);
+    let value = |key: &str| current.get(key).cloned().unwrap_or(Value::Null);
+
+    let precip_rate = current
+        .get("precipitation")
+        .and_then(Value::as_f64)
+        .zip(current.get("interval").and_then(Value::as_f64))
+        .filter(|(_, interval)| *interval > 0.0)
+        .map(|(precipitation, interval)| round(precipitation * 3600.0 / interval));
+
+    // Each hourly value covers the hour before its timestamp, so the one at
+    // midnight belongs to the previous day.
+    let hourly_times = series(json, "hourly", "time");
+    let hourly_precipitation = series(json, "hourly", "precipitation");
+    let midnight = hourly_times.first().and_then(Value::as_i64);
+    let precip_total = hourly_times
+        .iter()
+        .zip(hourly_precipitation)
+        .filter(|(time, _)| {
+            time.as_i64()
+                .is_some_and(|time| Some(time) > midnight && time <= epoch)
+        })
+        .filter_map(|(_, precipitation)| precipitation.as_f64())
+        .sum::<f64>();
+
+    Ok(json!({
+        "observations": [{
+            "stationID": "open-meteo",
+            "obsTimeUtc": utc_time(epoch).map(|time| time.format(OBS_TIME_UTC).to_string()),
+            "obsTimeLocal": local_time(epoch, offset).map(|time| time.format(OBS_TIME_LOCAL).to_string()),
+            "epoch": epoch,
+            "lat": json.get("latitude"),
+            "lon": json.get("longitude"),
+            "humidity": value("relative_humidity_2m"),
+            "winddir": value("wind_direction_10m"),
+            "uv": value("uv_index"),
+            "solarRadiation": value("shortwave_radiation"),
+            "metric": {
+                "temp": value("temperature_2m"),
+                "dewpt": value("dew_point_2m"),
+                "windSpeed": value("wind_speed_10m"),
+                "windGust": value("wind_gusts_10m"),
+                "pressure": value("pressure_msl"),
+                "precipRate": precip_rate,
+                "precipTotal": round(precip_total),
+                "elev": json.get("elevation"),
+            },
+        }],
+    }))
+}
+
+/// Maps the `daily` block to the parallel per-day arrays of the v3 daily
+/// forecast. Day and night parts both carry the day's precipitation chance.
+fn daily_forecast(json: &Value) -> Result<Value> {
+    let offset = utc_offset(json);
+    let times: Vec<i64> = series(json, "daily", "time")
+        .iter()
+        .filter_map(Value::as_i64)
+        .collect();
+    if times.is_empty() {
+        return Err(Error::UpstreamPayload("no daily forecast"));
+    }
+    let daily = |key: &str| series(json, "daily", key);
+    let local = |epoch: &Value, format: &str| {
+        epoch
+            .as_i64()
+            .and_then(|epoch| local_time(epoch, offset))
+            .map(|time| time.format(format).to_string())
+    };
+
+    let max = daily("temperature_2m_max");
+    let min = daily("temperature_2m_min");
+    let codes = daily("weather_code");
+    let narrative: Vec<Option<String>> = (0..times.len())
+        .map(|idx| {
+            let description = codes.get(idx).and_then(Value::as_i64).and_then(describe)?;
+            Some(narrative(
+                description,
+                max.get(idx).and_then(Value::as_f64),
+                min.get(idx).and_then(Value::as_f64),
+            ))
+        })
+        .collect();
+
+    let precip_chance: Vec<Value> = daily("precipitation_probability_max")
+        .into_iter()
+        .flat_map(|chance| [chance.clone(), chance])
+        .collect();
+    let day_or_night: Vec<&str> = times.iter().flat_map(|_| ["D", "N"]).collect();
+    let sunrise = daily("sunrise");
+    let sunset = daily("sunset");
+
+    Ok(json!({
+        "dayOfWeek": times.iter().map(|time| local(&json!(time), "%A")).collect::<Vec<_>>(),
+        "validTimeLocal": times.iter().map(|time| local(&json!(time), FORECAST_TIME_LOCAL)).collect::<Vec<_>>(),
+        "validTimeUtc": times,
+        "narrative": narrative,
+        "temperatureMax": max,
+        "temperatureMin": min,
+        "qpf": daily("precipitation_sum"),
+        "qpfSnow": daily("snowfall_sum"),
+        "sunriseTimeUtc": sunrise,
+        "sunriseTimeLocal": sunrise.iter().map(|time| local(time, FORECAST_TIME_LOCAL)).collect::<Vec<_>>(),
+        "sunsetTimeUtc": sunset,
+        "sunsetTimeLocal": sunset.iter().map(|time| local(time, FORECAST_TIME_LOCAL)).collect::<Vec<_>>(),
+        "daypart": [{
+            "dayOrNight": day_or_night,
+            "precipChance": precip_chance,
+        }],
+    }))
+}
+
+/// WMO weather interpretation codes as used by Open-Meteo.
+fn describe(code: i64) -> Option<&'static str> {
+    Some(match code {
+        0 => "Clear sky",
+        1 => "Mainly clear",
+        2 => "Partly cloudy",
+        3 => "Overcast",
+        45 | 48 => "Fog",
+        51 | 53 | 55 => "Drizzle",
+        56 | 57 => "Freezing drizzle",
+        61 | 63 | 65 => "Rain",
+        66 | 67 => "Freezing rain",
+        71 | 73 | 75 => "Snow",
+        77 => "Snow grains",
+        80..=82 => "Rain showers",
+        85 | 86 => "Snow showers",
+        95 => "Thunderstorm",
+        96 | 99 => "Thunderstorm with hail",
+        _ => return None,
+    })
+}
+
+fn series(json: &Value, block: &str, key: &str) -> Vec<Value> {
+    json.get(block)
+        .and_then(|block| block.get(key))
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn utc_offset(json: &Value) -> FixedOffset {
+    fixed_offset(json.get("utc_offset_seconds").and_then(Value::as_i64))
+}
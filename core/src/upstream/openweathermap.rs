@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use chrono::{NaiveDate, TimeZone, Timelike};
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use super::{
+    capitalize, coordinates, fetch_json, fixed_offset, local_time, narrative, round, utc_time,
+    WeatherProvider, FORECAST_TIME_LOCAL, OBS_TIME_LOCAL, OBS_TIME_UTC,
+};
+use crate::{Error, Result};
+
+const KMH_PER_MS: f64 = 3.6;
+/// Local hours counted as the day part of a forecast day; the rest is night.
+const DAYTIME_HOURS: std::ops::Range<u32> = 6..18;
+
+/// The free OpenWeatherMap 2.5 APIs: current weather at `location` and the
+/// 3-hourly 5 day forecast, aggregated into days in the location's timezone.
+#[derive(Debug)]
+pub struct OpenWeatherMap {
+    pub location: String,
+    pub api_key: String,
+}
+
+#[async_trait]
+impl WeatherProvider for OpenWeatherMap {
+    async fn current(&self, client: &Client) -> Result<Value> {
+        let (lat, lon) = coordinates(&self.location)?;
+        let api_key = &self.api_key;
+        let json = fetch_json(
+            client,
+            "https://api.openweathermap.org/data/2.5/weather",
+            &[
+                ("lat", lat),
+                ("lon", lon),
+                ("units", "metric"),
+                ("appid", api_key),
+            ],
+        )
+        .await?;
+
+        observations(&json)
+    }
+
+    async fn forecast(&self, client: &Client, geocode: &str, language: &str) -> Result<Value> {
+        let (lat, lon) = coordinates(geocode)?;
+        let api_key = &self.api_key;
+        // OpenWeatherMap takes bare language codes such as `en` or `pl`.
+        let lang = language.split(['-', '_']).next().unwrap_or(language);
+        let json = fetch_json(
+            client,
+            "https://api.openweathermap.org/data/2.5/forecast",
+            &[
+                ("lat", lat),
+                ("lon", lon),
+                ("units", "metric"),
+                ("lang", lang),
+                ("appid", api_key),
+            ],
+        )
+        .await?;
+
+        daily_forecast(&json)
+    }
+}
+
+fn observations(json: &Value) -> Result<Value> {
+    let epoch = json
+        .get("dt")
+        .and_then(Value::as_i64)
+        .ok_or(Error::UpstreamPayload("no observation time"))?;
+    let offset = fixed_offset(json.get("timezone").and_then(Value::as_i64));
+    let number = |block: &str, key: &str| {
+        json.get(block)
+            .and_then(|block| block.get(key))
+            .and_then(Value::as_f64)
+    };
+    let kmh = |key: &str| number("wind", key).map(|speed| round(speed * KMH_PER_MS));
+
+    Ok(json!({
+        "observations": [{
+            "stationID": "openweathermap",
+            "neighborhood": json.get("name"),
+            "obsTimeUtc": utc_time(epoch).map(|time| time.format(OBS_TIME_UTC).to_string()),
+            "obsTimeLocal": local_time(epoch, offset).map(|time| time.format(OBS_TIME_LOCAL).to_string()),
+            "epoch": epoch,
+            "lat": number("coord", "lat"),
+            "lon": number("coord", "lon"),
+            "humidity": number("main", "humidity"),
+            "winddir": number("wind", "deg"),
+            "metric": {
+                "temp": number("main", "temp"),
+                "windSpeed": kmh("speed"),
+                "windGust": kmh("gust"),
+                "pressure": number("main", "pressure"),
+                // Rain over the last hour, the closest thing to a rate upstream offers.
+                "precipRate": number("rain", "1h").unwrap_or(0.0),
+            },
+        }],
+    }))
+}
+
+#[derive(Default)]
+struct Day {
+    max: Option<f64>,
+    min: Option<f64>,
+    rain: f64,
+    snow: f64,
+    day_chance: Option<f64>,
+    night_chance: Option<f64>,
+    /// Description of the entry closest to local noon.
+    description: Option<(u32, String)>,
+}
+
+/// Folds the 3-hourly entries into the parallel per-day arrays of the v3 daily
+/// forecast. Days start at local midnight; the first and last days may be partial.
+fn daily_forecast(json: &Value) -> Result<Value> {
+    let entries = json
+        .get("list")
+        .and_then(Value::as_array)
+        .ok_or(Error::UpstreamPayload("no forecast"))?;
+    let city = json.get("city");
+    let offset = fixed_offset(city.and_then(|city| city.get("timezone")?.as_i64()));
+
+    let mut days: BTreeMap<NaiveDate, Day> = BTreeMap::new();
+    for entry in entries {
+        let Some(time) = entry
+            .get("dt")
+            .and_then(Value::as_i64)
+            .and_then(|epoch| local_time(epoch, offset))
+        else {
+            continue;
+        };
+        let day = days.entry(time.date_naive()).or_default();
+        let number = |block: &str, key: &str| entry.get(block)?.get(key)?.as_f64();
+
+        if let Some(max) = number("main", "temp_max") {
+            day.max = Some(day.max.map_or(max, |current| current.max(max)));
+        }
+        if let Some(min) = number("main", "temp_min") {
+            day.min = Some(day.min.map_or(min, |current| current.min(min)));
+        }
+        day.rain += number("rain", "3h").unwrap_or(0.0);
+        day.snow += number("snow", "3h").unwrap_or(0.0);
+
+        if let Some(chance) = entry.get("pop").and_then(Value::as_f64) {
+            let chance = (chance * 100.0).round();
+            let part = if DAYTIME_HOURS.contains(&time.hour()) {
+                &mut day.day_chance
+            } else {
+                &mut day.night_chance
+            };
+            *part = Some(part.map_or(chance, |current| current.max(chance)));
+        }
+
+        let distance = time.hour().abs_diff(12);
+        let description = entry
+            .get("weather")
+            .and_then(|weather| weather.get(0)?.get("description")?.as_str());
+        if let Some(description) = description {
+            if day
+                .description
+                .as_ref()
+                .is_none_or(|(closest, _)| distance < *closest)
+            {
+                day.description = Some((distance, capitalize(description)));
+            }
+        }
+    }
+
+    let days: Vec<(NaiveDate, Day)> = days.into_iter().take(5).collect();
+    let midnight = |date: &NaiveDate| {
+        offset
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+            .single()
+    };
+
+    Ok(json!({
+        "dayOfWeek": days.iter().map(|(date, _)| date.format("%A").to_string()).collect::<Vec<_>>(),
+        "validTimeLocal": days.iter().map(|(date, _)| midnight(date).map(|time| time.format(FORECAST_TIME_LOCAL).to_string())).collect::<Vec<_>>(),
+        "validTimeUtc": days.iter().map(|(date, _)| midnight(date).map(|time| time.timestamp())).collect::<Vec<_>>(),
+        "narrative": days.iter().map(|(_, day)| day.description.as_ref().map(|(_, description)| narrative(description, day.max, day.min))).collect::<Vec<_>>(),
+        "temperatureMax": days.iter().map(|(_, day)| day.max).collect::<Vec<_>>(),
+        "temperatureMin": days.iter().map(|(_, day)| day.min).collect::<Vec<_>>(),
+        "qpf": days.iter().map(|(_, day)| round(day.rain)).collect::<Vec<_>>(),
+        // Upstream reports snow as mm of water; at the usual 10:1 ratio that
+        // is the same number as the v3 forecast's cm of snow.
+        "qpfSnow": days.iter().map(|(_, day)| round(day.snow)).collect::<Vec<_>>(),
+        "daypart": [{
+            "dayOrNight": days.iter().flat_map(|_| ["D", "N"]).collect::<Vec<_>>(),
+            "precipChance": days.iter().flat_map(|(_, day)| [day.day_chance, day.night_chance]).collect::<Vec<_>>(),
+        }],
+    }))
+}
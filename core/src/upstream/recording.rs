@@ -0,0 +1,101 @@
+use std::{path::PathBuf, sync::Arc};
+
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::Value;
+
+use super::WeatherProvider;
+use crate::{Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordingMode {
+    /// Query upstream and save every payload.
+    Record,
+    /// Serve saved payloads without contacting upstream.
+    Replay,
+}
+
+/// Saves provider payloads as `<dir>/<endpoint>[_<geocode>_<language>].json`,
+/// or serves them back. Files are keyed by the request parameters only, so
+/// station ids and API keys never end up in a recording.
+#[derive(Debug)]
+pub struct Recording {
+    pub inner: Arc<dyn WeatherProvider>,
+    pub mode: RecordingMode,
+    pub dir: PathBuf,
+}
+
+impl Recording {
+    async fn through(
+        &self,
+        key: String,
+        fetch: impl std::future::Future<Output = Result<Value>>,
+    ) -> Result<Value> {
+        let path = self.dir.join(format!("{}.json", sanitize(&key)));
+        match self.mode {
+            RecordingMode::Replay => {
+                let raw = tokio::fs::read(&path)
+                    .await
+                    .map_err(|_| Error::MissingRecording(path.display().to_string()))?;
+                serde_json::from_slice(&raw).map_err(Error::InvalidPayload)
+            }
+            RecordingMode::Record => {
+                let value = fetch.await?;
+                let saved = async {
+                    tokio::fs::create_dir_all(&self.dir).await?;
+                    let pretty = serde_json::to_vec_pretty(&value)?;
+                    tokio::fs::write(&path, pretty).await
+                };
+                if let Err(err) = saved.await {
+                    tracing::warn!("recording {} failed: {err}", path.display());
+                }
+                Ok(value)
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for Recording {
+    async fn current(&self, client: &Client) -> Result<Value> {
+        self.through("current".to_string(), self.inner.current(client))
+            .await
+    }
+
+    async fn forecast(&self, client: &Client, geocode: &str, language: &str) -> Result<Value> {
+        self.through(
+            format!("forecast_{geocode}_{language}"),
+            self.inner.forecast(client, geocode, language),
+        )
+        .await
+    }
+
+    async fn alerts(&self, client: &Client, geocode: &str, language: &str) -> Result<Value> {
+        self.through(
+            format!("alerts_{geocode}_{language}"),
+            self.inner.alerts(client, geocode, language),
+        )
+        .await
+    }
+
+    async fn location(&self, client: &Client, geocode: &str, language: &str) -> Result<Value> {
+        self.through(
+            format!("location_{geocode}_{language}"),
+            self.inner.location(client, geocode, language),
+        )
+        .await
+    }
+}
+
+/// Keeps file names portable; geocodes and language tags only lose spaces.
+fn sanitize(key: &str) -> String {
+    key.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | ',' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
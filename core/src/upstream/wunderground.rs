@@ -0,0 +1,94 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::Value;
+
+use super::{fetch_json, WeatherProvider};
+use crate::Result;
+
+/// The weather.com APIs behind Wunderground, for a single personal weather station.
+#[derive(Debug)]
+pub struct Wunderground {
+    pub pws_id: String,
+    pub api_key: String,
+    /// `https://api.weather.com`, or a stand-in such as the mock upstream.
+    pub base_url: String,
+}
+
+#[async_trait]
+impl WeatherProvider for Wunderground {
+    async fn current(&self, client: &Client) -> Result<Value> {
+        let Wunderground {
+            pws_id,
+            api_key,
+            base_url,
+        } = self;
+
+        fetch_json(
+            client,
+            &format!("{base_url}/v2/pws/observations/current"),
+            &[
+                ("stationId", pws_id),
+                ("format", "json"),
+                ("units", "m"),
+                ("apiKey", api_key),
+                ("numericPrecision", "decimal"),
+            ],
+        )
+        .await
+    }
+
+    async fn forecast(&self, client: &Client, geocode: &str, language: &str) -> Result<Value> {
+        let Wunderground {
+            api_key, base_url, ..
+        } = self;
+
+        fetch_json(
+            client,
+            &format!("{base_url}/v3/wx/forecast/daily/5day"),
+            &[
+                ("geocode", geocode),
+                ("format", "json"),
+                ("units", "m"),
+                ("apiKey", api_key),
+                ("language", language),
+            ],
+        )
+        .await
+    }
+
+    async fn alerts(&self, client: &Client, geocode: &str, language: &str) -> Result<Value> {
+        let Wunderground {
+            api_key, base_url, ..
+        } = self;
+
+        fetch_json(
+            client,
+            &format!("{base_url}/v3/alerts/headlines"),
+            &[
+                ("geocode", geocode),
+                ("format", "json"),
+                ("apiKey", api_key),
+                ("language", language),
+            ],
+        )
+        .await
+    }
+
+    async fn location(&self, client: &Client, geocode: &str, language: &str) -> Result<Value> {
+        let Wunderground {
+            api_key, base_url, ..
+        } = self;
+
+        fetch_json(
+            client,
+            &format!("{base_url}/v3/location/point"),
+            &[
+                ("geocode", geocode),
+                ("format", "json"),
+                ("apiKey", api_key),
+                ("language", language),
+            ],
+        )
+        .await
+    }
+}
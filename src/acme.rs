@@ -0,0 +1,585 @@
+use std::{collections::HashMap, io, path::Path, sync::Arc, time::Duration};
+
+use axum::{
+    extract::{Path as UrlPath, State},
+    http::StatusCode,
+    routing::get,
+    Router,
+};
+use base64::{
+    prelude::{BASE64_STANDARD, BASE64_URL_SAFE_NO_PAD},
+    Engine,
+};
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
+use reqwest::{header, Client, Response};
+use ring::{
+    digest,
+    rand::SystemRandom,
+    signature::{
+        EcdsaKeyPair, EcdsaSigningAlgorithm, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING,
+        ECDSA_P256_SHA256_FIXED_SIGNING,
+    },
+};
+use rustls::pki_types::{pem::PemObject, CertificateDer, PrivatePkcs8KeyDer};
+use serde_json::{json, Value};
+use tokio::{net::TcpListener, sync::RwLock};
+
+use crate::{AcmeConfig, TlsConfig};
+
+/// CAs ask clients to identify themselves (RFC 8555, section 6.1).
+const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+/// Where the CA fetches HTTP-01 key authorizations.
+const CHALLENGE_ROUTE: &str = "/.well-known/acme-challenge/:token";
+/// Pending authorizations and orders are checked this often, up to
+/// `POLL_ATTEMPTS` times.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const POLL_ATTEMPTS: u32 = 60;
+/// How often the certificate's expiry is checked.
+const CHECK_INTERVAL: Duration = Duration::from_secs(12 * 60 * 60);
+/// A failed renewal is retried after this long.
+const RETRY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+const BAD_NONCE: &str = "urn:ietf:params:acme:error:badNonce";
+
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const INTEGER: u8 = 0x02;
+const BIT_STRING: u8 = 0x03;
+const OCTET_STRING: u8 = 0x04;
+const UTF8_STRING: u8 = 0x0c;
+const UTC_TIME: u8 = 0x17;
+const GENERALIZED_TIME: u8 = 0x18;
+const CONTEXT_0: u8 = 0xa0;
+/// `dNSName` of a `GeneralName`.
+const DNS_NAME: u8 = 0x82;
+// Encoded object identifiers, tag and length included.
+const OID_COMMON_NAME: &[u8] = &[0x06, 0x03, 0x55, 0x04, 0x03];
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const OID_P256: &[u8] = &[0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const OID_ECDSA_SHA256: &[u8] = &[0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+const OID_EXTENSION_REQUEST: &[u8] = &[
+    0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x0e,
+];
+const OID_SUBJECT_ALT_NAME: &[u8] = &[0x06, 0x03, 0x55, 0x1d, 0x11];
+
+/// Key authorizations by challenge token.
+type Challenges = Arc<RwLock<HashMap<String, String>>>;
+
+/// Answers HTTP-01 challenges on `http_addr`, orders a certificate when the
+/// one at `cert_path` is missing or expires within `renew_days`, and keeps
+/// renewing it in the background. Certificates are written over
+/// `cert_path` and `key_path`, where the TLS reload picks them up.
+pub(crate) async fn provision(tls: &TlsConfig, acme: &AcmeConfig) -> io::Result<()> {
+    let challenges = Challenges::default();
+    let listener = TcpListener::bind(acme.http_addr).await?;
+    tracing::info!("answering ACME challenges on {}", acme.http_addr);
+    let app = Router::new()
+        .route(CHALLENGE_ROUTE, get(answer))
+        .with_state(challenges.clone());
+    tokio::spawn(async move {
+        if let Err(err) = axum::serve(listener, app).await {
+            tracing::warn!("ACME challenge listener stopped: {err}");
+        }
+    });
+
+    if needs_renewal(tls, acme) {
+        if let Err(err) = issue(tls, acme, &challenges).await {
+            // An expiring certificate still beats not serving at all.
+            if !tls.cert_path.exists() {
+                return Err(err);
+            }
+            tracing::warn!("renewing the TLS certificate failed: {err}");
+        }
+    }
+
+    let (tls, acme) = (tls.clone(), acme.clone());
+    tokio::spawn(async move {
+        let mut wait = CHECK_INTERVAL;
+        loop {
+            tokio::time::sleep(wait).await;
+            wait = CHECK_INTERVAL;
+            if !needs_renewal(&tls, &acme) {
+                continue;
+            }
+            if let Err(err) = issue(&tls, &acme, &challenges).await {
+                tracing::warn!("renewing the TLS certificate failed: {err}");
+                wait = RETRY_INTERVAL;
+            }
+        }
+    });
+    Ok(())
+}
+
+async fn answer(
+    State(challenges): State<Challenges>,
+    UrlPath(token): UrlPath<String>,
+) -> Result<String, StatusCode> {
+    challenges
+        .read()
+        .await
+        .get(&token)
+        .cloned()
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+fn needs_renewal(tls: &TlsConfig, acme: &AcmeConfig) -> bool {
+    let expires = CertificateDer::pem_file_iter(&tls.cert_path)
+        .ok()
+        .and_then(|mut certs| certs.next()?.ok())
+        .and_then(|cert| not_after(&cert));
+    match expires {
+        Some(expires) => expires - Utc::now() < TimeDelta::days(i64::from(acme.renew_days)),
+        None => true,
+    }
+}
+
+/// Orders a certificate for every domain and writes it with its new key.
+async fn issue(tls: &TlsConfig, acme: &AcmeConfig, challenges: &Challenges) -> io::Result<()> {
+    tracing::info!("ordering a TLS certificate for {}", acme.domains.join(", "));
+    let mut session = Session::open(acme).await?;
+
+    let identifiers: Vec<Value> = acme
+        .domains
+        .iter()
+        .map(|domain| json!({ "type": "dns", "value": domain }))
+        .collect();
+    let new_order = session.directory("newOrder")?;
+    let response = session
+        .post(&new_order, Some(&json!({ "identifiers": identifiers })))
+        .await?;
+    let order_url = location(&response)?;
+    let order: Value = response.json().await.map_err(io::Error::other)?;
+
+    for authorization in order["authorizations"].as_array().into_iter().flatten() {
+        let url = authorization.as_str().unwrap_or_default();
+        session.authorize(url, challenges).await?;
+    }
+
+    let rng = SystemRandom::new();
+    let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng)
+        .map_err(|_| io::Error::other("generating the certificate key failed"))?;
+    let key = key_pair(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref())?;
+    let csr = csr(&acme.domains, &key)?;
+    let finalize = order["finalize"].as_str().unwrap_or_default();
+    session
+        .post(
+            finalize,
+            Some(&json!({ "csr": BASE64_URL_SAFE_NO_PAD.encode(csr) })),
+        )
+        .await?;
+    let order = session.poll(&order_url, "valid").await?;
+
+    let certificate = order["certificate"].as_str().unwrap_or_default();
+    let chain = session
+        .post(certificate, None)
+        .await?
+        .text()
+        .await
+        .map_err(io::Error::other)?;
+    // The key goes first: a reload between the writes fails on the
+    // mismatched pair and is retried on the next tick.
+    write_private(&tls.key_path, &pem("PRIVATE KEY", pkcs8.as_ref())).await?;
+    write_private(&tls.cert_path, &chain).await?;
+    tracing::info!("TLS certificate issued for {}", acme.domains.join(", "));
+    Ok(())
+}
+
+/// An ACME account session: requests are signed with the account key and
+/// carry the last nonce the server handed out.
+struct Session {
+    client: Client,
+    key: EcdsaKeyPair,
+    directory: Value,
+    nonce: Option<String>,
+    /// Account URL, once registered.
+    kid: Option<String>,
+}
+
+impl Session {
+    /// Fetches the directory and registers the account, or looks up the
+    /// existing one for the key.
+    async fn open(acme: &AcmeConfig) -> io::Result<Session> {
+        let client = Client::builder()
+            .user_agent(USER_AGENT)
+            .timeout(Duration::from_secs(30))
+            .build()
+            .map_err(io::Error::other)?;
+        let directory = client
+            .get(&acme.directory_url)
+            .send()
+            .await
+            .and_then(Response::error_for_status)
+            .map_err(io::Error::other)?
+            .json()
+            .await
+            .map_err(io::Error::other)?;
+        let mut session = Session {
+            client,
+            key: account_key(&acme.account_key_path).await?,
+            directory,
+            nonce: None,
+            kid: None,
+        };
+
+        let contact: Vec<String> = acme
+            .email
+            .iter()
+            .map(|email| format!("mailto:{email}"))
+            .collect();
+        let new_account = session.directory("newAccount")?;
+        let response = session
+            .post(
+                &new_account,
+                Some(&json!({ "termsOfServiceAgreed": true, "contact": contact })),
+            )
+            .await?;
+        session.kid = Some(location(&response)?);
+        Ok(session)
+    }
+
+    fn directory(&self, resource: &str) -> io::Result<String> {
+        self.directory[resource]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| io::Error::other(format!("ACME directory has no {resource}")))
+    }
+
+    /// Publishes the HTTP-01 key authorization and waits for the CA to check
+    /// it.
+    async fn authorize(&mut self, url: &str, challenges: &Challenges) -> io::Result<()> {
+        let authorization: Value = self
+            .post(url, None)
+            .await?
+            .json()
+            .await
+            .map_err(io::Error::other)?;
+        if authorization["status"] == "valid" {
+            return Ok(());
+        }
+        let domain = authorization["identifier"]["value"]
+            .as_str()
+            .unwrap_or_default();
+        let challenge = authorization["challenges"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|challenge| challenge["type"] == "http-01")
+            .ok_or_else(|| io::Error::other(format!("no HTTP-01 challenge for {domain}")))?;
+        let token = challenge["token"].as_str().unwrap_or_default().to_string();
+        let challenge_url = challenge["url"].as_str().unwrap_or_default();
+
+        let key_authorization = format!("{token}.{}", thumbprint(&self.key));
+        challenges
+            .write()
+            .await
+            .insert(token.clone(), key_authorization);
+        let result = async {
+            self.post(challenge_url, Some(&json!({}))).await?;
+            self.poll(url, "valid").await
+        }
+        .await;
+        challenges.write().await.remove(&token);
+        result.map(|_| ())
+    }
+
+    /// Fetches `url` until its status is `wanted`, failing once it is
+    /// anything but pending.
+    async fn poll(&mut self, url: &str, wanted: &str) -> io::Result<Value> {
+        for _ in 0..POLL_ATTEMPTS {
+            let resource: Value = self
+                .post(url, None)
+                .await?
+                .json()
+                .await
+                .map_err(io::Error::other)?;
+            match resource["status"].as_str().unwrap_or_default() {
+                status if status == wanted => return Ok(resource),
+                "pending" | "processing" | "ready" => tokio::time::sleep(POLL_INTERVAL).await,
+                status => {
+                    let detail = resource["challenges"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .chain([&resource])
+                        .find_map(|resource| resource["error"]["detail"].as_str())
+                        .unwrap_or_default();
+                    return Err(io::Error::other(format!("{url} is {status}: {detail}")));
+                }
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("{url} did not become {wanted}"),
+        ))
+    }
+
+    /// Sends a JWS-signed request; `None` is a POST-as-GET. A rejected
+    /// nonce is retried once with the fresh one from the rejection.
+    async fn post(&mut self, url: &str, payload: Option<&Value>) -> io::Result<Response> {
+        let payload = match payload {
+            Some(payload) => BASE64_URL_SAFE_NO_PAD.encode(payload.to_string()),
+            None => String::new(),
+        };
+        let mut retried = false;
+        loop {
+            let nonce = match self.nonce.take() {
+                Some(nonce) => nonce,
+                None => self.new_nonce().await?,
+            };
+            let mut protected = json!({ "alg": "ES256", "nonce": nonce, "url": url });
+            match &self.kid {
+                Some(kid) => protected["kid"] = kid.as_str().into(),
+                None => protected["jwk"] = jwk(&self.key),
+            }
+            let protected = BASE64_URL_SAFE_NO_PAD.encode(protected.to_string());
+            let signature = self
+                .key
+                .sign(
+                    &SystemRandom::new(),
+                    format!("{protected}.{payload}").as_bytes(),
+                )
+                .map_err(|_| io::Error::other("signing the ACME request failed"))?;
+            let body = json!({
+                "protected": protected,
+                "payload": payload,
+                "signature": BASE64_URL_SAFE_NO_PAD.encode(signature),
+            });
+
+            let response = self
+                .client
+                .post(url)
+                .header(header::CONTENT_TYPE, "application/jose+json")
+                .body(body.to_string())
+                .send()
+                .await
+                .map_err(io::Error::other)?;
+            self.nonce = response
+                .headers()
+                .get("replay-nonce")
+                .and_then(|nonce| nonce.to_str().ok())
+                .map(String::from);
+            if response.status().is_success() {
+                return Ok(response);
+            }
+            let status = response.status();
+            let problem: Value = response.json().await.unwrap_or_default();
+            if problem["type"] == BAD_NONCE && !retried {
+                retried = true;
+                continue;
+            }
+            return Err(io::Error::other(format!(
+                "ACME request to {url} failed with {status}: {}",
+                problem["detail"].as_str().unwrap_or_default()
+            )));
+        }
+    }
+
+    async fn new_nonce(&self) -> io::Result<String> {
+        let response = self
+            .client
+            .head(self.directory("newNonce")?)
+            .send()
+            .await
+            .map_err(io::Error::other)?;
+        response
+            .headers()
+            .get("replay-nonce")
+            .and_then(|nonce| nonce.to_str().ok())
+            .map(String::from)
+            .ok_or_else(|| io::Error::other("ACME server sent no nonce"))
+    }
+}
+
+fn location(response: &Response) -> io::Result<String> {
+    response
+        .headers()
+        .get(header::LOCATION)
+        .and_then(|location| location.to_str().ok())
+        .map(String::from)
+        .ok_or_else(|| io::Error::other("ACME response has no Location"))
+}
+
+/// Loads the account key, creating it on first use.
+async fn account_key(path: &Path) -> io::Result<EcdsaKeyPair> {
+    if tokio::fs::try_exists(path).await? {
+        let pkcs8 = PrivatePkcs8KeyDer::from_pem_file(path).map_err(io::Error::other)?;
+        return key_pair(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.secret_pkcs8_der());
+    }
+    let pkcs8 =
+        EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &SystemRandom::new())
+            .map_err(|_| io::Error::other("generating the ACME account key failed"))?;
+    write_private(path, &pem("PRIVATE KEY", pkcs8.as_ref())).await?;
+    key_pair(&ECDSA_P256_SHA256_FIXED_SIGNING, pkcs8.as_ref())
+}
+
+fn key_pair(algorithm: &'static EcdsaSigningAlgorithm, pkcs8: &[u8]) -> io::Result<EcdsaKeyPair> {
+    EcdsaKeyPair::from_pkcs8(algorithm, pkcs8, &SystemRandom::new())
+        .map_err(|err| io::Error::other(format!("unusable P-256 key: {err}")))
+}
+
+/// Writes through a temporary file, so readers never see a partial file,
+/// readable by the owner only.
+async fn write_private(path: &Path, contents: &str) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temporary = path.with_extension("tmp");
+    tokio::fs::write(&temporary, contents).await?;
+    tokio::fs::set_permissions(&temporary, std::fs::Permissions::from_mode(0o600)).await?;
+    tokio::fs::rename(&temporary, path).await
+}
+
+fn pem(label: &str, der: &[u8]) -> String {
+    let encoded = BASE64_STANDARD.encode(der);
+    let mut pem = format!("-----BEGIN {label}-----\n");
+    for line in encoded.as_bytes().chunks(64) {
+        pem.push_str(std::str::from_utf8(line).unwrap_or_default());
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {label}-----\n"));
+    pem
+}
+
+/// The public key as a JWK; members in the lexicographic order RFC 7638
+/// thumbprints need.
+fn jwk(key: &EcdsaKeyPair) -> Value {
+    let (x, y) = coordinates(key);
+    json!({ "crv": "P-256", "kty": "EC", "x": x, "y": y })
+}
+
+fn thumbprint(key: &EcdsaKeyPair) -> String {
+    let (x, y) = coordinates(key);
+    let canonical = format!(r#"{{"crv":"P-256","kty":"EC","x":"{x}","y":"{y}"}}"#);
+    BASE64_URL_SAFE_NO_PAD.encode(digest::digest(&digest::SHA256, canonical.as_bytes()))
+}
+
+/// Base64url `x` and `y` of the uncompressed point `04 || x || y`.
+fn coordinates(key: &EcdsaKeyPair) -> (String, String) {
+    let point = key.public_key().as_ref();
+    (
+        BASE64_URL_SAFE_NO_PAD.encode(&point[1..33]),
+        BASE64_URL_SAFE_NO_PAD.encode(&point[33..65]),
+    )
+}
+
+/// A PKCS #10 request for `domains`, the first one as the common name and
+/// all of them as subject alternative names.
+fn csr(domains: &[String], key: &EcdsaKeyPair) -> io::Result<Vec<u8>> {
+    let common_name = domains.first().map(String::as_bytes).unwrap_or_default();
+    let subject = der(
+        SEQUENCE,
+        &der(
+            SET,
+            &der(
+                SEQUENCE,
+                &[OID_COMMON_NAME, &der(UTF8_STRING, common_name)].concat(),
+            ),
+        ),
+    );
+    let public_key = der(
+        SEQUENCE,
+        &[
+            der(SEQUENCE, &[OID_EC_PUBLIC_KEY, OID_P256].concat()),
+            der(BIT_STRING, &[&[0], key.public_key().as_ref()].concat()),
+        ]
+        .concat(),
+    );
+    let names: Vec<u8> = domains
+        .iter()
+        .flat_map(|domain| der(DNS_NAME, domain.as_bytes()))
+        .collect();
+    let alt_names = der(
+        SEQUENCE,
+        &[
+            OID_SUBJECT_ALT_NAME,
+            &der(OCTET_STRING, &der(SEQUENCE, &names)),
+        ]
+        .concat(),
+    );
+    let attributes = der(
+        CONTEXT_0,
+        &der(
+            SEQUENCE,
+            &[OID_EXTENSION_REQUEST, &der(SET, &der(SEQUENCE, &alt_names))].concat(),
+        ),
+    );
+    let info = der(
+        SEQUENCE,
+        &[der(INTEGER, &[0]), subject, public_key, attributes].concat(),
+    );
+    let signature = key
+        .sign(&SystemRandom::new(), &info)
+        .map_err(|_| io::Error::other("signing the certificate request failed"))?;
+    Ok(der(
+        SEQUENCE,
+        &[
+            info,
+            der(SEQUENCE, OID_ECDSA_SHA256),
+            der(BIT_STRING, &[&[0], signature.as_ref()].concat()),
+        ]
+        .concat(),
+    ))
+}
+
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut encoded = vec![tag];
+    let length = content.len();
+    if length < 0x80 {
+        encoded.push(length as u8);
+    } else {
+        let bytes = length.to_be_bytes();
+        let significant = &bytes[bytes.iter().take_while(|byte| **byte == 0).count()..];
+        encoded.push(0x80 | significant.len() as u8);
+        encoded.extend_from_slice(significant);
+    }
+    encoded.extend_from_slice(content);
+    encoded
+}
+
+/// The end of a certificate's validity period.
+fn not_after(cert: &[u8]) -> Option<DateTime<Utc>> {
+    let (_, certificate, _) = tlv(cert)?;
+    let (_, mut tbs, _) = tlv(certificate)?;
+    if tbs.first() == Some(&CONTEXT_0) {
+        tbs = tlv(tbs)?.2;
+    }
+    // Serial number, signature algorithm and issuer come first.
+    for _ in 0..3 {
+        tbs = tlv(tbs)?.2;
+    }
+    let (_, validity, _) = tlv(tbs)?;
+    let (_, _, rest) = tlv(validity)?;
+    let (tag, time, _) = tlv(rest)?;
+    let time = std::str::from_utf8(time).ok()?;
+    let format = match tag {
+        UTC_TIME => "%y%m%d%H%M%SZ",
+        GENERALIZED_TIME => "%Y%m%d%H%M%SZ",
+        _ => return None,
+    };
+    NaiveDateTime::parse_from_str(time, format)
+        .ok()
+        .map(|time| time.and_utc())
+}
+
+/// Splits the first DER element off `input`: its tag, content and whatever
+/// follows it.
+fn tlv(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, rest) = input.split_first()?;
+    let (&first, mut rest) = rest.split_first()?;
+    let length = if first < 0x80 {
+        usize::from(first)
+    } else {
+        let count = usize::from(first & 0x7f);
+        if count > std::mem::size_of::<usize>() || rest.len() < count {
+            return None;
+        }
+        let (bytes, after) = rest.split_at(count);
+        rest = after;
+        bytes
+            .iter()
+            .fold(0usize, |length, byte| (length << 8) | usize::from(*byte))
+    };
+    if rest.len() < length {
+        return None;
+    }
+    let (content, after) = rest.split_at(length);
+    Some((tag, content, after))
+}
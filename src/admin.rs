@@ -0,0 +1,124 @@
+use std::{fmt::Write, sync::Arc};
+
+#[cfg(feature = "recorder")]
+use axum::routing::post;
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{delete, get},
+    Json, Router,
+};
+use serde::Serialize;
+
+#[cfg(feature = "recorder")]
+use crate::{recorder::Pruned, AppError};
+use crate::{
+    tenants::{ClientUsage, Tenants},
+    weather_metrics::escape_label,
+    AppState,
+};
+
+pub(crate) const CACHE_ENTRIES: &str = "wunderground_proxy_cache_entries";
+pub(crate) const CACHE_ENTRY_AGE: &str = "wunderground_proxy_cache_entry_age_seconds";
+
+#[derive(Serialize)]
+struct CacheEntryInfo {
+    key: String,
+    age_secs: u64,
+    fresh: bool,
+    content_hash: String,
+}
+
+/// Operational routes, served on the admin listener only. `tenants` is
+/// `None` when the proxy is open to everyone.
+pub(crate) fn routes(state: AppState, tenants: Option<Arc<Tenants>>) -> Router {
+    let router = Router::new()
+        .route("/metrics", get(metrics))
+        .route("/debug/cache", get(debug_cache))
+        .route("/admin/cache", delete(purge_cache));
+    #[cfg(feature = "recorder")]
+    let router = router.route("/admin/history/prune", post(prune_history));
+    router
+        .with_state(state)
+        .route("/admin/clients", get(clients).with_state(tenants))
+}
+
+/// Cache size and entry ages in the Prometheus text exposition format.
+async fn metrics(State(state): State<AppState>) -> Response {
+    let mut entries = state.cached_entries.entries().await;
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut output = String::new();
+    let _ = writeln!(output, "# HELP {CACHE_ENTRIES} Payloads held in the cache.");
+    let _ = writeln!(output, "# TYPE {CACHE_ENTRIES} gauge");
+    let _ = writeln!(output, "{CACHE_ENTRIES} {}", entries.len());
+    let _ = writeln!(
+        output,
+        "# HELP {CACHE_ENTRY_AGE} Time since the payload was fetched."
+    );
+    let _ = writeln!(output, "# TYPE {CACHE_ENTRY_AGE} gauge");
+    for (key, entry) in &entries {
+        let _ = writeln!(
+            output,
+            "{CACHE_ENTRY_AGE}{{key=\"{}\"}} {}",
+            escape_label(&key.to_string()),
+            entry.fetched_at.elapsed().as_secs_f64()
+        );
+    }
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        output,
+    )
+        .into_response()
+}
+
+/// Every cached payload's key and age, without the payload itself.
+async fn debug_cache(State(state): State<AppState>) -> Json<Vec<CacheEntryInfo>> {
+    let mut entries: Vec<CacheEntryInfo> = state
+        .cached_entries
+        .entries()
+        .await
+        .into_iter()
+        .map(|(key, entry)| {
+            let age_secs = entry.fetched_at.elapsed().as_secs();
+            CacheEntryInfo {
+                key: key.to_string(),
+                age_secs,
+                fresh: age_secs < state.config.cache_duration_secs,
+                content_hash: format!("{:016x}", entry.content_hash),
+            }
+        })
+        .collect();
+    entries.sort_by(|a, b| a.key.cmp(&b.key));
+    Json(entries)
+}
+
+/// Drops every cached payload; the next request of each kind goes upstream.
+async fn purge_cache(State(state): State<AppState>) -> StatusCode {
+    state.cached_entries.clear().await;
+    tracing::info!("cache purged");
+    StatusCode::NO_CONTENT
+}
+
+/// Deletes recorded data past its retention now, instead of at the next
+/// hourly run.
+#[cfg(feature = "recorder")]
+async fn prune_history(State(state): State<AppState>) -> crate::Result<Json<Pruned>> {
+    let store = state
+        .observations
+        .as_ref()
+        .ok_or(AppError::NotConfigured("OBSERVATIONS_DB"))?;
+    let pruned = store.prune(state.config.observations_retention).await?;
+    tracing::info!(?pruned, "observations pruned");
+    Ok(Json(pruned))
+}
+
+/// Today's usage, quota and last request of every tenant key.
+async fn clients(State(tenants): State<Option<Arc<Tenants>>>) -> Json<Vec<ClientUsage>> {
+    match tenants {
+        Some(tenants) => Json(tenants.usage().await),
+        None => Json(Vec::new()),
+    }
+}
@@ -0,0 +1,520 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    str::FromStr,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use reqwest::{Client, RequestBuilder};
+use serde::{de, Deserialize, Deserializer, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::watch;
+
+/// How long a channel may take to answer.
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often the latest payload is evaluated again when it hasn't changed,
+/// so `age_minutes` keeps growing while a station is silent.
+const REEVALUATE_INTERVAL: Duration = Duration::from_secs(60);
+const TELEGRAM_API: &str = "https://api.telegram.org";
+const NTFY_SERVER: &str = "https://ntfy.sh";
+const PUSHOVER_API: &str = "https://api.pushover.net/1/messages.json";
+/// How often Pushover repeats an `urgent` alert until it is acknowledged,
+/// and for how long.
+const PUSHOVER_RETRY_SECS: u64 = 300;
+const PUSHOVER_EXPIRE_SECS: u64 = 3600;
+
+/// Minutes since the observation was taken, computed from its `epoch`.
+const AGE_MINUTES: &str = "age_minutes";
+
+/// Rule metric names and the dot path of their value inside an observation,
+/// metric units.
+const METRICS: &[(&str, &str)] = &[
+    ("temp", "metric.temp"),
+    ("dewpt", "metric.dewpt"),
+    ("heat_index", "metric.heatIndex"),
+    ("wind_chill", "metric.windChill"),
+    ("humidity", "humidity"),
+    ("pressure", "metric.pressure"),
+    ("wind_speed", "metric.windSpeed"),
+    ("wind_gust", "metric.windGust"),
+    ("wind_direction", "winddir"),
+    ("rain_rate", "metric.precipRate"),
+    ("rain_total", "metric.precipTotal"),
+    ("solar_radiation", "solarRadiation"),
+    ("uv", "uv"),
+    (AGE_MINUTES, "epoch"),
+];
+
+/// A threshold on an observed value, listed as `[[rule]]` in
+/// `ALERT_RULES_FILE` and evaluated for every station on each `/current`
+/// refresh.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AlertRule {
+    pub name: String,
+    /// `<metric> <op> <threshold>`, e.g. `temp < 0`; see [`Condition`].
+    pub condition: Condition,
+    /// Receives the JSON event when the rule fires and when it clears.
+    #[serde(default)]
+    pub webhook: Option<String>,
+    /// Names of `[channels.<name>]` notified alongside `webhook`.
+    #[serde(default)]
+    pub channels: Vec<String>,
+    /// How far back past the threshold the value has to go before the rule
+    /// clears, so a reading hovering around it doesn't fire repeatedly.
+    #[serde(default)]
+    pub hysteresis: f64,
+    /// Minimum time between two firings for the same station.
+    #[serde(default = "default_cooldown_secs")]
+    pub cooldown_secs: u64,
+    /// How insistently ntfy and Pushover announce the rule firing; it
+    /// clearing is announced at `default` at most.
+    #[serde(default)]
+    pub priority: Priority,
+}
+
+/// Alert priority, mapped onto each push service's own scale.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    Min,
+    Low,
+    #[default]
+    Default,
+    High,
+    /// Pushover repeats it until acknowledged.
+    Urgent,
+}
+
+impl Priority {
+    /// 1 to 5.
+    fn ntfy(self) -> u8 {
+        self as u8 + 1
+    }
+
+    /// -2 to 2.
+    fn pushover(self) -> i8 {
+        self as i8 - 2
+    }
+}
+
+fn default_cooldown_secs() -> u64 {
+    3600
+}
+
+/// Where alerts are sent, listed as `[channels.<name>]` in
+/// `ALERT_RULES_FILE` and selected by `kind`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum AlertChannel {
+    /// The JSON event, as sent to a rule's `webhook`.
+    Webhook {
+        url: String,
+    },
+    /// A message from a bot that is a member of the chat.
+    Telegram {
+        bot_token: String,
+        chat_id: String,
+    },
+    Discord {
+        webhook: String,
+    },
+    /// A Slack incoming webhook.
+    Slack {
+        webhook: String,
+    },
+    /// A message published to an ntfy topic.
+    Ntfy {
+        topic: String,
+        /// Base URL of a self-hosted server; `https://ntfy.sh` by default.
+        server: Option<String>,
+        /// Access token, for protected topics.
+        token: Option<String>,
+    },
+    Pushover {
+        /// The application's API token.
+        token: String,
+        /// User or group key.
+        user: String,
+        /// Only this device of the user is notified; all when absent.
+        device: Option<String>,
+    },
+}
+
+impl AlertChannel {
+    fn kind(&self) -> &'static str {
+        match self {
+            AlertChannel::Webhook { .. } => "webhook",
+            AlertChannel::Telegram { .. } => "Telegram",
+            AlertChannel::Discord { .. } => "Discord",
+            AlertChannel::Slack { .. } => "Slack",
+            AlertChannel::Ntfy { .. } => "ntfy",
+            AlertChannel::Pushover { .. } => "Pushover",
+        }
+    }
+
+    /// The parts of the configuration granting access to post; an ntfy
+    /// topic without a token is readable by anyone knowing its name.
+    pub(crate) fn secrets(&self) -> Vec<&String> {
+        match self {
+            AlertChannel::Webhook { url } => vec![url],
+            AlertChannel::Telegram { bot_token, .. } => vec![bot_token],
+            AlertChannel::Discord { webhook } | AlertChannel::Slack { webhook } => vec![webhook],
+            AlertChannel::Ntfy { topic, token, .. } => {
+                std::iter::once(topic).chain(token).collect()
+            }
+            AlertChannel::Pushover { token, user, .. } => vec![token, user],
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct AlertRulesFile {
+    #[serde(default)]
+    pub(crate) rule: Vec<AlertRule>,
+    #[serde(default)]
+    pub(crate) channels: HashMap<String, AlertChannel>,
+}
+
+impl AlertRulesFile {
+    /// Every rule notifies somewhere, and only through defined channels.
+    pub(crate) fn check(&self) -> Result<(), String> {
+        for rule in &self.rule {
+            if rule.webhook.is_none() && rule.channels.is_empty() {
+                return Err(format!("rule {} has no webhook or channels", rule.name));
+            }
+            if let Some(unknown) = rule
+                .channels
+                .iter()
+                .find(|name| !self.channels.contains_key(*name))
+            {
+                return Err(format!("rule {} uses unknown channel {unknown}", rule.name));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compares one observed value against a threshold. Metrics are `temp`,
+/// `dewpt`, `heat_index`, `wind_chill`, `humidity`, `pressure`, `wind_speed`,
+/// `wind_gust`, `wind_direction`, `rain_rate`, `rain_total`,
+/// `solar_radiation`, `uv` and `age_minutes`, which flags a station that
+/// stopped reporting; operators `<`, `<=`, `>` and `>=`.
+#[derive(Debug, Clone, Copy)]
+pub struct Condition {
+    metric: &'static str,
+    path: &'static str,
+    operator: Operator,
+    threshold: f64,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Operator {
+    Below,
+    AtMost,
+    Above,
+    AtLeast,
+}
+
+impl Condition {
+    /// The metric's value in `observation`, `None` when not reported.
+    fn reading(&self, observation: &Value, now: i64) -> Option<f64> {
+        let value = self
+            .path
+            .split('.')
+            .try_fold(observation, |value, key| value.get(key))?;
+        if self.metric == AGE_MINUTES {
+            return Some((now - value.as_i64()?) as f64 / 60.0);
+        }
+        value.as_f64()
+    }
+
+    fn holds(&self, value: f64) -> bool {
+        match self.operator {
+            Operator::Below => value < self.threshold,
+            Operator::AtMost => value <= self.threshold,
+            Operator::Above => value > self.threshold,
+            Operator::AtLeast => value >= self.threshold,
+        }
+    }
+
+    /// Whether `value` is back past the threshold by at least `hysteresis`.
+    fn cleared(&self, value: f64, hysteresis: f64) -> bool {
+        match self.operator {
+            Operator::Below | Operator::AtMost => value >= self.threshold + hysteresis,
+            Operator::Above | Operator::AtLeast => value <= self.threshold - hysteresis,
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        let mut parts = raw.split_whitespace();
+        let (Some(metric), Some(operator), Some(threshold), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(format!(
+                "condition {raw:?} is not `<metric> <op> <threshold>`"
+            ));
+        };
+        let (metric, path) = METRICS
+            .iter()
+            .find(|(name, _)| *name == metric)
+            .ok_or_else(|| format!("unknown metric {metric}"))?;
+        let operator = match operator {
+            "<" => Operator::Below,
+            "<=" => Operator::AtMost,
+            ">" => Operator::Above,
+            ">=" => Operator::AtLeast,
+            _ => return Err(format!("unknown operator {operator}")),
+        };
+        let threshold = threshold
+            .parse()
+            .map_err(|_| format!("threshold {threshold} is not a number"))?;
+        Ok(Condition {
+            metric,
+            path,
+            operator,
+            threshold,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for Condition {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = match self.operator {
+            Operator::Below => "<",
+            Operator::AtMost => "<=",
+            Operator::Above => ">",
+            Operator::AtLeast => ">=",
+        };
+        write!(f, "{} {operator} {}", self.metric, self.threshold)
+    }
+}
+
+/// Where a rule stands for one station.
+#[derive(Debug, Default)]
+struct RuleState {
+    active: bool,
+    /// Whether the current activation was announced; a rule that fired
+    /// within its cooldown stays quiet when it clears too.
+    notified: bool,
+    last_fired: Option<Instant>,
+}
+
+/// What happened, as sent to webhooks.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Alert<'a> {
+    rule: &'a str,
+    /// `firing` or `resolved`.
+    state: &'static str,
+    station: &'a str,
+    condition: String,
+    metric: &'static str,
+    value: f64,
+    threshold: f64,
+    obs_time_utc: Option<&'a str>,
+}
+
+impl Alert<'_> {
+    fn title(&self) -> String {
+        format!("{} {}", self.rule, self.state)
+    }
+
+    /// One line for chat channels.
+    fn text(&self) -> String {
+        format!(
+            "{} {} at {}: {} (now {})",
+            self.rule, self.state, self.station, self.condition, self.value
+        )
+    }
+}
+
+/// Evaluates `rules` against every new `/current` payload, and the latest one
+/// again every [`REEVALUATE_INTERVAL`]. Subscribing keeps the background
+/// refresh running, so rules are checked even without clients.
+pub(crate) async fn run(
+    client: Client,
+    rules: Vec<AlertRule>,
+    channels: HashMap<String, AlertChannel>,
+    mut updates: watch::Receiver<Option<Value>>,
+) {
+    let targets: Vec<Vec<AlertChannel>> = rules
+        .iter()
+        .map(|rule| {
+            let webhook = rule
+                .webhook
+                .clone()
+                .map(|url| AlertChannel::Webhook { url });
+            let named = rule.channels.iter().filter_map(|name| channels.get(name));
+            webhook.into_iter().chain(named.cloned()).collect()
+        })
+        .collect();
+    let mut states: HashMap<(usize, String), RuleState> = HashMap::new();
+    let mut interval = tokio::time::interval(REEVALUATE_INTERVAL);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            changed = updates.changed() => if changed.is_err() {
+                return;
+            },
+            _ = interval.tick() => {}
+        }
+        let Some(current) = updates.borrow_and_update().clone() else {
+            continue;
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+        let observations = current
+            .get("observations")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten();
+        for observation in observations {
+            let Some(station) = observation.get("stationID").and_then(Value::as_str) else {
+                continue;
+            };
+            for (index, rule) in rules.iter().enumerate() {
+                let Some(value) = rule.condition.reading(observation, now) else {
+                    continue;
+                };
+                let state = states.entry((index, station.to_string())).or_default();
+                let Some(firing) = transition(rule, state, value) else {
+                    continue;
+                };
+                let alert = Alert {
+                    rule: &rule.name,
+                    state: if firing { "firing" } else { "resolved" },
+                    station,
+                    condition: rule.condition.to_string(),
+                    metric: rule.condition.metric,
+                    value,
+                    threshold: rule.condition.threshold,
+                    obs_time_utc: observation.get("obsTimeUtc").and_then(Value::as_str),
+                };
+                let priority = if firing {
+                    rule.priority
+                } else {
+                    rule.priority.min(Priority::Default)
+                };
+                for channel in &targets[index] {
+                    tokio::spawn(notify(
+                        request(&client, &alert, priority, channel),
+                        rule.name.clone(),
+                        channel.kind(),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Advances `state` for a new reading: `Some(true)` when the rule fires,
+/// `Some(false)` when an announced activation clears.
+fn transition(rule: &AlertRule, state: &mut RuleState, value: f64) -> Option<bool> {
+    if !state.active && rule.condition.holds(value) {
+        state.active = true;
+        let cooldown = Duration::from_secs(rule.cooldown_secs);
+        state.notified = state
+            .last_fired
+            .is_none_or(|fired| fired.elapsed() >= cooldown);
+        if state.notified {
+            state.last_fired = Some(Instant::now());
+            return Some(true);
+        }
+    } else if state.active && rule.condition.cleared(value, rule.hysteresis) {
+        state.active = false;
+        if std::mem::take(&mut state.notified) {
+            return Some(false);
+        }
+    }
+    None
+}
+
+/// The request announcing `alert` on `channel`.
+fn request(
+    client: &Client,
+    alert: &Alert<'_>,
+    priority: Priority,
+    channel: &AlertChannel,
+) -> RequestBuilder {
+    match channel {
+        AlertChannel::Webhook { url } => client.post(url).json(alert),
+        AlertChannel::Telegram { bot_token, chat_id } => client
+            .post(format!("{TELEGRAM_API}/bot{bot_token}/sendMessage"))
+            .json(&json!({ "chat_id": chat_id, "text": alert.text() })),
+        AlertChannel::Discord { webhook } => client
+            .post(webhook)
+            .json(&json!({ "content": alert.text() })),
+        AlertChannel::Slack { webhook } => {
+            client.post(webhook).json(&json!({ "text": alert.text() }))
+        }
+        AlertChannel::Ntfy {
+            topic,
+            server,
+            token,
+        } => {
+            // Published as JSON to the server root, which names the topic.
+            let request = client
+                .post(server.as_deref().unwrap_or(NTFY_SERVER))
+                .json(&json!({
+                    "topic": topic,
+                    "title": alert.title(),
+                    "message": alert.text(),
+                    "priority": priority.ntfy(),
+                    "tags": [if alert.state == "firing" { "warning" } else { "white_check_mark" }],
+                }));
+            match token {
+                Some(token) => request.bearer_auth(token),
+                None => request,
+            }
+        }
+        AlertChannel::Pushover {
+            token,
+            user,
+            device,
+        } => {
+            let mut body = json!({
+                "token": token,
+                "user": user,
+                "title": alert.title(),
+                "message": alert.text(),
+                "priority": priority.pushover(),
+            });
+            if priority == Priority::Urgent {
+                body["retry"] = json!(PUSHOVER_RETRY_SECS);
+                body["expire"] = json!(PUSHOVER_EXPIRE_SECS);
+            }
+            if let Some(device) = device {
+                body["device"] = json!(device);
+            }
+            client.post(PUSHOVER_API).json(&body)
+        }
+    }
+}
+
+async fn notify(request: RequestBuilder, rule: String, kind: &'static str) {
+    let response = request
+        .timeout(NOTIFY_TIMEOUT)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+    match response {
+        Ok(_) => tracing::info!("alert {rule} sent to {kind}"),
+        Err(err) => tracing::warn!("alert {rule} to {kind} failed: {err}"),
+    }
+}
@@ -0,0 +1,294 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::Utc;
+use reqwest::{Client, Method, Response, Url};
+use ring::{digest, hmac};
+use tokio::time::MissedTickBehavior;
+
+use crate::{recorder::ObservationStore, AppConfig, BackupConfig};
+
+/// Backups are `<prefix>observations-<UTC time>.db`, so they sort by age.
+const OBJECT_STEM: &str = "observations-";
+const OBJECT_SUFFIX: &str = ".db";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+/// Restores this object when given to `--restore-from`.
+const LATEST: &str = "latest";
+
+/// Uploads a snapshot of `store` every `interval_secs`, starting at startup,
+/// and deletes all but the newest `keep` backups.
+pub(crate) async fn run(
+    client: Client,
+    store: ObservationStore,
+    db_path: PathBuf,
+    config: BackupConfig,
+) {
+    let bucket = Bucket {
+        client,
+        config: &config,
+    };
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval_secs));
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        match back_up(&bucket, &store, &db_path).await {
+            Ok(key) => tracing::info!("observations backed up to {key}"),
+            Err(err) => tracing::warn!("backing up observations failed: {err}"),
+        }
+    }
+}
+
+async fn back_up(
+    bucket: &Bucket<'_>,
+    store: &ObservationStore,
+    db_path: &Path,
+) -> io::Result<String> {
+    let snapshot = sibling(db_path, "backup");
+    let _ = tokio::fs::remove_file(&snapshot).await;
+    store
+        .snapshot(snapshot.clone())
+        .await
+        .map_err(io::Error::other)?;
+    let body = tokio::fs::read(&snapshot).await;
+    let _ = tokio::fs::remove_file(&snapshot).await;
+
+    let key = format!(
+        "{}{OBJECT_STEM}{}{OBJECT_SUFFIX}",
+        bucket.config.prefix,
+        Utc::now().format("%Y%m%dT%H%M%SZ")
+    );
+    bucket.put(&key, body?).await?;
+
+    let backups = bucket.backups().await?;
+    let stale = backups.len().saturating_sub(bucket.config.keep);
+    for old in &backups[..stale] {
+        bucket.delete(old).await?;
+        tracing::debug!("deleted old backup {old}");
+    }
+    Ok(key)
+}
+
+/// Replaces `OBSERVATIONS_DB` with the backup at `source`, an object key in
+/// the backup bucket or `latest`. Runs before the database is opened; the
+/// replaced database is kept as `<name>.pre-restore`.
+pub async fn restore(config: &AppConfig, source: &str) -> io::Result<()> {
+    let (Some(backup), Some(db_path)) = (&config.backup, &config.observations_db) else {
+        return Err(io::Error::other(
+            "--restore-from needs BACKUP_S3_BUCKET and OBSERVATIONS_DB",
+        ));
+    };
+    let bucket = Bucket {
+        client: Client::new(),
+        config: backup,
+    };
+    let key = if source == LATEST {
+        bucket
+            .backups()
+            .await?
+            .pop()
+            .ok_or_else(|| io::Error::other("no backups in the bucket"))?
+    } else {
+        source.to_string()
+    };
+    let body = bucket.get(&key).await?;
+
+    let download = sibling(db_path, "restore");
+    tokio::fs::write(&download, &body).await?;
+    if tokio::fs::try_exists(db_path).await? {
+        tokio::fs::rename(db_path, sibling(db_path, "pre-restore")).await?;
+    }
+    // A journal left behind would be replayed into the restored database.
+    for journal in ["-wal", "-shm", "-journal"] {
+        let _ = tokio::fs::remove_file(with_suffix(db_path, journal)).await;
+    }
+    tokio::fs::rename(&download, db_path).await?;
+    tracing::info!("restored {} from {key}", db_path.display());
+    Ok(())
+}
+
+/// `<path>.<extension>`.
+fn sibling(path: &Path, extension: &str) -> PathBuf {
+    with_suffix(path, &format!(".{extension}"))
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// The few S3 calls needed, signed with AWS Signature Version 4.
+struct Bucket<'a> {
+    client: Client,
+    config: &'a BackupConfig,
+}
+
+impl Bucket<'_> {
+    async fn put(&self, key: &str, body: Vec<u8>) -> io::Result<()> {
+        self.send(Method::PUT, key, &[], body).await.map(drop)
+    }
+
+    async fn get(&self, key: &str) -> io::Result<Vec<u8>> {
+        let response = self.send(Method::GET, key, &[], Vec::new()).await?;
+        Ok(response.bytes().await.map_err(io::Error::other)?.to_vec())
+    }
+
+    async fn delete(&self, key: &str) -> io::Result<()> {
+        self.send(Method::DELETE, key, &[], Vec::new())
+            .await
+            .map(drop)
+    }
+
+    /// Keys of the backups under the prefix, oldest first.
+    async fn backups(&self) -> io::Result<Vec<String>> {
+        let prefix = format!("{}{OBJECT_STEM}", self.config.prefix);
+        let mut keys = Vec::new();
+        let mut token: Option<String> = None;
+        loop {
+            let mut query = vec![("list-type", "2".to_string()), ("prefix", prefix.clone())];
+            if let Some(token) = token.take() {
+                query.push(("continuation-token", token));
+            }
+            let listing = self
+                .send(Method::GET, "", &query, Vec::new())
+                .await?
+                .text()
+                .await
+                .map_err(io::Error::other)?;
+            keys.extend(
+                xml_values(&listing, "Key")
+                    .into_iter()
+                    .filter(|key| key.ends_with(OBJECT_SUFFIX)),
+            );
+            token = xml_values(&listing, "NextContinuationToken").pop();
+            if token.is_none() {
+                break;
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        key: &str,
+        query: &[(&str, String)],
+        body: Vec<u8>,
+    ) -> io::Result<Response> {
+        let config = self.config;
+        let path = format!(
+            "/{}/{}",
+            uri_encode(&config.bucket, false),
+            uri_encode(key, true)
+        );
+        let mut query: Vec<(String, String)> = query
+            .iter()
+            .map(|(name, value)| (uri_encode(name, false), uri_encode(value, false)))
+            .collect();
+        query.sort();
+        let query = query
+            .iter()
+            .map(|(name, value)| format!("{name}={value}"))
+            .collect::<Vec<_>>()
+            .join("&");
+        let mut url = format!("{}{path}", config.endpoint);
+        if !query.is_empty() {
+            url = format!("{url}?{query}");
+        }
+        let url = Url::parse(&url).map_err(io::Error::other)?;
+        let host = match url.port() {
+            Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+
+        let now = Utc::now();
+        let date_time = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = hex(digest::digest(&digest::SHA256, &body).as_ref());
+        let canonical_request = format!(
+            "{method}\n{path}\n{query}\nhost:{host}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{date_time}\n\n{SIGNED_HEADERS}\n{payload_hash}"
+        );
+        let scope = format!("{date}/{}/s3/aws4_request", config.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{date_time}\n{scope}\n{}",
+            hex(digest::digest(&digest::SHA256, canonical_request.as_bytes()).as_ref())
+        );
+        let signing_key = [config.region.as_str(), "s3", "aws4_request"].iter().fold(
+            sign(
+                format!("AWS4{}", config.secret_access_key).as_bytes(),
+                date.as_bytes(),
+            ),
+            |key, part| sign(&key, part.as_bytes()),
+        );
+        let signature = hex(&sign(&signing_key, string_to_sign.as_bytes()));
+
+        self.client
+            .request(method, url)
+            .timeout(REQUEST_TIMEOUT)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("x-amz-date", date_time)
+            .header(
+                "authorization",
+                format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={SIGNED_HEADERS}, Signature={signature}",
+                    config.access_key_id
+                ),
+            )
+            .body(body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(io::Error::other)
+    }
+}
+
+const SIGNED_HEADERS: &str = "host;x-amz-content-sha256;x-amz-date";
+
+fn sign(key: &[u8], message: &[u8]) -> Vec<u8> {
+    hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), message)
+        .as_ref()
+        .to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Percent-encodes all but unreserved characters, and `/` when `keep_slash`.
+fn uri_encode(value: &str, keep_slash: bool) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            b'/' if keep_slash => "/".to_string(),
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// The text of every `<tag>` element; enough for S3 listings, which only
+/// escape `&`, `<` and `>` in keys.
+fn xml_values(xml: &str, tag: &str) -> Vec<String> {
+    let open = format!("<{tag}>");
+    let close = format!("</{tag}>");
+    xml.split(open.as_str())
+        .skip(1)
+        .filter_map(|rest| {
+            let value = rest.split_once(close.as_str())?.0;
+            Some(
+                value
+                    .replace("&lt;", "<")
+                    .replace("&gt;", ">")
+                    .replace("&quot;", "\"")
+                    .replace("&apos;", "'")
+                    .replace("&amp;", "&"),
+            )
+        })
+        .collect()
+}
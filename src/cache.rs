@@ -0,0 +1,287 @@
+use std::{borrow::Cow, time::Duration};
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde_json::Value;
+use tokio::sync::SemaphorePermit;
+pub(crate) use wunderground_proxy_core::cache::CachedEntry;
+use wunderground_proxy_core::cache::KeyRef;
+
+use crate::{calibration, geohash, models, places, quality, AppState, Result};
+
+/// Observation fields copied from upstream onto local readings.
+const STATION_METADATA: [&str; 4] = ["lat", "lon", "neighborhood", "country"];
+
+pub(crate) async fn current_value(state: &AppState) -> Result<Value> {
+    current_entry(state).await.map(|entry| entry.value)
+}
+
+pub(crate) async fn current_entry(state: &AppState) -> Result<CachedEntry> {
+    match fresh_entry(state, KeyRef::Current).await {
+        None => refresh_current(state).await,
+        Some(cached_value) => Ok(cached_value),
+    }
+}
+
+/// Fetches fresh observations, caches them and notifies live subscribers if
+/// the payload differs from the last one published. In local-first mode a
+/// recent enough reading from the local station is used instead, and
+/// upstream is only asked once the station has gone quiet.
+pub(crate) async fn refresh_current(state: &AppState) -> Result<CachedEntry> {
+    let json = match local_current(state).await {
+        Some(json) => json,
+        None => {
+            let json = {
+                let _permit = upstream_permit(state).await;
+                state.provider.current(&state.client).await?
+            };
+            let mut json = models::validate_current(json)?;
+            calibration::apply(&state.config.calibration, &mut json);
+            quality::check(state, &mut json).await;
+            *state.upstream_current.write().await = Some(json.clone());
+            json
+        }
+    };
+    Ok(publish_current(state, json).await)
+}
+
+/// Publishes a reading the local station just uploaded, when it is to be
+/// served as `/current`.
+pub(crate) async fn publish_local(state: &AppState) {
+    if let Some(json) = local_current(state).await {
+        publish_current(state, json).await;
+    }
+}
+
+async fn publish_current(state: &AppState, json: Value) -> CachedEntry {
+    let entry = store_entry(state, KeyRef::Current, json.clone()).await;
+    #[cfg(feature = "history")]
+    {
+        state.today.lock().await.record(&json);
+        state.pressure_history.lock().await.record(&json);
+    }
+
+    state.current_updates.send_if_modified(|latest| {
+        if latest.as_ref() == Some(&json) {
+            false
+        } else {
+            *latest = Some(json.clone());
+            true
+        }
+    });
+
+    entry
+}
+
+/// The local station's latest reading if local-first serving is on and it is
+/// no older than `local_first_max_age_secs`, completed with the location and
+/// local time offset of the last upstream payload, which uploads don't carry.
+async fn local_current(state: &AppState) -> Option<Value> {
+    let max_age = state.config.local_first_max_age_secs?;
+    let mut local = state.local_observation.read().await.clone()?;
+    let observation = local.pointer_mut("/observations/0")?.as_object_mut()?;
+    let epoch = observation.get("epoch")?.as_i64()?;
+    if Utc::now().timestamp() - epoch > max_age as i64 {
+        return None;
+    }
+
+    if let Some(upstream) = state
+        .upstream_current
+        .read()
+        .await
+        .as_ref()
+        .and_then(|upstream| upstream.pointer("/observations/0"))
+    {
+        for key in STATION_METADATA {
+            if let Some(value) = upstream.get(key) {
+                observation.insert(key.to_string(), value.clone());
+            }
+        }
+        if let (Some(elev), Some(metric)) = (
+            upstream.pointer("/metric/elev"),
+            observation.get_mut("metric").and_then(Value::as_object_mut),
+        ) {
+            metric.insert("elev".to_string(), elev.clone());
+        }
+        if let Some(local_time) = local_time(upstream, epoch) {
+            observation.insert("obsTimeLocal".to_string(), Value::from(local_time));
+        }
+    }
+    models::validate_current(local).ok()
+}
+
+/// `epoch` as `obsTimeLocal`, at the UTC offset of `upstream`'s observation.
+fn local_time(upstream: &Value, epoch: i64) -> Option<String> {
+    let upstream_local =
+        NaiveDateTime::parse_from_str(upstream.get("obsTimeLocal")?.as_str()?, "%Y-%m-%d %H:%M:%S")
+            .ok()?;
+    let offset = upstream_local.and_utc().timestamp() - upstream.get("epoch")?.as_i64()?;
+    let local = DateTime::from_timestamp(epoch + offset, 0)?;
+    Some(local.format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+/// Cache key of the forecast for a location and language.
+pub fn forecast_key<'a>(geocode: &'a str, language: &'a str) -> KeyRef<'a> {
+    KeyRef::Forecast { geocode, language }
+}
+
+/// The geocode a forecast is cached under and fetched for, so nearby
+/// coordinates share one entry: snapped to the nearest point of the
+/// `forecast_grid_degrees` grid, or the center of the geohash cell of
+/// `forecast_geohash_precision` holding it. Geocodes that don't parse are
+/// left for upstream to reject.
+pub(crate) fn forecast_geocode<'a>(state: &AppState, geocode: &'a str) -> Cow<'a, str> {
+    let parsed = geocode
+        .split_once(',')
+        .and_then(|(lat, lon)| Some((lat.trim().parse().ok()?, lon.trim().parse().ok()?)));
+    let Some((lat, lon)) = parsed else {
+        return Cow::Borrowed(geocode);
+    };
+    if let Some(grid) = state.config.forecast_grid_degrees {
+        let decimals = grid_decimals(grid);
+        let snap = |degrees: f64| (degrees / grid).round() * grid;
+        return Cow::Owned(format!(
+            "{:.decimals$},{:.decimals$}",
+            snap(lat).clamp(-90.0, 90.0),
+            snap(lon)
+        ));
+    }
+    if let Some(precision) = state.config.forecast_geohash_precision {
+        let (lat, lon) = geohash::cell_center(lat, lon, precision);
+        return Cow::Owned(format!("{lat:.6},{lon:.6}"));
+    }
+    Cow::Borrowed(geocode)
+}
+
+/// Decimals that print every multiple of `grid` exactly, e.g. 2 for 0.05
+/// and 0.25.
+fn grid_decimals(grid: f64) -> usize {
+    (0..6)
+        .find(|&decimals| {
+            let scaled = grid * 10f64.powi(decimals as i32);
+            (scaled - scaled.round()).abs() < 1e-9
+        })
+        .unwrap_or(6)
+}
+
+pub(crate) async fn forecast_value(
+    state: &AppState,
+    geocode: &str,
+    language: &str,
+) -> Result<Value> {
+    forecast_entry(state, geocode, language)
+        .await
+        .map(|entry| entry.value)
+}
+
+pub(crate) async fn forecast_entry(
+    state: &AppState,
+    geocode: &str,
+    language: &str,
+) -> Result<CachedEntry> {
+    let geocode = &forecast_geocode(state, geocode);
+    let cache_key = forecast_key(geocode, language);
+    match fresh_entry(state, cache_key).await {
+        None => refresh_forecast(state, geocode, language).await,
+        Some(cached_value) => Ok(cached_value),
+    }
+}
+
+/// Fetches a fresh forecast and caches it, keeping the replaced one for
+/// `/forecast/changes` if the content differs.
+pub(crate) async fn refresh_forecast(
+    state: &AppState,
+    geocode: &str,
+    language: &str,
+) -> Result<CachedEntry> {
+    let geocode = &forecast_geocode(state, geocode);
+    let cache_key = forecast_key(geocode, language);
+    let json = {
+        let _permit = upstream_permit(state).await;
+        state
+            .provider
+            .forecast(&state.client, geocode, language)
+            .await?
+    };
+    let mut json = models::validate_forecast(json)?;
+    if state.config.place_names {
+        places::annotate(state, geocode, language, &mut json).await;
+    }
+    #[cfg(feature = "history")]
+    let replaced = state.cached_entries.get(cache_key).await;
+    let entry = store_entry(state, cache_key, json).await;
+    #[cfg(feature = "history")]
+    if let Some(replaced) = replaced {
+        if replaced.content_hash != entry.content_hash {
+            state
+                .previous_forecasts
+                .write()
+                .await
+                .insert(cache_key.to_owned_key(), replaced);
+        }
+    }
+    Ok(entry)
+}
+
+#[cfg(feature = "alerts")]
+fn alerts_key<'a>(geocode: &'a str, language: &'a str) -> KeyRef<'a> {
+    KeyRef::Alerts { geocode, language }
+}
+
+/// Active alert headlines for a geocode; `null` when upstream reports none.
+#[cfg(feature = "alerts")]
+pub(crate) async fn alerts_entry(
+    state: &AppState,
+    geocode: &str,
+    language: &str,
+) -> Result<CachedEntry> {
+    let cache_key = alerts_key(geocode, language);
+    match fresh_entry(state, cache_key).await {
+        None => refresh_alerts(state, geocode, language).await,
+        Some(cached_value) => Ok(cached_value),
+    }
+}
+
+#[cfg(feature = "alerts")]
+pub(crate) async fn refresh_alerts(
+    state: &AppState,
+    geocode: &str,
+    language: &str,
+) -> Result<CachedEntry> {
+    let json = {
+        let _permit = upstream_permit(state).await;
+        state
+            .provider
+            .alerts(&state.client, geocode, language)
+            .await?
+    };
+    let cache_key = alerts_key(geocode, language);
+    Ok(store_entry(state, cache_key, json).await)
+}
+
+/// Waits for one of the `upstream_concurrency` slots, held for a single fetch.
+pub(crate) async fn upstream_permit(state: &AppState) -> SemaphorePermit<'_> {
+    state
+        .upstream_permits
+        .acquire()
+        .await
+        .expect("upstream semaphore is never closed")
+}
+
+/// Returns the cached entry for `cache_key` if it is younger than the cache duration.
+pub(crate) async fn fresh_entry(state: &AppState, cache_key: KeyRef<'_>) -> Option<CachedEntry> {
+    let max_age = Duration::from_secs(state.config.cache_duration_secs);
+    state.cached_entries.fresh(cache_key, max_age).await
+}
+
+pub(crate) async fn store_entry(
+    state: &AppState,
+    cache_key: KeyRef<'_>,
+    value: Value,
+) -> CachedEntry {
+    let entry = CachedEntry::new(value);
+    state
+        .cached_entries
+        .insert(cache_key.to_owned_key(), entry.clone())
+        .await;
+    entry
+}
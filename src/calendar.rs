@@ -0,0 +1,97 @@
+use chrono::Utc;
+use serde_json::Value;
+
+/// Renders each forecast day as an all-day VEVENT with the temperature range
+/// and precipitation chance in the summary and the narrative as description.
+pub fn render(forecast: &Value, geocode: &str) -> String {
+    let at = |key: &str, idx: usize| forecast.get(key).and_then(|values| values.get(idx));
+    let precip_chance = |idx: usize| {
+        let chances = forecast
+            .get("daypart")
+            .and_then(|dayparts| dayparts.get(0))
+            .and_then(|daypart| daypart.get("precipChance"));
+        // Day and night parts are interleaved; today's day part is null once it has passed.
+        [idx * 2, idx * 2 + 1]
+            .into_iter()
+            .filter_map(|part| chances?.get(part)?.as_f64())
+            .reduce(f64::max)
+    };
+
+    let stamp = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//wunderground-cache//forecast//EN".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "X-WR-CALNAME:Weather forecast".to_string(),
+    ];
+
+    let days = forecast
+        .get("validTimeLocal")
+        .and_then(Value::as_array)
+        .map_or(0, Vec::len);
+    for idx in 0..days {
+        let Some(date) = at("validTimeLocal", idx)
+            .and_then(Value::as_str)
+            .and_then(|time| time.get(..10))
+            .map(|date| date.replace('-', ""))
+        else {
+            continue;
+        };
+
+        let number = |value: Option<&Value>| {
+            value
+                .and_then(Value::as_f64)
+                .map_or_else(|| "-".to_string(), |value| format!("{value:.0}"))
+        };
+        let mut summary = format!(
+            "{} {}°/{}°",
+            at("dayOfWeek", idx).and_then(Value::as_str).unwrap_or(""),
+            number(at("temperatureMax", idx)),
+            number(at("temperatureMin", idx)),
+        );
+        if let Some(chance) = precip_chance(idx) {
+            summary.push_str(&format!(", {chance:.0}% precip"));
+        }
+
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!(
+            "UID:{date}-{}@wunderground-cache",
+            escape(&geocode.replace(',', "_"))
+        ));
+        lines.push(format!("DTSTAMP:{stamp}"));
+        lines.push(format!("DTSTART;VALUE=DATE:{date}"));
+        lines.push(format!("SUMMARY:{}", escape(summary.trim())));
+        if let Some(narrative) = at("narrative", idx).and_then(Value::as_str) {
+            lines.push(format!("DESCRIPTION:{}", escape(narrative)));
+        }
+        lines.push("TRANSP:TRANSPARENT".to_string());
+        lines.push("END:VEVENT".to_string());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter().map(|line| fold(line)).collect()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Folds a content line at 75 octets as required by RFC 5545 and terminates it with CRLF.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 8);
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            octets = 1;
+        }
+        folded.push(c);
+        octets += c.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
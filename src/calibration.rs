@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::derived;
+
+/// Corrections for one station's sensors, listed under `[stations.<id>]` in
+/// `CALIBRATION_FILE`. Applied to the metric values of its observations
+/// before they are cached, so every endpoint and integration sees them.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Calibration {
+    /// °C added to the temperature; the heat index and wind chill move with
+    /// it.
+    #[serde(default)]
+    pub temp_offset: f64,
+    /// Percentage points added to the humidity, kept within 0 to 100.
+    #[serde(default)]
+    pub humidity_offset: f64,
+    /// hPa added to the pressure.
+    #[serde(default)]
+    pub pressure_offset: f64,
+    /// Wind speed and gust are multiplied by this.
+    #[serde(default = "unscaled")]
+    pub wind_factor: f64,
+    /// Rain rate and total are multiplied by this.
+    #[serde(default = "unscaled")]
+    pub rain_factor: f64,
+}
+
+fn unscaled() -> f64 {
+    1.0
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CalibrationFile {
+    #[serde(default)]
+    pub(crate) stations: HashMap<String, Calibration>,
+}
+
+/// Corrects every observation in a `/current` payload whose station has a
+/// calibration. The dew point is recomputed when the temperature or humidity
+/// changed.
+pub(crate) fn apply(calibrations: &HashMap<String, Calibration>, current: &mut Value) {
+    if calibrations.is_empty() {
+        return;
+    }
+    let observations = current
+        .get_mut("observations")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten();
+    for observation in observations {
+        let calibration = observation
+            .get("stationID")
+            .and_then(Value::as_str)
+            .and_then(|station| calibrations.get(station));
+        let (Some(calibration), Some(observation)) = (calibration, observation.as_object_mut())
+        else {
+            continue;
+        };
+
+        let humidity = observation
+            .get("humidity")
+            .and_then(Value::as_f64)
+            .map(|humidity| (humidity + calibration.humidity_offset).clamp(0.0, 100.0));
+        if calibration.humidity_offset != 0.0 {
+            set(observation, "humidity", humidity.map(tenths));
+        }
+
+        let Some(metric) = observation.get_mut("metric").and_then(Value::as_object_mut) else {
+            continue;
+        };
+        let mut adjust = |key: &str, correct: &dyn Fn(f64) -> f64| {
+            let value = metric.get(key).and_then(Value::as_f64).map(correct);
+            if value.is_some() {
+                set(metric, key, value);
+            }
+            value
+        };
+        let temp = adjust("temp", &|temp| tenths(temp + calibration.temp_offset));
+        for key in ["heatIndex", "windChill"] {
+            adjust(key, &|value| tenths(value + calibration.temp_offset));
+        }
+        adjust("pressure", &|pressure| {
+            hundredths(pressure + calibration.pressure_offset)
+        });
+        for key in ["windSpeed", "windGust"] {
+            adjust(key, &|speed| tenths(speed * calibration.wind_factor));
+        }
+        for key in ["precipRate", "precipTotal"] {
+            adjust(key, &|rain| hundredths(rain * calibration.rain_factor));
+        }
+        if calibration.temp_offset != 0.0 || calibration.humidity_offset != 0.0 {
+            if let (Some(temp), Some(humidity)) = (temp, humidity) {
+                set(
+                    metric,
+                    "dewpt",
+                    Some(tenths(derived::dew_point(temp, humidity))),
+                );
+            }
+        }
+    }
+}
+
+fn set(object: &mut Map<String, Value>, key: &str, value: Option<f64>) {
+    object.insert(key.to_string(), value.into());
+}
+
+fn tenths(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+fn hundredths(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
@@ -0,0 +1,220 @@
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::header,
+    middleware::Next,
+    response::Response,
+};
+use ipnet::IpNet;
+use tracing::Instrument;
+
+use crate::constants::FORWARDED_FOR_HEADER;
+
+/// The address of the client behind any trusted proxies, `None` when it can't
+/// be told (a Unix socket peer without forwarding headers, or no peer known
+/// at all). Added to every request's extensions.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ClientIp(pub(crate) Option<IpAddr>);
+
+/// Marks requests received over the Unix socket, whose peers are local
+/// processes. Inserted by the server, never by the router itself.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct UnixPeer;
+
+/// Resolves the [`ClientIp`] and runs the rest of the request in a span
+/// carrying it, so every log line names the real client.
+pub(crate) async fn resolve(
+    State(trusted_proxies): State<Arc<Vec<IpNet>>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let client = client_ip(&trusted_proxies, &request);
+    request.extensions_mut().insert(ClientIp(client));
+
+    let span = match client {
+        Some(ip) => tracing::info_span!("request", client = %ip),
+        None => tracing::info_span!("request", client = "unknown"),
+    };
+    next.run(request).instrument(span).await
+}
+
+/// The peer address, or when the peer is a trusted proxy, the rightmost
+/// forwarded address not added by another trusted proxy. Entries left of
+/// that are supplied by the client and ignored. `Forwarded` takes precedence
+/// over `X-Forwarded-For` when a proxy sends both. Unix socket peers have no
+/// address and are trusted, as only local processes can connect. Without a
+/// known peer, e.g. when the router is embedded without `ConnectInfo`, the
+/// headers could come from anyone and the client is unknown.
+fn client_ip(trusted_proxies: &[IpNet], request: &Request) -> Option<IpAddr> {
+    let extensions = request.extensions();
+    let peer = extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_canonical());
+    let is_trusted = |ip: &IpAddr| trusted_proxies.iter().any(|net| net.contains(ip));
+    match peer {
+        Some(ip) if !is_trusted(&ip) => return peer,
+        None if extensions.get::<UnixPeer>().is_none() => return None,
+        _ => {}
+    }
+
+    let mut source = peer;
+    for hop in forwarded_hops(request).into_iter().rev() {
+        let Some(ip) = hop else {
+            // An obfuscated or malformed hop can't be attributed; stop at the
+            // last trusted one.
+            break;
+        };
+        source = Some(ip.to_canonical());
+        if !is_trusted(&ip) {
+            break;
+        }
+    }
+    source
+}
+
+/// Forwarded addresses from client to nearest proxy, `None` for entries that
+/// aren't an IP address.
+fn forwarded_hops(request: &Request) -> Vec<Option<IpAddr>> {
+    let headers = request.headers();
+    let forwarded: Vec<&str> = headers
+        .get_all(header::FORWARDED)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+    if !forwarded.is_empty() {
+        return forwarded
+            .into_iter()
+            .map(|element| {
+                element
+                    .split(';')
+                    .filter_map(|pair| pair.trim().split_once('='))
+                    .find(|(name, _)| name.eq_ignore_ascii_case("for"))
+                    .and_then(|(_, node)| parse_node(node))
+            })
+            .collect();
+    }
+
+    headers
+        .get_all(FORWARDED_FOR_HEADER)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|entry| entry.trim().parse().ok())
+        .collect()
+}
+
+/// A `Forwarded` node: `192.0.2.43`, `"192.0.2.43:47011"` or
+/// `"[2001:db8::17]:4711"`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split_once(']')?.0.parse().ok();
+    }
+    let host = match node.split_once(':') {
+        Some((host, port)) if !port.contains(':') => host,
+        _ => node,
+    };
+    host.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+
+    use super::*;
+
+    fn proxies() -> Vec<IpNet> {
+        vec!["10.0.0.0/8".parse().unwrap()]
+    }
+
+    fn request(peer: Option<&str>, headers: &[(&str, &str)]) -> Request {
+        let mut builder = Request::builder();
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        let mut request = builder.body(Body::empty()).unwrap();
+        if let Some(peer) = peer {
+            let peer: SocketAddr = peer.parse().unwrap();
+            request.extensions_mut().insert(ConnectInfo(peer));
+        }
+        request
+    }
+
+    fn ip(raw: &str) -> Option<IpAddr> {
+        Some(raw.parse().unwrap())
+    }
+
+    #[test]
+    fn untrusted_peer_cannot_claim_another_address() {
+        let request = request(
+            Some("203.0.113.7:5000"),
+            &[
+                ("x-forwarded-for", "192.0.2.1"),
+                ("forwarded", "for=192.0.2.1"),
+            ],
+        );
+        assert_eq!(client_ip(&proxies(), &request), ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn unknown_peer_is_not_trusted() {
+        let request = request(None, &[("x-forwarded-for", "192.0.2.1")]);
+        assert_eq!(client_ip(&proxies(), &request), None);
+    }
+
+    #[test]
+    fn unix_peer_is_trusted() {
+        let mut request = request(None, &[("x-forwarded-for", "192.0.2.1")]);
+        request.extensions_mut().insert(UnixPeer);
+        assert_eq!(client_ip(&proxies(), &request), ip("192.0.2.1"));
+    }
+
+    #[test]
+    fn trusted_peer_yields_rightmost_untrusted_hop() {
+        // The client prepended 192.0.2.1 itself; 198.51.100.9 is what the
+        // first trusted proxy saw.
+        let request = request(
+            Some("10.0.0.2:5000"),
+            &[("x-forwarded-for", "192.0.2.1, 198.51.100.9, 10.0.0.3")],
+        );
+        assert_eq!(client_ip(&proxies(), &request), ip("198.51.100.9"));
+    }
+
+    #[test]
+    fn trusted_peer_without_headers_is_the_client() {
+        let request = request(Some("10.0.0.2:5000"), &[]);
+        assert_eq!(client_ip(&proxies(), &request), ip("10.0.0.2"));
+    }
+
+    #[test]
+    fn malformed_hop_stops_at_last_trusted_one() {
+        let request = request(
+            Some("10.0.0.2:5000"),
+            &[("x-forwarded-for", "192.0.2.1, unknown, 10.0.0.3")],
+        );
+        assert_eq!(client_ip(&proxies(), &request), ip("10.0.0.3"));
+    }
+
+    #[test]
+    fn forwarded_takes_precedence() {
+        let request = request(
+            Some("10.0.0.2:5000"),
+            &[
+                ("forwarded", r#"for="[2001:db8::17]:4711""#),
+                ("x-forwarded-for", "192.0.2.1"),
+            ],
+        );
+        assert_eq!(client_ip(&proxies(), &request), ip("2001:db8::17"));
+    }
+
+    #[test]
+    fn mapped_peer_is_canonical() {
+        let request = request(Some("[::ffff:203.0.113.7]:5000"), &[]);
+        assert_eq!(client_ip(&proxies(), &request), ip("203.0.113.7"));
+    }
+}
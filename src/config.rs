@@ -0,0 +1,1224 @@
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use chrono::NaiveTime;
+use chrono_tz::Tz;
+use ipnet::IpNet;
+use serde::Deserialize;
+
+use crate::{
+    alert_rules::{AlertChannel, AlertRule, AlertRulesFile},
+    calibration::{Calibration, CalibrationFile},
+    constants::{
+        ACME_ACCOUNT_KEY_PATH, ACME_DIRECTORY_URL, ACME_DOMAINS, ACME_EMAIL, ACME_HTTP_ADDR,
+        ACME_RENEW_DAYS, ADMIN_LISTEN_ADDR, ALERT_RULES_FILE, API_KEY, API_KEYS,
+        AUTH_EXEMPT_HEALTH, BACKUP_INTERVAL_SECS, BACKUP_KEEP, BACKUP_S3_ACCESS_KEY_ID,
+        BACKUP_S3_BUCKET, BACKUP_S3_ENDPOINT, BACKUP_S3_PREFIX, BACKUP_S3_REGION,
+        BACKUP_S3_SECRET_ACCESS_KEY, BASIC_AUTH_PASSWORD, BASIC_AUTH_USERNAME, CACHE_DURATION_SECS,
+        CALIBRATION_FILE, DAILY_ROLLUP_RETENTION_DAYS, DIGEST_FROM, DIGEST_GEOCODE,
+        DIGEST_LANGUAGE, DIGEST_SEND_AT, DIGEST_TIMEZONE, DIGEST_TO, DOCS_ENABLED, ECOWITT_ENABLED,
+        ECOWITT_PASSKEY, ECOWITT_STATION_ID, FAILOVER_FILE, FORECAST_GEOHASH_PRECISION,
+        FORECAST_GRID_DEGREES, HMAC_SECRET, HMAC_WINDOW_SECS, HOURLY_ROLLUP_RETENTION_DAYS,
+        INFLUXDB_BUCKET, INFLUXDB_ORG, INFLUXDB_TOKEN, INFLUXDB_URL, IP_ALLOWLIST, IP_DENYLIST,
+        JWT_AUDIENCE, JWT_ISSUER, JWT_JWKS_URL, JWT_SECRET, LISTEN_ADDR, LISTEN_BACKLOG,
+        LISTEN_REUSEPORT, LOCAL_FIRST_MAX_AGE_SECS, LOCATION, MAX_HEADER_BYTES, MAX_QUERY_PARAMS,
+        MAX_URI_LENGTH, MERGE_FILE, MET_NO_USER_AGENT, MQTT_CLIENT_ID, MQTT_DISCOVERY_PREFIX,
+        MQTT_FORECAST_GEOCODE, MQTT_HOST, MQTT_PASSWORD, MQTT_PORT, MQTT_RETAIN, MQTT_TOPIC_PREFIX,
+        MQTT_USERNAME, OBSERVATIONS_DB, OBSERVATIONS_RETENTION_DAYS, PLACE_NAMES, PREFETCH_FILE,
+        PROVIDER, PWS_ID, QUALITY_CHECK, RECORDING_DIR, RECORDING_MODE, REMOTE_WRITE_JOB,
+        REMOTE_WRITE_PASSWORD, REMOTE_WRITE_URL, REMOTE_WRITE_USERNAME, RUNTIME_FLAVOR,
+        SERVER_HTTP, SMTP_HOST, SMTP_PASSWORD, SMTP_PORT, SMTP_TLS, SMTP_USERNAME,
+        SSE_KEEP_ALIVE_SECS, TCP_KEEPALIVE_SECS, TCP_NODELAY, TENANTS_FILE, TLS_CERT_PATH,
+        TLS_CLIENT_CA_PATH, TLS_KEY_PATH, TLS_RELOAD_SECS, TRANSFORMERS_FILE, TRUSTED_PROXIES,
+        UNIX_SOCKET, UNIX_SOCKET_MODE, UPLOAD_FORWARD, UPLOAD_PASSWORD, UPLOAD_STATION_ID,
+        UPSTREAM_CONCURRENCY, UPSTREAM_DNS_CACHE_SECS, UPSTREAM_HEDGE_BUDGET_PERCENT,
+        UPSTREAM_HEDGE_MIN_DELAY_MS, UPSTREAM_HEDGE_PERCENTILE, UPSTREAM_HTTP,
+        UPSTREAM_POOL_IDLE_TIMEOUT_SECS, UPSTREAM_POOL_MAX_IDLE_PER_HOST, UPSTREAM_PREWARM,
+        UPSTREAM_RESOLVE, UPSTREAM_TCP_KEEPALIVE_SECS, WORKER_THREADS,
+    },
+    geohash,
+    prefetch::{PrefetchFile, PrefetchJob},
+    quality::QualityMode,
+    transform::{Transformer, TransformersFile},
+    upstream::{ProviderConfig, RecordingMode},
+};
+use wunderground_proxy_core::{
+    constants::WEATHER_COM_URL,
+    upstream::{FieldGroup, PRIMARY},
+};
+
+#[derive(Debug, Clone)]
+pub struct AppConfig {
+    /// How long upstream responses are served from the cache.
+    pub cache_duration_secs: u64,
+    pub provider: ProviderConfig,
+    /// Interval of SSE keep-alive comments on `/current/stream`.
+    pub sse_keep_alive_secs: u64,
+    /// Serve Swagger UI at `/docs`.
+    pub docs_enabled: bool,
+    /// Protocol used towards the weather provider.
+    pub upstream_http: HttpVersion,
+    /// Connection reuse towards the weather provider.
+    pub upstream_pool: UpstreamPool,
+    /// How upstream host names are resolved.
+    pub upstream_dns: UpstreamDns,
+    /// Upstream fetches in flight at once, across all requests.
+    pub upstream_concurrency: usize,
+    /// Duplicate slow upstream requests; off when `None`.
+    pub hedge: Option<HedgeConfig>,
+    /// Client keys by tenant name; when empty the proxy is open to everyone.
+    pub tenants: HashMap<String, TenantConfig>,
+    /// Bearer tokens accepted alongside tenant keys.
+    pub jwt: Option<JwtConfig>,
+    /// A single username and password accepted via HTTP Basic auth.
+    pub basic_auth: Option<BasicAuthConfig>,
+    /// Shared secret for scripts signing their requests instead of sending a key.
+    pub hmac: Option<HmacConfig>,
+    /// Let `/health` through without credentials when authentication is on.
+    pub auth_exempt_health: bool,
+    /// Reverse proxies whose `Forwarded` and `X-Forwarded-For` entries are
+    /// believed when working out the client address. The TCP peer is read
+    /// from `ConnectInfo<SocketAddr>` when the router is embedded; without it
+    /// the headers are ignored and the client is unknown.
+    pub trusted_proxies: Vec<IpNet>,
+    /// Client address restrictions, applied to every route.
+    pub ip_filter: Option<IpFilterConfig>,
+    /// Bounds on request size, checked before any other work.
+    pub request_limits: RequestLimits,
+    /// Payload rewrites by route (`/current`, `/current/derived`, `/forecast`).
+    pub transformers: HashMap<String, Vec<Transformer>>,
+    /// Refreshes run on a schedule regardless of client traffic.
+    pub prefetch: Vec<PrefetchJob>,
+    /// Thresholds checked on every `/current` refresh.
+    pub alert_rules: Vec<AlertRule>,
+    /// Notification targets of `alert_rules`, by name.
+    pub alert_channels: HashMap<String, AlertChannel>,
+    /// Sensor corrections by station ID, applied to upstream and uploaded
+    /// observations before they are cached.
+    pub calibration: HashMap<String, Calibration>,
+    /// What to do with implausible readings after calibration; unchecked
+    /// when `None`.
+    pub quality_check: Option<QualityMode>,
+    /// Further providers whose payloads are combined with the primary's.
+    pub merge: Option<MergeConfig>,
+    /// Provider serving `/forecast` while the primary one fails.
+    pub failover: Option<FailoverConfig>,
+    /// Geohash length forecast geocodes are bucketed to; exact geocodes are
+    /// cached when `None`.
+    pub forecast_geohash_precision: Option<u8>,
+    /// Spacing in degrees of the grid forecast geocodes are snapped to;
+    /// excludes `forecast_geohash_precision`.
+    pub forecast_grid_degrees: Option<f64>,
+    /// Add the name of the place, looked up upstream, to forecasts as
+    /// `placeName`.
+    pub place_names: bool,
+    /// Broker refreshed observations and the forecast are published to;
+    /// needs the `mqtt` feature.
+    pub mqtt: Option<MqttConfig>,
+    /// InfluxDB v2 server refreshed observations are written to.
+    pub influxdb: Option<InfluxConfig>,
+    /// Prometheus remote-write endpoint the gauges are pushed to; needs the
+    /// `remote-write` feature.
+    pub remote_write: Option<RemoteWriteConfig>,
+    /// Record upstream payloads to `recording_dir`, or replay them from it.
+    pub recording: Option<RecordingMode>,
+    pub recording_dir: PathBuf,
+    /// SQLite database every new observation is appended to; needs the
+    /// `recorder` feature.
+    pub observations_db: Option<PathBuf>,
+    /// How long recorded data is kept in `observations_db`.
+    pub observations_retention: ObservationRetention,
+    /// Bucket `observations_db` is backed up to; needs the `recorder`
+    /// feature.
+    pub backup: Option<BackupConfig>,
+
+    /// Daily email with the forecast, yesterday's extremes and active
+    /// alerts; needs the `email` feature.
+    pub digest: Option<DigestConfig>,
+    /// Accepts readings from a local station at the Wunderground upload
+    /// path.
+    pub upload: Option<UploadConfig>,
+    /// Accepts readings from an Ecowitt gateway's customized upload.
+    pub ecowitt: Option<EcowittConfig>,
+    /// Serve `/current` from local station readings up to this old, and
+    /// fetch upstream only when they are older; `None` when no receiver is
+    /// configured or `LOCAL_FIRST_MAX_AGE_SECS` is 0.
+    pub local_first_max_age_secs: Option<u64>,
+}
+
+/// Age in days past which recorded data is pruned; kept forever when `None`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ObservationRetention {
+    /// Raw samples. At least 2, as rollups of the current day are recomputed
+    /// from them.
+    pub raw_days: Option<u64>,
+    pub hourly_days: Option<u64>,
+    pub daily_days: Option<u64>,
+}
+
+/// An S3-compatible bucket, addressed path-style so MinIO works too.
+#[derive(Debug, Clone)]
+pub struct BackupConfig {
+    /// e.g. `https://s3.eu-central-1.amazonaws.com` or `http://minio:9000`.
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    /// Prepended to the object keys, e.g. `wunderground/`.
+    pub prefix: String,
+    pub interval_secs: u64,
+    /// Backups kept in the bucket; older ones are deleted.
+    pub keep: usize,
+}
+
+/// Further providers and which of them each field group is taken from, read
+/// from `MERGE_FILE`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MergeConfig {
+    /// Providers besides `PROVIDER`, by the name groups refer to them with.
+    #[serde(default)]
+    pub providers: HashMap<String, ProviderConfig>,
+    /// Priority order of provider names per group; `primary` is `PROVIDER`.
+    #[serde(default)]
+    pub groups: HashMap<FieldGroup, Vec<String>>,
+}
+
+impl MergeConfig {
+    /// Groups only name defined providers, and none is defined as `primary`.
+    fn check(&self) -> Result<(), String> {
+        if self.providers.contains_key(PRIMARY) {
+            return Err(format!("provider name {PRIMARY} is reserved for PROVIDER"));
+        }
+        for (group, order) in &self.groups {
+            if let Some(unknown) = order
+                .iter()
+                .find(|name| *name != PRIMARY && !self.providers.contains_key(*name))
+            {
+                return Err(format!("group {group:?} uses unknown provider {unknown}"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Provider `/forecast` falls back to while `PROVIDER` is failing, read from
+/// `FAILOVER_FILE`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FailoverConfig {
+    pub provider: ProviderConfig,
+    /// Failures in a row that open the circuit.
+    #[serde(default = "default_failover_threshold")]
+    pub threshold: u32,
+    /// How long the secondary is used before `PROVIDER` is tried again.
+    #[serde(default = "default_failover_cooldown_secs")]
+    pub cooldown_secs: u64,
+}
+
+fn default_failover_threshold() -> u32 {
+    3
+}
+
+fn default_failover_cooldown_secs() -> u64 {
+    300
+}
+
+#[derive(Debug, Clone)]
+pub struct UploadConfig {
+    /// Station `ID` accepted; any when `None`.
+    pub station_id: Option<String>,
+    /// `PASSWORD` the station must send, usually its Wunderground key.
+    pub password: String,
+    /// Relay every upload to Wunderground as well.
+    pub forward: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct EcowittConfig {
+    /// `PASSKEY` the gateway must send; it takes the place of tenant keys.
+    pub passkey: String,
+    /// `stationID` of the served readings; gateways don't send one.
+    pub station_id: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct DigestConfig {
+    pub smtp: SmtpConfig,
+    pub from: String,
+    pub to: Vec<String>,
+    /// Local time in `timezone` the digest is sent at.
+    pub send_at: NaiveTime,
+    /// Also decides which day is "yesterday".
+    pub timezone: Tz,
+    /// Location of the forecast; the station's own when `None`.
+    pub geocode: Option<String>,
+    /// Language of the forecast narratives.
+    pub language: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub tls: SmtpTls,
+    /// `AUTH PLAIN`, sent when set.
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpTls {
+    /// TLS from the first byte, usually on port 465.
+    Implicit,
+    /// Plain connection upgraded with `STARTTLS`, usually on port 587.
+    StartTls,
+    /// No encryption; only for relays on a trusted network.
+    None,
+}
+
+impl FromStr for SmtpTls {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw {
+            "tls" => Ok(SmtpTls::Implicit),
+            "starttls" => Ok(SmtpTls::StartTls),
+            "none" => Ok(SmtpTls::None),
+            _ => Err(format!("unknown SMTP TLS mode {raw}")),
+        }
+    }
+}
+
+/// A client of a shared proxy, listed under `[tenants.<name>]` in `TENANTS_FILE`
+/// or given as a bare key in `API_KEYS`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TenantConfig {
+    /// Value of the `X-Api-Key` header identifying the tenant.
+    pub key: String,
+    /// Station ids (`stationID` of the observations) the tenant may read; all
+    /// when empty.
+    #[serde(default)]
+    pub stations: Vec<String>,
+    /// Path prefixes without `/v1`, e.g. `/current`; all when empty.
+    #[serde(default)]
+    pub endpoints: Vec<String>,
+    /// Requests per minute; unlimited when absent.
+    pub rate_limit: Option<u32>,
+    /// Requests per UTC day; unlimited when absent.
+    pub daily_quota: Option<u32>,
+}
+
+/// JWT validation, for proxies behind an OIDC provider.
+#[derive(Debug, Clone)]
+pub struct JwtConfig {
+    pub keys: JwtKeys,
+    /// Required `aud` claim; not checked when absent.
+    pub audience: Option<String>,
+    /// Required `iss` claim; not checked when absent.
+    pub issuer: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum JwtKeys {
+    /// Shared secret for HS256, HS384 and HS512 tokens.
+    Secret(String),
+    /// JSON Web Key Set published by the identity provider.
+    JwksUrl(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct BasicAuthConfig {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct HmacConfig {
+    pub secret: String,
+    /// How far `X-Signature-Timestamp` may be from the server clock.
+    pub window_secs: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct MqttConfig {
+    pub host: String,
+    pub port: u16,
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Topics are `<prefix>/<station>/current`, `<prefix>/forecast` and
+    /// `<prefix>/status`.
+    pub topic_prefix: String,
+    pub retain: bool,
+    /// Home Assistant discovery prefix, usually `homeassistant`; no discovery
+    /// payloads are published when `None`.
+    pub discovery_prefix: Option<String>,
+    /// Location of the published forecast; the station's own when `None`.
+    pub forecast_geocode: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct InfluxConfig {
+    /// Base URL of the server, e.g. `http://localhost:8086`.
+    pub url: String,
+    pub org: String,
+    pub bucket: String,
+    /// API token with write access to `bucket`.
+    pub token: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct RemoteWriteConfig {
+    /// e.g. `http://prometheus:9090/api/v1/write`.
+    pub url: String,
+    /// Basic auth, sent when set.
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// `job` label of every pushed series.
+    pub job: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct IpFilterConfig {
+    /// Sources that may connect; everyone not denied when empty.
+    pub allow: Vec<IpNet>,
+    /// Sources rejected even when allowed.
+    pub deny: Vec<IpNet>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RequestLimits {
+    /// Path and query string, in bytes; longer requests get 414.
+    pub max_uri_length: usize,
+    /// Query parameters; more get 414.
+    pub max_query_params: usize,
+    /// Names and values of all headers, in bytes; larger requests get 431.
+    pub max_header_bytes: usize,
+}
+
+#[derive(Deserialize)]
+struct TenantsFile {
+    #[serde(default)]
+    tenants: HashMap<String, TenantConfig>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HedgeConfig {
+    /// Percentile of recent fetch times after which a second request is sent.
+    pub percentile: f64,
+    /// Lower bound of the delay, also used until enough fetches were timed.
+    pub min_delay_ms: u64,
+    /// Share of fetches, in percent, that may be hedged; once it's spent
+    /// slow fetches are waited out.
+    pub budget_percent: f64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct UpstreamDns {
+    /// How long a resolved address is reused; every connection resolves
+    /// afresh when `None`.
+    pub cache_secs: Option<u64>,
+    /// Addresses used for a host instead of asking DNS at all.
+    pub overrides: HashMap<String, Vec<IpAddr>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct UpstreamPool {
+    /// Idle connections kept per host; unbounded when `None`.
+    pub max_idle_per_host: Option<usize>,
+    /// How long an idle connection is kept before closing it.
+    pub idle_timeout_secs: u64,
+    /// TCP keep-alive probe interval; off when `None`.
+    pub tcp_keepalive_secs: Option<u64>,
+    /// Open a connection to the provider at startup, so the first fetch
+    /// doesn't pay for the TLS handshake.
+    pub prewarm: bool,
+}
+
+/// HTTP protocol selection, shared by the listeners and the upstream client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HttpVersion {
+    /// HTTP/1.1 or HTTP/2, whichever the peer picks.
+    Auto,
+    Http1,
+    /// HTTP/2 with prior knowledge, including cleartext h2c.
+    Http2,
+}
+
+impl FromStr for HttpVersion {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw {
+            "auto" => Ok(HttpVersion::Auto),
+            "http1" => Ok(HttpVersion::Http1),
+            "http2" => Ok(HttpVersion::Http2),
+            _ => Err(format!("unknown HTTP version {raw}")),
+        }
+    }
+}
+
+/// Tokio scheduler the binary runs on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeFlavor {
+    MultiThread,
+    /// Everything on the main thread; the smallest footprint on single-core
+    /// boards.
+    CurrentThread,
+}
+
+impl FromStr for RuntimeFlavor {
+    type Err = String;
+
+    fn from_str(raw: &str) -> Result<Self, Self::Err> {
+        match raw {
+            "multi_thread" => Ok(RuntimeFlavor::MultiThread),
+            "current_thread" => Ok(RuntimeFlavor::CurrentThread),
+            _ => Err(format!("unknown runtime flavor {raw}")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RuntimeConfig {
+    pub flavor: RuntimeFlavor,
+    /// Worker threads of the multi-threaded runtime; one per core when `None`.
+    pub worker_threads: Option<usize>,
+}
+
+/// Where the binary accepts connections; at least one listener is required.
+#[derive(Debug, Clone)]
+pub struct ServerConfig {
+    /// TCP addresses, none when `LISTEN_ADDR` is set to an empty string.
+    /// `[::]` accepts IPv4 connections as well, unless an IPv4 address with
+    /// the same port is listed too.
+    pub listen_addrs: Vec<SocketAddr>,
+    /// Unix domain socket path, for reverse proxies on the same host.
+    pub unix_socket: Option<PathBuf>,
+    /// Permission bits applied to the socket file after binding.
+    pub unix_socket_mode: u32,
+    /// Protocols accepted on every listener.
+    pub http: HttpVersion,
+    /// Terminate TLS on the TCP listeners.
+    pub tls: Option<TlsConfig>,
+    /// Plain HTTP listener for the admin routes; not served when `None`.
+    pub admin_addr: Option<SocketAddr>,
+    /// Applied to the public TCP listener and its connections.
+    pub socket: SocketOptions,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SocketOptions {
+    /// Send small responses right away instead of waiting for the client's
+    /// delayed ACK (disables Nagle's algorithm).
+    pub nodelay: bool,
+    /// Idle time before keep-alive probes are sent; off when `None`.
+    pub keepalive_secs: Option<u64>,
+    /// Connections the kernel queues until they are accepted. Ignored for
+    /// sockets passed by systemd.
+    pub backlog: u32,
+    /// Bind with `SO_REUSEPORT`, so several instances can listen on the same
+    /// port and the kernel spreads connections between them. Each instance
+    /// keeps its own cache.
+    pub reuseport: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    /// PEM certificate chain.
+    pub cert_path: PathBuf,
+    /// PEM private key.
+    pub key_path: PathBuf,
+    /// PEM CA certificates; when set, clients must present a certificate
+    /// issued by one of them.
+    pub client_ca_path: Option<PathBuf>,
+    /// How often both files are checked for changes and reloaded.
+    pub reload_secs: u64,
+    /// Obtain and renew the certificate and key automatically.
+    pub acme: Option<AcmeConfig>,
+}
+
+/// Certificates from an ACME CA such as Let's Encrypt, validated through
+/// HTTP-01 challenges. They are written to the TLS certificate and key
+/// paths.
+#[derive(Debug, Clone)]
+pub struct AcmeConfig {
+    /// Names on the certificate; the first one is its common name.
+    pub domains: Vec<String>,
+    /// Contact for expiry and policy notices from the CA.
+    pub email: Option<String>,
+    pub directory_url: String,
+    /// Plain HTTP listener answering challenges; the CA connects to port 80.
+    pub http_addr: SocketAddr,
+    /// PEM account key, created on first use.
+    pub account_key_path: PathBuf,
+    /// Renew once the certificate expires within this many days.
+    pub renew_days: u32,
+}
+
+/// Reads the configuration from environment variables, panicking on missing
+/// required or malformed values.
+pub fn load_config() -> AppConfig {
+    load_config_with_provider(load_provider_config())
+}
+
+/// Reads the upstream selected by `PROVIDER` and its credentials.
+pub fn load_provider_config() -> ProviderConfig {
+    match std::env::var(PROVIDER).as_deref() {
+        Err(_) | Ok("wunderground") => ProviderConfig::Wunderground {
+            pws_id: std::env::var(PWS_ID).expect("PWS_ID not defined"),
+            api_key: std::env::var(API_KEY).expect("API_KEY not defined"),
+            base_url: WEATHER_COM_URL.to_string(),
+        },
+        Ok("open-meteo") => ProviderConfig::OpenMeteo {
+            location: std::env::var(LOCATION).expect("LOCATION not defined"),
+        },
+        Ok("openweathermap") => ProviderConfig::OpenWeatherMap {
+            location: std::env::var(LOCATION).expect("LOCATION not defined"),
+            api_key: std::env::var(API_KEY).expect("API_KEY not defined"),
+        },
+        Ok("met.no") => ProviderConfig::MetNo {
+            location: std::env::var(LOCATION).expect("LOCATION not defined"),
+            user_agent: std::env::var(MET_NO_USER_AGENT).expect("MET_NO_USER_AGENT not defined"),
+        },
+        Ok(_) => panic!("PROVIDER wrong value"),
+    }
+}
+
+/// Like [`load_config`], with the upstream given instead of read from the
+/// environment.
+pub fn load_config_with_provider(provider: ProviderConfig) -> AppConfig {
+    let raw_cache_duration_secs =
+        std::env::var(CACHE_DURATION_SECS).expect("CACHE_DURATION_SECS not defined");
+    let cache_duration_secs: u64 = raw_cache_duration_secs
+        .parse()
+        .expect("CACHE_DURATION_SECS wrong value");
+
+    let sse_keep_alive_secs: u64 = std::env::var(SSE_KEEP_ALIVE_SECS)
+        .map(|raw| {
+            raw.parse()
+                .ok()
+                .filter(|secs| *secs > 0)
+                .expect("SSE_KEEP_ALIVE_SECS wrong value")
+        })
+        .unwrap_or(15);
+
+    let docs_enabled: bool = std::env::var(DOCS_ENABLED)
+        .map(|raw| raw.parse().expect("DOCS_ENABLED wrong value"))
+        .unwrap_or(true);
+
+    let upstream_http: HttpVersion = std::env::var(UPSTREAM_HTTP)
+        .map(|raw| raw.parse().expect("UPSTREAM_HTTP wrong value"))
+        .unwrap_or(HttpVersion::Auto);
+
+    let upstream_pool = UpstreamPool {
+        max_idle_per_host: std::env::var(UPSTREAM_POOL_MAX_IDLE_PER_HOST)
+            .ok()
+            .map(|raw| {
+                raw.parse()
+                    .expect("UPSTREAM_POOL_MAX_IDLE_PER_HOST wrong value")
+            }),
+        idle_timeout_secs: std::env::var(UPSTREAM_POOL_IDLE_TIMEOUT_SECS)
+            .map(|raw| {
+                raw.parse()
+                    .expect("UPSTREAM_POOL_IDLE_TIMEOUT_SECS wrong value")
+            })
+            .unwrap_or(90),
+        tcp_keepalive_secs: std::env::var(UPSTREAM_TCP_KEEPALIVE_SECS).ok().map(|raw| {
+            raw.parse()
+                .expect("UPSTREAM_TCP_KEEPALIVE_SECS wrong value")
+        }),
+        prewarm: std::env::var(UPSTREAM_PREWARM)
+            .map(|raw| raw.parse().expect("UPSTREAM_PREWARM wrong value"))
+            .unwrap_or(true),
+    };
+
+    let upstream_dns = UpstreamDns {
+        cache_secs: std::env::var(UPSTREAM_DNS_CACHE_SECS)
+            .ok()
+            .map(|raw| raw.parse().expect("UPSTREAM_DNS_CACHE_SECS wrong value")),
+        overrides: std::env::var(UPSTREAM_RESOLVE)
+            .map(|raw| parse_resolve(&raw))
+            .unwrap_or_default(),
+    };
+
+    let upstream_concurrency: usize = std::env::var(UPSTREAM_CONCURRENCY)
+        .map(|raw| {
+            raw.parse()
+                .ok()
+                .filter(|&permits| permits > 0)
+                .expect("UPSTREAM_CONCURRENCY wrong value")
+        })
+        .unwrap_or(8);
+
+    let hedge = std::env::var(UPSTREAM_HEDGE_PERCENTILE)
+        .ok()
+        .map(|raw| HedgeConfig {
+            percentile: raw
+                .parse()
+                .ok()
+                .filter(|percentile| (0.0..=100.0).contains(percentile))
+                .expect("UPSTREAM_HEDGE_PERCENTILE wrong value"),
+            min_delay_ms: std::env::var(UPSTREAM_HEDGE_MIN_DELAY_MS)
+                .map(|raw| {
+                    raw.parse()
+                        .expect("UPSTREAM_HEDGE_MIN_DELAY_MS wrong value")
+                })
+                .unwrap_or(1500),
+            budget_percent: std::env::var(UPSTREAM_HEDGE_BUDGET_PERCENT)
+                .map(|raw| {
+                    raw.parse()
+                        .ok()
+                        .filter(|percent| (0.0..=100.0).contains(percent))
+                        .expect("UPSTREAM_HEDGE_BUDGET_PERCENT wrong value")
+                })
+                .unwrap_or(10.0),
+        });
+
+    let mut tenants: HashMap<String, TenantConfig> = std::env::var(TENANTS_FILE)
+        .map(|path| {
+            let raw = std::fs::read_to_string(path).expect("TENANTS_FILE not readable");
+            let file: TenantsFile = toml::from_str(&raw).expect("TENANTS_FILE wrong value");
+            file.tenants
+        })
+        .unwrap_or_default();
+    if let Ok(raw) = std::env::var(API_KEYS) {
+        tenants.extend(parse_api_keys(&raw));
+    }
+
+    let jwt_keys: Option<JwtKeys> = match (std::env::var(JWT_SECRET), std::env::var(JWT_JWKS_URL)) {
+        (Ok(_), Ok(_)) => panic!("JWT_SECRET and JWT_JWKS_URL are mutually exclusive"),
+        (Ok(secret), Err(_)) => Some(JwtKeys::Secret(secret)),
+        (Err(_), Ok(url)) => Some(JwtKeys::JwksUrl(url)),
+        (Err(_), Err(_)) => None,
+    };
+    let jwt: Option<JwtConfig> = jwt_keys.map(|keys| JwtConfig {
+        keys,
+        audience: std::env::var(JWT_AUDIENCE).ok(),
+        issuer: std::env::var(JWT_ISSUER).ok(),
+    });
+
+    let basic_auth: Option<BasicAuthConfig> = match (
+        std::env::var(BASIC_AUTH_USERNAME),
+        std::env::var(BASIC_AUTH_PASSWORD),
+    ) {
+        (Ok(username), Ok(password)) => Some(BasicAuthConfig { username, password }),
+        (Err(_), Err(_)) => None,
+        _ => panic!("BASIC_AUTH_USERNAME and BASIC_AUTH_PASSWORD must be set together"),
+    };
+
+    let hmac: Option<HmacConfig> = std::env::var(HMAC_SECRET).ok().map(|secret| HmacConfig {
+        secret,
+        window_secs: std::env::var(HMAC_WINDOW_SECS)
+            .map(|raw| raw.parse().expect("HMAC_WINDOW_SECS wrong value"))
+            .unwrap_or(300),
+    });
+
+    let auth_exempt_health: bool = std::env::var(AUTH_EXEMPT_HEALTH)
+        .map(|raw| raw.parse().expect("AUTH_EXEMPT_HEALTH wrong value"))
+        .unwrap_or(true);
+
+    let allow = std::env::var(IP_ALLOWLIST)
+        .map(|raw| parse_networks(&raw, IP_ALLOWLIST))
+        .unwrap_or_default();
+    let deny = std::env::var(IP_DENYLIST)
+        .map(|raw| parse_networks(&raw, IP_DENYLIST))
+        .unwrap_or_default();
+    let trusted_proxies = std::env::var(TRUSTED_PROXIES)
+        .map(|raw| parse_networks(&raw, TRUSTED_PROXIES))
+        .unwrap_or_default();
+    let ip_filter: Option<IpFilterConfig> =
+        (!allow.is_empty() || !deny.is_empty()).then_some(IpFilterConfig { allow, deny });
+
+    let request_limits = RequestLimits {
+        max_uri_length: std::env::var(MAX_URI_LENGTH)
+            .map(|raw| raw.parse().expect("MAX_URI_LENGTH wrong value"))
+            .unwrap_or(2048),
+        max_query_params: std::env::var(MAX_QUERY_PARAMS)
+            .map(|raw| raw.parse().expect("MAX_QUERY_PARAMS wrong value"))
+            .unwrap_or(32),
+        max_header_bytes: std::env::var(MAX_HEADER_BYTES)
+            .map(|raw| raw.parse().expect("MAX_HEADER_BYTES wrong value"))
+            .unwrap_or(16 * 1024),
+    };
+
+    let transformers: HashMap<String, Vec<Transformer>> = std::env::var(TRANSFORMERS_FILE)
+        .map(|path| {
+            let raw = std::fs::read_to_string(path).expect("TRANSFORMERS_FILE not readable");
+            let file: TransformersFile =
+                toml::from_str(&raw).expect("TRANSFORMERS_FILE wrong value");
+            file.routes
+        })
+        .unwrap_or_default();
+
+    let prefetch: Vec<PrefetchJob> = std::env::var(PREFETCH_FILE)
+        .map(|path| {
+            let raw = std::fs::read_to_string(path).expect("PREFETCH_FILE not readable");
+            let file: PrefetchFile = toml::from_str(&raw).expect("PREFETCH_FILE wrong value");
+            file.prefetch
+        })
+        .unwrap_or_default();
+
+    let (alert_rules, alert_channels) = std::env::var(ALERT_RULES_FILE)
+        .map(|path| {
+            let raw = std::fs::read_to_string(path).expect("ALERT_RULES_FILE not readable");
+            let file: AlertRulesFile = toml::from_str(&raw).expect("ALERT_RULES_FILE wrong value");
+            if let Err(err) = file.check() {
+                panic!("ALERT_RULES_FILE wrong value: {err}");
+            }
+            (file.rule, file.channels)
+        })
+        .unwrap_or_default();
+
+    let calibration: HashMap<String, Calibration> = std::env::var(CALIBRATION_FILE)
+        .map(|path| {
+            let raw = std::fs::read_to_string(path).expect("CALIBRATION_FILE not readable");
+            let file: CalibrationFile = toml::from_str(&raw).expect("CALIBRATION_FILE wrong value");
+            file.stations
+        })
+        .unwrap_or_default();
+
+    let merge: Option<MergeConfig> = std::env::var(MERGE_FILE).ok().map(|path| {
+        let raw = std::fs::read_to_string(path).expect("MERGE_FILE not readable");
+        let merge: MergeConfig = toml::from_str(&raw).expect("MERGE_FILE wrong value");
+        if let Err(err) = merge.check() {
+            panic!("MERGE_FILE wrong value: {err}");
+        }
+        merge
+    });
+
+    let failover: Option<FailoverConfig> = std::env::var(FAILOVER_FILE).ok().map(|path| {
+        let raw = std::fs::read_to_string(path).expect("FAILOVER_FILE not readable");
+        toml::from_str(&raw).expect("FAILOVER_FILE wrong value")
+    });
+
+    let forecast_geohash_precision: Option<u8> =
+        std::env::var(FORECAST_GEOHASH_PRECISION).ok().map(|raw| {
+            raw.parse()
+                .ok()
+                .filter(|precision| (1..=geohash::MAX_PRECISION).contains(precision))
+                .expect("FORECAST_GEOHASH_PRECISION wrong value")
+        });
+
+    let forecast_grid_degrees: Option<f64> = std::env::var(FORECAST_GRID_DEGREES).ok().map(|raw| {
+        raw.parse()
+            .ok()
+            .filter(|grid: &f64| grid.is_finite() && *grid > 0.0 && *grid <= 90.0)
+            .expect("FORECAST_GRID_DEGREES wrong value")
+    });
+    if forecast_grid_degrees.is_some() && forecast_geohash_precision.is_some() {
+        panic!("FORECAST_GRID_DEGREES and FORECAST_GEOHASH_PRECISION are mutually exclusive");
+    }
+
+    let place_names: bool = std::env::var(PLACE_NAMES)
+        .map(|raw| raw.parse().expect("PLACE_NAMES wrong value"))
+        .unwrap_or(false);
+
+    let quality_check: Option<QualityMode> = match std::env::var(QUALITY_CHECK).as_deref() {
+        Err(_) | Ok("off") => None,
+        Ok("flag") => Some(QualityMode::Flag),
+        Ok("substitute") => Some(QualityMode::Substitute),
+        Ok(_) => panic!("QUALITY_CHECK wrong value"),
+    };
+
+    let recording: Option<RecordingMode> = match std::env::var(RECORDING_MODE).as_deref() {
+        Err(_) => None,
+        Ok("record") => Some(RecordingMode::Record),
+        Ok("replay") => Some(RecordingMode::Replay),
+        Ok(_) => panic!("RECORDING_MODE wrong value"),
+    };
+
+    let recording_dir: PathBuf = std::env::var(RECORDING_DIR)
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("recordings"));
+
+    let mqtt: Option<MqttConfig> = std::env::var(MQTT_HOST).ok().map(|host| MqttConfig {
+        host,
+        port: std::env::var(MQTT_PORT)
+            .map(|raw| raw.parse().expect("MQTT_PORT wrong value"))
+            .unwrap_or(1883),
+        client_id: std::env::var(MQTT_CLIENT_ID)
+            .unwrap_or_else(|_| "wunderground-cache".to_string()),
+        username: std::env::var(MQTT_USERNAME).ok(),
+        password: std::env::var(MQTT_PASSWORD).ok(),
+        topic_prefix: std::env::var(MQTT_TOPIC_PREFIX)
+            .unwrap_or_else(|_| "wunderground".to_string()),
+        retain: std::env::var(MQTT_RETAIN)
+            .map(|raw| raw.parse().expect("MQTT_RETAIN wrong value"))
+            .unwrap_or(true),
+        discovery_prefix: std::env::var(MQTT_DISCOVERY_PREFIX).ok(),
+        forecast_geocode: std::env::var(MQTT_FORECAST_GEOCODE).ok(),
+    });
+
+    let influxdb: Option<InfluxConfig> = std::env::var(INFLUXDB_URL).ok().map(|url| InfluxConfig {
+        url: url.trim_end_matches('/').to_string(),
+        org: std::env::var(INFLUXDB_ORG).expect("INFLUXDB_ORG not defined"),
+        bucket: std::env::var(INFLUXDB_BUCKET).expect("INFLUXDB_BUCKET not defined"),
+        token: std::env::var(INFLUXDB_TOKEN).expect("INFLUXDB_TOKEN not defined"),
+    });
+
+    let remote_write: Option<RemoteWriteConfig> =
+        std::env::var(REMOTE_WRITE_URL)
+            .ok()
+            .map(|url| RemoteWriteConfig {
+                url,
+                username: std::env::var(REMOTE_WRITE_USERNAME).ok(),
+                password: std::env::var(REMOTE_WRITE_PASSWORD).ok(),
+                job: std::env::var(REMOTE_WRITE_JOB)
+                    .unwrap_or_else(|_| "wunderground-cache".to_string()),
+            });
+
+    let observations_db: Option<PathBuf> = std::env::var(OBSERVATIONS_DB).ok().map(PathBuf::from);
+    let observations_retention = ObservationRetention {
+        raw_days: std::env::var(OBSERVATIONS_RETENTION_DAYS).ok().map(|raw| {
+            raw.parse()
+                .ok()
+                .filter(|days| *days >= 2)
+                .expect("OBSERVATIONS_RETENTION_DAYS wrong value")
+        }),
+        hourly_days: std::env::var(HOURLY_ROLLUP_RETENTION_DAYS).ok().map(|raw| {
+            raw.parse()
+                .expect("HOURLY_ROLLUP_RETENTION_DAYS wrong value")
+        }),
+        daily_days: std::env::var(DAILY_ROLLUP_RETENTION_DAYS).ok().map(|raw| {
+            raw.parse()
+                .expect("DAILY_ROLLUP_RETENTION_DAYS wrong value")
+        }),
+    };
+
+    let backup: Option<BackupConfig> =
+        std::env::var(BACKUP_S3_BUCKET)
+            .ok()
+            .map(|bucket| BackupConfig {
+                endpoint: std::env::var(BACKUP_S3_ENDPOINT)
+                    .expect("BACKUP_S3_ENDPOINT not defined")
+                    .trim_end_matches('/')
+                    .to_string(),
+                bucket,
+                region: std::env::var(BACKUP_S3_REGION).unwrap_or_else(|_| "us-east-1".to_string()),
+                access_key_id: std::env::var(BACKUP_S3_ACCESS_KEY_ID)
+                    .expect("BACKUP_S3_ACCESS_KEY_ID not defined"),
+                secret_access_key: std::env::var(BACKUP_S3_SECRET_ACCESS_KEY)
+                    .expect("BACKUP_S3_SECRET_ACCESS_KEY not defined"),
+                prefix: std::env::var(BACKUP_S3_PREFIX).unwrap_or_default(),
+                interval_secs: std::env::var(BACKUP_INTERVAL_SECS)
+                    .map(|raw| {
+                        raw.parse()
+                            .ok()
+                            .filter(|secs| *secs > 0)
+                            .expect("BACKUP_INTERVAL_SECS wrong value")
+                    })
+                    .unwrap_or(86_400),
+                keep: std::env::var(BACKUP_KEEP)
+                    .map(|raw| {
+                        raw.parse()
+                            .ok()
+                            .filter(|keep| *keep > 0)
+                            .expect("BACKUP_KEEP wrong value")
+                    })
+                    .unwrap_or(7),
+            });
+
+    let digest: Option<DigestConfig> = std::env::var(DIGEST_TO).ok().map(|to| {
+        let port = std::env::var(SMTP_PORT)
+            .map(|raw| raw.parse().expect("SMTP_PORT wrong value"))
+            .unwrap_or(587);
+        DigestConfig {
+            smtp: SmtpConfig {
+                host: std::env::var(SMTP_HOST).expect("SMTP_HOST not defined"),
+                port,
+                tls: std::env::var(SMTP_TLS)
+                    .map(|raw| raw.parse().expect("SMTP_TLS wrong value"))
+                    .unwrap_or(if port == 465 {
+                        SmtpTls::Implicit
+                    } else {
+                        SmtpTls::StartTls
+                    }),
+                username: std::env::var(SMTP_USERNAME).ok(),
+                password: std::env::var(SMTP_PASSWORD).ok(),
+            },
+            from: std::env::var(DIGEST_FROM).expect("DIGEST_FROM not defined"),
+            to: to
+                .split(',')
+                .map(str::trim)
+                .filter(|address| !address.is_empty())
+                .map(String::from)
+                .collect(),
+            send_at: std::env::var(DIGEST_SEND_AT)
+                .map(|raw| {
+                    NaiveTime::parse_from_str(&raw, "%H:%M").expect("DIGEST_SEND_AT wrong value")
+                })
+                .unwrap_or_else(|_| NaiveTime::from_hms_opt(7, 0, 0).unwrap_or_default()),
+            timezone: std::env::var(DIGEST_TIMEZONE)
+                .map(|raw| raw.parse().expect("DIGEST_TIMEZONE wrong value"))
+                .unwrap_or(Tz::UTC),
+            geocode: std::env::var(DIGEST_GEOCODE).ok(),
+            language: std::env::var(DIGEST_LANGUAGE).unwrap_or_else(|_| "en-US".to_string()),
+        }
+    });
+
+    let upload: Option<UploadConfig> =
+        std::env::var(UPLOAD_PASSWORD)
+            .ok()
+            .map(|password| UploadConfig {
+                station_id: std::env::var(UPLOAD_STATION_ID).ok(),
+                password,
+                forward: std::env::var(UPLOAD_FORWARD)
+                    .map(|raw| raw.parse().expect("UPLOAD_FORWARD wrong value"))
+                    .unwrap_or(false),
+            });
+
+    let ecowitt: Option<EcowittConfig> = std::env::var(ECOWITT_ENABLED)
+        .map(|raw| raw.parse().expect("ECOWITT_ENABLED wrong value"))
+        .unwrap_or(false)
+        .then(|| EcowittConfig {
+            passkey: std::env::var(ECOWITT_PASSKEY).expect("ECOWITT_PASSKEY not defined"),
+            station_id: std::env::var(ECOWITT_STATION_ID).unwrap_or_else(|_| "ecowitt".to_string()),
+        });
+
+    let local_first_max_age_secs: Option<u64> = (upload.is_some() || ecowitt.is_some())
+        .then(|| {
+            std::env::var(LOCAL_FIRST_MAX_AGE_SECS)
+                .map(|raw| raw.parse().expect("LOCAL_FIRST_MAX_AGE_SECS wrong value"))
+                .unwrap_or(300)
+        })
+        .filter(|secs| *secs > 0);
+
+    AppConfig {
+        cache_duration_secs,
+        provider,
+        sse_keep_alive_secs,
+        docs_enabled,
+        upstream_http,
+        upstream_pool,
+        upstream_dns,
+        upstream_concurrency,
+        hedge,
+        tenants,
+        jwt,
+        basic_auth,
+        hmac,
+        auth_exempt_health,
+        trusted_proxies,
+        ip_filter,
+        request_limits,
+        transformers,
+        prefetch,
+        alert_rules,
+        alert_channels,
+        calibration,
+        quality_check,
+        merge,
+        failover,
+        forecast_geohash_precision,
+        forecast_grid_degrees,
+        place_names,
+        mqtt,
+        influxdb,
+        remote_write,
+        recording,
+        recording_dir,
+        observations_db,
+        observations_retention,
+        backup,
+        digest,
+        upload,
+        ecowitt,
+        local_first_max_age_secs,
+    }
+}
+
+/// Parses comma separated `key[:requests_per_minute[:requests_per_day]]`
+/// entries into tenants named `api-key-<n>`, numbered from 1, with no
+/// endpoint or station restrictions. An empty limit means unlimited.
+fn parse_api_keys(raw: &str) -> impl Iterator<Item = (String, TenantConfig)> + '_ {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .enumerate()
+        .map(|(index, entry)| {
+            let mut parts = entry.split(':');
+            let key = parts.next().unwrap_or_default();
+            let mut limit = || {
+                parts
+                    .next()
+                    .filter(|raw| !raw.is_empty())
+                    .map(|raw| raw.parse().expect("API_KEYS wrong value"))
+            };
+            let tenant = TenantConfig {
+                key: key.to_string(),
+                stations: Vec::new(),
+                endpoints: Vec::new(),
+                rate_limit: limit(),
+                daily_quota: limit(),
+            };
+            (format!("api-key-{}", index + 1), tenant)
+        })
+}
+
+/// Parses `host=ip[,ip...]` entries separated by `;`, e.g.
+/// `api.weather.com=23.45.67.89,2001:db8::1`.
+fn parse_resolve(raw: &str) -> HashMap<String, Vec<IpAddr>> {
+    raw.split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (host, addrs) = entry.split_once('=').expect("UPSTREAM_RESOLVE wrong value");
+            let addrs = addrs
+                .split(',')
+                .map(|addr| addr.trim().parse().expect("UPSTREAM_RESOLVE wrong value"))
+                .collect();
+            (host.trim().to_string(), addrs)
+        })
+        .collect()
+}
+
+/// Parses comma separated CIDR networks, with bare addresses taken as
+/// single-host networks.
+fn parse_networks(raw: &str, name: &str) -> Vec<IpNet> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            entry
+                .parse::<IpNet>()
+                .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                .unwrap_or_else(|_| panic!("{name} wrong value"))
+        })
+        .collect()
+}
+
+/// Reads the runtime configuration from environment variables, panicking on
+/// malformed values or worker threads requested for a current-thread runtime.
+pub fn load_runtime_config() -> RuntimeConfig {
+    let flavor: RuntimeFlavor = std::env::var(RUNTIME_FLAVOR)
+        .map(|raw| raw.parse().expect("RUNTIME_FLAVOR wrong value"))
+        .unwrap_or(RuntimeFlavor::MultiThread);
+
+    let worker_threads: Option<usize> = std::env::var(WORKER_THREADS).ok().map(|raw| {
+        raw.parse()
+            .ok()
+            .filter(|&threads| threads > 0)
+            .expect("WORKER_THREADS wrong value")
+    });
+
+    if flavor == RuntimeFlavor::CurrentThread && worker_threads.is_some() {
+        panic!("WORKER_THREADS requires RUNTIME_FLAVOR=multi_thread");
+    }
+
+    RuntimeConfig {
+        flavor,
+        worker_threads,
+    }
+}
+
+/// Reads the listener configuration from environment variables, panicking on
+/// malformed values or when every listener is disabled.
+pub fn load_server_config() -> ServerConfig {
+    let listen_addrs: Vec<SocketAddr> = match std::env::var(LISTEN_ADDR) {
+        Err(_) => vec![SocketAddr::from(([0, 0, 0, 0], 8080))],
+        Ok(raw) if raw.is_empty() => Vec::new(),
+        Ok(raw) => raw
+            .split(',')
+            .map(|addr| addr.trim().parse().expect("LISTEN_ADDR wrong value"))
+            .collect(),
+    };
+
+    let unix_socket: Option<PathBuf> = std::env::var(UNIX_SOCKET).ok().map(PathBuf::from);
+
+    let unix_socket_mode: u32 = std::env::var(UNIX_SOCKET_MODE)
+        .map(|raw| u32::from_str_radix(&raw, 8).expect("UNIX_SOCKET_MODE wrong value"))
+        .unwrap_or(0o660);
+
+    let http: HttpVersion = std::env::var(SERVER_HTTP)
+        .map(|raw| raw.parse().expect("SERVER_HTTP wrong value"))
+        .unwrap_or(HttpVersion::Auto);
+
+    let tls = match (std::env::var(TLS_CERT_PATH), std::env::var(TLS_KEY_PATH)) {
+        (Ok(cert_path), Ok(key_path)) => {
+            let key_path = PathBuf::from(key_path);
+            let acme = std::env::var(ACME_DOMAINS).ok().map(|raw| AcmeConfig {
+                domains: raw
+                    .split(',')
+                    .map(|domain| domain.trim().to_string())
+                    .collect(),
+                email: std::env::var(ACME_EMAIL).ok(),
+                directory_url: std::env::var(ACME_DIRECTORY_URL).unwrap_or_else(|_| {
+                    "https://acme-v02.api.letsencrypt.org/directory".to_string()
+                }),
+                http_addr: std::env::var(ACME_HTTP_ADDR)
+                    .map(|raw| raw.parse().expect("ACME_HTTP_ADDR wrong value"))
+                    .unwrap_or_else(|_| SocketAddr::from(([0, 0, 0, 0], 80))),
+                account_key_path: std::env::var(ACME_ACCOUNT_KEY_PATH)
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|_| key_path.with_file_name("acme-account.pem")),
+                renew_days: std::env::var(ACME_RENEW_DAYS)
+                    .map(|raw| raw.parse().expect("ACME_RENEW_DAYS wrong value"))
+                    .unwrap_or(30),
+            });
+            if acme
+                .as_ref()
+                .is_some_and(|acme| acme.domains.iter().any(String::is_empty))
+            {
+                panic!("ACME_DOMAINS wrong value");
+            }
+            Some(TlsConfig {
+                cert_path: PathBuf::from(cert_path),
+                key_path,
+                client_ca_path: std::env::var(TLS_CLIENT_CA_PATH).ok().map(PathBuf::from),
+                reload_secs: std::env::var(TLS_RELOAD_SECS)
+                    .map(|raw| raw.parse().expect("TLS_RELOAD_SECS wrong value"))
+                    .unwrap_or(60),
+                acme,
+            })
+        }
+        (Err(_), Err(_)) if std::env::var(TLS_CLIENT_CA_PATH).is_ok() => {
+            panic!("TLS_CLIENT_CA_PATH requires TLS_CERT_PATH and TLS_KEY_PATH")
+        }
+        (Err(_), Err(_)) if std::env::var(ACME_DOMAINS).is_ok() => {
+            panic!("ACME_DOMAINS requires TLS_CERT_PATH and TLS_KEY_PATH")
+        }
+        (Err(_), Err(_)) => None,
+        _ => panic!("TLS_CERT_PATH and TLS_KEY_PATH must be defined together"),
+    };
+
+    if listen_addrs.is_empty() && unix_socket.is_none() {
+        panic!("LISTEN_ADDR or UNIX_SOCKET must be defined");
+    }
+
+    let admin_addr: Option<SocketAddr> = std::env::var(ADMIN_LISTEN_ADDR)
+        .ok()
+        .map(|raw| raw.parse().expect("ADMIN_LISTEN_ADDR wrong value"));
+
+    let socket = SocketOptions {
+        nodelay: std::env::var(TCP_NODELAY)
+            .map(|raw| raw.parse().expect("TCP_NODELAY wrong value"))
+            .unwrap_or(false),
+        keepalive_secs: std::env::var(TCP_KEEPALIVE_SECS)
+            .ok()
+            .map(|raw| raw.parse().expect("TCP_KEEPALIVE_SECS wrong value")),
+        backlog: std::env::var(LISTEN_BACKLOG)
+            .map(|raw| raw.parse().expect("LISTEN_BACKLOG wrong value"))
+            .unwrap_or(1024),
+        reuseport: std::env::var(LISTEN_REUSEPORT)
+            .map(|raw| raw.parse().expect("LISTEN_REUSEPORT wrong value"))
+            .unwrap_or(false),
+    };
+
+    ServerConfig {
+        listen_addrs,
+        unix_socket,
+        unix_socket_mode,
+        http,
+        tls,
+        admin_addr,
+        socket,
+    }
+}
@@ -1,8 +1,135 @@
 pub const CACHE_DURATION_SECS: &str = "CACHE_DURATION_SECS";
 pub const PWS_ID: &str = "PWS_ID";
 pub const API_KEY: &str = "API_KEY";
+pub const PROVIDER: &str = "PROVIDER";
+pub const LOCATION: &str = "LOCATION";
+pub const MET_NO_USER_AGENT: &str = "MET_NO_USER_AGENT";
+pub const SSE_KEEP_ALIVE_SECS: &str = "SSE_KEEP_ALIVE_SECS";
+pub const DOCS_ENABLED: &str = "DOCS_ENABLED";
+pub const TENANTS_FILE: &str = "TENANTS_FILE";
+pub const API_KEYS: &str = "API_KEYS";
+pub const JWT_SECRET: &str = "JWT_SECRET";
+pub const JWT_JWKS_URL: &str = "JWT_JWKS_URL";
+pub const JWT_AUDIENCE: &str = "JWT_AUDIENCE";
+pub const JWT_ISSUER: &str = "JWT_ISSUER";
+pub const BASIC_AUTH_USERNAME: &str = "BASIC_AUTH_USERNAME";
+pub const BASIC_AUTH_PASSWORD: &str = "BASIC_AUTH_PASSWORD";
+pub const HMAC_SECRET: &str = "HMAC_SECRET";
+pub const HMAC_WINDOW_SECS: &str = "HMAC_WINDOW_SECS";
+pub const AUTH_EXEMPT_HEALTH: &str = "AUTH_EXEMPT_HEALTH";
+pub const IP_ALLOWLIST: &str = "IP_ALLOWLIST";
+pub const IP_DENYLIST: &str = "IP_DENYLIST";
+pub const TRUSTED_PROXIES: &str = "TRUSTED_PROXIES";
+pub const MAX_URI_LENGTH: &str = "MAX_URI_LENGTH";
+pub const MAX_QUERY_PARAMS: &str = "MAX_QUERY_PARAMS";
+pub const MAX_HEADER_BYTES: &str = "MAX_HEADER_BYTES";
+pub const TRANSFORMERS_FILE: &str = "TRANSFORMERS_FILE";
+pub const PREFETCH_FILE: &str = "PREFETCH_FILE";
+pub const ALERT_RULES_FILE: &str = "ALERT_RULES_FILE";
+pub const CALIBRATION_FILE: &str = "CALIBRATION_FILE";
+pub const QUALITY_CHECK: &str = "QUALITY_CHECK";
+pub const MERGE_FILE: &str = "MERGE_FILE";
+pub const FAILOVER_FILE: &str = "FAILOVER_FILE";
+pub const FORECAST_GEOHASH_PRECISION: &str = "FORECAST_GEOHASH_PRECISION";
+pub const FORECAST_GRID_DEGREES: &str = "FORECAST_GRID_DEGREES";
+pub const PLACE_NAMES: &str = "PLACE_NAMES";
+pub const MQTT_HOST: &str = "MQTT_HOST";
+pub const MQTT_PORT: &str = "MQTT_PORT";
+pub const MQTT_CLIENT_ID: &str = "MQTT_CLIENT_ID";
+pub const MQTT_USERNAME: &str = "MQTT_USERNAME";
+pub const MQTT_PASSWORD: &str = "MQTT_PASSWORD";
+pub const MQTT_TOPIC_PREFIX: &str = "MQTT_TOPIC_PREFIX";
+pub const MQTT_RETAIN: &str = "MQTT_RETAIN";
+pub const MQTT_DISCOVERY_PREFIX: &str = "MQTT_DISCOVERY_PREFIX";
+pub const MQTT_FORECAST_GEOCODE: &str = "MQTT_FORECAST_GEOCODE";
+pub const INFLUXDB_URL: &str = "INFLUXDB_URL";
+pub const INFLUXDB_ORG: &str = "INFLUXDB_ORG";
+pub const INFLUXDB_BUCKET: &str = "INFLUXDB_BUCKET";
+pub const INFLUXDB_TOKEN: &str = "INFLUXDB_TOKEN";
+pub const REMOTE_WRITE_URL: &str = "REMOTE_WRITE_URL";
+pub const REMOTE_WRITE_USERNAME: &str = "REMOTE_WRITE_USERNAME";
+pub const REMOTE_WRITE_PASSWORD: &str = "REMOTE_WRITE_PASSWORD";
+pub const REMOTE_WRITE_JOB: &str = "REMOTE_WRITE_JOB";
+pub const RECORDING_MODE: &str = "RECORDING_MODE";
+pub const RECORDING_DIR: &str = "RECORDING_DIR";
+pub const OBSERVATIONS_DB: &str = "OBSERVATIONS_DB";
+pub const OBSERVATIONS_RETENTION_DAYS: &str = "OBSERVATIONS_RETENTION_DAYS";
+pub const HOURLY_ROLLUP_RETENTION_DAYS: &str = "HOURLY_ROLLUP_RETENTION_DAYS";
+pub const DAILY_ROLLUP_RETENTION_DAYS: &str = "DAILY_ROLLUP_RETENTION_DAYS";
+pub const BACKUP_S3_ENDPOINT: &str = "BACKUP_S3_ENDPOINT";
+pub const BACKUP_S3_BUCKET: &str = "BACKUP_S3_BUCKET";
+pub const BACKUP_S3_REGION: &str = "BACKUP_S3_REGION";
+pub const BACKUP_S3_ACCESS_KEY_ID: &str = "BACKUP_S3_ACCESS_KEY_ID";
+pub const BACKUP_S3_SECRET_ACCESS_KEY: &str = "BACKUP_S3_SECRET_ACCESS_KEY";
+pub const BACKUP_S3_PREFIX: &str = "BACKUP_S3_PREFIX";
+pub const BACKUP_INTERVAL_SECS: &str = "BACKUP_INTERVAL_SECS";
+pub const BACKUP_KEEP: &str = "BACKUP_KEEP";
+pub const SMTP_HOST: &str = "SMTP_HOST";
+pub const SMTP_PORT: &str = "SMTP_PORT";
+pub const SMTP_TLS: &str = "SMTP_TLS";
+pub const SMTP_USERNAME: &str = "SMTP_USERNAME";
+pub const SMTP_PASSWORD: &str = "SMTP_PASSWORD";
+pub const DIGEST_FROM: &str = "DIGEST_FROM";
+pub const DIGEST_TO: &str = "DIGEST_TO";
+pub const DIGEST_SEND_AT: &str = "DIGEST_SEND_AT";
+pub const DIGEST_TIMEZONE: &str = "DIGEST_TIMEZONE";
+pub const DIGEST_GEOCODE: &str = "DIGEST_GEOCODE";
+pub const DIGEST_LANGUAGE: &str = "DIGEST_LANGUAGE";
+pub const UPLOAD_PASSWORD: &str = "UPLOAD_PASSWORD";
+pub const UPLOAD_STATION_ID: &str = "UPLOAD_STATION_ID";
+pub const UPLOAD_FORWARD: &str = "UPLOAD_FORWARD";
+pub const ECOWITT_ENABLED: &str = "ECOWITT_ENABLED";
+pub const ECOWITT_PASSKEY: &str = "ECOWITT_PASSKEY";
+pub const ECOWITT_STATION_ID: &str = "ECOWITT_STATION_ID";
+pub const LOCAL_FIRST_MAX_AGE_SECS: &str = "LOCAL_FIRST_MAX_AGE_SECS";
+pub const LOADTEST_DURATION_SECS: &str = "LOADTEST_DURATION_SECS";
+pub const LOADTEST_CONCURRENCY: &str = "LOADTEST_CONCURRENCY";
+pub const LISTEN_ADDR: &str = "LISTEN_ADDR";
+pub const ADMIN_LISTEN_ADDR: &str = "ADMIN_LISTEN_ADDR";
+pub const SERVER_HTTP: &str = "SERVER_HTTP";
+pub const RUNTIME_FLAVOR: &str = "RUNTIME_FLAVOR";
+pub const WORKER_THREADS: &str = "WORKER_THREADS";
+pub const TCP_NODELAY: &str = "TCP_NODELAY";
+pub const TCP_KEEPALIVE_SECS: &str = "TCP_KEEPALIVE_SECS";
+pub const LISTEN_BACKLOG: &str = "LISTEN_BACKLOG";
+pub const LISTEN_REUSEPORT: &str = "LISTEN_REUSEPORT";
+pub const UPSTREAM_HTTP: &str = "UPSTREAM_HTTP";
+pub const UPSTREAM_POOL_MAX_IDLE_PER_HOST: &str = "UPSTREAM_POOL_MAX_IDLE_PER_HOST";
+pub const UPSTREAM_POOL_IDLE_TIMEOUT_SECS: &str = "UPSTREAM_POOL_IDLE_TIMEOUT_SECS";
+pub const UPSTREAM_TCP_KEEPALIVE_SECS: &str = "UPSTREAM_TCP_KEEPALIVE_SECS";
+pub const UPSTREAM_PREWARM: &str = "UPSTREAM_PREWARM";
+pub const UPSTREAM_CONCURRENCY: &str = "UPSTREAM_CONCURRENCY";
+pub const UPSTREAM_DNS_CACHE_SECS: &str = "UPSTREAM_DNS_CACHE_SECS";
+pub const UPSTREAM_RESOLVE: &str = "UPSTREAM_RESOLVE";
+pub const UPSTREAM_HEDGE_PERCENTILE: &str = "UPSTREAM_HEDGE_PERCENTILE";
+pub const UPSTREAM_HEDGE_MIN_DELAY_MS: &str = "UPSTREAM_HEDGE_MIN_DELAY_MS";
+pub const UPSTREAM_HEDGE_BUDGET_PERCENT: &str = "UPSTREAM_HEDGE_BUDGET_PERCENT";
+pub const UNIX_SOCKET: &str = "UNIX_SOCKET";
+pub const UNIX_SOCKET_MODE: &str = "UNIX_SOCKET_MODE";
+pub const TLS_CERT_PATH: &str = "TLS_CERT_PATH";
+pub const TLS_KEY_PATH: &str = "TLS_KEY_PATH";
+pub const TLS_CLIENT_CA_PATH: &str = "TLS_CLIENT_CA_PATH";
+pub const TLS_RELOAD_SECS: &str = "TLS_RELOAD_SECS";
+pub const ACME_DOMAINS: &str = "ACME_DOMAINS";
+pub const ACME_EMAIL: &str = "ACME_EMAIL";
+pub const ACME_DIRECTORY_URL: &str = "ACME_DIRECTORY_URL";
+pub const ACME_HTTP_ADDR: &str = "ACME_HTTP_ADDR";
+pub const ACME_ACCOUNT_KEY_PATH: &str = "ACME_ACCOUNT_KEY_PATH";
+pub const ACME_RENEW_DAYS: &str = "ACME_RENEW_DAYS";
 
-pub const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/111.0.0.0 Safari/537.36";
+pub const API_PREFIX: &str = "/v1";
+pub const API_KEY_HEADER: &str = "x-api-key";
+pub const SIGNATURE_HEADER: &str = "x-signature";
+pub const SIGNATURE_TIMESTAMP_HEADER: &str = "x-signature-timestamp";
+pub const FORWARDED_FOR_HEADER: &str = "x-forwarded-for";
+/// The snapped or bucketed geocode a forecast was fetched for.
+pub const FORECAST_GEOCODE_HEADER: &str = "x-forecast-geocode";
+pub const BASIC_AUTH_CHALLENGE: &str = "Basic realm=\"wunderground-proxy\", charset=\"UTF-8\"";
+pub const HEALTH_PATH: &str = "/health";
+/// Where Wunderground stations send their readings.
+pub const UPLOAD_PATH: &str = "/weatherstation/updateweatherstation.php";
+/// Path to enter in the Ecowitt gateway's customized upload settings.
+pub const ECOWITT_PATH: &str = "/data/report";
 
 pub const CURRENT: &str = "current";
 pub const FORECAST: &str = "forecast";
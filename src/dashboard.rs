@@ -0,0 +1,134 @@
+use serde_json::Value;
+
+/// Renders a self-contained page (inline CSS, no scripts) with the current
+/// observation and the daily forecast. The page reloads itself every
+/// `refresh_secs` so a wall-mounted display stays current.
+pub fn render(current: &Value, forecast: Option<&Value>, refresh_secs: u64) -> String {
+    let observation = current.get("observations").and_then(|obs| obs.get(0));
+    let text = |key: &str| {
+        observation
+            .and_then(|obs| obs.get(key))
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+    };
+    let number = |value: Option<&Value>, decimals: usize| {
+        value
+            .and_then(Value::as_f64)
+            .map_or_else(|| "–".to_string(), |value| format!("{value:.decimals$}"))
+    };
+    let metric = |key: &str, decimals: usize| {
+        number(
+            observation
+                .and_then(|obs| obs.get("metric"))
+                .and_then(|metric| metric.get(key)),
+            decimals,
+        )
+    };
+    let observed =
+        |key: &str, decimals: usize| number(observation.and_then(|obs| obs.get(key)), decimals);
+
+    let station = match text("neighborhood") {
+        "" => text("stationID"),
+        neighborhood => neighborhood,
+    };
+
+    let details = [
+        ("Feels like", format!("{} °C", metric("heatIndex", 1))),
+        ("Dew point", format!("{} °C", metric("dewpt", 1))),
+        ("Humidity", format!("{} %", observed("humidity", 0))),
+        (
+            "Wind",
+            format!(
+                "{} km/h, gusts {}",
+                metric("windSpeed", 0),
+                metric("windGust", 0)
+            ),
+        ),
+        ("Pressure", format!("{} hPa", metric("pressure", 1))),
+        (
+            "Rain",
+            format!(
+                "{} mm ({} mm/h)",
+                metric("precipTotal", 1),
+                metric("precipRate", 1)
+            ),
+        ),
+        ("UV index", observed("uv", 0)),
+    ]
+    .iter()
+    .map(|(label, value)| format!("<dt>{label}</dt><dd>{}</dd>", escape(value)))
+    .collect::<String>();
+
+    let days = forecast.map(render_days).unwrap_or_default();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta http-equiv="refresh" content="{refresh_secs}">
+<title>Weather – {station}</title>
+<style>
+body {{ font-family: system-ui, sans-serif; margin: 0; padding: 2rem; background: #10161f; color: #e8edf3; }}
+h1 {{ font-size: 1.2rem; font-weight: normal; color: #9fb0c3; margin: 0; }}
+.temp {{ font-size: 6rem; font-weight: 200; margin: 0.5rem 0; }}
+dl {{ display: grid; grid-template-columns: max-content auto; gap: 0.3rem 1.5rem; font-size: 1.2rem; }}
+dt {{ color: #9fb0c3; }}
+dd {{ margin: 0; }}
+.days {{ display: flex; gap: 1rem; margin-top: 2rem; flex-wrap: wrap; }}
+.day {{ flex: 1; min-width: 9rem; background: #1b2431; border-radius: 0.6rem; padding: 1rem; }}
+.day h2 {{ font-size: 1.1rem; margin: 0 0 0.5rem; }}
+.range {{ font-size: 1.5rem; }}
+.narrative {{ color: #9fb0c3; font-size: 0.9rem; }}
+footer {{ margin-top: 2rem; color: #5f7086; font-size: 0.8rem; }}
+</style>
+</head>
+<body>
+<h1>{station}</h1>
+<div class="temp">{temp} °C</div>
+<dl>{details}</dl>
+<div class="days">{days}</div>
+<footer>Observed {observed_at}</footer>
+</body>
+</html>
+"#,
+        station = escape(station),
+        temp = metric("temp", 1),
+        observed_at = escape(text("obsTimeLocal")),
+    )
+}
+
+fn render_days(forecast: &Value) -> String {
+    let at = |key: &str, idx: usize| forecast.get(key).and_then(|values| values.get(idx));
+    let days = forecast
+        .get("dayOfWeek")
+        .and_then(Value::as_array)
+        .map_or(0, Vec::len);
+
+    (0..days)
+        .map(|idx| {
+            let number = |key: &str| {
+                at(key, idx)
+                    .and_then(Value::as_f64)
+                    .map_or_else(|| "–".to_string(), |value| format!("{value:.0}°"))
+            };
+            let text = |key: &str| at(key, idx).and_then(Value::as_str).unwrap_or_default();
+
+            format!(
+                r#"<div class="day"><h2>{}</h2><div class="range">{} / {}</div><p class="narrative">{}</p></div>"#,
+                escape(text("dayOfWeek")),
+                number("temperatureMax"),
+                number("temperatureMin"),
+                escape(text("narrative")),
+            )
+        })
+        .collect()
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
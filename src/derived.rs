@@ -0,0 +1,129 @@
+use serde_json::{json, Map, Value};
+
+/// Builds a `/current`-shaped payload with dew point, heat index, wind chill,
+/// and apparent temperature for each observation. Values the station reports
+/// are passed through; missing ones are computed and listed under `computed`.
+pub fn derive(current: &Value) -> Value {
+    let observations: Vec<Value> = current
+        .get("observations")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .map(derive_observation)
+        .collect();
+
+    json!({ "observations": observations })
+}
+
+fn derive_observation(observation: &Value) -> Value {
+    let metric = observation.get("metric");
+    let reported = |key: &str| {
+        metric
+            .and_then(|metric| metric.get(key))
+            .and_then(Value::as_f64)
+    };
+
+    let temperature = reported("temp");
+    let humidity = observation.get("humidity").and_then(Value::as_f64);
+    let wind_speed = reported("windSpeed");
+
+    let mut computed = Vec::new();
+    let mut measurements = Map::new();
+    measurements.insert("temp".to_string(), json!(temperature));
+
+    let mut fill = |key: &str, reported: Option<f64>, formula: Option<f64>| {
+        let value = reported.or_else(|| formula.inspect(|_| computed.push(Value::from(key))));
+        measurements.insert(key.to_string(), json!(value.map(round_tenth)));
+    };
+
+    fill(
+        "dewpt",
+        reported("dewpt"),
+        temperature.zip(humidity).map(|(t, rh)| dew_point(t, rh)),
+    );
+    fill(
+        "heatIndex",
+        reported("heatIndex"),
+        temperature.zip(humidity).map(|(t, rh)| heat_index(t, rh)),
+    );
+    fill(
+        "windChill",
+        reported("windChill"),
+        temperature.zip(wind_speed).map(|(t, v)| wind_chill(t, v)),
+    );
+    fill(
+        "apparentTemp",
+        None,
+        temperature
+            .zip(humidity)
+            .zip(wind_speed)
+            .map(|((t, rh), v)| apparent_temperature(t, rh, v)),
+    );
+
+    let mut derived = Map::new();
+    for key in [
+        "stationID",
+        "obsTimeUtc",
+        "obsTimeLocal",
+        "epoch",
+        "humidity",
+    ] {
+        if let Some(value) = observation.get(key) {
+            derived.insert(key.to_string(), value.clone());
+        }
+    }
+    derived.insert("metric".to_string(), Value::Object(measurements));
+    derived.insert("computed".to_string(), Value::Array(computed));
+
+    Value::Object(derived)
+}
+
+/// Magnus formula, °C.
+pub(crate) fn dew_point(temperature: f64, humidity: f64) -> f64 {
+    const B: f64 = 17.62;
+    const C: f64 = 243.12;
+    let gamma = (humidity.max(1.0) / 100.0).ln() + B * temperature / (C + temperature);
+    C * gamma / (B - gamma)
+}
+
+/// NWS Rothfusz regression, which is only meaningful from about 27°C; below
+/// that the air temperature is returned.
+fn heat_index(temperature: f64, humidity: f64) -> f64 {
+    if temperature < 26.7 {
+        return temperature;
+    }
+
+    let t = temperature * 9.0 / 5.0 + 32.0;
+    let rh = humidity;
+    let hi = -42.379 + 2.049_015_23 * t + 10.143_331_27 * rh
+        - 0.224_755_41 * t * rh
+        - 6.837_83e-3 * t * t
+        - 5.481_717e-2 * rh * rh
+        + 1.228_74e-3 * t * t * rh
+        + 8.528_2e-4 * t * rh * rh
+        - 1.99e-6 * t * t * rh * rh;
+    (hi - 32.0) * 5.0 / 9.0
+}
+
+/// Environment Canada wind chill index for km/h, defined for temperatures up
+/// to 10°C and wind above 4.8 km/h; otherwise the air temperature is returned.
+fn wind_chill(temperature: f64, wind_speed: f64) -> f64 {
+    if temperature > 10.0 || wind_speed <= 4.8 {
+        return temperature;
+    }
+
+    let v = wind_speed.powf(0.16);
+    13.12 + 0.6215 * temperature - 11.37 * v + 0.3965 * temperature * v
+}
+
+/// Steadman apparent temperature as used by the Australian Bureau of
+/// Meteorology, wind speed in km/h.
+fn apparent_temperature(temperature: f64, humidity: f64, wind_speed: f64) -> f64 {
+    let vapour_pressure =
+        humidity / 100.0 * 6.105 * (17.27 * temperature / (237.7 + temperature)).exp();
+    temperature + 0.33 * vapour_pressure - 0.70 * (wind_speed / 3.6) - 4.00
+}
+
+fn round_tenth(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
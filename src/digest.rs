@@ -0,0 +1,213 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, Days, NaiveDate, TimeDelta, TimeZone, Utc};
+use serde_json::Value;
+use tokio::sync::watch;
+
+use crate::{
+    cache::{current_value, forecast_value},
+    handlers::station_geocode,
+    smtp::{self, Message},
+    today::{DailySummary, Extreme},
+    AppState, DigestConfig, Result,
+};
+
+/// Mails the digest every day at `send_at`. Holding `updates` keeps the
+/// background refresh running, so yesterday's extremes are seen even
+/// without clients.
+pub(crate) async fn run(
+    state: AppState,
+    config: DigestConfig,
+    _updates: watch::Receiver<Option<Value>>,
+) {
+    loop {
+        let now = Utc::now();
+        let at = next_send(now, &config);
+        tokio::time::sleep((at - now).to_std().unwrap_or_default()).await;
+
+        let today = at.with_timezone(&config.timezone).date_naive();
+        let (subject, body) = match compose(&state, &config, today).await {
+            Ok(digest) => digest,
+            Err(err) => {
+                tracing::warn!("composing the digest failed: {err}");
+                continue;
+            }
+        };
+        let message = Message {
+            from: &config.from,
+            to: &config.to,
+            subject: &subject,
+            body: &body,
+        };
+        match smtp::send(&config.smtp, &message).await {
+            Ok(()) => tracing::info!("digest mailed to {} recipients", config.to.len()),
+            Err(err) => tracing::warn!("mailing the digest failed: {err}"),
+        }
+    }
+}
+
+/// The first `send_at` in `timezone` after `now`. A time skipped by a DST
+/// change is sent an hour later.
+fn next_send(now: DateTime<Utc>, config: &DigestConfig) -> DateTime<Utc> {
+    let tz = config.timezone;
+    let today = now.with_timezone(&tz).date_naive();
+    (0..=2)
+        .filter_map(|days| {
+            let local = today
+                .checked_add_days(Days::new(days))?
+                .and_time(config.send_at);
+            tz.from_local_datetime(&local)
+                .earliest()
+                .or_else(|| {
+                    tz.from_local_datetime(&(local + TimeDelta::hours(1)))
+                        .earliest()
+                })
+                .map(|at| at.with_timezone(&Utc))
+        })
+        .find(|at| *at > now)
+        .unwrap_or(now + TimeDelta::days(1))
+}
+
+/// Subject and plain-text body. Only the station's observations are
+/// required; a missing forecast or alert lookup is noted in the text.
+async fn compose(
+    state: &AppState,
+    config: &DigestConfig,
+    today: NaiveDate,
+) -> Result<(String, String)> {
+    let current = current_value(state).await?;
+    let station = current
+        .get("observations")
+        .and_then(|observations| observations.get(0))
+        .and_then(|observation| observation.get("stationID"))
+        .and_then(Value::as_str)
+        .unwrap_or("your station")
+        .to_string();
+    let geocode = config.geocode.clone().or_else(|| station_geocode(&current));
+
+    let forecast = match &geocode {
+        Some(geocode) => forecast_value(state, geocode, &config.language)
+            .await
+            .inspect_err(|err| tracing::warn!("digest forecast unavailable: {err}"))
+            .ok(),
+        None => None,
+    };
+    let yesterday = match today.pred_opt() {
+        Some(yesterday) => Some(
+            state
+                .today
+                .lock()
+                .await
+                .day_summary(yesterday, config.timezone),
+        ),
+        None => None,
+    };
+
+    let mut body = format!(
+        "Weather for {station} on {}\n",
+        today.format("%A, %-d %B %Y")
+    );
+
+    body.push_str("\nForecast\n");
+    match forecast.as_ref().map(narratives) {
+        Some(narratives) if !narratives.is_empty() => {
+            for (name, narrative) in narratives {
+                let _ = writeln!(body, "{name}: {narrative}");
+            }
+        }
+        _ => body.push_str("Unavailable.\n"),
+    }
+
+    body.push_str("\nYesterday\n");
+    match yesterday.filter(|summary| summary.samples > 0) {
+        Some(summary) => write_extremes(&mut body, &summary),
+        None => body.push_str("No observations were recorded.\n"),
+    }
+
+    #[cfg(feature = "alerts")]
+    {
+        let alerts = match &geocode {
+            Some(geocode) => alert_headlines(state, geocode, &config.language).await,
+            None => Some(Vec::new()),
+        };
+        body.push_str("\nAlerts\n");
+        match alerts {
+            Some(alerts) if alerts.is_empty() => body.push_str("None.\n"),
+            Some(alerts) => {
+                for headline in alerts {
+                    let _ = writeln!(body, "- {headline}");
+                }
+            }
+            None => body.push_str("Unavailable.\n"),
+        }
+    }
+
+    Ok((format!("Weather digest for {station}, {today}"), body))
+}
+
+/// The upcoming day and night parts by name, e.g. `Today` and `Tonight`, or
+/// the first daily narrative when the day parts are missing.
+fn narratives(forecast: &Value) -> Vec<(String, String)> {
+    let daypart = forecast.get("daypart").and_then(|daypart| daypart.get(0));
+    let array = |key: &str| {
+        daypart
+            .and_then(|daypart| daypart.get(key))
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default()
+    };
+    let parts: Vec<(String, String)> = array("daypartName")
+        .iter()
+        .zip(array("narrative").iter())
+        // Parts already over are null.
+        .filter_map(|(name, narrative)| {
+            Some((name.as_str()?.to_string(), narrative.as_str()?.to_string()))
+        })
+        .take(2)
+        .collect();
+    if !parts.is_empty() {
+        return parts;
+    }
+    forecast
+        .get("narrative")
+        .and_then(|narrative| narrative.get(0))
+        .and_then(Value::as_str)
+        .map(|narrative| vec![("Today".to_string(), narrative.to_string())])
+        .unwrap_or_default()
+}
+
+fn write_extremes(body: &mut String, summary: &DailySummary) {
+    let mut extreme = |label: &str, extreme: &Option<Extreme>, unit: &str| {
+        if let Some(Extreme { value, at }) = extreme {
+            // `at` is RFC 3339 in the digest's timezone.
+            let time = at.get(11..16).unwrap_or(at);
+            let _ = writeln!(body, "{label}: {value:.1} {unit} at {time}");
+        }
+    };
+    extreme("Low", &summary.min_temp, "°C");
+    extreme("High", &summary.max_temp, "°C");
+    extreme("Peak gust", &summary.peak_gust, "km/h");
+    if let Some(rain) = summary.rain_total {
+        let _ = writeln!(body, "Rain: {rain:.1} mm");
+    }
+}
+
+/// Headlines of the active alerts; `None` when they couldn't be fetched.
+#[cfg(feature = "alerts")]
+async fn alert_headlines(state: &AppState, geocode: &str, language: &str) -> Option<Vec<String>> {
+    let entry = crate::cache::alerts_entry(state, geocode, language)
+        .await
+        .inspect_err(|err| tracing::warn!("digest alerts unavailable: {err}"))
+        .ok()?;
+    Some(
+        entry
+            .value
+            .get("alerts")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|alert| alert.get("headlineText")?.as_str())
+            .map(String::from)
+            .collect(),
+    )
+}
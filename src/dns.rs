@@ -0,0 +1,69 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+
+/// Addresses of a host and when they were looked up.
+type Answer = (Instant, Vec<SocketAddr>);
+
+/// Resolves upstream hosts through the system resolver and keeps the answers
+/// for `ttl`. Once an answer expires, a failed lookup falls back to it, so a
+/// flaky resolver doesn't fail a refresh that the last answer would serve.
+#[derive(Debug)]
+pub(crate) struct CachingResolver {
+    ttl: Duration,
+    answers: Arc<Mutex<HashMap<String, Answer>>>,
+}
+
+impl CachingResolver {
+    pub(crate) fn new(ttl: Duration) -> CachingResolver {
+        CachingResolver {
+            ttl,
+            answers: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+}
+
+impl Resolve for CachingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        let ttl = self.ttl;
+        let answers = self.answers.clone();
+        Box::pin(async move {
+            let cached = answers
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .get(&host)
+                .cloned();
+            if let Some((resolved_at, addrs)) = &cached {
+                if resolved_at.elapsed() < ttl {
+                    return Ok(Box::new(addrs.clone().into_iter()) as Addrs);
+                }
+            }
+
+            let lookup = tokio::net::lookup_host((host.as_str(), 0))
+                .await
+                .map(|addrs| addrs.collect::<Vec<SocketAddr>>());
+            match lookup {
+                Ok(addrs) => {
+                    answers
+                        .lock()
+                        .unwrap_or_else(|err| err.into_inner())
+                        .insert(host, (Instant::now(), addrs.clone()));
+                    Ok(Box::new(addrs.into_iter()) as Addrs)
+                }
+                Err(err) => match cached {
+                    Some((_, addrs)) => {
+                        tracing::warn!("resolving {host} failed, using the last answer: {err}");
+                        Ok(Box::new(addrs.into_iter()) as Addrs)
+                    }
+                    None => Err(err.into()),
+                },
+            }
+        })
+    }
+}
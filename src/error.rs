@@ -0,0 +1,97 @@
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use wunderground_proxy_core::Error as ProviderError;
+
+use crate::{constants::BASIC_AUTH_CHALLENGE, redact::redact};
+
+/// Errors from the weather provider, and requests rejected before reaching a
+/// handler. Handlers and middleware return them directly; the response
+/// carries the message and a matching status.
+#[derive(Debug, thiserror::Error)]
+pub enum AppError {
+    #[error(transparent)]
+    Provider(#[from] ProviderError),
+    #[error("missing or unknown X-Api-Key or bearer token")]
+    Unauthorized,
+    /// Wrong Basic auth credentials, or none where Basic auth is the only
+    /// configured method; the response asks the client for a login.
+    #[error("invalid username or password")]
+    BasicAuthRequired,
+    /// The bearer token failed validation, with the reason.
+    #[error("invalid bearer token: {0}")]
+    InvalidToken(String),
+    /// The tenant's key is valid but doesn't cover the endpoint or station.
+    #[error("{0} is not allowed for this API key")]
+    Forbidden(String),
+    /// The path and query string are too long or have too many parameters.
+    #[error("{0}")]
+    UriTooLong(String),
+    #[error("request headers exceed {0} bytes")]
+    HeadersTooLarge(usize),
+    /// The request signature is missing its timestamp, wrong, stale or reused.
+    #[error("invalid request signature: {0}")]
+    InvalidSignature(String),
+    /// The client address is denied or not on the allowlist.
+    #[error("requests from {0} are not allowed")]
+    SourceDenied(String),
+    /// Seconds until the tenant may send the next request.
+    #[error("rate limit exceeded, retry in {0}s")]
+    RateLimited(u64),
+    /// Seconds until the tenant's daily quota resets.
+    #[error("daily quota exhausted, resets in {0}s")]
+    QuotaExceeded(u64),
+    /// A query parameter the extractor accepted but the handler couldn't use.
+    #[error("{0}")]
+    InvalidQuery(String),
+    /// The endpoint depends on a feature that isn't configured.
+    #[error("{0} is not configured")]
+    NotConfigured(&'static str),
+    /// `/current/local` before the station's first upload.
+    #[error("no reading has been uploaded yet")]
+    NoUpload,
+    #[cfg(feature = "recorder")]
+    #[error("observation database error: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            AppError::Provider(ProviderError::InvalidGeocode(_)) => StatusCode::BAD_REQUEST,
+            AppError::Provider(_) => StatusCode::BAD_GATEWAY,
+            AppError::Unauthorized | AppError::InvalidToken(_) | AppError::InvalidSignature(_) => {
+                StatusCode::UNAUTHORIZED
+            }
+            AppError::Forbidden(_) | AppError::SourceDenied(_) => StatusCode::FORBIDDEN,
+            AppError::UriTooLong(_) => StatusCode::URI_TOO_LONG,
+            AppError::HeadersTooLarge(_) => StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE,
+            AppError::InvalidQuery(_) => StatusCode::BAD_REQUEST,
+            AppError::NotConfigured(_) | AppError::NoUpload => StatusCode::NOT_FOUND,
+            #[cfg(feature = "recorder")]
+            AppError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::BasicAuthRequired => {
+                tracing::warn!("{self}");
+                return (
+                    StatusCode::UNAUTHORIZED,
+                    [(header::WWW_AUTHENTICATE, BASIC_AUTH_CHALLENGE)],
+                    self.to_string(),
+                )
+                    .into_response();
+            }
+            AppError::RateLimited(retry_after) | AppError::QuotaExceeded(retry_after) => {
+                return (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(header::RETRY_AFTER, retry_after.to_string())],
+                    self.to_string(),
+                )
+                    .into_response();
+            }
+        };
+        // Upstream messages are beyond our control and may quote a credential.
+        let message = redact(&self.to_string()).into_owned();
+        tracing::warn!("{message}");
+        (status, message).into_response()
+    }
+}
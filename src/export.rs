@@ -0,0 +1,302 @@
+use std::io;
+#[cfg(feature = "parquet")]
+use std::sync::Arc;
+
+use axum::body::Body;
+#[cfg(feature = "parquet")]
+use parquet::{
+    basic::{Compression, LogicalType, Repetition, Type as PhysicalType},
+    data_type::{ByteArray, ByteArrayType, DoubleType, Int64Type},
+    file::{
+        properties::WriterProperties,
+        writer::{SerializedColumnWriter, SerializedFileWriter, SerializedRowGroupWriter},
+    },
+    schema::types::Type,
+};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+use crate::recorder::{ObservationStore, Sample, MEASUREMENTS};
+
+/// Samples read from the database at a time; each page becomes one chunk of
+/// the response, and one row group in Parquet.
+const PAGE: u32 = 5000;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Format {
+    Csv,
+    #[cfg(feature = "parquet")]
+    Parquet,
+}
+
+impl Format {
+    pub(crate) fn content_type(self) -> &'static str {
+        match self {
+            Format::Csv => "text/csv; charset=utf-8",
+            #[cfg(feature = "parquet")]
+            Format::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            #[cfg(feature = "parquet")]
+            Format::Parquet => "parquet",
+        }
+    }
+}
+
+type Chunk = io::Result<Vec<u8>>;
+
+/// The samples of `stations` (all when empty) with `from <= epoch < to`,
+/// oldest first, read page by page as the client consumes the body. A failure midway aborts the response, so a
+/// truncated file can't be mistaken for a complete one.
+pub(crate) fn stream(
+    store: ObservationStore,
+    format: Format,
+    from: i64,
+    to: i64,
+    stations: Vec<String>,
+) -> Body {
+    let (sender, receiver) = mpsc::channel::<Chunk>(2);
+    tokio::spawn(async move {
+        if let Err(err) = write(&store, format, from, to, &stations, &sender).await {
+            tracing::warn!("observation export failed: {err}");
+            let _ = sender.send(Err(err)).await;
+        }
+    });
+    Body::from_stream(ReceiverStream::new(receiver))
+}
+
+async fn write(
+    store: &ObservationStore,
+    format: Format,
+    from: i64,
+    to: i64,
+    stations: &[String],
+    sender: &mpsc::Sender<Chunk>,
+) -> io::Result<()> {
+    let mut encoder = Encoder::new(format)?;
+    let mut after = None;
+    loop {
+        let samples = store
+            .range(from, to, after.take(), PAGE, stations)
+            .await
+            .map_err(io::Error::other)?;
+        let chunk = encoder.page(&samples)?;
+        if sender.send(Ok(chunk)).await.is_err() {
+            // The client went away.
+            return Ok(());
+        }
+        match samples.last() {
+            Some(last) if samples.len() == PAGE as usize => {
+                after = Some((last.epoch, last.station.clone()));
+            }
+            _ => break,
+        }
+    }
+    let _ = sender.send(Ok(encoder.finish()?)).await;
+    Ok(())
+}
+
+enum Encoder {
+    Csv {
+        header_written: bool,
+    },
+    #[cfg(feature = "parquet")]
+    Parquet(Box<SerializedFileWriter<Vec<u8>>>),
+}
+
+impl Encoder {
+    fn new(format: Format) -> io::Result<Encoder> {
+        match format {
+            Format::Csv => Ok(Encoder::Csv {
+                header_written: false,
+            }),
+            #[cfg(feature = "parquet")]
+            Format::Parquet => {
+                let properties = WriterProperties::builder()
+                    .set_compression(Compression::SNAPPY)
+                    .build();
+                SerializedFileWriter::new(Vec::new(), parquet_schema(), Arc::new(properties))
+                    .map(|writer| Encoder::Parquet(Box::new(writer)))
+                    .map_err(io::Error::other)
+            }
+        }
+    }
+
+    /// The bytes encoding `samples`, starting with the header or file magic
+    /// on the first page.
+    fn page(&mut self, samples: &[Sample]) -> io::Result<Vec<u8>> {
+        match self {
+            Encoder::Csv { header_written } => {
+                let mut output = String::new();
+                if !*header_written {
+                    output.push_str("station,epoch,obsTimeUtc");
+                    for (name, _) in MEASUREMENTS {
+                        output.push(',');
+                        output.push_str(name);
+                    }
+                    output.push('\n');
+                    *header_written = true;
+                }
+                for sample in samples {
+                    csv_row(&mut output, sample);
+                }
+                Ok(output.into_bytes())
+            }
+            #[cfg(feature = "parquet")]
+            Encoder::Parquet(writer) => {
+                if !samples.is_empty() {
+                    write_row_group(writer, samples).map_err(io::Error::other)?;
+                }
+                writer.flush()?;
+                Ok(std::mem::take(writer.inner_mut()))
+            }
+        }
+    }
+
+    /// Whatever follows the last page: the Parquet footer.
+    fn finish(self) -> io::Result<Vec<u8>> {
+        match self {
+            Encoder::Csv { .. } => Ok(Vec::new()),
+            #[cfg(feature = "parquet")]
+            Encoder::Parquet(writer) => writer.into_inner().map_err(io::Error::other),
+        }
+    }
+}
+
+fn csv_row(output: &mut String, sample: &Sample) {
+    output.push_str(&csv_field(&sample.station));
+    output.push(',');
+    output.push_str(&sample.epoch.to_string());
+    output.push(',');
+    output.push_str(&csv_field(
+        sample.obs_time_utc.as_deref().unwrap_or_default(),
+    ));
+    for (_, value) in MEASUREMENTS {
+        output.push(',');
+        if let Some(value) = value(sample) {
+            output.push_str(&value.to_string());
+        }
+    }
+    output.push('\n');
+}
+
+/// Quoted only when it has to be.
+fn csv_field(value: &str) -> std::borrow::Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\"")).into()
+    } else {
+        value.into()
+    }
+}
+
+#[cfg(feature = "parquet")]
+fn parquet_schema() -> Arc<Type> {
+    let string = |name: &str, repetition| {
+        Type::primitive_type_builder(name, PhysicalType::BYTE_ARRAY)
+            .with_repetition(repetition)
+            .with_logical_type(Some(LogicalType::String))
+            .build()
+    };
+    let mut fields = vec![
+        string("station", Repetition::REQUIRED),
+        Type::primitive_type_builder("epoch", PhysicalType::INT64)
+            .with_repetition(Repetition::REQUIRED)
+            .build(),
+        string("obsTimeUtc", Repetition::OPTIONAL),
+    ];
+    for (name, _) in MEASUREMENTS {
+        fields.push(
+            Type::primitive_type_builder(name, PhysicalType::DOUBLE)
+                .with_repetition(Repetition::OPTIONAL)
+                .build(),
+        );
+    }
+    let fields = fields
+        .into_iter()
+        .map(|field| field.map(Arc::new))
+        .collect::<parquet::errors::Result<_>>()
+        .expect("observation schema is valid");
+    Arc::new(
+        Type::group_type_builder("observation")
+            .with_fields(fields)
+            .build()
+            .expect("observation schema is valid"),
+    )
+}
+
+/// One row group holding `samples`, columns in schema order.
+#[cfg(feature = "parquet")]
+fn write_row_group(
+    writer: &mut SerializedFileWriter<Vec<u8>>,
+    samples: &[Sample],
+) -> parquet::errors::Result<()> {
+    /// Present values and the definition level of every row.
+    fn optional<T>(values: impl Iterator<Item = Option<T>>) -> (Vec<T>, Vec<i16>) {
+        let mut present = Vec::new();
+        let levels = values
+            .map(|value| match value {
+                Some(value) => {
+                    present.push(value);
+                    1
+                }
+                None => 0,
+            })
+            .collect();
+        (present, levels)
+    }
+
+    fn column<'a>(
+        row_group: &'a mut SerializedRowGroupWriter<'_, Vec<u8>>,
+    ) -> parquet::errors::Result<SerializedColumnWriter<'a>> {
+        Ok(row_group
+            .next_column()?
+            .expect("one column is written per schema field"))
+    }
+
+    let mut row_group = writer.next_row_group()?;
+
+    let mut station = column(&mut row_group)?;
+    let stations: Vec<ByteArray> = samples
+        .iter()
+        .map(|sample| sample.station.as_str().into())
+        .collect();
+    station
+        .typed::<ByteArrayType>()
+        .write_batch(&stations, None, None)?;
+    station.close()?;
+
+    let mut epoch = column(&mut row_group)?;
+    let epochs: Vec<i64> = samples.iter().map(|sample| sample.epoch).collect();
+    epoch
+        .typed::<Int64Type>()
+        .write_batch(&epochs, None, None)?;
+    epoch.close()?;
+
+    let mut obs_time = column(&mut row_group)?;
+    let (times, levels) = optional(
+        samples
+            .iter()
+            .map(|sample| sample.obs_time_utc.as_deref().map(ByteArray::from)),
+    );
+    obs_time
+        .typed::<ByteArrayType>()
+        .write_batch(&times, Some(&levels), None)?;
+    obs_time.close()?;
+
+    for (_, value) in MEASUREMENTS {
+        let mut measurement = column(&mut row_group)?;
+        let (values, levels) = optional(samples.iter().map(value));
+        measurement
+            .typed::<DoubleType>()
+            .write_batch(&values, Some(&levels), None)?;
+        measurement.close()?;
+    }
+    row_group.close()?;
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+/// A rendered feed together with the content hashes of the cache entries it
+/// was built from, so it is only regenerated when one of them changes.
+#[derive(Debug, Clone)]
+pub struct RenderedFeed {
+    pub sources: (u64, u64),
+    pub body: String,
+}
+
+/// Renders an RSS 2.0 channel with one item per active alert followed by one
+/// item per forecast day narrative.
+pub fn render(forecast: &Value, alerts: &Value, geocode: &str, language: &str) -> String {
+    let now = Utc::now().to_rfc2822();
+    let mut items = String::new();
+
+    let active_alerts = alerts
+        .get("alerts")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten();
+    for alert in active_alerts {
+        let text = |key: &str| alert.get(key).and_then(Value::as_str);
+        let Some(headline) = text("headlineText") else {
+            continue;
+        };
+        let published = text("issueTimeLocal")
+            .and_then(|issued| DateTime::parse_from_rfc3339(issued).ok())
+            .map_or_else(|| now.clone(), |issued| issued.to_rfc2822());
+
+        push_item(
+            &mut items,
+            headline,
+            text("eventDescription").unwrap_or(headline),
+            text("detailKey").unwrap_or(headline),
+            &published,
+        );
+    }
+
+    let days = forecast
+        .get("narrative")
+        .and_then(Value::as_array)
+        .map_or(0, Vec::len);
+    for idx in 0..days {
+        let at = |key: &str| {
+            forecast
+                .get(key)
+                .and_then(|values| values.get(idx))
+                .and_then(Value::as_str)
+        };
+        let Some(narrative) = at("narrative") else {
+            continue;
+        };
+        let day = at("dayOfWeek").unwrap_or("Forecast");
+        let date = at("validTimeLocal")
+            .and_then(|time| time.get(..10))
+            .unwrap_or_default();
+
+        push_item(
+            &mut items,
+            &format!("{day}: {narrative}"),
+            narrative,
+            &format!("forecast-{date}-{geocode}"),
+            &now,
+        );
+    }
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?><rss version="2.0"><channel><title>Weather for {geocode}</title><link>{link}</link><description>Daily forecast and active alerts</description><lastBuildDate>{now}</lastBuildDate>{items}</channel></rss>"#,
+        link = escape(&format!("/forecast?geocode={geocode}&language={language}")),
+        geocode = escape(geocode),
+    )
+}
+
+fn push_item(items: &mut String, title: &str, description: &str, guid: &str, published: &str) {
+    items.push_str(&format!(
+        r#"<item><title>{}</title><description>{}</description><guid isPermaLink="false">{}</guid><pubDate>{published}</pubDate></item>"#,
+        escape(title),
+        escape(description),
+        escape(guid),
+    ));
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
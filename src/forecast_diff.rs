@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// Keys that are calendar/astronomy bookkeeping rather than forecast content.
+const IGNORED_PREFIXES: &[&str] = &[
+    "validTime",
+    "expirationTime",
+    "sunrise",
+    "sunset",
+    "moonrise",
+    "moonset",
+    "moonPhase",
+    "dayOfWeek",
+    "daypart",
+    "dayOrNight",
+];
+
+#[derive(Debug, Serialize)]
+pub struct Change {
+    date: String,
+    /// `day` or `night` for daypart fields, absent for whole-day fields.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    part: Option<&'static str>,
+    field: String,
+    from: Value,
+    to: Value,
+}
+
+/// Compares two daily forecast payloads day by day, aligned on the local date
+/// so that a forecast issued after midnight doesn't report every day as changed.
+pub fn diff(previous: &Value, latest: &Value) -> Vec<Change> {
+    let previous_days = day_index(previous);
+    let mut changes = Vec::new();
+
+    for (latest_idx, date) in dates(latest).into_iter().enumerate() {
+        let Some(&previous_idx) = previous_days.get(&date) else {
+            continue;
+        };
+
+        // Whole-day arrays hold one entry per day.
+        for (field, from, to) in diff_fields(previous, latest, previous_idx, latest_idx) {
+            changes.push(Change {
+                date: date.clone(),
+                part: None,
+                field,
+                from,
+                to,
+            });
+        }
+
+        // Daypart arrays interleave a day and a night entry per day.
+        let (Some(previous_parts), Some(latest_parts)) = (daypart(previous), daypart(latest))
+        else {
+            continue;
+        };
+        for (offset, part) in [(0, "day"), (1, "night")] {
+            let previous_at = previous_idx * 2 + offset;
+            let latest_at = latest_idx * 2 + offset;
+            for (field, from, to) in
+                diff_fields(previous_parts, latest_parts, previous_at, latest_at)
+            {
+                changes.push(Change {
+                    date: date.clone(),
+                    part: Some(part),
+                    field,
+                    from,
+                    to,
+                });
+            }
+        }
+    }
+
+    changes
+}
+
+/// Returns `(field, from, to)` for every array field whose entry differs
+/// between `previous[previous_at]` and `latest[latest_at]`.
+fn diff_fields(
+    previous: &Value,
+    latest: &Value,
+    previous_at: usize,
+    latest_at: usize,
+) -> Vec<(String, Value, Value)> {
+    let (Some(previous), Some(latest)) = (previous.as_object(), latest.as_object()) else {
+        return Vec::new();
+    };
+
+    latest
+        .iter()
+        .filter(|(field, _)| {
+            !IGNORED_PREFIXES
+                .iter()
+                .any(|prefix| field.starts_with(prefix))
+        })
+        .filter_map(|(field, latest_values)| {
+            let from = previous.get(field)?.as_array()?.get(previous_at)?;
+            let to = latest_values.as_array()?.get(latest_at)?;
+            (from != to).then(|| (field.clone(), from.clone(), to.clone()))
+        })
+        .collect()
+}
+
+fn daypart(forecast: &Value) -> Option<&Value> {
+    forecast.get("daypart")?.get(0)
+}
+
+fn dates(forecast: &Value) -> Vec<String> {
+    forecast
+        .get("validTimeLocal")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .map(|time| {
+            time.as_str()
+                .and_then(|time| time.get(..10))
+                .unwrap_or_default()
+                .to_string()
+        })
+        .collect()
+}
+
+fn day_index(forecast: &Value) -> HashMap<String, usize> {
+    dates(forecast)
+        .into_iter()
+        .enumerate()
+        .map(|(idx, date)| (date, idx))
+        .collect()
+}
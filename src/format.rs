@@ -0,0 +1,486 @@
+use axum::{
+    body::Bytes,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use utoipa::ToSchema;
+
+use crate::PayloadKind;
+
+/// Output representations a cached payload can be rendered into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    Json,
+    Xml,
+    Csv,
+    Text,
+    Influx,
+    Msgpack,
+    Cbor,
+}
+
+impl Format {
+    fn from_media_type(media_type: &str) -> Option<Format> {
+        match media_type {
+            "application/json" | "application/*" | "*/*" => Some(Format::Json),
+            "application/xml" | "text/xml" => Some(Format::Xml),
+            "text/csv" => Some(Format::Csv),
+            "text/plain" => Some(Format::Text),
+            "application/msgpack" | "application/x-msgpack" => Some(Format::Msgpack),
+            "application/cbor" => Some(Format::Cbor),
+            _ => None,
+        }
+    }
+
+    fn content_type(self) -> &'static str {
+        match self {
+            Format::Json => "application/json",
+            Format::Xml => "application/xml",
+            Format::Csv => "text/csv; charset=utf-8",
+            Format::Text | Format::Influx => "text/plain; charset=utf-8",
+            Format::Msgpack => "application/msgpack",
+            Format::Cbor => "application/cbor",
+        }
+    }
+}
+
+/// Picks the output format: an explicit `?format=` wins, then the highest
+/// weighted supported `Accept` entry, falling back to JSON. HTML isn't
+/// offered, so browsers, which list it first and XML just below, get JSON;
+/// only entries weighted above any HTML one are considered.
+pub fn negotiate(explicit: Option<Format>, headers: &HeaderMap) -> Format {
+    if let Some(format) = explicit {
+        return format;
+    }
+
+    let Some(accept) = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+    else {
+        return Format::Json;
+    };
+
+    let entries: Vec<(String, f32)> = accept
+        .split(',')
+        .filter_map(|entry| {
+            let mut parts = entry.split(';').map(str::trim);
+            let media_type = parts.next()?.to_ascii_lowercase();
+            let quality = parts
+                .find_map(|param| param.strip_prefix("q="))
+                .and_then(|q| q.parse().ok())
+                .unwrap_or(1.0);
+            Some((media_type, quality))
+        })
+        .collect();
+    let html_quality = entries
+        .iter()
+        .filter(|(media_type, _)| {
+            media_type == "text/html" || media_type == "application/xhtml+xml"
+        })
+        .map(|(_, quality)| *quality)
+        .fold(0.0, f32::max);
+
+    let mut candidates: Vec<(f32, Format)> = entries
+        .iter()
+        .filter(|(_, quality)| *quality > html_quality)
+        .filter_map(|(media_type, quality)| Some((*quality, Format::from_media_type(media_type)?)))
+        .collect();
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    candidates
+        .first()
+        .map(|(_, format)| *format)
+        .unwrap_or(Format::Json)
+}
+
+/// A payload encoded into one of the [`Format`]s, cheap to clone so the
+/// bytes can be cached and shared between responses.
+#[derive(Debug, Clone)]
+pub struct Encoded {
+    content_type: &'static str,
+    body: Bytes,
+}
+
+impl IntoResponse for Encoded {
+    fn into_response(self) -> Response {
+        (
+            [(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static(self.content_type),
+            )],
+            self.body,
+        )
+            .into_response()
+    }
+}
+
+/// An encoded representation and the content hash of the cached payload it
+/// was rendered from.
+#[derive(Debug)]
+pub struct RenderedPayload {
+    pub source: u64,
+    pub encoded: Encoded,
+}
+
+/// Renders `value` as `format`. `pretty` indents JSON output and is ignored
+/// by the other formats.
+pub fn render(value: Value, kind: PayloadKind, format: Format, pretty: bool) -> Response {
+    match encode(&value, kind, format, pretty) {
+        Ok(encoded) => encoded.into_response(),
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+/// Status and message sent instead of a representation.
+pub type Rejection = (StatusCode, String);
+
+/// Like [`render`], keeping the encoded bytes; formats unavailable for `kind`
+/// and encoding failures come back as the rejection to send instead.
+pub fn encode(
+    value: &Value,
+    kind: PayloadKind,
+    format: Format,
+    pretty: bool,
+) -> Result<Encoded, Rejection> {
+    let body: Bytes = match (format, kind) {
+        (Format::Json, _) if pretty => serde_json::to_vec_pretty(value)
+            .map_err(|err| encoding_failed(format, err))?
+            .into(),
+        (Format::Json, _) => serde_json::to_vec(value)
+            .map_err(|err| encoding_failed(format, err))?
+            .into(),
+        (Format::Xml, _) => to_xml(value, kind.name()).into(),
+        (Format::Msgpack, _) => rmp_serde::to_vec_named(value)
+            .map_err(|err| encoding_failed(format, err))?
+            .into(),
+        (Format::Cbor, _) => {
+            let mut bytes = Vec::new();
+            ciborium::into_writer(value, &mut bytes).map_err(|err| encoding_failed(format, err))?;
+            bytes.into()
+        }
+        (Format::Csv, PayloadKind::Current) => to_csv(&observation_rows(value)).into(),
+        (Format::Text, PayloadKind::Current) => match to_text(value) {
+            Some(text) => text.into(),
+            None => {
+                return Err((
+                    StatusCode::BAD_GATEWAY,
+                    "no observation available".to_string(),
+                ))
+            }
+        },
+        (Format::Influx, PayloadKind::Current) => to_line_protocol(value).into(),
+        (Format::Csv | Format::Text | Format::Influx, _) => return Err(unsupported(format, kind)),
+    };
+
+    Ok(Encoded {
+        content_type: format.content_type(),
+        body,
+    })
+}
+
+fn unsupported(format: Format, kind: PayloadKind) -> Rejection {
+    (
+        StatusCode::NOT_ACCEPTABLE,
+        format!("{format:?} output is not available for {}", kind.name()),
+    )
+}
+
+fn encoding_failed(format: Format, err: impl std::fmt::Display) -> Rejection {
+    tracing::error!("failed to encode {format:?} response: {err}");
+    (StatusCode::INTERNAL_SERVER_ERROR, String::new())
+}
+
+/// Converts JSON to XML: object keys become elements, array entries become
+/// repeated `<item>` elements and `null` becomes an empty element.
+fn to_xml(value: &Value, root: &str) -> String {
+    let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    write_element(&mut xml, root, value);
+    xml
+}
+
+fn write_element(xml: &mut String, name: &str, value: &Value) {
+    let name = element_name(name);
+    match value {
+        Value::Null => {
+            xml.push_str(&format!("<{name}/>"));
+            return;
+        }
+        _ => xml.push_str(&format!("<{name}>")),
+    }
+
+    match value {
+        Value::Object(map) => map
+            .iter()
+            .for_each(|(key, child)| write_element(xml, key, child)),
+        Value::Array(items) => items
+            .iter()
+            .for_each(|item| write_element(xml, "item", item)),
+        Value::String(text) => escape_into(xml, text),
+        other => xml.push_str(&other.to_string()),
+    }
+
+    xml.push_str(&format!("</{name}>"));
+}
+
+/// Replaces characters that are not valid in XML element names.
+fn element_name(key: &str) -> String {
+    let mut name: String = key
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, '_' | '-' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if !name.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+        name.insert(0, '_');
+    }
+    name
+}
+
+fn escape_into(xml: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '<' => xml.push_str("&lt;"),
+            '>' => xml.push_str("&gt;"),
+            '&' => xml.push_str("&amp;"),
+            '"' => xml.push_str("&quot;"),
+            '\'' => xml.push_str("&apos;"),
+            c => xml.push(c),
+        }
+    }
+}
+
+fn observation_rows(value: &Value) -> Vec<Map<String, Value>> {
+    value
+        .get("observations")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .map(|observation| {
+            let mut row = Map::new();
+            flatten_into(&mut row, "", observation);
+            row
+        })
+        .collect()
+}
+
+/// Flattens nested objects into dot-separated keys, e.g. `metric.temp`.
+fn flatten_into(row: &mut Map<String, Value>, prefix: &str, value: &Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                let key = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                flatten_into(row, &key, child);
+            }
+        }
+        other => {
+            row.insert(prefix.to_string(), other.clone());
+        }
+    }
+}
+
+/// Renders rows as CSV with a header line taken from the first row's keys.
+fn to_csv(rows: &[Map<String, Value>]) -> String {
+    let Some(first) = rows.first() else {
+        return String::new();
+    };
+    let columns: Vec<&String> = first.keys().collect();
+
+    let mut csv = columns
+        .iter()
+        .map(|column| csv_field(column))
+        .collect::<Vec<_>>()
+        .join(",");
+    csv.push('\n');
+
+    for row in rows {
+        let line = columns
+            .iter()
+            .map(|column| match row.get(*column) {
+                None | Some(Value::Null) => String::new(),
+                Some(Value::String(text)) => csv_field(text),
+                Some(other) => csv_field(&other.to_string()),
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        csv.push_str(&line);
+        csv.push('\n');
+    }
+
+    csv
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Builds a one-line summary such as `21.3°C, 62% RH, wind 12 km/h NW, rain 0.0mm`
+/// from the first observation, in whichever unit system it was converted to.
+fn to_text(value: &Value) -> Option<String> {
+    let observation = value.get("observations")?.get(0)?;
+    let (measurements, labels) = [
+        ("metric", ("°C", "km/h", "mm")),
+        ("imperial", ("°F", "mph", "in")),
+        ("uk_hybrid", ("°C", "mph", "mm")),
+    ]
+    .into_iter()
+    .find_map(|(key, labels)| Some((observation.get(key)?, labels)))?;
+    let (temperature_unit, speed_unit, precipitation_unit) = labels;
+
+    let number = |value: Option<&Value>, decimals: usize| {
+        value
+            .and_then(Value::as_f64)
+            .map(|number| format!("{number:.decimals$}"))
+            .unwrap_or_else(|| "-".to_string())
+    };
+
+    let mut wind = format!(
+        "wind {} {speed_unit}",
+        number(measurements.get("windSpeed"), 0)
+    );
+    if let Some(direction) = observation.get("winddir").and_then(Value::as_f64) {
+        wind.push(' ');
+        wind.push_str(compass_point(direction));
+    }
+
+    Some(format!(
+        "{}{temperature_unit}, {}% RH, {wind}, rain {}{precipitation_unit}",
+        number(measurements.get("temp"), 1),
+        number(observation.get("humidity"), 0),
+        number(measurements.get("precipTotal"), 1),
+    ))
+}
+
+/// Maps a direction in degrees onto a 16-point compass rose.
+fn compass_point(degrees: f64) -> &'static str {
+    const POINTS: [&str; 16] = [
+        "N", "NNE", "NE", "ENE", "E", "ESE", "SE", "SSE", "S", "SSW", "SW", "WSW", "W", "WNW",
+        "NW", "NNW",
+    ];
+    let index = ((degrees.rem_euclid(360.0) / 22.5).round() as usize) % POINTS.len();
+    POINTS[index]
+}
+
+/// Renders each observation as an InfluxDB line protocol point in the `pws`
+/// measurement, tagged by station, country and neighborhood and timestamped
+/// in nanoseconds.
+pub(crate) fn to_line_protocol(value: &Value) -> String {
+    const SKIPPED_FIELDS: &[&str] = &["epoch", "lat", "lon"];
+    // Before `station`, so all tags are sorted by key as InfluxDB prefers.
+    const TAGS: &[&str] = &["country", "neighborhood"];
+
+    let mut lines = String::new();
+    for row in observation_rows(value) {
+        let Some(station) = row.get("stationID").and_then(Value::as_str) else {
+            continue;
+        };
+
+        let fields: Vec<String> = row
+            .iter()
+            .filter(|(key, _)| !SKIPPED_FIELDS.contains(&key.as_str()))
+            .filter_map(|(key, value)| {
+                let name = key.rsplit('.').next().unwrap_or(key);
+                Some(format!(
+                    "{}={}",
+                    escape_line_protocol(name),
+                    value.as_f64()?
+                ))
+            })
+            .collect();
+        if fields.is_empty() {
+            continue;
+        }
+
+        lines.push_str("pws");
+        for tag in TAGS {
+            if let Some(value) = row.get(*tag).and_then(Value::as_str) {
+                if !value.is_empty() {
+                    lines.push_str(&format!(",{tag}={}", escape_line_protocol(value)));
+                }
+            }
+        }
+        lines.push_str(",station=");
+        lines.push_str(&escape_line_protocol(station));
+        lines.push(' ');
+        lines.push_str(&fields.join(","));
+        if let Some(epoch) = row.get("epoch").and_then(Value::as_i64) {
+            lines.push_str(&format!(" {}", epoch * 1_000_000_000));
+        }
+        lines.push('\n');
+    }
+
+    lines
+}
+
+fn escape_line_protocol(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn negotiated(accept: &str) -> Format {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_str(accept).unwrap());
+        negotiate(None, &headers)
+    }
+
+    #[test]
+    fn browsers_get_json() {
+        let chrome = "text/html,application/xhtml+xml,application/xml;q=0.9,image/avif,\
+                      image/webp,image/apng,*/*;q=0.8,application/signed-exchange;v=b3;q=0.7";
+        assert_eq!(negotiated(chrome), Format::Json);
+        let firefox = "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8";
+        assert_eq!(negotiated(firefox), Format::Json);
+    }
+
+    #[test]
+    fn formats_preferred_over_html_are_chosen() {
+        assert_eq!(negotiated("text/html;q=0.5, application/xml"), Format::Xml);
+    }
+
+    #[test]
+    fn highest_weighted_format_wins() {
+        assert_eq!(negotiated("application/xml"), Format::Xml);
+        assert_eq!(negotiated("text/csv;q=0.5, application/xml"), Format::Xml);
+        assert_eq!(negotiated("application/xml;q=0.5, text/csv"), Format::Csv);
+    }
+
+    #[test]
+    fn wildcards_and_unknown_types_fall_back_to_json() {
+        assert_eq!(negotiated("*/*"), Format::Json);
+        assert_eq!(negotiated("image/png"), Format::Json);
+        assert_eq!(negotiate(None, &HeaderMap::new()), Format::Json);
+    }
+
+    #[test]
+    fn refused_formats_are_skipped() {
+        assert_eq!(negotiated("application/xml;q=0, */*;q=0.1"), Format::Json);
+    }
+
+    #[test]
+    fn explicit_format_wins() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_static("application/xml"));
+        assert_eq!(negotiate(Some(Format::Csv), &headers), Format::Csv);
+    }
+}
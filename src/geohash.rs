@@ -0,0 +1,25 @@
+/// Bits of a geohash per character.
+const BITS_PER_CHAR: u32 = 5;
+/// Longest geohash whose cells still map to distinct `f64` centers.
+pub(crate) const MAX_PRECISION: u8 = 12;
+
+/// Center of the geohash cell of `precision` characters containing
+/// `lat,lon`. The bits of a geohash alternate between longitude and
+/// latitude, starting with longitude, so a cell spans 360° and 180° halved
+/// that many times.
+pub(crate) fn cell_center(lat: f64, lon: f64, precision: u8) -> (f64, f64) {
+    let bits = u32::from(precision.min(MAX_PRECISION)) * BITS_PER_CHAR;
+    let lon_bits = bits.div_ceil(2);
+    let lat_bits = bits / 2;
+    (
+        center(lat, -90.0, 180.0, lat_bits),
+        center(lon, -180.0, 360.0, lon_bits),
+    )
+}
+
+fn center(value: f64, min: f64, span: f64, bits: u32) -> f64 {
+    let cells = f64::from(2u32).powi(bits as i32);
+    let size = span / cells;
+    let cell = ((value - min) / size).floor().clamp(0.0, cells - 1.0);
+    min + (cell + 0.5) * size
+}
@@ -0,0 +1,249 @@
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::State,
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    cache::{current_value, forecast_value},
+    handlers::{observations::store, station_geocode},
+    recorder::{Measurement, ObservationStore, MEASUREMENTS},
+    tenants::Stations,
+    AppError, AppState, Result,
+};
+
+/// Daily forecast arrays served as `forecast.<name>` targets, one point per
+/// day at its `validTimeUtc`.
+const FORECAST_FIELDS: [&str; 6] = [
+    "temperatureMax",
+    "temperatureMin",
+    "calendarDayTemperatureMax",
+    "calendarDayTemperatureMin",
+    "qpf",
+    "qpfSnow",
+];
+const FORECAST_PREFIX: &str = "forecast.";
+const FORECAST_LANGUAGE: &str = "en-US";
+/// Samples read from the database at a time.
+const PAGE: u32 = 5000;
+const DEFAULT_MAX_DATA_POINTS: i64 = 1000;
+
+/// The endpoints of Grafana's JSON datasource (simple-json): a connection
+/// test at `/grafana`, target names at `/grafana/search` and series at
+/// `/grafana/query`.
+pub(crate) fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/grafana", get(test))
+        .route("/grafana/search", post(search))
+        .route("/grafana/query", post(query))
+        .route("/grafana/annotations", post(annotations))
+        .with_state(state)
+}
+
+#[derive(Deserialize)]
+pub(crate) struct SearchRequest {
+    #[serde(default)]
+    target: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct QueryRequest {
+    range: TimeRange,
+    targets: Vec<QueryTarget>,
+    max_data_points: Option<i64>,
+    interval_ms: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct TimeRange {
+    from: String,
+    to: String,
+}
+
+#[derive(Deserialize)]
+struct QueryTarget {
+    #[serde(default)]
+    target: String,
+}
+
+/// One series; datapoints are `[value, epoch milliseconds]`.
+#[derive(Serialize)]
+pub(crate) struct Series {
+    target: String,
+    datapoints: Vec<(f64, i64)>,
+}
+
+async fn test() -> &'static str {
+    "OK"
+}
+
+#[utoipa::path(
+    post,
+    path = "/grafana/search",
+    request_body(content = Object, description = "Grafana JSON datasource search with an optional `target` substring", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Queryable targets: the recorded measurements, one series per station, and `forecast.*` daily forecast values", body = [String]),
+    )
+)]
+pub(crate) async fn search(Json(request): Json<SearchRequest>) -> Json<Vec<String>> {
+    let filter = request.target.to_lowercase();
+    let targets = MEASUREMENTS
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .chain(
+            FORECAST_FIELDS
+                .iter()
+                .map(|field| format!("{FORECAST_PREFIX}{field}")),
+        )
+        .filter(|target| target.to_lowercase().contains(&filter))
+        .collect();
+    Json(targets)
+}
+
+#[utoipa::path(
+    post,
+    path = "/grafana/query",
+    request_body(content = Object, description = "Grafana JSON datasource query with `range`, `targets`, `maxDataPoints` and `intervalMs`", content_type = "application/json"),
+    responses(
+        (status = 200, description = "Per target, one series per station of the recorded values averaged over intervals so at most `maxDataPoints` are returned, or the forecast values in the range; upstream is contacted for forecasts only", body = [Object]),
+        (status = 400, description = "Invalid range or unknown target", body = String, content_type = "text/plain"),
+        (status = 404, description = "No observation database is configured", body = String, content_type = "text/plain"),
+        (status = 502, description = "Upstream request for the forecast failed", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn query(
+    State(state): State<AppState>,
+    stations: Stations,
+    Json(request): Json<QueryRequest>,
+) -> Result<Json<Vec<Series>>> {
+    let from = parse_time(&request.range.from, "range.from")?;
+    let to = parse_time(&request.range.to, "range.to")?;
+    let max_points = request
+        .max_data_points
+        .unwrap_or(DEFAULT_MAX_DATA_POINTS)
+        .max(1);
+    let interval = ((to - from) / max_points)
+        .max(request.interval_ms.unwrap_or(0) / 1000)
+        .max(1);
+
+    let mut measurements = Vec::new();
+    let mut forecasts = Vec::new();
+    for QueryTarget { target } in &request.targets {
+        if let Some(field) = target.strip_prefix(FORECAST_PREFIX) {
+            if !FORECAST_FIELDS.contains(&field) {
+                return Err(unknown(target));
+            }
+            forecasts.push(field);
+        } else {
+            let measurement = MEASUREMENTS
+                .iter()
+                .find(|(name, _)| name == target)
+                .ok_or_else(|| unknown(target))?;
+            measurements.push(*measurement);
+        }
+    }
+
+    let mut series = Vec::new();
+    if !measurements.is_empty() {
+        let store = store(&state, &stations).await?;
+        series.extend(recorded(store, &measurements, from, to, interval, stations.ids()).await?);
+    }
+    if !forecasts.is_empty() {
+        let current = stations.current(current_value(&state).await?)?;
+        let forecast = match station_geocode(&current) {
+            Some(geocode) => forecast_value(&state, &geocode, FORECAST_LANGUAGE).await?,
+            None => Value::Null,
+        };
+        series.extend(forecasts.into_iter().map(|field| Series {
+            target: format!("{FORECAST_PREFIX}{field}"),
+            datapoints: forecast_points(&forecast, field, from, to),
+        }));
+    }
+    Ok(Json(series))
+}
+
+/// No annotations are recorded; answered so enabling them in Grafana
+/// doesn't fail the panel.
+async fn annotations() -> Json<Vec<Value>> {
+    Json(Vec::new())
+}
+
+/// `measurements` of the samples of `stations` with `from <= epoch < to`,
+/// averaged per `interval` seconds, named `<station> <measurement>`.
+async fn recorded(
+    store: &ObservationStore,
+    measurements: &[(&str, Measurement)],
+    from: i64,
+    to: i64,
+    interval: i64,
+    stations: &[String],
+) -> Result<Vec<Series>> {
+    // (measurement, station) -> bucket -> (sum, count)
+    let mut buckets: BTreeMap<(usize, String), BTreeMap<i64, (f64, u32)>> = BTreeMap::new();
+    let mut after = None;
+    loop {
+        let samples = store.range(from, to, after.take(), PAGE, stations).await?;
+        for sample in &samples {
+            let bucket = sample.epoch - (sample.epoch - from).rem_euclid(interval);
+            for (index, (_, value)) in measurements.iter().enumerate() {
+                if let Some(value) = value(sample) {
+                    let (sum, count) = buckets
+                        .entry((index, sample.station.clone()))
+                        .or_default()
+                        .entry(bucket)
+                        .or_default();
+                    *sum += value;
+                    *count += 1;
+                }
+            }
+        }
+        match samples.last() {
+            Some(last) if samples.len() == PAGE as usize => {
+                after = Some((last.epoch, last.station.clone()));
+            }
+            _ => break,
+        }
+    }
+    Ok(buckets
+        .into_iter()
+        .map(|((index, station), points)| Series {
+            target: format!("{station} {}", measurements[index].0),
+            datapoints: points
+                .into_iter()
+                .map(|(bucket, (sum, count))| (sum / f64::from(count), bucket * 1000))
+                .collect(),
+        })
+        .collect())
+}
+
+/// The days of the daily forecast array `field` valid within the range.
+fn forecast_points(forecast: &Value, field: &str, from: i64, to: i64) -> Vec<(f64, i64)> {
+    let values = forecast.get(field).and_then(Value::as_array);
+    let times = forecast.get("validTimeUtc").and_then(Value::as_array);
+    let (Some(values), Some(times)) = (values, times) else {
+        return Vec::new();
+    };
+    values
+        .iter()
+        .zip(times)
+        .filter_map(|(value, time)| Some((value.as_f64()?, time.as_i64()?)))
+        .filter(|(_, time)| (from..to).contains(time))
+        .map(|(value, time)| (value, time * 1000))
+        .collect()
+}
+
+fn parse_time(raw: &str, name: &str) -> Result<i64> {
+    DateTime::parse_from_rfc3339(raw)
+        .map(|time| time.timestamp())
+        .map_err(|_| AppError::InvalidQuery(format!("{name} must be RFC 3339")))
+}
+
+fn unknown(target: &str) -> AppError {
+    AppError::InvalidQuery(format!("unknown target {target}"))
+}
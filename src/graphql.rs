@@ -0,0 +1,87 @@
+use async_graphql::{
+    http::GraphiQLSource, Context, EmptyMutation, EmptySubscription, Object, Schema,
+};
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse},
+    routing::get,
+    Json, Router,
+};
+use serde_json::Value;
+
+use crate::{
+    cache::{current_value, forecast_value},
+    records::{ForecastDay, Observation},
+    tenants::Stations,
+    AppState,
+};
+
+pub type WeatherSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+fn build_schema(state: AppState) -> WeatherSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(state)
+        .finish()
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Latest observations of the configured station, or of the stations
+    /// the tenant may read.
+    async fn current(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Observation>> {
+        let state = ctx.data::<AppState>()?;
+        let stations = ctx.data_opt::<Stations>().cloned().unwrap_or_default();
+        let json = stations.current(current_value(state).await?)?;
+
+        Ok(json
+            .get("observations")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .map(Observation::from_json)
+            .collect())
+    }
+
+    /// Daily forecast for a `lat,lon` geocode.
+    async fn forecast(
+        &self,
+        ctx: &Context<'_>,
+        geocode: String,
+        #[graphql(default = "en-US")] language: String,
+    ) -> async_graphql::Result<Vec<ForecastDay>> {
+        let state = ctx.data::<AppState>()?;
+        let json = forecast_value(state, &geocode, &language).await?;
+
+        Ok(ForecastDay::from_json(&json))
+    }
+}
+
+/// Routes serving the schema at `/graphql`: queries are POSTed as JSON and a
+/// GET opens the GraphiQL explorer.
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/graphql", get(graphiql).post(execute))
+        .with_state(build_schema(state))
+}
+
+#[utoipa::path(
+    post,
+    path = "/graphql",
+    request_body(content = Object, description = "GraphQL request with `query` and optional `variables`", content_type = "application/json"),
+    responses(
+        (status = 200, description = "GraphQL response with `data` and `errors`", body = Object),
+    )
+)]
+pub(crate) async fn execute(
+    State(schema): State<WeatherSchema>,
+    stations: Stations,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    Json(schema.execute(request.data(stations)).await)
+}
+
+async fn graphiql() -> impl IntoResponse {
+    Html(GraphiQLSource::build().endpoint("/graphql").finish())
+}
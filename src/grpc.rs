@@ -0,0 +1,160 @@
+use std::pin::Pin;
+
+use serde_json::Value;
+use tokio_stream::{wrappers::WatchStream, Stream, StreamExt};
+use tonic::{Request, Response, Status};
+
+use crate::{
+    cache::{current_value, forecast_value},
+    records::{ForecastDay, Observation},
+    tenants::Stations,
+    AppState,
+};
+
+pub mod proto {
+    tonic::include_proto!("weather.v1");
+}
+
+use proto::{
+    weather_server::{Weather, WeatherServer},
+    CurrentResponse, ForecastResponse, GetCurrentRequest, GetForecastRequest, WatchCurrentRequest,
+};
+
+/// The `weather.v1.Weather` service, served next to the HTTP routes on the same port.
+pub fn router(state: AppState) -> axum::Router {
+    tonic::service::Routes::new(WeatherServer::new(WeatherService { state })).into_axum_router()
+}
+
+struct WeatherService {
+    state: AppState,
+}
+
+#[tonic::async_trait]
+impl Weather for WeatherService {
+    async fn get_current(
+        &self,
+        request: Request<GetCurrentRequest>,
+    ) -> Result<Response<CurrentResponse>, Status> {
+        let json = current_value(&self.state)
+            .await
+            .map_err(|err| Status::unavailable(err.to_string()))?;
+        let json = stations(&request)
+            .current(json)
+            .map_err(|err| Status::permission_denied(err.to_string()))?;
+
+        Ok(Response::new(current_response(&json)))
+    }
+
+    async fn get_forecast(
+        &self,
+        request: Request<GetForecastRequest>,
+    ) -> Result<Response<ForecastResponse>, Status> {
+        let request = request.into_inner();
+        if request.geocode.is_empty() {
+            return Err(Status::invalid_argument("geocode is required"));
+        }
+        let language = match request.language.as_str() {
+            "" => "en-US",
+            language => language,
+        };
+        let json = forecast_value(&self.state, &request.geocode, language)
+            .await
+            .map_err(|err| Status::unavailable(err.to_string()))?;
+
+        Ok(Response::new(ForecastResponse {
+            days: ForecastDay::from_json(&json)
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+        }))
+    }
+
+    type WatchCurrentStream = Pin<Box<dyn Stream<Item = Result<CurrentResponse, Status>> + Send>>;
+
+    async fn watch_current(
+        &self,
+        request: Request<WatchCurrentRequest>,
+    ) -> Result<Response<Self::WatchCurrentStream>, Status> {
+        let stations = stations(&request);
+        if let Some(latest) = self.state.current_updates.borrow().clone() {
+            stations
+                .current(latest)
+                .map_err(|err| Status::permission_denied(err.to_string()))?;
+        }
+        let updates = WatchStream::new(self.state.current_updates.subscribe())
+            .filter_map(move |value| {
+                value
+                    .and_then(|value| stations.observations(value))
+                    .map(|value| current_response(&value))
+            })
+            .map(Ok);
+
+        Ok(Response::new(Box::pin(updates)))
+    }
+}
+
+/// The stations the tenant authorized on the HTTP request may read.
+fn stations<T>(request: &Request<T>) -> Stations {
+    request
+        .extensions()
+        .get::<Stations>()
+        .cloned()
+        .unwrap_or_default()
+}
+
+fn current_response(current: &Value) -> CurrentResponse {
+    CurrentResponse {
+        observations: current
+            .get("observations")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .map(|observation| Observation::from_json(observation).into())
+            .collect(),
+    }
+}
+
+impl From<Observation> for proto::Observation {
+    fn from(observation: Observation) -> proto::Observation {
+        proto::Observation {
+            station_id: observation.station_id,
+            obs_time_utc: observation.obs_time_utc,
+            obs_time_local: observation.obs_time_local,
+            epoch: observation.epoch,
+            neighborhood: observation.neighborhood,
+            lat: observation.lat,
+            lon: observation.lon,
+            humidity: observation.humidity,
+            wind_direction: observation.wind_direction,
+            solar_radiation: observation.solar_radiation,
+            uv: observation.uv,
+            temperature: observation.temperature,
+            dew_point: observation.dew_point,
+            heat_index: observation.heat_index,
+            wind_chill: observation.wind_chill,
+            wind_speed: observation.wind_speed,
+            wind_gust: observation.wind_gust,
+            pressure: observation.pressure,
+            precip_rate: observation.precip_rate,
+            precip_total: observation.precip_total,
+            elevation: observation.elevation,
+        }
+    }
+}
+
+impl From<ForecastDay> for proto::ForecastDay {
+    fn from(day: ForecastDay) -> proto::ForecastDay {
+        proto::ForecastDay {
+            day_of_week: day.day_of_week,
+            valid_time_local: day.valid_time_local,
+            valid_time_utc: day.valid_time_utc,
+            narrative: day.narrative,
+            temperature_max: day.temperature_max,
+            temperature_min: day.temperature_min,
+            qpf: day.qpf,
+            qpf_snow: day.qpf_snow,
+            day_precip_chance: day.day_precip_chance,
+            night_precip_chance: day.night_precip_chance,
+        }
+    }
+}
@@ -0,0 +1,735 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use axum::{
+    extract::{Query, RawQuery, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{Html, IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use utoipa::IntoParams;
+
+#[cfg(feature = "recorder")]
+use crate::grafana;
+#[cfg(feature = "graphql")]
+use crate::graphql;
+use crate::{
+    cache::{
+        current_entry, current_value, forecast_entry, forecast_geocode, forecast_value, CachedEntry,
+    },
+    calendar,
+    config::AppConfig,
+    constants::{FORECAST_GEOCODE_HEADER, HEALTH_PATH},
+    dashboard, derived,
+    feed::{self, RenderedFeed},
+    format::{self, Encoded, Format, Rejection, RenderedPayload},
+    live, projection,
+    tenants::Stations,
+    timezone,
+    transform::{self, Transformer},
+    units::{self, Units},
+    upload, warnings, weather_metrics, AppState, PayloadKind, Result,
+};
+
+#[cfg(feature = "history")]
+pub(crate) mod history;
+#[cfg(feature = "recorder")]
+pub(crate) mod observations;
+
+/// Encoded representations kept by [`respond_cached`].
+const MAX_RENDERED_PAYLOADS: usize = 512;
+/// Feeds kept by [`feed_rss`].
+const MAX_RENDERED_FEEDS: usize = 256;
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct ForecastQueryParams {
+    /// Latitude and longitude, e.g. `50.06,19.94`.
+    pub(crate) geocode: String,
+    /// Language of the narratives, e.g. `en-US`.
+    pub(crate) language: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct DashboardParams {
+    /// Forecast location; defaults to the station's own coordinates.
+    geocode: Option<String>,
+    /// Language of the forecast narratives.
+    #[serde(default = "default_language")]
+    language: String,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct LocalWarningsParams {
+    /// Forecast location, e.g. `50.06,19.94`; defaults to the station's own
+    /// coordinates.
+    geocode: Option<String>,
+}
+
+fn default_language() -> String {
+    "en-US".to_string()
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct ResponseParams {
+    /// Comma-separated dot paths to keep, e.g. `observations.0.metric.temp`.
+    fields: Option<String>,
+    units: Option<Units>,
+    /// Output format; takes precedence over the `Accept` header.
+    format: Option<Format>,
+    /// Indent JSON output.
+    #[serde(default)]
+    pretty: bool,
+    /// Return `observations[0]` as a single-level object (current observations only).
+    #[serde(default)]
+    flatten: bool,
+    /// IANA timezone, e.g. `Europe/Warsaw`; epoch and UTC timestamps are
+    /// rewritten to local ISO-8601 strings.
+    #[param(value_type = Option<String>)]
+    tz: Option<Tz>,
+    /// JMESPath expression applied last, e.g. `observations[0].metric.temp`;
+    /// only its result is returned.
+    query: Option<String>,
+}
+
+pub(crate) async fn dashboard(
+    State(state): State<AppState>,
+    stations: Stations,
+    query: Query<DashboardParams>,
+) -> Result<Html<String>> {
+    // With an explicit location both payloads can be fetched at once; otherwise
+    // the forecast waits for the station's coordinates.
+    let (current, forecast) = match &query.geocode {
+        Some(geocode) => {
+            let (current, forecast) = tokio::join!(
+                current_value(&state),
+                dashboard_forecast(&state, geocode, &query.language),
+            );
+            (stations.current(current?)?, forecast)
+        }
+        None => {
+            let current = stations.current(current_value(&state).await?)?;
+            let forecast = match station_geocode(&current) {
+                Some(geocode) => dashboard_forecast(&state, &geocode, &query.language).await,
+                None => None,
+            };
+            (current, forecast)
+        }
+    };
+
+    Ok(Html(dashboard::render(
+        &current,
+        forecast.as_ref(),
+        state.config.cache_duration_secs.max(60),
+    )))
+}
+
+pub(crate) fn station_geocode(current: &Value) -> Option<String> {
+    let observation = current.get("observations")?.get(0)?;
+    let lat = observation.get("lat")?.as_f64()?;
+    let lon = observation.get("lon")?.as_f64()?;
+    Some(format!("{lat},{lon}"))
+}
+
+/// The dashboard still renders observations when the forecast is unavailable.
+async fn dashboard_forecast(state: &AppState, geocode: &str, language: &str) -> Option<Value> {
+    forecast_value(state, geocode, language)
+        .await
+        .inspect_err(|err| tracing::warn!("dashboard forecast unavailable: {err}"))
+        .ok()
+}
+
+pub(crate) fn api_routes(state: AppState) -> Router {
+    let routes = Router::new()
+        .route("/current", get(current))
+        .route("/current/stream", get(live::sse))
+        .route("/current/derived", get(current_derived))
+        .route("/current/flat", get(current_flat))
+        .route("/current/compact", get(current_compact))
+        .route("/current/local", get(upload::current_local))
+        .route("/forecast", get(forecast))
+        .route("/forecast.ics", get(forecast_ics))
+        .route("/summary", get(summary))
+        .route("/feed.rss", get(feed_rss))
+        .route("/metrics/weather", get(weather_metrics))
+        .route("/influx", get(influx))
+        .route("/warnings/local", get(local_warnings))
+        .route("/ws", get(live::ws))
+        .route(HEALTH_PATH, get(health));
+
+    #[cfg(feature = "history")]
+    let routes = routes
+        .route("/current/trend", get(history::current_trend))
+        .route("/today/summary", get(history::today_summary))
+        .route("/forecast/changes", get(history::forecast_changes));
+
+    #[cfg(feature = "recorder")]
+    let routes = routes
+        .route("/history", get(observations::history))
+        .route("/history/hourly", get(observations::hourly))
+        .route("/history/daily", get(observations::daily))
+        .route("/history/series", get(observations::series))
+        .route("/history/export", get(observations::export))
+        .route("/rain", get(observations::rain))
+        .route("/stats", get(observations::stats))
+        .route("/degree-days", get(observations::degree_days))
+        .route("/wind/rose", get(observations::wind_rose));
+
+    let routes = routes.with_state(state.clone());
+
+    #[cfg(feature = "recorder")]
+    let routes = routes.merge(grafana::router(state.clone()));
+
+    #[cfg(feature = "graphql")]
+    let routes = routes.merge(graphql::router(state));
+
+    routes
+}
+
+#[utoipa::path(
+    method(get, head),
+    path = "/current",
+    params(ResponseParams),
+    responses(
+        (status = 200, description = "Current observations of the configured station in the negotiated format", headers(
+            ("ETag" = String, description = "Weak validator of the cached payload and requested representation"),
+            ("Age" = u64, description = "Seconds since the payload was fetched upstream"),
+            ("Cache-Control" = String, description = "`max-age` set to the seconds until the cached payload expires"),
+            ("Vary" = String, description = "`Accept`, which can select the format"),
+        )),
+        (status = 400, description = "Invalid query parameters or JMESPath expression", body = String, content_type = "text/plain"),
+        (status = 406, description = "Format not available for this resource", body = String, content_type = "text/plain"),
+        (status = 502, description = "Upstream request failed or returned an unusable payload", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn current(
+    State(state): State<AppState>,
+    stations: Stations,
+    params: Query<ResponseParams>,
+    RawQuery(query): RawQuery,
+    headers: HeaderMap,
+) -> Result<Response> {
+    let entry = stations.entry(current_entry(&state).await?)?;
+    let response = respond_cached(
+        &state,
+        &entry,
+        "/current",
+        &query,
+        format::negotiate(params.format, &headers),
+        stations.ids(),
+        |format| {
+            respond(
+                entry.value.clone(),
+                PayloadKind::Current,
+                transformers(&state, "/current"),
+                &params,
+                format,
+            )
+        },
+    )
+    .await;
+    let variant = (query, headers.get(header::ACCEPT).cloned());
+    Ok(with_cache_headers(response, &entry, &state.config, variant))
+}
+
+#[utoipa::path(
+    get,
+    path = "/current/flat",
+    params(ResponseParams),
+    responses(
+        (status = 200, description = "The first observation as a single-level object with snake_case keys, same as `/current?flatten=true`"),
+        (status = 400, description = "Invalid query parameters", body = String, content_type = "text/plain"),
+        (status = 502, description = "Upstream request failed or returned an unusable payload", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn current_flat(
+    state: State<AppState>,
+    stations: Stations,
+    Query(mut params): Query<ResponseParams>,
+    raw_query: RawQuery,
+    headers: HeaderMap,
+) -> Result<Response> {
+    params.flatten = true;
+    current(state, stations, Query(params), raw_query, headers).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/current/compact",
+    responses(
+        (status = 200, description = "Temperature `t` (°C), humidity `h` (%), wind speed `ws` and gust `wg` (km/h), wind direction `wd` (°), pressure `p` (hPa), today's rain `r` (mm) and observation epoch `e`; missing values are omitted", body = Object),
+        (status = 502, description = "Upstream request failed or returned an unusable payload", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn current_compact(
+    State(state): State<AppState>,
+    stations: Stations,
+) -> Result<Response> {
+    let entry = stations.entry(current_entry(&state).await?)?;
+    let response = Json(projection::compact_observation(&entry.value)).into_response();
+    Ok(with_cache_headers(response, &entry, &state.config, ()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/current/derived",
+    params(ResponseParams),
+    responses(
+        (status = 200, description = "Dew point, heat index, wind chill and apparent temperature per observation; values computed by the proxy are listed in `computed`"),
+        (status = 400, description = "Invalid query parameters", body = String, content_type = "text/plain"),
+        (status = 502, description = "Upstream request failed or returned an unusable payload", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn current_derived(
+    State(state): State<AppState>,
+    stations: Stations,
+    params: Query<ResponseParams>,
+    RawQuery(query): RawQuery,
+    headers: HeaderMap,
+) -> Result<Response> {
+    let entry = stations.entry(current_entry(&state).await?)?;
+    let response = respond_cached(
+        &state,
+        &entry,
+        "/current/derived",
+        &query,
+        format::negotiate(params.format, &headers),
+        stations.ids(),
+        |format| {
+            respond(
+                derived::derive(&entry.value),
+                PayloadKind::Current,
+                transformers(&state, "/current/derived"),
+                &params,
+                format,
+            )
+        },
+    )
+    .await;
+    let variant = (query, headers.get(header::ACCEPT).cloned());
+    Ok(with_cache_headers(response, &entry, &state.config, variant))
+}
+
+#[utoipa::path(
+    method(get, head),
+    path = "/forecast",
+    params(ForecastQueryParams, ResponseParams),
+    responses(
+        (status = 200, description = "5-day daily forecast in the negotiated format; with `FAILOVER_FILE` set, `source` names the provider it came from, and with `PLACE_NAMES` set, `placeName` the place, e.g. `Kraków, PL`", headers(
+            ("ETag" = String, description = "Weak validator of the cached payload and requested representation"),
+            ("Age" = u64, description = "Seconds since the payload was fetched upstream"),
+            ("Cache-Control" = String, description = "`max-age` set to the seconds until the cached payload expires"),
+            ("Vary" = String, description = "`Accept`, which can select the format"),
+            ("X-Forecast-Geocode" = String, description = "The geocode the forecast was fetched for, when `FORECAST_GRID_DEGREES` or `FORECAST_GEOHASH_PRECISION` moved the requested one"),
+        )),
+        (status = 400, description = "Missing or invalid query parameters or JMESPath expression", body = String, content_type = "text/plain"),
+        (status = 406, description = "Format not available for this resource", body = String, content_type = "text/plain"),
+        (status = 502, description = "Upstream request failed or returned an unusable payload", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn forecast(
+    State(state): State<AppState>,
+    query: Query<ForecastQueryParams>,
+    params: Query<ResponseParams>,
+    RawQuery(raw_query): RawQuery,
+    headers: HeaderMap,
+) -> Result<Response> {
+    let entry = forecast_entry(&state, &query.geocode, &query.language).await?;
+    let response = respond_cached(
+        &state,
+        &entry,
+        "/forecast",
+        &raw_query,
+        format::negotiate(params.format, &headers),
+        &[],
+        |format| {
+            respond(
+                entry.value.clone(),
+                PayloadKind::Forecast,
+                transformers(&state, "/forecast"),
+                &params,
+                format,
+            )
+        },
+    )
+    .await;
+    let variant = (raw_query, headers.get(header::ACCEPT).cloned());
+    let mut response = with_cache_headers(response, &entry, &state.config, variant);
+    let geocode = forecast_geocode(&state, &query.geocode);
+    if geocode != query.geocode {
+        if let Ok(value) = HeaderValue::from_str(&geocode) {
+            response
+                .headers_mut()
+                .insert(FORECAST_GEOCODE_HEADER, value);
+        }
+    }
+    Ok(response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/summary",
+    params(ForecastQueryParams),
+    responses(
+        (status = 200, description = "Current observations and the forecast for `geocode` as `{\"current\", \"forecast\"}`; both are fetched concurrently", body = Object, content_type = "application/json"),
+        (status = 400, description = "Missing or invalid query parameters", body = String, content_type = "text/plain"),
+        (status = 502, description = "Upstream request failed or returned an unusable payload", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn summary(
+    State(state): State<AppState>,
+    stations: Stations,
+    query: Query<ForecastQueryParams>,
+) -> Result<Response> {
+    let (current, forecast) = tokio::try_join!(
+        current_entry(&state),
+        forecast_entry(&state, &query.geocode, &query.language),
+    )?;
+    let current = stations.entry(current)?;
+    let response = Json(json!({
+        "current": current.value,
+        "forecast": forecast.value,
+    }))
+    .into_response();
+    // The older entry expires first, so it bounds how long the pair is fresh.
+    let oldest = if current.fetched_at <= forecast.fetched_at {
+        &current
+    } else {
+        &forecast
+    };
+    Ok(with_cache_headers(
+        response,
+        oldest,
+        &state.config,
+        (current.content_hash, forecast.content_hash),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/forecast.ics",
+    params(ForecastQueryParams),
+    responses(
+        (status = 200, description = "Forecast days as all-day iCalendar events", body = String, content_type = "text/calendar"),
+        (status = 400, description = "Missing or invalid query parameters", body = String, content_type = "text/plain"),
+        (status = 502, description = "Upstream request failed or returned an unusable payload", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn forecast_ics(
+    State(state): State<AppState>,
+    query: Query<ForecastQueryParams>,
+) -> Result<Response> {
+    let entry = forecast_entry(&state, &query.geocode, &query.language).await?;
+    let response = (
+        [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        calendar::render(&entry.value, &query.geocode),
+    )
+        .into_response();
+    Ok(with_cache_headers(response, &entry, &state.config, ()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/feed.rss",
+    params(ForecastQueryParams),
+    responses(
+        (status = 200, description = "RSS feed of active alerts and daily forecast narratives for the geocode the forecast is fetched for, which `FORECAST_GRID_DEGREES` or `FORECAST_GEOHASH_PRECISION` can move", body = String, content_type = "application/rss+xml"),
+        (status = 400, description = "Missing or invalid query parameters", body = String, content_type = "text/plain"),
+        (status = 502, description = "Upstream request failed or returned an unusable payload", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn feed_rss(
+    State(state): State<AppState>,
+    query: Query<ForecastQueryParams>,
+) -> Result<Response> {
+    // Built entirely for the geocode the forecast is cached under, so nearby
+    // coordinates share one feed as they share the forecast.
+    let geocode = forecast_geocode(&state, &query.geocode);
+    let (forecast, alerts) = tokio::join!(
+        forecast_entry(&state, &geocode, &query.language),
+        feed_alerts(&state, &geocode, &query.language),
+    );
+    let forecast = forecast?;
+
+    let sources = (
+        forecast.content_hash,
+        alerts.as_ref().map_or(0, |alerts| alerts.content_hash),
+    );
+    let feed_key = format!("{geocode}_{}", query.language);
+    let cached = state
+        .rendered_feeds
+        .read()
+        .await
+        .get(&feed_key)
+        .filter(|feed| feed.sources == sources)
+        .map(|feed| feed.body.clone());
+
+    let body = match cached {
+        Some(body) => body,
+        None => {
+            let alerts_value = alerts.as_ref().map_or(&Value::Null, |alerts| &alerts.value);
+            let body = feed::render(&forecast.value, alerts_value, &geocode, &query.language);
+            let mut rendered = state.rendered_feeds.write().await;
+            // Every geocode cell makes a new key; start over rather than grow
+            // without bound.
+            if rendered.len() >= MAX_RENDERED_FEEDS && !rendered.contains_key(&feed_key) {
+                rendered.clear();
+            }
+            rendered.insert(
+                feed_key,
+                RenderedFeed {
+                    sources,
+                    body: body.clone(),
+                },
+            );
+            body
+        }
+    };
+
+    let response = (
+        [(header::CONTENT_TYPE, "application/rss+xml; charset=utf-8")],
+        body,
+    )
+        .into_response();
+    Ok(with_cache_headers(
+        response,
+        &forecast,
+        &state.config,
+        sources,
+    ))
+}
+
+/// Alerts are supplementary; a failed lookup still yields the forecast items.
+#[cfg(feature = "alerts")]
+async fn feed_alerts(state: &AppState, geocode: &str, language: &str) -> Option<CachedEntry> {
+    crate::cache::alerts_entry(state, geocode, language)
+        .await
+        .inspect_err(|err| tracing::warn!("failed to fetch alerts: {err}"))
+        .ok()
+}
+
+#[cfg(not(feature = "alerts"))]
+async fn feed_alerts(_state: &AppState, _geocode: &str, _language: &str) -> Option<CachedEntry> {
+    None
+}
+
+#[utoipa::path(
+    get,
+    path = "/warnings/local",
+    params(LocalWarningsParams),
+    responses(
+        (status = 200, description = "Frost risk from tonight's forecast minimum, adjusted for the current dew point and wind, and heat risk from today's forecast maximum and the current heat index, each `none`, `low`, `moderate` or `high`, as `{\"station\", \"observedAt\", \"frost\", \"heat\"}`", body = Object),
+        (status = 502, description = "Upstream request failed or returned an unusable payload", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn local_warnings(
+    State(state): State<AppState>,
+    stations: Stations,
+    query: Query<LocalWarningsParams>,
+) -> Result<Json<warnings::LocalWarnings>> {
+    let current = stations.current(current_value(&state).await?)?;
+    let geocode = query.geocode.clone().or_else(|| station_geocode(&current));
+    let forecast = match geocode {
+        Some(geocode) => Some(forecast_value(&state, &geocode, &default_language()).await?),
+        None => None,
+    };
+    Ok(Json(warnings::assess(&current, forecast.as_ref())))
+}
+
+#[utoipa::path(
+    get,
+    path = "/health",
+    responses(
+        (status = 200, description = "The proxy is up; upstream is not contacted", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn health() -> &'static str {
+    "ok"
+}
+
+#[utoipa::path(
+    get,
+    path = "/metrics/weather",
+    responses(
+        (status = 200, description = "Current observations as Prometheus gauges", body = String, content_type = "text/plain; version=0.0.4"),
+        (status = 502, description = "Upstream request failed or returned an unusable payload", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn weather_metrics(
+    State(state): State<AppState>,
+    stations: Stations,
+) -> Result<Response> {
+    let entry = stations.entry(current_entry(&state).await?)?;
+    let response = (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        weather_metrics::render(&entry.value),
+    )
+        .into_response();
+    Ok(with_cache_headers(response, &entry, &state.config, ()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/influx",
+    responses(
+        (status = 200, description = "Current observations in InfluxDB line protocol", body = String, content_type = "text/plain"),
+        (status = 502, description = "Upstream request failed or returned an unusable payload", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn influx(State(state): State<AppState>, stations: Stations) -> Result<Response> {
+    let entry = stations.entry(current_entry(&state).await?)?;
+    let response = format::render(
+        entry.value.clone(),
+        PayloadKind::Current,
+        Format::Influx,
+        false,
+    );
+    Ok(with_cache_headers(response, &entry, &state.config, ()))
+}
+
+/// Sets `Age` to the time since the entry was fetched, `Cache-Control`
+/// `max-age` to the time until it expires, and a weak `ETag` combining the
+/// cached content with the request `variant` that shaped the representation.
+fn with_cache_headers(
+    mut response: Response,
+    entry: &CachedEntry,
+    config: &AppConfig,
+    variant: impl Hash,
+) -> Response {
+    let age = entry.fetched_at.elapsed().as_secs();
+    let max_age = config.cache_duration_secs.saturating_sub(age);
+
+    let mut hasher = DefaultHasher::new();
+    variant.hash(&mut hasher);
+    let etag = format!("W/\"{:016x}-{:016x}\"", entry.content_hash, hasher.finish());
+
+    let headers = response.headers_mut();
+    headers.insert(
+        header::ETAG,
+        HeaderValue::from_str(&etag).expect("valid header value"),
+    );
+    headers.insert(header::AGE, HeaderValue::from(age));
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_str(&format!("max-age={max_age}")).expect("valid header value"),
+    );
+    response
+}
+
+/// Transformers configured for `route`, given without the `/v1` prefix.
+fn transformers<'a>(state: &'a AppState, route: &str) -> &'a [Transformer] {
+    state
+        .config
+        .transformers
+        .get(route)
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+fn respond(
+    mut value: Value,
+    kind: PayloadKind,
+    transformers: &[Transformer],
+    params: &ResponseParams,
+    format: Format,
+) -> std::result::Result<Encoded, Rejection> {
+    if let Some(units) = params.units.or(transform::units(transformers)) {
+        match kind {
+            PayloadKind::Current => units::convert_current(&mut value, units),
+            PayloadKind::Forecast => units::convert_forecast(&mut value, units),
+        }
+    }
+
+    transform::apply(&mut value, transformers);
+
+    if let Some(tz) = params.tz {
+        timezone::localize(&mut value, tz);
+    }
+
+    if params.flatten && matches!(kind, PayloadKind::Current) {
+        value = projection::flatten_observation(&value);
+    }
+
+    if let Some(fields) = &params.fields {
+        value = projection::project(&value, fields);
+    }
+
+    if let Some(expression) = &params.query {
+        value = match projection::query(&value, expression) {
+            Ok(result) => result,
+            Err(err) => return Err((StatusCode::BAD_REQUEST, format!("invalid query: {err}"))),
+        };
+    }
+
+    format::encode(&value, kind, format, params.pretty)
+}
+
+/// Encodes through `build` only when no earlier request with the same route,
+/// query string, negotiated format and allowed `stations` was rendered from
+/// the current payload; otherwise the stored bytes are sent again. Error responses aren't kept.
+/// The format can follow `Accept`, so responses carry `Vary: Accept` for
+/// shared caches.
+async fn respond_cached(
+    state: &AppState,
+    entry: &CachedEntry,
+    route: &str,
+    query: &Option<String>,
+    format: Format,
+    stations: &[String],
+    build: impl FnOnce(Format) -> std::result::Result<Encoded, Rejection>,
+) -> Response {
+    let key = format!(
+        "{route}?{}#{format:?}@{}",
+        query.as_deref().unwrap_or_default(),
+        stations.join(",")
+    );
+
+    let cached = state
+        .rendered_payloads
+        .read()
+        .await
+        .get(&key)
+        .filter(|rendered| rendered.source == entry.content_hash)
+        .map(|rendered| rendered.encoded.clone());
+    let mut response = match cached {
+        Some(encoded) => encoded.into_response(),
+        None => render(state, entry, key, build(format)).await,
+    };
+    response
+        .headers_mut()
+        .insert(header::VARY, HeaderValue::from_static("accept"));
+    response
+}
+
+/// Stores a successful rendering under `key`.
+async fn render(
+    state: &AppState,
+    entry: &CachedEntry,
+    key: String,
+    built: std::result::Result<Encoded, Rejection>,
+) -> Response {
+    match built {
+        Ok(encoded) => {
+            let mut rendered = state.rendered_payloads.write().await;
+            // Any query string makes a new key; start over rather than grow
+            // without bound.
+            if rendered.len() >= MAX_RENDERED_PAYLOADS && !rendered.contains_key(&key) {
+                rendered.clear();
+            }
+            rendered.insert(
+                key,
+                RenderedPayload {
+                    source: entry.content_hash,
+                    encoded: encoded.clone(),
+                },
+            );
+            encoded.into_response()
+        }
+        Err(rejection) => rejection.into_response(),
+    }
+}
@@ -0,0 +1,98 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use chrono_tz::Tz;
+use serde::Deserialize;
+use serde_json::Value;
+use utoipa::IntoParams;
+use wunderground_proxy_core::cache::AsKeyRef;
+
+use super::ForecastQueryParams;
+use crate::{
+    cache::{current_entry, forecast_entry, forecast_geocode, forecast_key},
+    forecast_diff,
+    tenants::Stations,
+    today::DailySummary,
+    trend::PressureTrend,
+    AppState, Result,
+};
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct TimezoneParams {
+    /// IANA timezone, e.g. `Europe/Warsaw`, whose midnight starts the day.
+    #[param(value_type = Option<String>)]
+    tz: Option<Tz>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/current/trend",
+    responses(
+        (status = 200, description = "1h/3h pressure change (hPa) and a rising/steady/falling tendency from recent refreshes", body = Object),
+        (status = 502, description = "Upstream request failed or returned an unusable payload", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn current_trend(
+    State(state): State<AppState>,
+    stations: Stations,
+) -> Result<Json<PressureTrend>> {
+    stations.first(&current_entry(&state).await?.value)?;
+    Ok(Json(state.pressure_history.lock().await.trend()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/today/summary",
+    params(TimezoneParams),
+    responses(
+        (status = 200, description = "Min/max temperature, peak gust and rain total (metric) seen across today's refreshes; the day follows the station's local time unless `tz` is given", body = Object),
+        (status = 400, description = "Invalid query parameters", body = String, content_type = "text/plain"),
+        (status = 502, description = "Upstream request failed or returned an unusable payload", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn today_summary(
+    State(state): State<AppState>,
+    stations: Stations,
+    query: Query<TimezoneParams>,
+) -> Result<Json<DailySummary>> {
+    // Make sure a stale cache contributes a fresh sample before summarizing.
+    stations.first(&current_entry(&state).await?.value)?;
+    Ok(Json(state.today.lock().await.summary(query.tz)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/forecast/changes",
+    params(ForecastQueryParams),
+    responses(
+        (status = 200, description = "Per-day fields that differ between the latest forecast and the one it replaced; `previousAgeSecs` is null until the forecast has changed once", body = Object),
+        (status = 400, description = "Missing or invalid query parameters", body = String, content_type = "text/plain"),
+        (status = 502, description = "Upstream request failed or returned an unusable payload", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn forecast_changes(
+    State(state): State<AppState>,
+    query: Query<ForecastQueryParams>,
+) -> Result<Json<Value>> {
+    let latest = forecast_entry(&state, &query.geocode, &query.language).await?;
+    let geocode = forecast_geocode(&state, &query.geocode);
+    let cache_key = forecast_key(&geocode, &query.language);
+    let previous = state
+        .previous_forecasts
+        .read()
+        .await
+        .get(&cache_key as &dyn AsKeyRef)
+        .cloned();
+
+    let changes = previous
+        .as_ref()
+        .map(|previous| forecast_diff::diff(&previous.value, &latest.value))
+        .unwrap_or_default();
+
+    Ok(Json(serde_json::json!({
+        "previousAgeSecs": previous.map(|previous| previous.fetched_at.elapsed().as_secs()),
+        "changes": changes,
+    })))
+}
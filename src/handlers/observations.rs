@@ -0,0 +1,832 @@
+use axum::{
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
+
+use crate::{
+    export::{self, Format},
+    recorder::{
+        hundredths, ObservationStore, Resolution, Rollup, Sample, StationStats, MEASUREMENTS,
+    },
+    tenants::Stations,
+    AppError, AppState, Result,
+};
+
+const DEFAULT_LIMIT: u32 = 500;
+const MAX_LIMIT: u32 = 5000;
+const DEFAULT_POINTS: usize = 300;
+const MAX_POINTS: usize = 5000;
+const DEFAULT_ROSE_PERIOD: &str = "30d";
+const DEFAULT_BINS: usize = 16;
+const MAX_BINS: usize = 72;
+/// Lower edges of the wind rose speed bands in km/h, Beaufort 1 to 7 and
+/// above; anything slower is calm.
+const SPEED_BANDS: [f64; 7] = [1.0, 6.0, 12.0, 20.0, 29.0, 39.0, 50.0];
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct HistoryParams {
+    /// Start of the range, inclusive: RFC 3339 or epoch seconds. Unbounded
+    /// when absent.
+    from: Option<String>,
+    /// End of the range, exclusive: RFC 3339 or epoch seconds.
+    to: Option<String>,
+    /// Entries per page, at most 5000.
+    limit: Option<u32>,
+    /// `nextCursor` of the previous page.
+    cursor: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HistoryPage {
+    samples: Vec<Sample>,
+    /// Pass as `cursor` for the next page; `null` on the last one.
+    next_cursor: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct ExportParams {
+    /// `csv` or `parquet`.
+    #[param(value_type = String)]
+    format: Format,
+    /// Start of the range, inclusive: RFC 3339 or epoch seconds. Unbounded
+    /// when absent.
+    from: Option<String>,
+    /// End of the range, exclusive: RFC 3339 or epoch seconds.
+    to: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RollupPage {
+    rollups: Vec<Rollup>,
+    /// Pass as `cursor` for the next page; `null` on the last one.
+    next_cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub(crate) enum RainPeriod {
+    #[serde(rename = "1h")]
+    Hour,
+    #[serde(rename = "24h")]
+    Day,
+    #[serde(rename = "7d")]
+    Week,
+    #[serde(rename = "month")]
+    Month,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct RainParams {
+    /// `1h`, `24h` or `7d` before now, or `month` for the calendar month so
+    /// far.
+    #[param(value_type = String)]
+    period: RainPeriod,
+    /// IANA timezone, e.g. `Europe/Warsaw`, whose midnight starts the month;
+    /// UTC when absent.
+    #[param(value_type = Option<String>)]
+    tz: Option<Tz>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RainAccumulation {
+    period: RainPeriod,
+    from: i64,
+    to: i64,
+    stations: Vec<StationRain>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StationRain {
+    station: String,
+    /// mm, to 0.01.
+    rain_total: f64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum StatsPeriod {
+    Month,
+    Year,
+}
+
+impl StatsPeriod {
+    /// From the start of the period to just past `now`.
+    fn range(self, now: DateTime<Utc>) -> (i64, i64) {
+        let from = match self {
+            StatsPeriod::Month => month_start(now, Tz::UTC),
+            StatsPeriod::Year => Utc
+                .with_ymd_and_hms(now.year(), 1, 1, 0, 0, 0)
+                .single()
+                .map_or(now.timestamp(), |start| start.timestamp()),
+        };
+        (from, now.timestamp() + 1)
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct StatsParams {
+    /// `month` or `year`: the UTC calendar month or year so far.
+    #[param(value_type = String)]
+    period: StatsPeriod,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StationRecords {
+    period: StatsPeriod,
+    from: i64,
+    to: i64,
+    stations: Vec<StationStats>,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct SeriesParams {
+    /// A `/history` sample field, e.g. `temp` or `windGust`.
+    metric: String,
+    /// Start of the range, inclusive: RFC 3339 or epoch seconds. Unbounded
+    /// when absent.
+    from: Option<String>,
+    /// End of the range, exclusive: RFC 3339 or epoch seconds.
+    to: Option<String>,
+    /// Points per station, 3 to 5000; 300 by default.
+    points: Option<usize>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SeriesPage {
+    metric: String,
+    series: Vec<StationSeries>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StationSeries {
+    station: String,
+    /// Recorded before downsampling.
+    samples: usize,
+    /// `[epoch, value]`, oldest first.
+    points: Vec<(i64, f64)>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum DegreeDayKind {
+    Heating,
+    Cooling,
+    Growing,
+}
+
+impl DegreeDayKind {
+    fn default_base(self) -> f64 {
+        match self {
+            DegreeDayKind::Heating | DegreeDayKind::Cooling => 18.0,
+            DegreeDayKind::Growing => 10.0,
+        }
+    }
+
+    /// Degree days of one day, from the mean of its extremes.
+    fn of_day(self, base: f64, min: f64, max: f64) -> f64 {
+        let mean = (min + max) / 2.0;
+        match self {
+            DegreeDayKind::Heating => (base - mean).max(0.0),
+            DegreeDayKind::Cooling | DegreeDayKind::Growing => (mean - base).max(0.0),
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct DegreeDaysParams {
+    /// `heating`, `cooling` or `growing`.
+    #[serde(rename = "type")]
+    #[param(rename = "type", value_type = String)]
+    kind: DegreeDayKind,
+    /// Base temperature in °C; 18 for heating and cooling, 10 for growing
+    /// when absent.
+    base: Option<f64>,
+    /// `month` or `year`: the UTC calendar month or year so far.
+    #[param(value_type = String)]
+    period: StatsPeriod,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DegreeDays {
+    #[serde(rename = "type")]
+    kind: DegreeDayKind,
+    base: f64,
+    period: StatsPeriod,
+    from: i64,
+    to: i64,
+    stations: Vec<StationDegreeDays>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StationDegreeDays {
+    station: String,
+    total: f64,
+    days: Vec<DayDegreeDays>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DayDegreeDays {
+    /// UTC day.
+    date: String,
+    value: f64,
+}
+
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct WindRoseParams {
+    /// Hours or days before now, e.g. `24h` or `30d`; `30d` by default.
+    period: Option<String>,
+    /// Direction sectors, 4 to 72; 16 by default.
+    bins: Option<usize>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct WindRose {
+    period: String,
+    from: i64,
+    to: i64,
+    bins: usize,
+    /// km/h; the last band is open-ended.
+    speed_bands: Vec<SpeedBand>,
+    stations: Vec<StationWindRose>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SpeedBand {
+    from: f64,
+    to: Option<f64>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StationWindRose {
+    station: String,
+    samples: usize,
+    /// Percentage of samples below the first speed band.
+    calm: f64,
+    /// Clockwise from north.
+    sectors: Vec<Sector>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Sector {
+    /// Centre of the sector in degrees.
+    direction: f64,
+    /// Percentage of samples per speed band.
+    frequencies: Vec<f64>,
+    total: f64,
+}
+
+/// `HistoryParams` checked and defaulted.
+struct Range {
+    from: i64,
+    to: i64,
+    after: Option<(i64, String)>,
+    limit: u32,
+}
+
+impl HistoryParams {
+    fn range(&self) -> Result<Range> {
+        let (from, to) = bounds(self.from.as_deref(), self.to.as_deref())?;
+        Ok(Range {
+            from,
+            to,
+            after: self.cursor.as_deref().map(decode_cursor).transpose()?,
+            limit: self.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT),
+        })
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/history",
+    params(HistoryParams),
+    responses(
+        (status = 200, description = "Recorded observations in the range, oldest first, as `{\"samples\", \"nextCursor\"}`; upstream is not contacted", body = Object),
+        (status = 400, description = "Invalid range, limit or cursor", body = String, content_type = "text/plain"),
+        (status = 404, description = "No observation database is configured", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn history(
+    State(state): State<AppState>,
+    stations: Stations,
+    query: Query<HistoryParams>,
+) -> Result<Json<HistoryPage>> {
+    let Range {
+        from,
+        to,
+        after,
+        limit,
+    } = query.range()?;
+    let samples = store(&state, &stations)
+        .await?
+        .range(from, to, after, limit, stations.ids())
+        .await?;
+    let next_cursor = (samples.len() == limit as usize)
+        .then(|| samples.last())
+        .flatten()
+        .map(|last| encode_cursor(last.epoch, &last.station));
+    Ok(Json(HistoryPage {
+        samples,
+        next_cursor,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/history/hourly",
+    params(HistoryParams),
+    responses(
+        (status = 200, description = "Hourly temperature min/max/avg, rain total and peak gust per station, for hours starting in the range, oldest first, as `{\"rollups\", \"nextCursor\"}`", body = Object),
+        (status = 400, description = "Invalid range, limit or cursor", body = String, content_type = "text/plain"),
+        (status = 404, description = "No observation database is configured", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn hourly(
+    State(state): State<AppState>,
+    stations: Stations,
+    query: Query<HistoryParams>,
+) -> Result<Json<RollupPage>> {
+    rollups(&state, &stations, Resolution::Hourly, &query).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/history/daily",
+    params(HistoryParams),
+    responses(
+        (status = 200, description = "Daily (UTC) temperature min/max/avg, rain total and peak gust per station, for days starting in the range, oldest first, as `{\"rollups\", \"nextCursor\"}`", body = Object),
+        (status = 400, description = "Invalid range, limit or cursor", body = String, content_type = "text/plain"),
+        (status = 404, description = "No observation database is configured", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn daily(
+    State(state): State<AppState>,
+    stations: Stations,
+    query: Query<HistoryParams>,
+) -> Result<Json<RollupPage>> {
+    rollups(&state, &stations, Resolution::Daily, &query).await
+}
+
+async fn rollups(
+    state: &AppState,
+    stations: &Stations,
+    resolution: Resolution,
+    query: &HistoryParams,
+) -> Result<Json<RollupPage>> {
+    let Range {
+        from,
+        to,
+        after,
+        limit,
+    } = query.range()?;
+    let rollups = store(state, stations)
+        .await?
+        .rollups(resolution, from, to, after, limit, stations.ids())
+        .await?;
+    let next_cursor = (rollups.len() == limit as usize)
+        .then(|| rollups.last())
+        .flatten()
+        .map(|last| encode_cursor(last.bucket, &last.station));
+    Ok(Json(RollupPage {
+        rollups,
+        next_cursor,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/history/series",
+    params(SeriesParams),
+    responses(
+        (status = 200, description = "One metric per station over the range, downsampled with Largest-Triangle-Three-Buckets so peaks survive, as `{\"metric\", \"series\": [{\"station\", \"samples\", \"points\": [[epoch, value]]}]}`", body = Object),
+        (status = 400, description = "Unknown metric, or invalid range or point count", body = String, content_type = "text/plain"),
+        (status = 404, description = "No observation database is configured", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn series(
+    State(state): State<AppState>,
+    stations: Stations,
+    query: Query<SeriesParams>,
+) -> Result<Json<SeriesPage>> {
+    let (_, measurement) = MEASUREMENTS
+        .iter()
+        .find(|(name, _)| *name == query.metric)
+        .ok_or_else(|| AppError::InvalidQuery(format!("unknown metric {}", query.metric)))?;
+    let points = query.points.unwrap_or(DEFAULT_POINTS);
+    if !(3..=MAX_POINTS).contains(&points) {
+        return Err(AppError::InvalidQuery(format!(
+            "points must be between 3 and {MAX_POINTS}"
+        )));
+    }
+    let (from, to) = bounds(query.from.as_deref(), query.to.as_deref())?;
+    let series = store(&state, &stations)
+        .await?
+        .series(from, to, *measurement, stations.ids())
+        .await?
+        .into_iter()
+        .map(|(station, values)| StationSeries {
+            station,
+            samples: values.len(),
+            points: lttb(&values, points),
+        })
+        .collect();
+    Ok(Json(SeriesPage {
+        metric: query.metric.clone(),
+        series,
+    }))
+}
+
+/// Largest-Triangle-Three-Buckets: the first and last point, and from each
+/// of `threshold - 2` buckets in between the point spanning the largest
+/// triangle with the point picked before it and the next bucket's average.
+fn lttb(data: &[(i64, f64)], threshold: usize) -> Vec<(i64, f64)> {
+    if data.len() <= threshold {
+        return data.to_vec();
+    }
+    let every = (data.len() - 2) as f64 / (threshold - 2) as f64;
+    let bucket = |index: usize| {
+        let start = (index as f64 * every) as usize + 1;
+        let end = (((index + 1) as f64 * every) as usize + 1).min(data.len() - 1);
+        start..end
+    };
+
+    let mut sampled = Vec::with_capacity(threshold);
+    sampled.push(data[0]);
+    let mut previous = data[0];
+    for index in 0..threshold - 2 {
+        let next = bucket(index + 1);
+        let next = if next.is_empty() {
+            &data[data.len() - 1..]
+        } else {
+            &data[next]
+        };
+        let average_x = next.iter().map(|(x, _)| *x as f64).sum::<f64>() / next.len() as f64;
+        let average_y = next.iter().map(|(_, y)| y).sum::<f64>() / next.len() as f64;
+
+        let (previous_x, previous_y) = (previous.0 as f64, previous.1);
+        let area = |(x, y): &(i64, f64)| {
+            ((previous_x - average_x) * (y - previous_y)
+                - (previous_x - *x as f64) * (average_y - previous_y))
+                .abs()
+        };
+        if let Some(picked) = data[bucket(index)]
+            .iter()
+            .max_by(|a, b| area(a).total_cmp(&area(b)))
+        {
+            sampled.push(*picked);
+            previous = *picked;
+        }
+    }
+    sampled.push(data[data.len() - 1]);
+    sampled
+}
+
+#[utoipa::path(
+    get,
+    path = "/history/export",
+    params(ExportParams),
+    responses(
+        (status = 200, description = "Every recorded observation in the range, oldest first, streamed as a CSV or Parquet attachment with the `/history` sample fields as columns", body = String, content_type = "text/csv"),
+        (status = 400, description = "Unknown format or invalid range", body = String, content_type = "text/plain"),
+        (status = 404, description = "No observation database is configured", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn export(
+    State(state): State<AppState>,
+    stations: Stations,
+    query: Query<ExportParams>,
+) -> Result<Response> {
+    let (from, to) = bounds(query.from.as_deref(), query.to.as_deref())?;
+    let body = export::stream(
+        store(&state, &stations).await?.clone(),
+        query.format,
+        from,
+        to,
+        stations.ids().to_vec(),
+    );
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                query.format.content_type().to_string(),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"observations.{}\"",
+                    query.format.extension()
+                ),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/rain",
+    params(RainParams),
+    responses(
+        (status = 200, description = "Rain in mm per station over the period, summed from recorded observations across daily resets, as `{\"period\", \"from\", \"to\", \"stations\": [{\"station\", \"rainTotal\"}]}`; upstream is not contacted", body = Object),
+        (status = 400, description = "Unknown period or timezone", body = String, content_type = "text/plain"),
+        (status = 404, description = "No observation database is configured", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn rain(
+    State(state): State<AppState>,
+    stations: Stations,
+    query: Query<RainParams>,
+) -> Result<Json<RainAccumulation>> {
+    let now = Utc::now();
+    let from = match query.period {
+        RainPeriod::Hour => now.timestamp() - 3600,
+        RainPeriod::Day => now.timestamp() - 86_400,
+        RainPeriod::Week => now.timestamp() - 7 * 86_400,
+        RainPeriod::Month => month_start(now, query.tz.unwrap_or(Tz::UTC)),
+    };
+    // Include a sample observed this very second.
+    let to = now.timestamp() + 1;
+    let stations = store(&state, &stations)
+        .await?
+        .rain(from, to, stations.ids())
+        .await?
+        .into_iter()
+        .map(|(station, rain_total)| StationRain {
+            station,
+            rain_total: hundredths(rain_total),
+        })
+        .collect();
+    Ok(Json(RainAccumulation {
+        period: query.period,
+        from,
+        to,
+        stations,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/stats",
+    params(StatsParams),
+    responses(
+        (status = 200, description = "Station records over the period from the daily (UTC) rollups: hottest, coldest and wettest day and strongest gust, each as `{\"date\", \"value\"}`, plus average temperature, high and low and the rain total, metric units, as `{\"period\", \"from\", \"to\", \"stations\"}`; upstream is not contacted", body = Object),
+        (status = 400, description = "Unknown period", body = String, content_type = "text/plain"),
+        (status = 404, description = "No observation database is configured", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn stats(
+    State(state): State<AppState>,
+    stations: Stations,
+    query: Query<StatsParams>,
+) -> Result<Json<StationRecords>> {
+    let (from, to) = query.period.range(Utc::now());
+    let stations = store(&state, &stations)
+        .await?
+        .stats(from, to, stations.ids())
+        .await?;
+    Ok(Json(StationRecords {
+        period: query.period,
+        from,
+        to,
+        stations,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/degree-days",
+    params(DegreeDaysParams),
+    responses(
+        (status = 200, description = "Heating, cooling or growing degree days per station and UTC day over the period, from the mean of each day's recorded min and max temperature, as `{\"type\", \"base\", \"period\", \"from\", \"to\", \"stations\": [{\"station\", \"total\", \"days\": [{\"date\", \"value\"}]}]}`; days without temperatures are left out", body = Object),
+        (status = 400, description = "Unknown type or period, or invalid base", body = String, content_type = "text/plain"),
+        (status = 404, description = "No observation database is configured", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn degree_days(
+    State(state): State<AppState>,
+    stations: Stations,
+    query: Query<DegreeDaysParams>,
+) -> Result<Json<DegreeDays>> {
+    let base = query.base.unwrap_or(query.kind.default_base());
+    if !base.is_finite() {
+        return Err(AppError::InvalidQuery("base must be a number".to_string()));
+    }
+    let (from, to) = query.period.range(Utc::now());
+    let stations = store(&state, &stations)
+        .await?
+        .daily_temps(from, to, stations.ids())
+        .await?
+        .into_iter()
+        .map(|(station, temps)| {
+            let days: Vec<DayDegreeDays> = temps
+                .into_iter()
+                .map(|(bucket, min, max)| DayDegreeDays {
+                    date: DateTime::from_timestamp(bucket, 0)
+                        .map(|time| time.date_naive().to_string())
+                        .unwrap_or_default(),
+                    value: hundredths(query.kind.of_day(base, min, max)),
+                })
+                .collect();
+            StationDegreeDays {
+                station,
+                total: hundredths(days.iter().map(|day| day.value).sum()),
+                days,
+            }
+        })
+        .collect();
+    Ok(Json(DegreeDays {
+        kind: query.kind,
+        base,
+        period: query.period,
+        from,
+        to,
+        stations,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/wind/rose",
+    params(WindRoseParams),
+    responses(
+        (status = 200, description = "Wind rose per station from the recorded direction and speed: for each direction sector, the percentage of samples in each Beaufort speed band, plus the calm percentage, as `{\"period\", \"from\", \"to\", \"bins\", \"speedBands\", \"stations\": [{\"station\", \"samples\", \"calm\", \"sectors\": [{\"direction\", \"frequencies\", \"total\"}]}]}`; upstream is not contacted", body = Object),
+        (status = 400, description = "Invalid period or bin count", body = String, content_type = "text/plain"),
+        (status = 404, description = "No observation database is configured", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn wind_rose(
+    State(state): State<AppState>,
+    stations: Stations,
+    query: Query<WindRoseParams>,
+) -> Result<Json<WindRose>> {
+    let period = query.period.as_deref().unwrap_or(DEFAULT_ROSE_PERIOD);
+    let lookback = parse_lookback(period)?;
+    let bins = query.bins.unwrap_or(DEFAULT_BINS);
+    if !(4..=MAX_BINS).contains(&bins) {
+        return Err(AppError::InvalidQuery(format!(
+            "bins must be between 4 and {MAX_BINS}"
+        )));
+    }
+    let to = Utc::now().timestamp() + 1;
+    let from = to - lookback;
+    let stations = store(&state, &stations)
+        .await?
+        .wind(from, to, stations.ids())
+        .await?
+        .into_iter()
+        .map(|(station, wind)| rose(station, &wind, bins))
+        .collect();
+    let speed_bands = SPEED_BANDS
+        .iter()
+        .enumerate()
+        .map(|(index, from)| SpeedBand {
+            from: *from,
+            to: SPEED_BANDS.get(index + 1).copied(),
+        })
+        .collect();
+    Ok(Json(WindRose {
+        period: period.to_string(),
+        from,
+        to,
+        bins,
+        speed_bands,
+        stations,
+    }))
+}
+
+/// Bins `(direction, speed)` samples into `bins` sectors centred on north
+/// and the speed bands.
+fn rose(station: String, wind: &[(f64, f64)], bins: usize) -> StationWindRose {
+    let width = 360.0 / bins as f64;
+    let mut counts = vec![[0usize; SPEED_BANDS.len()]; bins];
+    let mut calm = 0;
+    for (direction, speed) in wind {
+        let Some(band) = SPEED_BANDS.iter().rposition(|from| speed >= from) else {
+            calm += 1;
+            continue;
+        };
+        let sector = ((direction.rem_euclid(360.0) + width / 2.0) / width) as usize % bins;
+        counts[sector][band] += 1;
+    }
+    let percent = |count: usize| hundredths(count as f64 * 100.0 / wind.len() as f64);
+    StationWindRose {
+        station,
+        samples: wind.len(),
+        calm: percent(calm),
+        sectors: counts
+            .iter()
+            .enumerate()
+            .map(|(sector, counts)| Sector {
+                direction: hundredths(sector as f64 * width),
+                frequencies: counts.iter().map(|count| percent(*count)).collect(),
+                total: percent(counts.iter().sum()),
+            })
+            .collect(),
+    }
+}
+
+/// Seconds in a period of whole hours (`24h`) or days (`30d`).
+fn parse_lookback(raw: &str) -> Result<i64> {
+    let (count, unit) = if let Some(hours) = raw.strip_suffix('h') {
+        (hours, 3600)
+    } else if let Some(days) = raw.strip_suffix('d') {
+        (days, 86_400)
+    } else {
+        (raw, 0)
+    };
+    count
+        .parse::<u32>()
+        .ok()
+        .filter(|count| *count > 0 && unit > 0)
+        .map(|count| i64::from(count) * unit)
+        .ok_or_else(|| {
+            AppError::InvalidQuery("period must be hours or days, e.g. 24h or 30d".to_string())
+        })
+}
+
+/// Midnight starting the 1st of `now`'s month in `tz`, or the first instant
+/// after it when a DST gap skips midnight.
+fn month_start(now: DateTime<Utc>, tz: Tz) -> i64 {
+    let local = now.with_timezone(&tz).date_naive();
+    let first = local.with_day(1).unwrap_or(local);
+    (0..24)
+        .filter_map(|hour| first.and_hms_opt(hour, 0, 0))
+        .find_map(|start| tz.from_local_datetime(&start).earliest())
+        .map_or(now.timestamp(), |start| start.timestamp())
+}
+
+/// The observation store, refusing tenants none of whose stations has been
+/// recorded.
+pub(crate) async fn store<'a>(
+    state: &'a AppState,
+    stations: &Stations,
+) -> Result<&'a ObservationStore> {
+    let store = state
+        .observations
+        .as_ref()
+        .ok_or(AppError::NotConfigured("OBSERVATIONS_DB"))?;
+    if !stations.ids().is_empty() && !store.records_any(stations.ids()).await? {
+        return Err(AppError::Forbidden(
+            "reading the recorded stations".to_string(),
+        ));
+    }
+    Ok(store)
+}
+
+/// The epoch range `from..to`, unbounded on the missing sides.
+fn bounds(from: Option<&str>, to: Option<&str>) -> Result<(i64, i64)> {
+    let from = from.map(|raw| parse_time(raw, "from")).transpose()?;
+    let to = to.map(|raw| parse_time(raw, "to")).transpose()?;
+    Ok((from.unwrap_or(i64::MIN), to.unwrap_or(i64::MAX)))
+}
+
+fn parse_time(raw: &str, name: &str) -> Result<i64> {
+    raw.parse::<i64>()
+        .ok()
+        .or_else(|| {
+            DateTime::parse_from_rfc3339(raw)
+                .ok()
+                .map(|time| time.timestamp())
+        })
+        .ok_or_else(|| AppError::InvalidQuery(format!("{name} must be RFC 3339 or epoch seconds")))
+}
+
+/// The position of the last entry served; opaque to clients.
+fn encode_cursor(epoch: i64, station: &str) -> String {
+    BASE64_URL_SAFE_NO_PAD.encode(format!("{epoch}:{station}"))
+}
+
+fn decode_cursor(raw: &str) -> Result<(i64, String)> {
+    let invalid = || AppError::InvalidQuery("invalid cursor".to_string());
+    let decoded = BASE64_URL_SAFE_NO_PAD.decode(raw).map_err(|_| invalid())?;
+    let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+    let (epoch, station) = decoded.split_once(':').ok_or_else(invalid)?;
+    Ok((epoch.parse().map_err(|_| invalid())?, station.to_string()))
+}
@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use reqwest::{header, Client, StatusCode};
+use serde_json::Value;
+use tokio::{sync::watch, time::MissedTickBehavior};
+
+use crate::{format::to_line_protocol, InfluxConfig};
+
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Pause before writing failed points again when no new observation comes.
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+/// Points kept for the next write while the server is unreachable; the
+/// oldest are dropped past this.
+const MAX_PENDING: usize = 10_000;
+
+/// Writes every new `/current` payload to the InfluxDB v2 bucket in `config`
+/// as `pws` points, tagged like the `/influx` endpoint's. Points that failed
+/// to write are sent again with the next observation or after
+/// [`RETRY_INTERVAL`], unless the server rejected them.
+pub(crate) async fn run(
+    client: Client,
+    config: InfluxConfig,
+    mut updates: watch::Receiver<Option<Value>>,
+) {
+    let url = format!("{}/api/v2/write", config.url);
+    let authorization = format!("Token {}", config.token);
+    let mut pending: Vec<String> = Vec::new();
+    let mut retry = tokio::time::interval(RETRY_INTERVAL);
+    retry.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            changed = updates.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+                if let Some(current) = updates.borrow_and_update().as_ref() {
+                    pending.extend(to_line_protocol(current).lines().map(String::from));
+                }
+            }
+            _ = retry.tick(), if !pending.is_empty() => {}
+        }
+        if pending.len() > MAX_PENDING {
+            let dropped = pending.len() - MAX_PENDING;
+            pending.drain(..dropped);
+            tracing::warn!("InfluxDB unreachable, dropped {dropped} points");
+        }
+        if pending.is_empty() {
+            continue;
+        }
+
+        let response = client
+            .post(&url)
+            .timeout(WRITE_TIMEOUT)
+            .query(&[
+                ("org", config.org.as_str()),
+                ("bucket", config.bucket.as_str()),
+                ("precision", "ns"),
+            ])
+            .header(header::AUTHORIZATION, &authorization)
+            .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+            .body(pending.join("\n"))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        match response {
+            Ok(_) => {
+                tracing::debug!("wrote {} points to InfluxDB", pending.len());
+                pending.clear();
+            }
+            // Sending the same points again won't change the answer.
+            Err(err)
+                if err.status().is_some_and(|status| {
+                    status.is_client_error() && status != StatusCode::TOO_MANY_REQUESTS
+                }) =>
+            {
+                tracing::warn!("InfluxDB rejected {} points: {err}", pending.len());
+                pending.clear();
+            }
+            Err(err) => tracing::warn!("writing to InfluxDB failed, will retry: {err}"),
+        }
+        retry.reset();
+    }
+}
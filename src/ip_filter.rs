@@ -0,0 +1,96 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{client_ip::ClientIp, AppError, IpFilterConfig, Result};
+
+/// Rejects sources on the denylist, or missing from a non-empty allowlist,
+/// before anything but the client address resolution runs.
+pub(crate) async fn filter(
+    State(config): State<Arc<IpFilterConfig>>,
+    request: Request,
+    next: Next,
+) -> Result<Response> {
+    let source = request
+        .extensions()
+        .get::<ClientIp>()
+        .and_then(|ClientIp(ip)| *ip);
+    let allowed = match source {
+        Some(ip) => {
+            !config.deny.iter().any(|net| net.contains(&ip))
+                && (config.allow.is_empty() || config.allow.iter().any(|net| net.contains(&ip)))
+        }
+        None => config.allow.is_empty(),
+    };
+
+    if !allowed {
+        let source = source.map_or_else(|| "unknown source".to_string(), |ip| ip.to_string());
+        return Err(AppError::SourceDenied(source));
+    }
+    Ok(next.run(request).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+
+    use axum::{
+        body::Body, extract::ConnectInfo, http::StatusCode, middleware, routing::get, Router,
+    };
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::client_ip;
+
+    fn app() -> Router {
+        let ip_filter = IpFilterConfig {
+            allow: vec!["192.0.2.0/24".parse().unwrap()],
+            deny: Vec::new(),
+        };
+        Router::new()
+            .route("/", get(|| async { "ok" }))
+            .layer(middleware::from_fn_with_state(Arc::new(ip_filter), filter))
+            .layer(middleware::from_fn_with_state(
+                Arc::new(vec!["10.0.0.0/8".parse().unwrap()]),
+                client_ip::resolve,
+            ))
+    }
+
+    async fn status(peer: Option<&str>, forwarded_for: &str) -> StatusCode {
+        let mut request = Request::builder()
+            .uri("/")
+            .header("x-forwarded-for", forwarded_for)
+            .body(Body::empty())
+            .unwrap();
+        if let Some(peer) = peer {
+            let peer: SocketAddr = peer.parse().unwrap();
+            request.extensions_mut().insert(ConnectInfo(peer));
+        }
+        app().oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn spoofed_header_from_untrusted_peer_is_denied() {
+        assert_eq!(
+            status(Some("203.0.113.7:5000"), "192.0.2.1").await,
+            StatusCode::FORBIDDEN
+        );
+    }
+
+    #[tokio::test]
+    async fn spoofed_header_without_peer_is_denied() {
+        assert_eq!(status(None, "192.0.2.1").await, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn header_from_trusted_proxy_is_allowed() {
+        assert_eq!(
+            status(Some("10.0.0.2:5000"), "192.0.2.1").await,
+            StatusCode::OK
+        );
+    }
+}
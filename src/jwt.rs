@@ -0,0 +1,261 @@
+use std::time::{Duration, Instant};
+
+use jsonwebtoken::{
+    decode, decode_header,
+    jwk::{Jwk, JwkSet},
+    Algorithm, DecodingKey, Validation,
+};
+use reqwest::Client;
+use serde::Deserialize;
+use tokio::sync::RwLock;
+
+use crate::config::{JwtConfig, JwtKeys};
+
+/// An unknown `kid` triggers a refetch of the key set at most this often, so
+/// tokens with made-up ids can't hammer the identity provider.
+const JWKS_REFETCH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Validates bearer tokens against a shared secret or the identity
+/// provider's published keys.
+#[derive(Debug)]
+pub(crate) struct Jwt {
+    keys: Keys,
+    audience: Option<String>,
+    issuer: Option<String>,
+}
+
+#[derive(Debug)]
+enum Keys {
+    Secret(Vec<u8>),
+    /// Fetched on first use and again when a token names an unknown key, so
+    /// rotations at the provider are picked up without a restart.
+    Jwks {
+        url: String,
+        client: Client,
+        cached: RwLock<Option<(JwkSet, Instant)>>,
+    },
+}
+
+#[derive(Deserialize)]
+struct Claims {
+    sub: Option<String>,
+}
+
+impl Jwt {
+    pub(crate) fn new(config: &JwtConfig, client: &Client) -> Jwt {
+        let keys = match &config.keys {
+            JwtKeys::Secret(secret) => Keys::Secret(secret.as_bytes().to_vec()),
+            JwtKeys::JwksUrl(url) => Keys::Jwks {
+                url: url.clone(),
+                client: client.clone(),
+                cached: RwLock::new(None),
+            },
+        };
+
+        Jwt {
+            keys,
+            audience: config.audience.clone(),
+            issuer: config.issuer.clone(),
+        }
+    }
+
+    /// Checks the signature, expiry, audience and issuer, returning the
+    /// token's subject.
+    pub(crate) async fn verify(&self, token: &str) -> Result<Option<String>, String> {
+        let header = decode_header(token).map_err(|err| err.to_string())?;
+        let (key, algorithms) = match &self.keys {
+            Keys::Secret(secret) => (
+                DecodingKey::from_secret(secret),
+                vec![Algorithm::HS256, Algorithm::HS384, Algorithm::HS512],
+            ),
+            Keys::Jwks {
+                url,
+                client,
+                cached,
+            } => {
+                let kid = header.kid.as_deref().ok_or("token has no kid")?;
+                let jwk = find_jwk(url, client, cached, kid).await?;
+                // The key family is checked against the algorithm, so an
+                // HMAC token can't be signed with a published public key.
+                let key = DecodingKey::from_jwk(&jwk).map_err(|err| err.to_string())?;
+                (key, vec![header.alg])
+            }
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.algorithms = algorithms;
+        match &self.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+
+        let data = decode::<Claims>(token, &key, &validation).map_err(|err| err.to_string())?;
+        Ok(data.claims.sub)
+    }
+}
+
+async fn find_jwk(
+    url: &str,
+    client: &Client,
+    cached: &RwLock<Option<(JwkSet, Instant)>>,
+    kid: &str,
+) -> Result<Jwk, String> {
+    if let Some((set, _)) = &*cached.read().await {
+        if let Some(jwk) = set.find(kid) {
+            return Ok(jwk.clone());
+        }
+    }
+
+    let mut cached = cached.write().await;
+    // Another request may have refetched the set while this one waited.
+    if let Some((set, fetched_at)) = &*cached {
+        if let Some(jwk) = set.find(kid) {
+            return Ok(jwk.clone());
+        }
+        if fetched_at.elapsed() < JWKS_REFETCH_INTERVAL {
+            return Err(format!("unknown kid {kid}"));
+        }
+    }
+
+    let set = fetch_jwks(url, client).await.map_err(|err| {
+        tracing::warn!("fetching JWKS from {url} failed: {err}");
+        format!("signing keys unavailable: {err}")
+    })?;
+    let jwk = set.find(kid).cloned();
+    *cached = Some((set, Instant::now()));
+    jwk.ok_or_else(|| format!("unknown kid {kid}"))
+}
+
+async fn fetch_jwks(url: &str, client: &Client) -> reqwest::Result<JwkSet> {
+    client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{prelude::BASE64_URL_SAFE_NO_PAD, Engine};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    const SECRET: &str = "jwt-secret";
+    /// Modulus of a published RSA key.
+    const MODULUS: &str = "sXchDaQebHnPiGvyDOAT4saGEUetSyo9MKLOoWFsueri23bOdgWp4Dy1WlUzewbgBHod5pcM9H95GQRV3JDXboIRROSBigeC5yjU1hGzHHyXss8UDprecbAYxknTcQkhslANGRUZmdTOQ5qTRsLAt6BTYuyvVRdhS8exSZEy_c4gs_7svlJJQ4H9_NxsiIoLwAEk7-Q3UXERGYw_75IDrGA84-lA_-Ct4eTlXHBIY2EaV7t7LjJaynVJCpkv4LKjTTAumiGUIuQhrNhZLuF_RJLqHpM2kgWFLU7-VTdL1VbC2tejvcI2BlMkEpk1BzBZI0KQB0GaDWFLN-aEAw3vRw";
+
+    fn jwt() -> Jwt {
+        let config = JwtConfig {
+            keys: JwtKeys::Secret(SECRET.to_string()),
+            audience: Some("weather".to_string()),
+            issuer: Some("https://id.example.com".to_string()),
+        };
+        Jwt::new(&config, &Client::new())
+    }
+
+    fn claims() -> Value {
+        json!({
+            "sub": "alice",
+            "aud": "weather",
+            "iss": "https://id.example.com",
+            "exp": chrono::Utc::now().timestamp() + 3600,
+        })
+    }
+
+    fn token(header: &Header, claims: &Value, secret: &str) -> String {
+        encode(header, claims, &EncodingKey::from_secret(secret.as_bytes())).unwrap()
+    }
+
+    #[tokio::test]
+    async fn valid_token_yields_subject() {
+        let token = token(&Header::default(), &claims(), SECRET);
+        assert_eq!(jwt().verify(&token).await, Ok(Some("alice".to_string())));
+    }
+
+    #[tokio::test]
+    async fn expired_token_is_rejected() {
+        let mut claims = claims();
+        // Past the default 60s leeway.
+        claims["exp"] = json!(chrono::Utc::now().timestamp() - 3600);
+        let token = token(&Header::default(), &claims, SECRET);
+        assert_eq!(
+            jwt().verify(&token).await,
+            Err("ExpiredSignature".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn wrong_audience_or_issuer_is_rejected() {
+        for (claim, value, error) in [
+            ("aud", "someone-else", "InvalidAudience"),
+            ("iss", "https://evil.example.com", "InvalidIssuer"),
+        ] {
+            let mut claims = claims();
+            claims[claim] = json!(value);
+            let token = token(&Header::default(), &claims, SECRET);
+            assert_eq!(jwt().verify(&token).await, Err(error.to_string()));
+        }
+    }
+
+    #[tokio::test]
+    async fn wrong_secret_is_rejected() {
+        let token = token(&Header::default(), &claims(), "other-secret");
+        assert_eq!(
+            jwt().verify(&token).await,
+            Err("InvalidSignature".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn wrong_algorithm_is_rejected() {
+        // An HMAC-signed token claiming to be RS256, and an unsigned one.
+        let signed = token(&Header::default(), &claims(), SECRET);
+        let (_, rest) = signed.split_once('.').unwrap();
+        for alg in ["RS256", "none"] {
+            let header = BASE64_URL_SAFE_NO_PAD.encode(format!(r#"{{"alg":"{alg}","typ":"JWT"}}"#));
+            let token = format!("{header}.{rest}");
+            assert!(jwt().verify(&token).await.is_err(), "{alg} accepted");
+        }
+    }
+
+    #[tokio::test]
+    async fn hmac_token_cannot_use_a_published_public_key() {
+        let set: JwkSet = serde_json::from_value(json!({
+            "keys": [{
+                "kty": "RSA",
+                "kid": "k1",
+                "use": "sig",
+                "alg": "RS256",
+                "n": MODULUS,
+                "e": "AQAB",
+            }],
+        }))
+        .unwrap();
+        let jwt = Jwt {
+            keys: Keys::Jwks {
+                url: "http://127.0.0.1:9/jwks".to_string(),
+                client: Client::new(),
+                cached: RwLock::new(Some((set, Instant::now()))),
+            },
+            audience: None,
+            issuer: None,
+        };
+        let header = Header {
+            kid: Some("k1".to_string()),
+            ..Header::default()
+        };
+        // Signed with the public key's bytes as an HMAC secret.
+        let token = token(&header, &claims(), MODULUS);
+        assert_eq!(
+            jwt.verify(&token).await,
+            Err("InvalidAlgorithm".to_string())
+        );
+    }
+}
@@ -0,0 +1,416 @@
+#![deny(warnings)]
+#![warn(rust_2018_idioms)]
+
+use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
+
+use axum::{middleware, routing::get, Router};
+#[cfg(feature = "history")]
+use cache::CachedEntry;
+use constants::{API_PREFIX, CURRENT, FORECAST};
+use dns::CachingResolver;
+use feed::RenderedFeed;
+use format::RenderedPayload;
+use places::PlaceNames;
+use quality::QualityCheck;
+#[cfg(feature = "recorder")]
+use recorder::ObservationStore;
+use reqwest::Client;
+use serde_json::Value;
+use tenants::Tenants;
+#[cfg(feature = "history")]
+use today::DailyHistory;
+use tokio::sync::{watch, Mutex, RwLock, Semaphore};
+#[cfg(feature = "history")]
+use trend::PressureHistory;
+use upstream::{Failover, Hedged, Merged, Recording, RecordingMode, WeatherProvider};
+#[cfg(feature = "history")]
+use wunderground_proxy_core::cache::CacheKey;
+use wunderground_proxy_core::cache::{MemoryCache, PayloadCache};
+pub use wunderground_proxy_core::{models, upstream};
+
+mod acme;
+mod admin;
+mod alert_rules;
+#[cfg(feature = "recorder")]
+pub mod backup;
+pub mod cache;
+mod calendar;
+mod calibration;
+mod client_ip;
+mod config;
+mod constants;
+mod dashboard;
+mod derived;
+#[cfg(feature = "email")]
+mod digest;
+mod dns;
+mod error;
+#[cfg(feature = "recorder")]
+mod export;
+mod feed;
+#[cfg(feature = "history")]
+mod forecast_diff;
+mod format;
+mod geohash;
+#[cfg(feature = "recorder")]
+mod grafana;
+#[cfg(feature = "graphql")]
+mod graphql;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod handlers;
+mod influxdb;
+mod ip_filter;
+mod jwt;
+mod limits;
+mod live;
+pub mod loadtest;
+pub mod mock;
+#[cfg(feature = "mqtt")]
+mod mqtt;
+mod openapi;
+mod places;
+mod prefetch;
+mod projection;
+mod quality;
+#[cfg(feature = "recorder")]
+mod recorder;
+#[cfg(any(feature = "graphql", feature = "grpc", feature = "mqtt"))]
+mod records;
+pub mod redact;
+#[cfg(feature = "remote-write")]
+mod remote_write;
+pub mod server;
+mod signature;
+#[cfg(feature = "email")]
+mod smtp;
+mod tenants;
+mod timezone;
+#[cfg(feature = "history")]
+mod today;
+mod transform;
+#[cfg(feature = "history")]
+mod trend;
+mod units;
+mod upload;
+mod warnings;
+mod weather_metrics;
+
+pub use alert_rules::{AlertChannel, AlertRule, Condition, Priority};
+pub use calibration::Calibration;
+pub use config::{
+    load_config, load_config_with_provider, load_provider_config, load_runtime_config,
+    load_server_config, AcmeConfig, AppConfig, BackupConfig, BasicAuthConfig, DigestConfig,
+    EcowittConfig, FailoverConfig, HedgeConfig, HmacConfig, HttpVersion, InfluxConfig,
+    IpFilterConfig, JwtConfig, JwtKeys, MergeConfig, MqttConfig, ObservationRetention,
+    RemoteWriteConfig, RequestLimits, RuntimeConfig, RuntimeFlavor, ServerConfig, SmtpConfig,
+    SmtpTls, SocketOptions, TenantConfig, TlsConfig, UploadConfig, UpstreamDns, UpstreamPool,
+};
+pub use error::AppError;
+pub use prefetch::{PrefetchJob, PrefetchTarget};
+pub use quality::QualityMode;
+pub use transform::Transformer;
+pub use units::Units;
+
+#[derive(Debug, Clone)]
+struct AppState {
+    config: AppConfig,
+    provider: Arc<dyn WeatherProvider>,
+    client: Client,
+    cached_entries: Arc<dyn PayloadCache>,
+    /// Bounds concurrent upstream fetches, hedged ones included, to
+    /// `upstream_concurrency`.
+    upstream_permits: Arc<Semaphore>,
+    current_updates: Arc<watch::Sender<Option<Value>>>,
+    #[cfg(feature = "history")]
+    today: Arc<Mutex<DailyHistory>>,
+    #[cfg(feature = "history")]
+    pressure_history: Arc<Mutex<PressureHistory>>,
+    /// `/feed.rss` bodies by forecast geocode and language.
+    rendered_feeds: Arc<RwLock<HashMap<String, RenderedFeed>>>,
+    /// Encoded `/current` and `/forecast` responses by route, query string
+    /// and format.
+    rendered_payloads: Arc<RwLock<HashMap<String, RenderedPayload>>>,
+    /// The forecast each location had before its content last changed.
+    #[cfg(feature = "history")]
+    previous_forecasts: Arc<RwLock<HashMap<CacheKey, CachedEntry>>>,
+    /// The latest reading uploaded by the local station.
+    local_observation: Arc<RwLock<Option<Value>>>,
+    /// The last `/current` payload fetched upstream.
+    upstream_current: Arc<RwLock<Option<Value>>>,
+    /// Previous accepted readings for `quality_check`.
+    quality: Arc<Mutex<QualityCheck>>,
+    /// Looked up names of forecast places, for `place_names`.
+    place_names: Arc<Mutex<PlaceNames>>,
+    /// Recorded observations, when `OBSERVATIONS_DB` is set.
+    #[cfg(feature = "recorder")]
+    observations: Option<ObservationStore>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum PayloadKind {
+    Current,
+    Forecast,
+}
+
+impl PayloadKind {
+    fn name(self) -> &'static str {
+        match self {
+            PayloadKind::Current => CURRENT,
+            PayloadKind::Forecast => FORECAST,
+        }
+    }
+}
+
+type Result<A> = std::result::Result<A, AppError>;
+
+/// The routers returned by [`build_routers`], sharing one cache.
+#[derive(Debug, Clone)]
+pub struct Routers {
+    /// Weather data, docs and health checks.
+    pub public: Router,
+    /// Operational routes (`/metrics`, `/debug/cache`, `/admin/*`) without
+    /// authentication, meant for a listener only operators can reach.
+    pub admin: Router,
+}
+
+/// Builds every public proxy route (HTTP API under `/v1` and unversioned,
+/// dashboard, OpenAPI docs, and GraphQL and gRPC when their features are
+/// enabled) so it can be served directly or merged into another axum
+/// application. See [`build_routers`] for the rest.
+pub fn build_router(config: AppConfig) -> Router {
+    build_routers(config).public
+}
+
+/// Builds the public routes and the admin routes on top of the same state.
+/// Must be called inside a Tokio runtime, since it spawns the background
+/// refresher for live subscribers and the prefetch jobs. Credentials in
+/// `config` are registered with [`redact::redact`] so they are hidden from
+/// logs and error bodies.
+pub fn build_routers(config: AppConfig) -> Routers {
+    redact::register(&config);
+
+    let pool = config.upstream_pool;
+    let mut builder = match config.upstream_http {
+        HttpVersion::Auto => Client::builder(),
+        HttpVersion::Http1 => Client::builder().http1_only(),
+        HttpVersion::Http2 => Client::builder().http2_prior_knowledge(),
+    }
+    .pool_idle_timeout(Duration::from_secs(pool.idle_timeout_secs))
+    .tcp_keepalive(pool.tcp_keepalive_secs.map(Duration::from_secs));
+    if let Some(max_idle) = pool.max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(max_idle);
+    }
+    if let Some(cache_secs) = config.upstream_dns.cache_secs {
+        builder = builder.dns_resolver(Arc::new(CachingResolver::new(Duration::from_secs(
+            cache_secs,
+        ))));
+    }
+    for (host, addrs) in &config.upstream_dns.overrides {
+        // Port 0 keeps the port of the request URL.
+        let addrs: Vec<SocketAddr> = addrs.iter().map(|ip| SocketAddr::new(*ip, 0)).collect();
+        builder = builder.resolve_to_addrs(host, &addrs);
+    }
+    let client = builder.build().expect("HTTP client could not be built");
+
+    // Replayed payloads never reach upstream, so there's nothing to warm up.
+    if pool.prewarm && config.recording != Some(RecordingMode::Replay) {
+        tokio::spawn(prewarm(
+            client.clone(),
+            config.provider.origin().to_string(),
+        ));
+    }
+
+    let mut provider = upstream::from_config(&config.provider);
+    if let Some(merge) = &config.merge {
+        let others = merge
+            .providers
+            .iter()
+            .map(|(name, config)| (name.clone(), upstream::from_config(config)))
+            .collect();
+        provider = Arc::new(Merged::new(provider, others, merge.groups.clone()));
+    }
+    if let Some(failover) = &config.failover {
+        provider = Arc::new(Failover::new(
+            provider,
+            config.provider.name(),
+            upstream::from_config(&failover.provider),
+            failover.provider.name(),
+            failover.threshold,
+            Duration::from_secs(failover.cooldown_secs),
+        ));
+    }
+    // Hedged requests take their slot from the same pool as fetches.
+    let upstream_permits = Arc::new(Semaphore::new(config.upstream_concurrency));
+    if let Some(hedge) = config.hedge {
+        provider = Arc::new(Hedged::new(
+            provider,
+            hedge.percentile,
+            Duration::from_millis(hedge.min_delay_ms),
+            hedge.budget_percent,
+            upstream_permits.clone(),
+        ));
+    }
+    if let Some(mode) = config.recording {
+        provider = Arc::new(Recording {
+            inner: provider,
+            mode,
+            dir: config.recording_dir.clone(),
+        });
+    }
+
+    #[cfg(feature = "recorder")]
+    let observations = config
+        .observations_db
+        .as_deref()
+        .map(|path| ObservationStore::open(path).expect("OBSERVATIONS_DB could not be opened"));
+
+    let state = AppState {
+        provider,
+        upstream_permits,
+        config,
+        client,
+        cached_entries: Arc::new(MemoryCache::default()),
+        current_updates: Arc::new(watch::Sender::new(None)),
+        #[cfg(feature = "history")]
+        today: Arc::new(Mutex::new(DailyHistory::default())),
+        #[cfg(feature = "history")]
+        pressure_history: Arc::new(Mutex::new(PressureHistory::default())),
+        rendered_feeds: Arc::new(RwLock::new(HashMap::new())),
+        rendered_payloads: Arc::new(RwLock::new(HashMap::new())),
+        #[cfg(feature = "history")]
+        previous_forecasts: Arc::new(RwLock::new(HashMap::new())),
+        local_observation: Arc::new(RwLock::new(None)),
+        upstream_current: Arc::new(RwLock::new(None)),
+        quality: Arc::new(Mutex::new(QualityCheck::default())),
+        place_names: Arc::new(Mutex::new(PlaceNames::default())),
+        #[cfg(feature = "recorder")]
+        observations,
+    };
+
+    prefetch::spawn_jobs(&state);
+    #[cfg(feature = "mqtt")]
+    if let Some(config) = state.config.mqtt.clone() {
+        mqtt::spawn(&state, config);
+    }
+    if !state.config.alert_rules.is_empty() {
+        tokio::spawn(alert_rules::run(
+            state.client.clone(),
+            state.config.alert_rules.clone(),
+            state.config.alert_channels.clone(),
+            state.current_updates.subscribe(),
+        ));
+    }
+    if let Some(config) = state.config.influxdb.clone() {
+        tokio::spawn(influxdb::run(
+            state.client.clone(),
+            config,
+            state.current_updates.subscribe(),
+        ));
+    }
+    #[cfg(feature = "remote-write")]
+    if let Some(config) = state.config.remote_write.clone() {
+        tokio::spawn(remote_write::run(
+            state.clone(),
+            config,
+            state.current_updates.subscribe(),
+        ));
+    }
+    #[cfg(feature = "recorder")]
+    if let Some(store) = state.observations.clone() {
+        tokio::spawn(recorder::run(
+            store.clone(),
+            state.current_updates.subscribe(),
+        ));
+        tokio::spawn(recorder::prune_periodically(
+            store.clone(),
+            state.config.observations_retention,
+        ));
+        if let (Some(backup), Some(path)) = (&state.config.backup, &state.config.observations_db) {
+            tokio::spawn(backup::run(
+                state.client.clone(),
+                store,
+                path.clone(),
+                backup.clone(),
+            ));
+        }
+    }
+    #[cfg(feature = "email")]
+    if let Some(config) = state.config.digest.clone() {
+        tokio::spawn(digest::run(
+            state.clone(),
+            config,
+            state.current_updates.subscribe(),
+        ));
+    }
+    // After the subscribers above, so its first tick already refreshes.
+    tokio::spawn(live::run_refresher(state.clone()));
+
+    // Unversioned paths are kept as aliases of /v1 for already deployed clients.
+    let api = handlers::api_routes(state.clone());
+    let mut app = Router::new()
+        .route("/", get(handlers::dashboard).with_state(state.clone()))
+        .nest(API_PREFIX, api.clone())
+        .merge(api);
+
+    #[cfg(feature = "grpc")]
+    {
+        app = app.merge(grpc::router(state.clone()));
+    }
+
+    let tenants = (!state.config.tenants.is_empty()
+        || state.config.jwt.is_some()
+        || state.config.basic_auth.is_some()
+        || state.config.hmac.is_some())
+    .then(|| Arc::new(Tenants::new(&state.config, &state.client)));
+    if let Some(tenants) = &tenants {
+        app = app.layer(middleware::from_fn_with_state(
+            tenants.clone(),
+            tenants::authorize,
+        ));
+    }
+
+    // Stations can't send API keys; uploads carry the station password.
+    let app = app.merge(upload::router(state.clone()));
+
+    // The API description stays public so tenants can discover the endpoints.
+    let app = app.route("/openapi.json", get(openapi::openapi_json));
+
+    let app = if state.config.docs_enabled {
+        app.route("/docs", get(openapi::docs))
+    } else {
+        app
+    };
+
+    let app = app.layer(middleware::from_fn_with_state(
+        state.config.request_limits,
+        limits::enforce,
+    ));
+
+    // Right after the client address is known, so rejected sources never
+    // reach authentication or the cache.
+    let app = match state.config.ip_filter.clone() {
+        Some(ip_filter) => app.layer(middleware::from_fn_with_state(
+            Arc::new(ip_filter),
+            ip_filter::filter,
+        )),
+        None => app,
+    };
+    let public = app.layer(middleware::from_fn_with_state(
+        Arc::new(state.config.trusted_proxies.clone()),
+        client_ip::resolve,
+    ));
+
+    Routers {
+        public,
+        admin: admin::routes(state, tenants),
+    }
+}
+
+/// Opens a pooled connection to `origin`; the response itself is irrelevant.
+async fn prewarm(client: Client, origin: String) {
+    match client.head(&origin).send().await {
+        Ok(_) => tracing::debug!("connection to {origin} warmed up"),
+        Err(err) => tracing::debug!("warming up connection to {origin} failed: {err}"),
+    }
+}
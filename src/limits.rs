@@ -0,0 +1,45 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+
+use crate::{AppError, RequestLimits, Result};
+
+/// Rejects oversized requests before their query is parsed or turned into a
+/// cache key.
+pub(crate) async fn enforce(
+    State(limits): State<RequestLimits>,
+    request: Request,
+    next: Next,
+) -> Result<Response> {
+    let uri = request.uri();
+    let uri_length = uri.path_and_query().map_or(0, |pq| pq.as_str().len());
+    if uri_length > limits.max_uri_length {
+        return Err(AppError::UriTooLong(format!(
+            "URI exceeds {} bytes",
+            limits.max_uri_length
+        )));
+    }
+
+    let query_params = uri.query().map_or(0, |query| {
+        query.split('&').filter(|param| !param.is_empty()).count()
+    });
+    if query_params > limits.max_query_params {
+        return Err(AppError::UriTooLong(format!(
+            "query exceeds {} parameters",
+            limits.max_query_params
+        )));
+    }
+
+    let header_bytes: usize = request
+        .headers()
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len())
+        .sum();
+    if header_bytes > limits.max_header_bytes {
+        return Err(AppError::HeadersTooLarge(limits.max_header_bytes));
+    }
+
+    Ok(next.run(request).await)
+}
@@ -0,0 +1,120 @@
+use std::{convert::Infallible, time::Duration};
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket},
+        State, WebSocketUpgrade,
+    },
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Response,
+    },
+};
+use serde_json::Value;
+use tokio_stream::{wrappers::WatchStream, Stream, StreamExt};
+
+use crate::{cache::refresh_current, tenants::Stations, AppState};
+
+/// Re-fetches `/current` every cache period while at least one live client is
+/// subscribed, so pushes keep flowing without anyone polling the HTTP API.
+pub async fn run_refresher(state: AppState) {
+    let period = Duration::from_secs(state.config.cache_duration_secs.max(1));
+    let mut interval = tokio::time::interval(period);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+        if state.current_updates.receiver_count() == 0 {
+            continue;
+        }
+        if let Err(err) = refresh_current(&state).await {
+            tracing::warn!("background refresh of current observations failed: {err}");
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/ws",
+    responses(
+        (status = 101, description = "WebSocket pushing the `/current` payload as a text message whenever it changes"),
+    )
+)]
+pub async fn ws(
+    State(state): State<AppState>,
+    stations: Stations,
+    upgrade: WebSocketUpgrade,
+) -> crate::Result<Response> {
+    check_latest(&state, &stations)?;
+    Ok(upgrade.on_upgrade(move |socket| push_updates(socket, state, stations)))
+}
+
+/// Refuses tenants that may read none of the stations in the latest payload.
+fn check_latest(state: &AppState, stations: &Stations) -> crate::Result<()> {
+    match state.current_updates.borrow().clone() {
+        Some(latest) => stations.current(latest).map(drop),
+        None => Ok(()),
+    }
+}
+
+/// Sends the latest known payload right away, then every changed payload
+/// until the client disconnects, each with the observations of `stations`
+/// only; payloads with none of them are skipped.
+async fn push_updates(mut socket: WebSocket, state: AppState, stations: Stations) {
+    let mut updates = state.current_updates.subscribe();
+    let initial = updates.borrow_and_update().clone();
+    if let Some(value) = initial.and_then(|value| stations.observations(value)) {
+        if send(&mut socket, &value).await.is_err() {
+            return;
+        }
+    }
+
+    loop {
+        tokio::select! {
+            changed = updates.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+                let value = updates.borrow_and_update().clone();
+                if let Some(value) = value.and_then(|value| stations.observations(value)) {
+                    if send(&mut socket, &value).await.is_err() {
+                        return;
+                    }
+                }
+            }
+            incoming = socket.recv() => match incoming {
+                None | Some(Err(_)) | Some(Ok(Message::Close(_))) => return,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+async fn send(socket: &mut WebSocket, value: &Value) -> Result<(), axum::Error> {
+    socket.send(Message::Text(value.to_string())).await
+}
+
+/// Streams an SSE `current` event for the latest payload and for every change
+/// after it, with keep-alive comments so idle proxies don't drop the connection.
+#[utoipa::path(
+    get,
+    path = "/current/stream",
+    responses(
+        (status = 200, description = "Server-Sent Events stream of `current` events carrying the `/current` payload", content_type = "text/event-stream"),
+    )
+)]
+pub async fn sse(
+    State(state): State<AppState>,
+    stations: Stations,
+) -> crate::Result<Sse<impl Stream<Item = Result<Event, Infallible>>>> {
+    check_latest(&state, &stations)?;
+    let events = WatchStream::new(state.current_updates.subscribe()).filter_map(move |value| {
+        value
+            .and_then(|value| stations.observations(value))
+            .map(|value| Ok(Event::default().event("current").data(value.to_string())))
+    });
+
+    Ok(Sse::new(events).keep_alive(
+        KeepAlive::new().interval(Duration::from_secs(state.config.sse_keep_alive_secs)),
+    ))
+}
@@ -0,0 +1,117 @@
+use std::{
+    io,
+    net::{Ipv4Addr, SocketAddr},
+    time::{Duration, Instant},
+};
+
+use axum::Router;
+use reqwest::Client;
+
+use crate::constants::{API_PREFIX, LOADTEST_CONCURRENCY, LOADTEST_DURATION_SECS};
+
+/// Requested in turn by every worker; the forecast location is the one the
+/// mock upstream fixtures describe.
+const PATHS: &[&str] = &[
+    "/current",
+    "/current?format=msgpack",
+    "/current/flat",
+    "/forecast?geocode=50.06,19.94&language=en-US",
+    "/summary?geocode=50.06,19.94&language=en-US",
+];
+
+#[derive(Default)]
+struct Samples {
+    latencies: Vec<Duration>,
+    failures: usize,
+}
+
+/// Serves `app` on a free localhost port and requests [`PATHS`] from
+/// `LOADTEST_CONCURRENCY` workers (default 32) for `LOADTEST_DURATION_SECS`
+/// (default 10), then prints throughput and latency percentiles per path.
+/// Meant to run against the mock upstream, so only the proxy is measured.
+pub async fn run(app: Router) -> io::Result<()> {
+    let duration = Duration::from_secs(
+        std::env::var(LOADTEST_DURATION_SECS)
+            .map(|raw| raw.parse().expect("LOADTEST_DURATION_SECS wrong value"))
+            .unwrap_or(10),
+    );
+    let concurrency: usize = std::env::var(LOADTEST_CONCURRENCY)
+        .map(|raw| raw.parse().expect("LOADTEST_CONCURRENCY wrong value"))
+        .unwrap_or(32);
+
+    let listener = tokio::net::TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        if let Err(err) = axum::serve(listener, app).await {
+            tracing::warn!("load test server stopped: {err}");
+        }
+    });
+
+    tracing::info!("load testing {addr} with {concurrency} workers for {duration:?}");
+    let client = Client::new();
+    let deadline = Instant::now() + duration;
+    let workers: Vec<_> = (0..concurrency)
+        .map(|worker| tokio::spawn(run_worker(client.clone(), addr, worker, deadline)))
+        .collect();
+
+    let mut samples: Vec<Samples> = PATHS.iter().map(|_| Samples::default()).collect();
+    for worker in workers {
+        let worker_samples = worker.await.map_err(io::Error::other)?;
+        for (total, sampled) in samples.iter_mut().zip(worker_samples) {
+            total.latencies.extend(sampled.latencies);
+            total.failures += sampled.failures;
+        }
+    }
+
+    println!(
+        "{:<50} {:>9} {:>9} {:>9} {:>9} {:>9}",
+        "path", "req/s", "p50 ms", "p99 ms", "max ms", "failed"
+    );
+    for (path, mut sampled) in PATHS.iter().zip(samples) {
+        sampled.latencies.sort();
+        println!(
+            "{:<50} {:>9.0} {:>9.2} {:>9.2} {:>9.2} {:>9}",
+            path,
+            sampled.latencies.len() as f64 / duration.as_secs_f64(),
+            percentile_ms(&sampled.latencies, 0.50),
+            percentile_ms(&sampled.latencies, 0.99),
+            percentile_ms(&sampled.latencies, 1.0),
+            sampled.failures,
+        );
+    }
+    Ok(())
+}
+
+/// Starts at a different path per worker so they don't move in lockstep.
+async fn run_worker(
+    client: Client,
+    addr: SocketAddr,
+    worker: usize,
+    deadline: Instant,
+) -> Vec<Samples> {
+    let mut samples: Vec<Samples> = PATHS.iter().map(|_| Samples::default()).collect();
+    let mut index = worker % PATHS.len();
+    while Instant::now() < deadline {
+        let url = format!("http://{addr}{API_PREFIX}{}", PATHS[index]);
+        let started = Instant::now();
+        let result = match client.get(&url).send().await {
+            Ok(response) if response.status().is_success() => response.bytes().await.ok(),
+            _ => None,
+        };
+        match result {
+            Some(_) => samples[index].latencies.push(started.elapsed()),
+            None => samples[index].failures += 1,
+        }
+        index = (index + 1) % PATHS.len();
+    }
+    samples
+}
+
+/// `sorted` must be in ascending order; 0 when empty.
+fn percentile_ms(sorted: &[Duration], quantile: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let index = ((sorted.len() - 1) as f64 * quantile).round() as usize;
+    sorted[index].as_secs_f64() * 1000.0
+}
@@ -0,0 +1,55 @@
+use std::{io, net::SocketAddr};
+
+use axum::{http::StatusCode, response::IntoResponse, routing::get, Router};
+
+use crate::upstream::ProviderConfig;
+
+const CURRENT_FIXTURE: &str = include_str!("../fixtures/current.json");
+const FORECAST_FIXTURE: &str = include_str!("../fixtures/forecast.json");
+const LOCATION_FIXTURE: &str = include_str!("../fixtures/location.json");
+
+/// Station id reported by the mock current observations.
+pub const MOCK_PWS_ID: &str = "MOCK1";
+
+/// Starts a stand-in for the weather.com endpoints on a free localhost port,
+/// answering every request with the canned fixtures regardless of query
+/// parameters. Alerts always come back empty (`204 No Content`).
+pub async fn spawn() -> io::Result<SocketAddr> {
+    let json = |body: &'static str| ([("content-type", "application/json")], body);
+    let app = Router::new()
+        .route(
+            "/v2/pws/observations/current",
+            get(move || async move { json(CURRENT_FIXTURE) }),
+        )
+        .route(
+            "/v3/wx/forecast/daily/5day",
+            get(move || async move { json(FORECAST_FIXTURE) }),
+        )
+        .route(
+            "/v3/location/point",
+            get(move || async move { json(LOCATION_FIXTURE) }),
+        )
+        .route(
+            "/v3/alerts/headlines",
+            get(|| async { StatusCode::NO_CONTENT.into_response() }),
+        );
+
+    let listener = tokio::net::TcpListener::bind((std::net::Ipv4Addr::LOCALHOST, 0)).await?;
+    let addr = listener.local_addr()?;
+    tokio::spawn(async move {
+        if let Err(err) = axum::serve(listener, app).await {
+            tracing::warn!("mock upstream stopped: {err}");
+        }
+    });
+
+    Ok(addr)
+}
+
+/// A Wunderground provider pointed at the mock upstream at `addr`.
+pub fn provider_config(addr: SocketAddr) -> ProviderConfig {
+    ProviderConfig::Wunderground {
+        pws_id: MOCK_PWS_ID.to_string(),
+        api_key: "mock".to_string(),
+        base_url: format!("http://{addr}"),
+    }
+}
@@ -0,0 +1,326 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rumqttc::{AsyncClient, Event, EventLoop, LastWill, MqttOptions, Packet, QoS};
+use serde_json::{json, Value};
+use tokio::{sync::watch, time::MissedTickBehavior};
+
+use crate::{
+    cache::forecast_value, handlers::station_geocode, records::ForecastDay, AppState, MqttConfig,
+};
+
+/// Requests queued for the broker while it is unreachable.
+const QUEUE_CAPACITY: usize = 64;
+const KEEP_ALIVE: Duration = Duration::from_secs(30);
+/// Pause between reconnection attempts.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const FORECAST_LANGUAGE: &str = "en-US";
+
+/// An observation value announced to Home Assistant as a sensor.
+struct Sensor {
+    key: &'static str,
+    name: &'static str,
+    /// Dot path of the value inside the published observation.
+    path: &'static str,
+    unit: Option<&'static str>,
+    device_class: Option<&'static str>,
+    state_class: &'static str,
+}
+
+const SENSORS: &[Sensor] = &[
+    Sensor {
+        key: "temp",
+        name: "Temperature",
+        path: "metric.temp",
+        unit: Some("°C"),
+        device_class: Some("temperature"),
+        state_class: "measurement",
+    },
+    Sensor {
+        key: "dewpt",
+        name: "Dew point",
+        path: "metric.dewpt",
+        unit: Some("°C"),
+        device_class: Some("temperature"),
+        state_class: "measurement",
+    },
+    Sensor {
+        key: "humidity",
+        name: "Humidity",
+        path: "humidity",
+        unit: Some("%"),
+        device_class: Some("humidity"),
+        state_class: "measurement",
+    },
+    Sensor {
+        key: "pressure",
+        name: "Pressure",
+        path: "metric.pressure",
+        unit: Some("hPa"),
+        device_class: Some("atmospheric_pressure"),
+        state_class: "measurement",
+    },
+    Sensor {
+        key: "wind_speed",
+        name: "Wind speed",
+        path: "metric.windSpeed",
+        unit: Some("km/h"),
+        device_class: Some("wind_speed"),
+        state_class: "measurement",
+    },
+    Sensor {
+        key: "wind_gust",
+        name: "Wind gust",
+        path: "metric.windGust",
+        unit: Some("km/h"),
+        device_class: Some("wind_speed"),
+        state_class: "measurement",
+    },
+    Sensor {
+        key: "wind_direction",
+        name: "Wind direction",
+        path: "winddir",
+        unit: Some("°"),
+        device_class: None,
+        state_class: "measurement",
+    },
+    Sensor {
+        key: "precip_rate",
+        name: "Rain rate",
+        path: "metric.precipRate",
+        unit: Some("mm/h"),
+        device_class: Some("precipitation_intensity"),
+        state_class: "measurement",
+    },
+    Sensor {
+        key: "precip_total",
+        name: "Rain today",
+        path: "metric.precipTotal",
+        unit: Some("mm"),
+        device_class: Some("precipitation"),
+        // Resets at the station's midnight.
+        state_class: "total_increasing",
+    },
+    Sensor {
+        key: "solar_radiation",
+        name: "Solar radiation",
+        path: "solarRadiation",
+        unit: Some("W/m²"),
+        device_class: Some("irradiance"),
+        state_class: "measurement",
+    },
+    Sensor {
+        key: "uv",
+        name: "UV index",
+        path: "uv",
+        unit: None,
+        device_class: None,
+        state_class: "measurement",
+    },
+];
+
+/// Connects to the broker in `config` and publishes every new `/current`
+/// payload, one retained message per station, and the daily forecast once
+/// per cache period. `<prefix>/status` is `online` while connected and
+/// `offline` once the broker notices the connection is gone.
+pub(crate) fn spawn(state: &AppState, config: MqttConfig) {
+    let status_topic = format!("{}/status", config.topic_prefix);
+    let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+    options
+        .set_keep_alive(KEEP_ALIVE)
+        .set_last_will(LastWill::new(
+            &status_topic,
+            "offline",
+            QoS::AtLeastOnce,
+            true,
+        ));
+    if let Some(username) = &config.username {
+        options.set_credentials(username, config.password.clone().unwrap_or_default());
+    }
+    let (client, event_loop) = AsyncClient::new(options, QUEUE_CAPACITY);
+
+    // Stations whose discovery payloads were sent on the current connection.
+    let announced = Arc::new(Mutex::new(HashSet::new()));
+    let config = Arc::new(config);
+    tokio::spawn(drive(
+        event_loop,
+        client.clone(),
+        status_topic,
+        announced.clone(),
+    ));
+    tokio::spawn(publish_current(
+        client.clone(),
+        config.clone(),
+        announced,
+        state.current_updates.subscribe(),
+    ));
+    tokio::spawn(publish_forecast(state.clone(), client, config));
+}
+
+/// Polls the connection, which is what actually sends queued publishes and
+/// reconnects after failures.
+async fn drive(
+    mut event_loop: EventLoop,
+    client: AsyncClient,
+    status_topic: String,
+    announced: Arc<Mutex<HashSet<String>>>,
+) {
+    loop {
+        match event_loop.poll().await {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                tracing::info!("connected to MQTT broker");
+                // Announce again in case the broker lost retained messages.
+                announced
+                    .lock()
+                    .unwrap_or_else(|err| err.into_inner())
+                    .clear();
+                // Publishing with `.await` here could wait on the queue this
+                // loop drains.
+                if let Err(err) =
+                    client.try_publish(&status_topic, QoS::AtLeastOnce, true, "online")
+                {
+                    tracing::warn!("publishing MQTT status failed: {err}");
+                }
+            }
+            Ok(_) => {}
+            Err(err) => {
+                tracing::warn!("MQTT connection failed: {err}");
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        }
+    }
+}
+
+async fn publish_current(
+    client: AsyncClient,
+    config: Arc<MqttConfig>,
+    announced: Arc<Mutex<HashSet<String>>>,
+    mut updates: watch::Receiver<Option<Value>>,
+) {
+    while updates.changed().await.is_ok() {
+        let Some(current) = updates.borrow_and_update().clone() else {
+            continue;
+        };
+        let observations = current
+            .get("observations")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten();
+        for observation in observations {
+            let Some(station) = observation.get("stationID").and_then(Value::as_str) else {
+                continue;
+            };
+            let state_topic = format!("{}/{station}/current", config.topic_prefix);
+            if let Some(discovery_prefix) = &config.discovery_prefix {
+                let first = announced
+                    .lock()
+                    .unwrap_or_else(|err| err.into_inner())
+                    .insert(station.to_string());
+                if first {
+                    announce(&client, &config, discovery_prefix, station, &state_topic).await;
+                }
+            }
+            publish(
+                &client,
+                &state_topic,
+                config.retain,
+                observation.to_string(),
+            )
+            .await;
+        }
+    }
+}
+
+/// Home Assistant discovery payloads for every sensor of `station`, grouped
+/// into one device.
+async fn announce(
+    client: &AsyncClient,
+    config: &MqttConfig,
+    discovery_prefix: &str,
+    station: &str,
+    state_topic: &str,
+) {
+    let node = station.to_lowercase();
+    for sensor in SENSORS {
+        let mut payload = json!({
+            "name": sensor.name,
+            "unique_id": format!("wunderground_{node}_{}", sensor.key),
+            "state_topic": state_topic,
+            "value_template": format!("{{{{ value_json.{} }}}}", sensor.path),
+            "unit_of_measurement": sensor.unit,
+            "device_class": sensor.device_class,
+            "state_class": sensor.state_class,
+            "availability_topic": format!("{}/status", config.topic_prefix),
+            "device": {
+                "identifiers": [format!("wunderground_{node}")],
+                "name": format!("Weather station {station}"),
+                "manufacturer": "Weather Underground",
+                "model": "Personal weather station",
+            },
+        });
+        if let Value::Object(fields) = &mut payload {
+            fields.retain(|_, value| !value.is_null());
+        }
+        let topic = format!("{discovery_prefix}/sensor/{node}/{}/config", sensor.key);
+        publish(client, &topic, true, payload.to_string()).await;
+    }
+}
+
+/// Publishes the daily forecast summary once per cache period, when it
+/// changed. Starts with the first observation when the location is the
+/// station's.
+async fn publish_forecast(state: AppState, client: AsyncClient, config: Arc<MqttConfig>) {
+    let topic = format!("{}/forecast", config.topic_prefix);
+    let period = Duration::from_secs(state.config.cache_duration_secs.max(1));
+    let mut interval = tokio::time::interval(period);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    if config.forecast_geocode.is_none() {
+        let _ = state
+            .current_updates
+            .subscribe()
+            .wait_for(Option::is_some)
+            .await;
+    }
+    let mut published = None;
+    loop {
+        interval.tick().await;
+        let geocode = config.forecast_geocode.clone().or_else(|| {
+            state
+                .current_updates
+                .borrow()
+                .as_ref()
+                .and_then(station_geocode)
+        });
+        let Some(geocode) = geocode else {
+            continue;
+        };
+        let forecast = match forecast_value(&state, &geocode, FORECAST_LANGUAGE).await {
+            Ok(forecast) => forecast,
+            Err(err) => {
+                tracing::warn!("forecast for MQTT unavailable: {err}");
+                continue;
+            }
+        };
+        let summary = json!({
+            "geocode": geocode,
+            "days": ForecastDay::from_json(&forecast),
+        })
+        .to_string();
+        if published.as_ref() != Some(&summary) {
+            publish(&client, &topic, config.retain, summary.clone()).await;
+            published = Some(summary);
+        }
+    }
+}
+
+async fn publish(client: &AsyncClient, topic: &str, retain: bool, payload: String) {
+    if let Err(err) = client
+        .publish(topic, QoS::AtLeastOnce, retain, payload)
+        .await
+    {
+        tracing::warn!("publishing to MQTT topic {topic} failed: {err}");
+    }
+}
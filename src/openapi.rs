@@ -0,0 +1,100 @@
+use axum::{response::Html, Json};
+use utoipa::OpenApi;
+
+use crate::{constants::API_PREFIX, format::Format, units::Units};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "wunderground-cache",
+        description = "Caching proxy for the weather.com PWS observation and forecast APIs."
+    ),
+    paths(
+        crate::handlers::current,
+        crate::handlers::current_flat,
+        crate::handlers::current_compact,
+        crate::handlers::current_derived,
+        crate::upload::current_local,
+        crate::handlers::forecast,
+        crate::handlers::forecast_ics,
+        crate::handlers::summary,
+        crate::handlers::feed_rss,
+        crate::handlers::weather_metrics,
+        crate::handlers::influx,
+        crate::handlers::local_warnings,
+        crate::live::ws,
+        crate::live::sse,
+        crate::handlers::health,
+    ),
+    components(schemas(Units, Format))
+)]
+struct ApiDoc;
+
+#[cfg(feature = "history")]
+#[derive(OpenApi)]
+#[openapi(paths(
+    crate::handlers::history::current_trend,
+    crate::handlers::history::today_summary,
+    crate::handlers::history::forecast_changes,
+))]
+struct HistoryDoc;
+
+#[cfg(feature = "recorder")]
+#[derive(OpenApi)]
+#[openapi(paths(
+    crate::handlers::observations::history,
+    crate::handlers::observations::hourly,
+    crate::handlers::observations::daily,
+    crate::handlers::observations::series,
+    crate::handlers::observations::export,
+    crate::handlers::observations::rain,
+    crate::handlers::observations::stats,
+    crate::handlers::observations::degree_days,
+    crate::handlers::observations::wind_rose,
+    crate::grafana::search,
+    crate::grafana::query,
+))]
+struct RecorderDoc;
+
+#[cfg(feature = "graphql")]
+#[derive(OpenApi)]
+#[openapi(paths(crate::graphql::execute))]
+struct GraphqlDoc;
+
+/// Documents the versioned paths; the unversioned aliases behave identically.
+pub async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    let mut doc = ApiDoc::openapi();
+    #[cfg(feature = "history")]
+    doc.merge(HistoryDoc::openapi());
+    #[cfg(feature = "recorder")]
+    doc.merge(RecorderDoc::openapi());
+    #[cfg(feature = "graphql")]
+    doc.merge(GraphqlDoc::openapi());
+    doc.paths.paths = std::mem::take(&mut doc.paths.paths)
+        .into_iter()
+        .map(|(path, item)| (format!("{API_PREFIX}{path}"), item))
+        .collect();
+    Json(doc)
+}
+
+/// Swagger UI page rendering `/openapi.json`. The UI bundle is loaded from a
+/// CDN so the binary doesn't need to embed it.
+pub async fn docs() -> Html<&'static str> {
+    Html(
+        r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8">
+  <title>wunderground-cache API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css">
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.ui = SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });
+  </script>
+</body>
+</html>"##,
+    )
+}
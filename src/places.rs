@@ -0,0 +1,84 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use serde_json::Value;
+
+use crate::{cache::upstream_permit, AppState};
+
+/// Places don't move; names are looked up again only this rarely.
+const NAME_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+/// A failed lookup is retried after this long rather than on every refresh.
+const RETRY_AFTER: Duration = Duration::from_secs(60 * 60);
+
+/// Place names by geocode and language, `None` for failed lookups.
+#[derive(Debug, Default)]
+pub(crate) struct PlaceNames {
+    names: HashMap<(String, String), (Instant, Option<String>)>,
+}
+
+/// Adds `placeName`, e.g. `Kraków, PL`, to a forecast for `geocode`. The
+/// forecast is left as is when the provider can't name the place.
+pub(crate) async fn annotate(
+    state: &AppState,
+    geocode: &str,
+    language: &str,
+    forecast: &mut Value,
+) {
+    let Some(name) = name(state, geocode, language).await else {
+        return;
+    };
+    if let Some(forecast) = forecast.as_object_mut() {
+        forecast.insert("placeName".to_string(), name.into());
+    }
+}
+
+async fn name(state: &AppState, geocode: &str, language: &str) -> Option<String> {
+    let key = (geocode.to_string(), language.to_string());
+    if let Some(cached) = state.place_names.lock().await.names.get(&key) {
+        if is_fresh(cached) {
+            return cached.1.clone();
+        }
+    }
+
+    let location = {
+        let _permit = upstream_permit(state).await;
+        state
+            .provider
+            .location(&state.client, geocode, language)
+            .await
+    };
+    let name = match location {
+        Ok(location) => place_name(&location),
+        Err(err) => {
+            tracing::warn!("naming {geocode} failed: {err}");
+            None
+        }
+    };
+    let mut place_names = state.place_names.lock().await;
+    place_names.names.retain(|_, cached| is_fresh(cached));
+    place_names
+        .names
+        .insert(key, (Instant::now(), name.clone()));
+    name
+}
+
+fn is_fresh((at, name): &(Instant, Option<String>)) -> bool {
+    let ttl = if name.is_some() {
+        NAME_TTL
+    } else {
+        RETRY_AFTER
+    };
+    at.elapsed() < ttl
+}
+
+/// `city, countryCode`, or the display name when either is missing.
+fn place_name(location: &Value) -> Option<String> {
+    let location = location.get("location")?;
+    let field = |key: &str| location.get(key).and_then(Value::as_str);
+    match (field("city"), field("countryCode")) {
+        (Some(city), Some(country)) => Some(format!("{city}, {country}")),
+        _ => field("displayName").map(String::from),
+    }
+}
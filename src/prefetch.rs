@@ -0,0 +1,87 @@
+use chrono::Utc;
+use chrono_tz::Tz;
+use cron::Schedule;
+use serde::Deserialize;
+
+use crate::{
+    cache::{refresh_current, refresh_forecast},
+    AppState, Result,
+};
+
+/// A scheduled refresh, listed as `[[prefetch]]` in `PREFETCH_FILE`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PrefetchJob {
+    /// Cron expression with a leading seconds field, e.g. `0 30 5 * * *`.
+    pub cron: Schedule,
+    /// Time zone the expression is evaluated in; UTC when absent.
+    pub tz: Option<Tz>,
+    #[serde(flatten)]
+    pub target: PrefetchTarget,
+}
+
+/// What a job refreshes, selected by `endpoint`; parameters match the
+/// corresponding query string.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "endpoint", rename_all = "snake_case")]
+pub enum PrefetchTarget {
+    Current,
+    Forecast {
+        geocode: String,
+        #[serde(default = "default_language")]
+        language: String,
+    },
+    #[cfg(feature = "alerts")]
+    Alerts {
+        geocode: String,
+        #[serde(default = "default_language")]
+        language: String,
+    },
+}
+
+fn default_language() -> String {
+    "en-US".to_string()
+}
+
+#[derive(Deserialize)]
+pub(crate) struct PrefetchFile {
+    #[serde(default)]
+    pub(crate) prefetch: Vec<PrefetchJob>,
+}
+
+/// Spawns one task per job that refreshes its target on schedule, whether or
+/// not the cached entry has expired yet.
+pub(crate) fn spawn_jobs(state: &AppState) {
+    for job in state.config.prefetch.clone() {
+        tokio::spawn(run_job(state.clone(), job));
+    }
+}
+
+async fn run_job(state: AppState, job: PrefetchJob) {
+    let tz = job.tz.unwrap_or(Tz::UTC);
+    // Looked up afresh each time so a slow refresh skips missed runs
+    // instead of firing them back to back.
+    while let Some(next) = job.cron.upcoming(tz).next() {
+        let wait = (next.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or_default();
+        tokio::time::sleep(wait).await;
+
+        if let Err(err) = refresh(&state, &job.target).await {
+            tracing::warn!("prefetch of {:?} failed: {err}", job.target);
+        }
+    }
+}
+
+async fn refresh(state: &AppState, target: &PrefetchTarget) -> Result<()> {
+    match target {
+        PrefetchTarget::Current => refresh_current(state).await?,
+        PrefetchTarget::Forecast { geocode, language } => {
+            refresh_forecast(state, geocode, language).await?
+        }
+        #[cfg(feature = "alerts")]
+        PrefetchTarget::Alerts { geocode, language } => {
+            crate::cache::refresh_alerts(state, geocode, language).await?
+        }
+    };
+    Ok(())
+}
@@ -0,0 +1,149 @@
+use std::collections::BTreeMap;
+
+use serde_json::{Map, Value};
+
+#[derive(Default)]
+struct FieldTree {
+    terminal: bool,
+    children: BTreeMap<String, FieldTree>,
+}
+
+/// Keeps only the comma-separated dot paths listed in `fields`, preserving the
+/// original nesting. Numeric segments index into arrays, which keep just the
+/// selected elements in ascending index order. Paths that do not resolve are
+/// skipped.
+pub fn project(value: &Value, fields: &str) -> Value {
+    let mut tree = FieldTree::default();
+    for path in fields
+        .split(',')
+        .map(str::trim)
+        .filter(|path| !path.is_empty())
+    {
+        let node = path.split('.').fold(&mut tree, |node, segment| {
+            node.children.entry(segment.to_string()).or_default()
+        });
+        node.terminal = true;
+    }
+
+    apply(value, &tree).unwrap_or_else(|| Value::Object(Map::new()))
+}
+
+fn apply(value: &Value, tree: &FieldTree) -> Option<Value> {
+    if tree.terminal {
+        return Some(value.clone());
+    }
+
+    match value {
+        Value::Object(map) => {
+            let projected: Map<String, Value> = tree
+                .children
+                .iter()
+                .filter_map(|(key, child)| Some((key.clone(), apply(map.get(key)?, child)?)))
+                .collect();
+            (!projected.is_empty()).then_some(Value::Object(projected))
+        }
+        Value::Array(items) => {
+            let mut indexed: Vec<(usize, &FieldTree)> = tree
+                .children
+                .iter()
+                .filter_map(|(key, child)| Some((key.parse().ok()?, child)))
+                .collect();
+            indexed.sort_by_key(|(idx, _)| *idx);
+
+            let projected: Vec<Value> = indexed
+                .into_iter()
+                .filter_map(|(idx, child)| apply(items.get(idx)?, child))
+                .collect();
+            (!projected.is_empty()).then_some(Value::Array(projected))
+        }
+        _ => None,
+    }
+}
+
+/// Flattens `observations[0]` into a single-level object with snake_case keys
+/// (`temp`, `humidity`, `wind_speed`, ...), merging in whichever unit-system
+/// object the observation carries and naming it under `units`.
+pub fn flatten_observation(current: &Value) -> Value {
+    let mut flat = Map::new();
+    let Some(Value::Object(observation)) = current.get("observations").and_then(|obs| obs.get(0))
+    else {
+        return Value::Object(flat);
+    };
+
+    for (key, value) in observation {
+        match value {
+            Value::Object(measurements) => {
+                flat.insert("units".to_string(), Value::from(key.as_str()));
+                for (key, value) in measurements {
+                    flat.insert(flat_key(key), value.clone());
+                }
+            }
+            value => {
+                flat.insert(flat_key(key), value.clone());
+            }
+        }
+    }
+
+    Value::Object(flat)
+}
+
+/// Short key, whether the value sits in the `metric` object, upstream key.
+const COMPACT_FIELDS: &[(&str, bool, &str)] = &[
+    ("t", true, "temp"),
+    ("h", false, "humidity"),
+    ("ws", true, "windSpeed"),
+    ("wg", true, "windGust"),
+    ("wd", false, "winddir"),
+    ("p", true, "pressure"),
+    ("r", true, "precipTotal"),
+    ("e", false, "epoch"),
+];
+
+/// Reduces `observations[0]` to a few metric values under one- or two-letter
+/// keys, small enough to parse into a fixed buffer on a microcontroller.
+/// Missing values are left out rather than sent as `null`.
+pub fn compact_observation(current: &Value) -> Value {
+    let observation = current.get("observations").and_then(|obs| obs.get(0));
+    let compact = COMPACT_FIELDS
+        .iter()
+        .filter_map(|(short, in_metric, key)| {
+            let source = if *in_metric {
+                observation?.get("metric")?
+            } else {
+                observation?
+            };
+            let value = source.get(*key).filter(|value| value.is_number())?;
+            Some((short.to_string(), value.clone()))
+        })
+        .collect();
+
+    Value::Object(compact)
+}
+
+fn flat_key(key: &str) -> String {
+    match key {
+        "stationID" => "station_id".to_string(),
+        "dewpt" => "dew_point".to_string(),
+        "elev" => "elevation".to_string(),
+        "winddir" => "wind_direction".to_string(),
+        key => {
+            let mut snake = String::with_capacity(key.len() + 4);
+            for c in key.chars() {
+                if c.is_ascii_uppercase() {
+                    snake.push('_');
+                    snake.push(c.to_ascii_lowercase());
+                } else {
+                    snake.push(c);
+                }
+            }
+            snake
+        }
+    }
+}
+
+/// Evaluates a JMESPath `expression` against `value` and returns just its result.
+pub fn query(value: &Value, expression: &str) -> Result<Value, String> {
+    let expression = jmespath::compile(expression).map_err(|err| err.to_string())?;
+    let result = expression.search(value).map_err(|err| err.to_string())?;
+    serde_json::to_value(&*result).map_err(|err| err.to_string())
+}
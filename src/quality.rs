@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+
+use serde_json::{json, Value};
+
+use crate::AppState;
+
+/// A value that differs from the previous accepted one by more than its
+/// step is only a spike when the two are at most this many seconds apart;
+/// after that the new level is trusted.
+const SPIKE_WINDOW_SECS: i64 = 15 * 60;
+/// `qcStatus` Wunderground gives observations failing its own checks.
+const QC_FAILED: i64 = -1;
+
+/// Checked fields by JSON pointer into an observation: the plausible range
+/// and the largest believable change between samples, in metric units.
+const LIMITS: [(&str, f64, f64, Option<f64>); 13] = [
+    ("/humidity", 0.0, 100.0, Some(50.0)),
+    ("/winddir", 0.0, 360.0, None),
+    ("/uv", 0.0, 20.0, None),
+    ("/solarRadiation", 0.0, 1800.0, None),
+    ("/metric/temp", -90.0, 60.0, Some(10.0)),
+    ("/metric/dewpt", -90.0, 40.0, Some(10.0)),
+    ("/metric/heatIndex", -90.0, 80.0, Some(15.0)),
+    ("/metric/windChill", -110.0, 60.0, Some(15.0)),
+    ("/metric/pressure", 850.0, 1090.0, Some(5.0)),
+    ("/metric/windSpeed", 0.0, 400.0, None),
+    ("/metric/windGust", 0.0, 400.0, None),
+    ("/metric/precipRate", 0.0, 2000.0, None),
+    ("/metric/precipTotal", 0.0, 2000.0, None),
+];
+
+/// What happens to a reading outside its plausible range or spiking from
+/// the previous one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityMode {
+    /// Keep the value, list the field in `qcFlags` and set `qcStatus` to -1.
+    Flag,
+    /// Serve the previous accepted value instead, or null without one, and
+    /// list the field in `qcFlags`.
+    Substitute,
+}
+
+/// The last accepted value of every checked field, by station.
+#[derive(Debug, Default)]
+pub(crate) struct QualityCheck {
+    accepted: HashMap<String, HashMap<&'static str, (i64, f64)>>,
+}
+
+impl QualityCheck {
+    /// Checks every observation in a `/current` payload against `LIMITS` and
+    /// the station's previous accepted readings.
+    pub(crate) fn apply(&mut self, mode: QualityMode, current: &mut Value) {
+        let observations = current
+            .get_mut("observations")
+            .and_then(Value::as_array_mut)
+            .into_iter()
+            .flatten();
+        for observation in observations {
+            let Some(station) = observation
+                .get("stationID")
+                .and_then(Value::as_str)
+                .map(String::from)
+            else {
+                continue;
+            };
+            let accepted = self.accepted.entry(station.clone()).or_default();
+            let epoch = observation.get("epoch").and_then(Value::as_i64);
+
+            let mut flags = Vec::new();
+            for (pointer, min, max, step) in LIMITS {
+                let Some(field) = observation.pointer_mut(pointer) else {
+                    continue;
+                };
+                let Some(value) = field.as_f64() else {
+                    continue;
+                };
+                let previous = accepted.get(pointer).copied();
+                let spike = match (step, previous, epoch) {
+                    (Some(step), Some((at, last)), Some(epoch)) => {
+                        (epoch - at).abs() <= SPIKE_WINDOW_SECS && (value - last).abs() > step
+                    }
+                    _ => false,
+                };
+                if !(min..=max).contains(&value) || spike {
+                    if mode == QualityMode::Substitute {
+                        *field = previous.map(|(_, last)| last).into();
+                    }
+                    flags.push(pointer[1..].replace('/', "."));
+                } else if let Some(epoch) = epoch {
+                    accepted.insert(pointer, (epoch, value));
+                }
+            }
+
+            if flags.is_empty() {
+                continue;
+            }
+            tracing::warn!("implausible readings from {station}: {}", flags.join(", "));
+            if let Some(observation) = observation.as_object_mut() {
+                if mode == QualityMode::Flag {
+                    observation.insert("qcStatus".to_string(), QC_FAILED.into());
+                }
+                observation.insert("qcFlags".to_string(), json!(flags));
+            }
+        }
+    }
+}
+
+/// Runs the configured check on a payload about to be cached.
+pub(crate) async fn check(state: &AppState, current: &mut Value) {
+    if let Some(mode) = state.config.quality_check {
+        state.quality.lock().await.apply(mode, current);
+    }
+}
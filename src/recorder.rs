@@ -0,0 +1,812 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chrono::DateTime;
+use rusqlite::{params, Connection, Row};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::{sync::watch, time::MissedTickBehavior};
+use wunderground_proxy_core::models::CurrentConditions;
+
+use crate::ObservationRetention;
+
+/// How often data past its retention is deleted.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Incremental vacuum lets pruning hand freed pages back to the file system;
+/// it only takes effect on a database created with it.
+const SCHEMA: &str = "
+PRAGMA auto_vacuum = INCREMENTAL;
+CREATE TABLE IF NOT EXISTS observations (
+    station TEXT NOT NULL,
+    epoch INTEGER NOT NULL,
+    obs_time_utc TEXT,
+    temp REAL,
+    dewpt REAL,
+    humidity REAL,
+    pressure REAL,
+    wind_speed REAL,
+    wind_gust REAL,
+    wind_direction REAL,
+    precip_rate REAL,
+    precip_total REAL,
+    solar_radiation REAL,
+    uv REAL,
+    PRIMARY KEY (station, epoch)
+);
+CREATE TABLE IF NOT EXISTS rollups (
+    resolution INTEGER NOT NULL,
+    station TEXT NOT NULL,
+    bucket INTEGER NOT NULL,
+    samples INTEGER NOT NULL,
+    min_temp REAL,
+    max_temp REAL,
+    avg_temp REAL,
+    rain_total REAL,
+    peak_gust REAL,
+    PRIMARY KEY (resolution, station, bucket)
+)";
+
+/// Recomputes the `?1`-second buckets starting at or after `?2`. Rain is
+/// summed from increments of the accumulated `precip_total`, which drops back
+/// at the station's midnight; the sample before a bucket is looked up so its
+/// first increment counts too.
+const ROLLUP: &str = "
+INSERT OR REPLACE INTO rollups (
+    resolution, station, bucket, samples, min_temp, max_temp, avg_temp, rain_total, peak_gust
+)
+SELECT ?1, station, epoch - epoch % ?1 AS bucket, COUNT(*), MIN(temp), MAX(temp), AVG(temp),
+       SUM(rain), MAX(wind_gust)
+FROM (
+    SELECT station, epoch, temp, wind_gust,
+           CASE
+               WHEN previous IS NULL OR precip_total IS NULL THEN 0
+               WHEN precip_total >= previous THEN precip_total - previous
+               ELSE precip_total
+           END AS rain
+    FROM (
+        SELECT station, epoch, temp, wind_gust, precip_total,
+               LAG(precip_total) OVER (PARTITION BY station ORDER BY epoch) AS previous
+        FROM observations
+        WHERE epoch >= ?2 - ?1
+    )
+)
+WHERE epoch >= ?2
+GROUP BY station, bucket";
+
+/// Rain per station from the samples with `?1 <= epoch < ?2`, counted like
+/// in [`ROLLUP`], looking back up to `?3` seconds for the sample before, of
+/// the stations in `?4` (see [`station_filter`]).
+const RAW_RAIN: &str = "
+SELECT station, SUM(rain)
+FROM (
+    SELECT station, epoch,
+           CASE
+               WHEN previous IS NULL OR precip_total IS NULL THEN 0
+               WHEN precip_total >= previous THEN precip_total - previous
+               ELSE precip_total
+           END AS rain
+    FROM (
+        SELECT station, epoch, precip_total,
+               LAG(precip_total) OVER (PARTITION BY station ORDER BY epoch) AS previous
+        FROM observations
+        WHERE epoch >= ?1 - ?3 AND epoch < ?2
+          AND (?4 = '[]' OR station IN (SELECT value FROM json_each(?4)))
+    )
+)
+WHERE epoch >= ?1
+GROUP BY station";
+
+/// Rollup bucket sizes.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Resolution {
+    Hourly = 3600,
+    /// UTC days.
+    Daily = 86400,
+}
+
+const RESOLUTIONS: [Resolution; 2] = [Resolution::Hourly, Resolution::Daily];
+
+/// A stored observation, metric units.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Sample {
+    pub(crate) station: String,
+    pub(crate) epoch: i64,
+    pub(crate) obs_time_utc: Option<String>,
+    temp: Option<f64>,
+    dewpt: Option<f64>,
+    humidity: Option<f64>,
+    pressure: Option<f64>,
+    wind_speed: Option<f64>,
+    wind_gust: Option<f64>,
+    wind_direction: Option<f64>,
+    precip_rate: Option<f64>,
+    precip_total: Option<f64>,
+    solar_radiation: Option<f64>,
+    uv: Option<f64>,
+}
+
+impl Sample {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Sample> {
+        Ok(Sample {
+            station: row.get(0)?,
+            epoch: row.get(1)?,
+            obs_time_utc: row.get(2)?,
+            temp: row.get(3)?,
+            dewpt: row.get(4)?,
+            humidity: row.get(5)?,
+            pressure: row.get(6)?,
+            wind_speed: row.get(7)?,
+            wind_gust: row.get(8)?,
+            wind_direction: row.get(9)?,
+            precip_rate: row.get(10)?,
+            precip_total: row.get(11)?,
+            solar_radiation: row.get(12)?,
+            uv: row.get(13)?,
+        })
+    }
+}
+
+/// Reads one numeric field of a [`Sample`].
+pub(crate) type Measurement = fn(&Sample) -> Option<f64>;
+
+/// The numeric [`Sample`] fields in column order, by their JSON name.
+pub(crate) const MEASUREMENTS: [(&str, Measurement); 11] = [
+    ("temp", |sample| sample.temp),
+    ("dewpt", |sample| sample.dewpt),
+    ("humidity", |sample| sample.humidity),
+    ("pressure", |sample| sample.pressure),
+    ("windSpeed", |sample| sample.wind_speed),
+    ("windGust", |sample| sample.wind_gust),
+    ("windDirection", |sample| sample.wind_direction),
+    ("precipRate", |sample| sample.precip_rate),
+    ("precipTotal", |sample| sample.precip_total),
+    ("solarRadiation", |sample| sample.solar_radiation),
+    ("uv", |sample| sample.uv),
+];
+
+/// Aggregates of the samples in one bucket, metric units.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Rollup {
+    pub(crate) station: String,
+    /// Epoch seconds the bucket starts at.
+    pub(crate) bucket: i64,
+    samples: u32,
+    min_temp: Option<f64>,
+    max_temp: Option<f64>,
+    avg_temp: Option<f64>,
+    rain_total: Option<f64>,
+    peak_gust: Option<f64>,
+}
+
+impl Rollup {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Rollup> {
+        Ok(Rollup {
+            station: row.get(0)?,
+            bucket: row.get(1)?,
+            samples: row.get(2)?,
+            min_temp: row.get(3)?,
+            max_temp: row.get(4)?,
+            avg_temp: row.get(5)?,
+            rain_total: row.get(6)?,
+            peak_gust: row.get(7)?,
+        })
+    }
+}
+
+/// Records and averages of one station over a range of daily rollups,
+/// metric units.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StationStats {
+    station: String,
+    /// Days with at least one sample.
+    days: u32,
+    hottest_day: Option<DayRecord>,
+    coldest_day: Option<DayRecord>,
+    wettest_day: Option<DayRecord>,
+    strongest_gust: Option<DayRecord>,
+    /// Over every sample.
+    average_temp: Option<f64>,
+    /// Of the daily maxima.
+    average_high: Option<f64>,
+    /// Of the daily minima.
+    average_low: Option<f64>,
+    rain_total: Option<f64>,
+}
+
+/// The value a record was set with and the UTC day it was set on.
+#[derive(Debug, Serialize)]
+pub(crate) struct DayRecord {
+    date: String,
+    value: f64,
+}
+
+/// Sum and count of the values seen.
+#[derive(Default)]
+struct Mean(f64, f64);
+
+impl Mean {
+    fn add(&mut self, value: Option<f64>, weight: f64) {
+        if let Some(value) = value {
+            self.0 += value * weight;
+            self.1 += weight;
+        }
+    }
+
+    fn get(&self) -> Option<f64> {
+        (self.1 > 0.0).then(|| hundredths(self.0 / self.1))
+    }
+}
+
+impl StationStats {
+    /// Folds the daily rollups of one station.
+    fn from_days(station: String, days: &[Rollup]) -> StationStats {
+        let record = |value: fn(&Rollup) -> Option<f64>, highest: bool| {
+            days.iter()
+                .filter_map(|day| Some((day, value(day)?)))
+                .reduce(|best, next| {
+                    let better = if highest {
+                        next.1 > best.1
+                    } else {
+                        next.1 < best.1
+                    };
+                    if better {
+                        next
+                    } else {
+                        best
+                    }
+                })
+                .map(|(day, value)| DayRecord {
+                    date: DateTime::from_timestamp(day.bucket, 0)
+                        .map(|time| time.date_naive().to_string())
+                        .unwrap_or_default(),
+                    value,
+                })
+        };
+        let (mut temp, mut high, mut low) = (Mean::default(), Mean::default(), Mean::default());
+        let mut rain_total = None;
+        for day in days {
+            temp.add(day.avg_temp, day.samples.into());
+            high.add(day.max_temp, 1.0);
+            low.add(day.min_temp, 1.0);
+            if let Some(rain) = day.rain_total {
+                *rain_total.get_or_insert(0.0) += rain;
+            }
+        }
+        StationStats {
+            station,
+            days: days.len() as u32,
+            hottest_day: record(|day| day.max_temp, true),
+            coldest_day: record(|day| day.min_temp, false),
+            wettest_day: record(|day| day.rain_total, true),
+            strongest_gust: record(|day| day.peak_gust, true),
+            average_temp: temp.get(),
+            average_high: high.get(),
+            average_low: low.get(),
+            rain_total: rain_total.map(hundredths),
+        }
+    }
+}
+
+/// The station ids a query is limited to, as the JSON array its
+/// `station IN (SELECT value FROM json_each(..))` condition reads; `[]`
+/// matches every station.
+fn station_filter(stations: &[String]) -> String {
+    Value::from(stations).to_string()
+}
+
+/// Rounded to 0.01, enough for every unit recorded.
+pub(crate) fn hundredths(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+/// Rows deleted by [`ObservationStore::prune`].
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct Pruned {
+    samples: usize,
+    hourly: usize,
+    daily: usize,
+}
+
+/// Observations recorded in the SQLite database at `OBSERVATIONS_DB`. Queries
+/// run on the blocking thread pool, one at a time.
+#[derive(Debug, Clone)]
+pub(crate) struct ObservationStore {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl ObservationStore {
+    /// Creates the tables if needed and brings the rollups up to date with
+    /// observations stored while they weren't maintained.
+    pub(crate) fn open(path: &Path) -> rusqlite::Result<ObservationStore> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        for resolution in RESOLUTIONS {
+            let latest: Option<i64> = connection.query_row(
+                "SELECT MAX(bucket) FROM rollups WHERE resolution = ?1",
+                [resolution as i64],
+                |row| row.get(0),
+            )?;
+            connection.execute(ROLLUP, params![resolution as i64, latest.unwrap_or(0)])?;
+        }
+        Ok(ObservationStore {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    async fn with<T, F>(&self, query: F) -> rusqlite::Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let connection = self.connection.clone();
+        tokio::task::spawn_blocking(move || {
+            query(&connection.lock().unwrap_or_else(|err| err.into_inner()))
+        })
+        .await
+        .expect("observation query panicked")
+    }
+
+    /// Stores the observations of a `/current` payload and updates the
+    /// rollups they fall in. Those without a station id or epoch can't be
+    /// keyed and are skipped; one already stored is left as it is.
+    async fn append(&self, current: Value) -> rusqlite::Result<()> {
+        let Ok(current) = serde_json::from_value::<CurrentConditions>(current) else {
+            return Ok(());
+        };
+        self.with(move |connection| {
+            let number =
+                |value: &Option<serde_json::Number>| value.as_ref().and_then(|n| n.as_f64());
+            for observation in &current.observations {
+                let (Some(station), Some(epoch)) = (&observation.station_id, observation.epoch)
+                else {
+                    continue;
+                };
+                let metric = observation.metric.as_ref();
+                let measurement = |field: fn(&_) -> &Option<serde_json::Number>| {
+                    metric.and_then(|metric| number(field(metric)))
+                };
+                let inserted = connection.execute(
+                    "INSERT OR IGNORE INTO observations (
+                        station, epoch, obs_time_utc, temp, dewpt, humidity, pressure, wind_speed,
+                        wind_gust, wind_direction, precip_rate, precip_total, solar_radiation, uv
+                    ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                    params![
+                        station,
+                        epoch,
+                        observation.obs_time_utc,
+                        measurement(|m| &m.temp),
+                        measurement(|m| &m.dewpt),
+                        number(&observation.humidity),
+                        measurement(|m| &m.pressure),
+                        measurement(|m| &m.wind_speed),
+                        measurement(|m| &m.wind_gust),
+                        number(&observation.wind_direction),
+                        measurement(|m| &m.precip_rate),
+                        measurement(|m| &m.precip_total),
+                        number(&observation.solar_radiation),
+                        number(&observation.uv),
+                    ],
+                )?;
+                if inserted == 0 {
+                    continue;
+                }
+                for resolution in RESOLUTIONS {
+                    let bucket = epoch - epoch.rem_euclid(resolution as i64);
+                    connection.execute(ROLLUP, params![resolution as i64, bucket])?;
+                }
+            }
+            Ok(())
+        })
+        .await
+    }
+
+    /// Whether any of `stations` has samples or rollups.
+    pub(crate) async fn records_any(&self, stations: &[String]) -> rusqlite::Result<bool> {
+        let stations = station_filter(stations);
+        self.with(move |connection| {
+            connection.query_row(
+                "SELECT EXISTS (
+                     SELECT 1 FROM observations
+                     WHERE station IN (SELECT value FROM json_each(?1))
+                 ) OR EXISTS (
+                     SELECT 1 FROM rollups
+                     WHERE resolution IN (?2, ?3) AND station IN (SELECT value FROM json_each(?1))
+                 )",
+                params![
+                    stations,
+                    Resolution::Hourly as i64,
+                    Resolution::Daily as i64
+                ],
+                |row| row.get(0),
+            )
+        })
+        .await
+    }
+
+    /// Up to `limit` samples of `stations` (all when empty) with
+    /// `from <= epoch < to`, oldest first, starting after `after` (an epoch
+    /// and station) when given.
+    pub(crate) async fn range(
+        &self,
+        from: i64,
+        to: i64,
+        after: Option<(i64, String)>,
+        limit: u32,
+        stations: &[String],
+    ) -> rusqlite::Result<Vec<Sample>> {
+        let stations = station_filter(stations);
+        self.with(move |connection| {
+            let (after_epoch, after_station) = after.unwrap_or((i64::MIN, String::new()));
+            let mut statement = connection.prepare_cached(
+                "SELECT station, epoch, obs_time_utc, temp, dewpt, humidity, pressure, wind_speed,
+                        wind_gust, wind_direction, precip_rate, precip_total, solar_radiation, uv
+                 FROM observations
+                 WHERE epoch >= ?1 AND epoch < ?2 AND (epoch, station) > (?3, ?4)
+                   AND (?6 = '[]' OR station IN (SELECT value FROM json_each(?6)))
+                 ORDER BY epoch, station
+                 LIMIT ?5",
+            )?;
+            let samples = statement
+                .query_map(
+                    params![from, to, after_epoch, after_station, limit, stations],
+                    Sample::from_row,
+                )?
+                .collect();
+            samples
+        })
+        .await
+    }
+
+    /// Every reported `measurement` of `stations` with `from <= epoch < to`
+    /// as `(epoch, value)`, oldest first, by station id.
+    pub(crate) async fn series(
+        &self,
+        from: i64,
+        to: i64,
+        measurement: Measurement,
+        stations: &[String],
+    ) -> rusqlite::Result<BTreeMap<String, Vec<(i64, f64)>>> {
+        const PAGE: u32 = 5000;
+        let mut series: BTreeMap<String, Vec<(i64, f64)>> = BTreeMap::new();
+        let mut after = None;
+        loop {
+            let samples = self.range(from, to, after.take(), PAGE, stations).await?;
+            for sample in &samples {
+                if let Some(value) = measurement(sample) {
+                    series
+                        .entry(sample.station.clone())
+                        .or_default()
+                        .push((sample.epoch, value));
+                }
+            }
+            match samples.last() {
+                Some(last) if samples.len() == PAGE as usize => {
+                    after = Some((last.epoch, last.station.clone()));
+                }
+                _ => return Ok(series),
+            }
+        }
+    }
+}
+
+impl ObservationStore {
+    /// `(direction, speed)` of the samples of `stations` with
+    /// `from <= epoch < to` that report both, by station id.
+    pub(crate) async fn wind(
+        &self,
+        from: i64,
+        to: i64,
+        stations: &[String],
+    ) -> rusqlite::Result<BTreeMap<String, Vec<(f64, f64)>>> {
+        let stations = station_filter(stations);
+        self.with(move |connection| {
+            let mut statement = connection.prepare_cached(
+                "SELECT station, wind_direction, wind_speed FROM observations
+                 WHERE epoch >= ?1 AND epoch < ?2
+                   AND wind_direction IS NOT NULL AND wind_speed IS NOT NULL
+                   AND (?3 = '[]' OR station IN (SELECT value FROM json_each(?3)))",
+            )?;
+            let mut wind: BTreeMap<String, Vec<(f64, f64)>> = BTreeMap::new();
+            let mut rows = statement.query(params![from, to, stations])?;
+            while let Some(row) = rows.next()? {
+                wind.entry(row.get(0)?)
+                    .or_default()
+                    .push((row.get(1)?, row.get(2)?));
+            }
+            Ok(wind)
+        })
+        .await
+    }
+}
+
+impl ObservationStore {
+    /// Up to `limit` rollups of `stations` (all when empty) with
+    /// `from <= bucket < to`, oldest first, starting after `after` (a bucket
+    /// and station) when given.
+    pub(crate) async fn rollups(
+        &self,
+        resolution: Resolution,
+        from: i64,
+        to: i64,
+        after: Option<(i64, String)>,
+        limit: u32,
+        stations: &[String],
+    ) -> rusqlite::Result<Vec<Rollup>> {
+        let stations = station_filter(stations);
+        self.with(move |connection| {
+            let (after_bucket, after_station) = after.unwrap_or((i64::MIN, String::new()));
+            let mut statement = connection.prepare_cached(
+                "SELECT station, bucket, samples, min_temp, max_temp, avg_temp, rain_total, peak_gust
+                 FROM rollups
+                 WHERE resolution = ?1 AND bucket >= ?2 AND bucket < ?3
+                   AND (bucket, station) > (?4, ?5)
+                   AND (?7 = '[]' OR station IN (SELECT value FROM json_each(?7)))
+                 ORDER BY bucket, station
+                 LIMIT ?6",
+            )?;
+            let rollups = statement
+                .query_map(
+                    params![
+                        resolution as i64,
+                        from,
+                        to,
+                        after_bucket,
+                        after_station,
+                        limit,
+                        stations
+                    ],
+                    Rollup::from_row,
+                )?
+                .collect();
+            rollups
+        })
+        .await
+    }
+
+    /// Minimum and maximum temperature of the days of `stations` starting in
+    /// `from <= bucket < to` that have both, as `(bucket, min, max)`, oldest
+    /// first, by station id.
+    pub(crate) async fn daily_temps(
+        &self,
+        from: i64,
+        to: i64,
+        stations: &[String],
+    ) -> rusqlite::Result<BTreeMap<String, Vec<(i64, f64, f64)>>> {
+        let mut temps: BTreeMap<String, Vec<(i64, f64, f64)>> = BTreeMap::new();
+        for day in self
+            .rollups(Resolution::Daily, from, to, None, u32::MAX, stations)
+            .await?
+        {
+            if let (Some(min), Some(max)) = (day.min_temp, day.max_temp) {
+                temps
+                    .entry(day.station)
+                    .or_default()
+                    .push((day.bucket, min, max));
+            }
+        }
+        Ok(temps)
+    }
+
+    /// Records and averages of `stations` from the daily rollups of the days
+    /// starting in `from <= bucket < to`, by station id.
+    pub(crate) async fn stats(
+        &self,
+        from: i64,
+        to: i64,
+        stations: &[String],
+    ) -> rusqlite::Result<Vec<StationStats>> {
+        let mut days = self
+            .rollups(Resolution::Daily, from, to, None, u32::MAX, stations)
+            .await?;
+        days.sort_by(|a, b| (&a.station, a.bucket).cmp(&(&b.station, b.bucket)));
+        Ok(days
+            .chunk_by(|a, b| a.station == b.station)
+            .map(|days| StationStats::from_days(days[0].station.clone(), days))
+            .collect())
+    }
+}
+
+impl ObservationStore {
+    /// Writes a consistent copy of the database to `path`, which must not
+    /// exist, while recording goes on.
+    pub(crate) async fn snapshot(&self, path: PathBuf) -> rusqlite::Result<()> {
+        self.with(move |connection| {
+            connection.execute("VACUUM INTO ?1", params![path.to_string_lossy()])?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Deletes samples and rollups older than `retention` allows.
+    pub(crate) async fn prune(&self, retention: ObservationRetention) -> rusqlite::Result<Pruned> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+        let cutoff = move |days: Option<u64>| days.map(|days| now - (days * 86400) as i64);
+        self.with(move |connection| {
+            let mut pruned = Pruned::default();
+            if let Some(cutoff) = cutoff(retention.raw_days) {
+                pruned.samples =
+                    connection.execute("DELETE FROM observations WHERE epoch < ?1", [cutoff])?;
+            }
+            for (resolution, days, deleted) in [
+                (
+                    Resolution::Hourly,
+                    retention.hourly_days,
+                    &mut pruned.hourly,
+                ),
+                (Resolution::Daily, retention.daily_days, &mut pruned.daily),
+            ] {
+                if let Some(cutoff) = cutoff(days) {
+                    *deleted = connection.execute(
+                        "DELETE FROM rollups WHERE resolution = ?1 AND bucket < ?2",
+                        params![resolution as i64, cutoff],
+                    )?;
+                }
+            }
+            connection.execute_batch("PRAGMA incremental_vacuum")?;
+            Ok(pruned)
+        })
+        .await
+    }
+}
+
+impl ObservationStore {
+    /// Rain in mm of `stations` with samples in `from <= epoch < to`, by
+    /// station id. Whole hours come from the hourly rollups, so the total
+    /// survives raw samples being pruned; only the partial hours at either
+    /// end are read from the samples.
+    pub(crate) async fn rain(
+        &self,
+        from: i64,
+        to: i64,
+        stations: &[String],
+    ) -> rusqlite::Result<BTreeMap<String, f64>> {
+        let stations = station_filter(stations);
+        let hour = Resolution::Hourly as i64;
+        let mut hours_from = from + (hour - from.rem_euclid(hour)) % hour;
+        let mut hours_to = to - to.rem_euclid(hour);
+        if hours_from >= hours_to {
+            (hours_from, hours_to) = (to, to);
+        }
+        self.with(move |connection| {
+            let mut totals = BTreeMap::new();
+            let mut add = |station: String, rain: Option<f64>| {
+                *totals.entry(station).or_insert(0.0) += rain.unwrap_or(0.0);
+            };
+
+            let mut raw = connection.prepare_cached(RAW_RAIN)?;
+            for (start, end) in [(from, hours_from), (hours_to, to)] {
+                if start < end {
+                    let mut rows = raw.query(params![start, end, hour, stations])?;
+                    while let Some(row) = rows.next()? {
+                        add(row.get(0)?, row.get(1)?);
+                    }
+                }
+            }
+
+            let mut hourly = connection.prepare_cached(
+                "SELECT station, SUM(rain_total) FROM rollups
+                 WHERE resolution = ?1 AND bucket >= ?2 AND bucket < ?3
+                   AND (?4 = '[]' OR station IN (SELECT value FROM json_each(?4)))
+                 GROUP BY station",
+            )?;
+            let mut rows = hourly.query(params![hour, hours_from, hours_to, stations])?;
+            while let Some(row) = rows.next()? {
+                add(row.get(0)?, row.get(1)?);
+            }
+            Ok(totals)
+        })
+        .await
+    }
+}
+
+/// Appends every new `/current` payload to `store`. Subscribing keeps the
+/// background refresh running, so samples are taken every cache period even
+/// without clients.
+pub(crate) async fn run(store: ObservationStore, mut updates: watch::Receiver<Option<Value>>) {
+    while updates.changed().await.is_ok() {
+        let Some(current) = updates.borrow_and_update().clone() else {
+            continue;
+        };
+        if let Err(err) = store.append(current).await {
+            tracing::warn!("recording observation failed: {err}");
+        }
+    }
+}
+
+/// Prunes `store` every [`PRUNE_INTERVAL`], starting at startup. Returns
+/// right away when everything is kept forever.
+pub(crate) async fn prune_periodically(store: ObservationStore, retention: ObservationRetention) {
+    if retention.raw_days.is_none()
+        && retention.hourly_days.is_none()
+        && retention.daily_days.is_none()
+    {
+        return;
+    }
+    let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        match store.prune(retention).await {
+            Ok(pruned) => tracing::debug!(?pruned, "observations pruned"),
+            Err(err) => tracing::warn!("pruning observations failed: {err}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    async fn store() -> ObservationStore {
+        let store = ObservationStore::open(Path::new(":memory:")).unwrap();
+        for (station, epoch) in [("KHOME1", 3600), ("KNEIGHBOR2", 3600), ("KHOME1", 7200)] {
+            let current = json!({
+                "observations": [{
+                    "stationID": station,
+                    "epoch": epoch,
+                    "winddir": 90,
+                    "metric": { "temp": 10.0, "windSpeed": 15.0, "precipTotal": 1.0 },
+                }],
+            });
+            store.append(current).await.unwrap();
+        }
+        store
+    }
+
+    fn ids(ids: &[&str]) -> Vec<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn queries_are_limited_to_the_given_stations() {
+        let store = store().await;
+        let neighbor = ids(&["KNEIGHBOR2"]);
+
+        let samples = store.range(0, i64::MAX, None, 10, &neighbor).await.unwrap();
+        assert_eq!(samples.len(), 1);
+        assert_eq!(samples[0].station, "KNEIGHBOR2");
+
+        let rollups = store
+            .rollups(Resolution::Hourly, 0, i64::MAX, None, 10, &neighbor)
+            .await
+            .unwrap();
+        assert!(rollups.iter().all(|rollup| rollup.station == "KNEIGHBOR2"));
+
+        let wind = store.wind(0, i64::MAX, &neighbor).await.unwrap();
+        assert_eq!(wind.keys().collect::<Vec<_>>(), ["KNEIGHBOR2"]);
+        let rain = store.rain(0, 10_000, &neighbor).await.unwrap();
+        assert_eq!(rain.keys().collect::<Vec<_>>(), ["KNEIGHBOR2"]);
+    }
+
+    #[tokio::test]
+    async fn no_stations_means_every_station() {
+        let store = store().await;
+        let samples = store.range(0, i64::MAX, None, 10, &[]).await.unwrap();
+        assert_eq!(samples.len(), 3);
+        let rain = store.rain(0, 10_000, &[]).await.unwrap();
+        assert_eq!(rain.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn records_any_checks_the_given_stations() {
+        let store = store().await;
+        assert!(store
+            .records_any(&ids(&["KHOME1", "KOTHER"]))
+            .await
+            .unwrap());
+        assert!(!store.records_any(&ids(&["KOTHER"])).await.unwrap());
+    }
+}
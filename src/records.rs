@@ -0,0 +1,132 @@
+use serde::Serialize;
+use serde_json::Value;
+
+/// An upstream observation with typed fields, as the GraphQL and gRPC
+/// interfaces serve it.
+#[cfg(any(feature = "graphql", feature = "grpc"))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct Observation {
+    pub(crate) station_id: Option<String>,
+    pub(crate) obs_time_utc: Option<String>,
+    pub(crate) obs_time_local: Option<String>,
+    pub(crate) epoch: Option<i64>,
+    pub(crate) neighborhood: Option<String>,
+    pub(crate) lat: Option<f64>,
+    pub(crate) lon: Option<f64>,
+    pub(crate) humidity: Option<f64>,
+    pub(crate) wind_direction: Option<f64>,
+    pub(crate) solar_radiation: Option<f64>,
+    pub(crate) uv: Option<f64>,
+    pub(crate) temperature: Option<f64>,
+    pub(crate) dew_point: Option<f64>,
+    pub(crate) heat_index: Option<f64>,
+    pub(crate) wind_chill: Option<f64>,
+    pub(crate) wind_speed: Option<f64>,
+    pub(crate) wind_gust: Option<f64>,
+    pub(crate) pressure: Option<f64>,
+    pub(crate) precip_rate: Option<f64>,
+    pub(crate) precip_total: Option<f64>,
+    pub(crate) elevation: Option<f64>,
+}
+
+#[cfg(any(feature = "graphql", feature = "grpc"))]
+impl Observation {
+    pub(crate) fn from_json(observation: &Value) -> Observation {
+        let string = |key: &str| {
+            observation
+                .get(key)
+                .and_then(Value::as_str)
+                .map(String::from)
+        };
+        let number = |key: &str| observation.get(key).and_then(Value::as_f64);
+        let metric = |key: &str| {
+            observation
+                .get("metric")
+                .and_then(|metric| metric.get(key))
+                .and_then(Value::as_f64)
+        };
+
+        Observation {
+            station_id: string("stationID"),
+            obs_time_utc: string("obsTimeUtc"),
+            obs_time_local: string("obsTimeLocal"),
+            epoch: observation.get("epoch").and_then(Value::as_i64),
+            neighborhood: string("neighborhood"),
+            lat: number("lat"),
+            lon: number("lon"),
+            humidity: number("humidity"),
+            wind_direction: number("winddir"),
+            solar_radiation: number("solarRadiation"),
+            uv: number("uv"),
+            temperature: metric("temp"),
+            dew_point: metric("dewpt"),
+            heat_index: metric("heatIndex"),
+            wind_chill: metric("windChill"),
+            wind_speed: metric("windSpeed"),
+            wind_gust: metric("windGust"),
+            pressure: metric("pressure"),
+            precip_rate: metric("precipRate"),
+            precip_total: metric("precipTotal"),
+            elevation: metric("elev"),
+        }
+    }
+}
+
+/// One day of the upstream forecast, as the GraphQL, gRPC and MQTT
+/// interfaces serve it.
+#[derive(Serialize)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[serde(rename_all = "camelCase")]
+pub struct ForecastDay {
+    pub(crate) day_of_week: Option<String>,
+    pub(crate) valid_time_local: Option<String>,
+    pub(crate) valid_time_utc: Option<i64>,
+    pub(crate) narrative: Option<String>,
+    pub(crate) temperature_max: Option<f64>,
+    pub(crate) temperature_min: Option<f64>,
+    pub(crate) qpf: Option<f64>,
+    pub(crate) qpf_snow: Option<f64>,
+    pub(crate) day_precip_chance: Option<f64>,
+    pub(crate) night_precip_chance: Option<f64>,
+}
+
+impl ForecastDay {
+    /// Zips the parallel per-day arrays of the upstream payload into one
+    /// object per day. Day/night values come from the interleaved `daypart` arrays.
+    pub(crate) fn from_json(forecast: &Value) -> Vec<ForecastDay> {
+        let at = |key: &str, idx: usize| forecast.get(key).and_then(|values| values.get(idx));
+        let daypart = |key: &str, idx: usize| {
+            forecast
+                .get("daypart")
+                .and_then(|dayparts| dayparts.get(0))
+                .and_then(|daypart| daypart.get(key))
+                .and_then(|values| values.get(idx))
+                .and_then(Value::as_f64)
+        };
+        let days = forecast
+            .get("dayOfWeek")
+            .and_then(Value::as_array)
+            .map_or(0, Vec::len);
+
+        (0..days)
+            .map(|idx| ForecastDay {
+                day_of_week: at("dayOfWeek", idx)
+                    .and_then(Value::as_str)
+                    .map(String::from),
+                valid_time_local: at("validTimeLocal", idx)
+                    .and_then(Value::as_str)
+                    .map(String::from),
+                valid_time_utc: at("validTimeUtc", idx).and_then(Value::as_i64),
+                narrative: at("narrative", idx)
+                    .and_then(Value::as_str)
+                    .map(String::from),
+                temperature_max: at("temperatureMax", idx).and_then(Value::as_f64),
+                temperature_min: at("temperatureMin", idx).and_then(Value::as_f64),
+                qpf: at("qpf", idx).and_then(Value::as_f64),
+                qpf_snow: at("qpfSnow", idx).and_then(Value::as_f64),
+                day_precip_chance: daypart("precipChance", idx * 2),
+                night_precip_chance: daypart("precipChance", idx * 2 + 1),
+            })
+            .collect()
+    }
+}
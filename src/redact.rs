@@ -0,0 +1,191 @@
+use std::{
+    borrow::Cow,
+    io::{self, Write},
+    sync::RwLock,
+};
+
+use tracing_subscriber::fmt::MakeWriter;
+use wunderground_proxy_core::upstream::ProviderConfig;
+
+use crate::{config::JwtKeys, AlertChannel, AppConfig};
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Query parameters whose values are credentials, compared case-insensitively.
+const SECRET_PARAMS: &[&str] = &[
+    "apikey",
+    "api_key",
+    "appid",
+    "key",
+    "token",
+    "access_token",
+    "password",
+    "secret",
+];
+
+/// Values this short would match all over the place; they are still covered
+/// by `SECRET_PARAMS` when they appear in a URL.
+const MIN_SECRET_LEN: usize = 4;
+
+static SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Registers the upstream API key, every client credential and the alert
+/// channel tokens and webhook URLs in `config`, so [`redact`] hides them
+/// wherever they show up.
+pub(crate) fn register(config: &AppConfig) {
+    let upstream_key = config.provider.api_key();
+    let merged_keys = config
+        .merge
+        .iter()
+        .flat_map(|merge| merge.providers.values())
+        .filter_map(ProviderConfig::api_key);
+    let failover_key = config
+        .failover
+        .as_ref()
+        .and_then(|failover| failover.provider.api_key());
+    let jwt_secret = config.jwt.as_ref().and_then(|jwt| match &jwt.keys {
+        JwtKeys::Secret(secret) => Some(secret),
+        JwtKeys::JwksUrl(_) => None,
+    });
+    let basic_password = config.basic_auth.as_ref().map(|basic| &basic.password);
+    let hmac_secret = config.hmac.as_ref().map(|hmac| &hmac.secret);
+    let mqtt_password = config.mqtt.as_ref().and_then(|mqtt| mqtt.password.as_ref());
+    let influxdb_token = config.influxdb.as_ref().map(|influxdb| &influxdb.token);
+    let backup_secret = config
+        .backup
+        .as_ref()
+        .map(|backup| &backup.secret_access_key);
+    let remote_write_password = config
+        .remote_write
+        .as_ref()
+        .and_then(|remote_write| remote_write.password.as_ref());
+    let smtp_password = config
+        .digest
+        .as_ref()
+        .and_then(|digest| digest.smtp.password.as_ref());
+    let upload_password = config.upload.as_ref().map(|upload| &upload.password);
+    let ecowitt_passkey = config.ecowitt.as_ref().map(|ecowitt| &ecowitt.passkey);
+    let tenant_keys = config.tenants.values().map(|tenant| &tenant.key);
+    let alert_channels = config
+        .alert_channels
+        .values()
+        .flat_map(AlertChannel::secrets);
+    let alert_webhooks = config
+        .alert_rules
+        .iter()
+        .filter_map(|rule| rule.webhook.as_ref());
+
+    let mut secrets = SECRETS.write().unwrap_or_else(|err| err.into_inner());
+    for secret in upstream_key
+        .into_iter()
+        .chain(merged_keys)
+        .chain(failover_key)
+        .chain(jwt_secret)
+        .chain(basic_password)
+        .chain(hmac_secret)
+        .chain(mqtt_password)
+        .chain(influxdb_token)
+        .chain(remote_write_password)
+        .chain(backup_secret)
+        .chain(smtp_password)
+        .chain(upload_password)
+        .chain(ecowitt_passkey)
+        .chain(tenant_keys)
+        .chain(alert_channels)
+        .chain(alert_webhooks)
+    {
+        if secret.len() >= MIN_SECRET_LEN && !secrets.contains(secret) {
+            secrets.push(secret.clone());
+        }
+    }
+    // Longest first, so a key containing another one is hidden whole.
+    secrets.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+}
+
+/// Replaces registered secrets and the values of credential query parameters
+/// (`apiKey=...`, `appid=...`) with a placeholder.
+pub fn redact(text: &str) -> Cow<'_, str> {
+    let mut redacted = Cow::Borrowed(text);
+
+    let secrets = SECRETS.read().unwrap_or_else(|err| err.into_inner());
+    for secret in secrets.iter() {
+        if redacted.contains(secret.as_str()) {
+            redacted = Cow::Owned(redacted.replace(secret.as_str(), REDACTED));
+        }
+    }
+
+    match redact_params(&redacted) {
+        Some(scrubbed) => Cow::Owned(scrubbed),
+        None => redacted,
+    }
+}
+
+/// `None` when no credential parameter is present.
+fn redact_params(text: &str) -> Option<String> {
+    let mut output = String::new();
+    let mut copied = 0;
+
+    for (start, _) in text.match_indices(['?', '&']) {
+        let name_start = start + 1;
+        let Some(name_len) = text[name_start..].find('=') else {
+            break;
+        };
+        let name = &text[name_start..name_start + name_len];
+        if !SECRET_PARAMS
+            .iter()
+            .any(|param| param.eq_ignore_ascii_case(name))
+        {
+            continue;
+        }
+
+        let value_start = name_start + name_len + 1;
+        let value_len = text[value_start..]
+            .find(|c: char| {
+                c == '&' || c == '#' || c == '"' || c == '\'' || c == ')' || c.is_whitespace()
+            })
+            .unwrap_or(text.len() - value_start);
+        if value_start < copied || value_len == 0 {
+            continue;
+        }
+        output.push_str(&text[copied..value_start]);
+        output.push_str(REDACTED);
+        copied = value_start + value_len;
+    }
+
+    if copied == 0 {
+        return None;
+    }
+    output.push_str(&text[copied..]);
+    Some(output)
+}
+
+/// Log writer passing every formatted event through [`redact`].
+#[derive(Debug, Clone, Copy)]
+pub struct RedactingWriter<M>(pub M);
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for RedactingWriter<M> {
+    type Writer = Redacting<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        Redacting(self.0.make_writer())
+    }
+}
+
+/// The formatter writes each event in a single call, so a secret is never
+/// split across two buffers.
+#[derive(Debug)]
+pub struct Redacting<W>(W);
+
+impl<W: Write> Write for Redacting<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match std::str::from_utf8(buf) {
+            Ok(text) => self.0.write_all(redact(text).as_bytes())?,
+            Err(_) => self.0.write_all(buf)?,
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
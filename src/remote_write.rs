@@ -0,0 +1,135 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use prost::Message;
+use reqwest::header;
+use serde_json::Value;
+use tokio::sync::watch;
+
+use crate::{
+    admin::{CACHE_ENTRIES, CACHE_ENTRY_AGE},
+    weather_metrics::{self, Gauge},
+    AppState, RemoteWriteConfig,
+};
+
+const PUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The remote-write 1.0 message, `prometheus.WriteRequest`.
+#[derive(Clone, PartialEq, Message)]
+struct WriteRequest {
+    #[prost(message, repeated, tag = "1")]
+    timeseries: Vec<TimeSeries>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct TimeSeries {
+    /// Sorted by name.
+    #[prost(message, repeated, tag = "1")]
+    labels: Vec<Label>,
+    #[prost(message, repeated, tag = "2")]
+    samples: Vec<Sample>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Label {
+    #[prost(string, tag = "1")]
+    name: String,
+    #[prost(string, tag = "2")]
+    value: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct Sample {
+    #[prost(double, tag = "1")]
+    value: f64,
+    /// Milliseconds since the epoch.
+    #[prost(int64, tag = "2")]
+    timestamp: i64,
+}
+
+/// Pushes the `/metrics/weather` gauges and the admin cache gauges to the
+/// remote-write endpoint in `config` whenever a new `/current` payload
+/// arrives. A failed push is not retried; the next one carries fresh values.
+pub(crate) async fn run(
+    state: AppState,
+    config: RemoteWriteConfig,
+    mut updates: watch::Receiver<Option<Value>>,
+) {
+    while updates.changed().await.is_ok() {
+        let Some(current) = updates.borrow_and_update().clone() else {
+            continue;
+        };
+        let request = write_request(&state, &config.job, &current).await;
+        let body = match snap::raw::Encoder::new().compress_vec(&request.encode_to_vec()) {
+            Ok(body) => body,
+            Err(err) => {
+                tracing::warn!("compressing remote write request failed: {err}");
+                continue;
+            }
+        };
+
+        let mut push = state
+            .client
+            .post(&config.url)
+            .timeout(PUSH_TIMEOUT)
+            .header(header::CONTENT_TYPE, "application/x-protobuf")
+            .header(header::CONTENT_ENCODING, "snappy")
+            .header("X-Prometheus-Remote-Write-Version", "0.1.0")
+            .body(body);
+        if let Some(username) = &config.username {
+            push = push.basic_auth(username, config.password.as_ref());
+        }
+        match push
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+        {
+            Ok(_) => tracing::debug!("pushed {} series", request.timeseries.len()),
+            Err(err) => tracing::warn!("remote write failed: {err}"),
+        }
+    }
+}
+
+async fn write_request(state: &AppState, job: &str, current: &Value) -> WriteRequest {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64;
+    let series = |name: &str, label: Option<(&str, &str)>, value: f64| {
+        let mut labels = vec![
+            Label {
+                name: "__name__".to_string(),
+                value: name.to_string(),
+            },
+            Label {
+                name: "job".to_string(),
+                value: job.to_string(),
+            },
+        ];
+        labels.extend(label.map(|(name, value)| Label {
+            name: name.to_string(),
+            value: value.to_string(),
+        }));
+        labels.sort_by(|a, b| a.name.cmp(&b.name));
+        TimeSeries {
+            labels,
+            samples: vec![Sample { value, timestamp }],
+        }
+    };
+
+    let mut timeseries = Vec::new();
+    for Gauge { name, samples, .. } in weather_metrics::gauges(current) {
+        for (station, value) in samples {
+            timeseries.push(series(name, Some(("station", station)), value));
+        }
+    }
+    let entries = state.cached_entries.entries().await;
+    timeseries.push(series(CACHE_ENTRIES, None, entries.len() as f64));
+    for (key, entry) in &entries {
+        timeseries.push(series(
+            CACHE_ENTRY_AGE,
+            Some(("key", &key.to_string())),
+            entry.fetched_at.elapsed().as_secs_f64(),
+        ));
+    }
+    WriteRequest { timeseries }
+}
@@ -0,0 +1,393 @@
+use std::{
+    fs::Permissions,
+    io,
+    net::SocketAddr,
+    os::{
+        fd::{FromRawFd, IntoRawFd, RawFd},
+        unix::fs::PermissionsExt,
+    },
+    path::Path,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use axum::{extract::ConnectInfo, http::Request, Router};
+use axum_server::{
+    accept::Accept,
+    tls_rustls::{RustlsAcceptor, RustlsConfig},
+};
+use hyper::{
+    body::Incoming,
+    server::conn::{http1, http2},
+};
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::conn::auto,
+    service::TowerToHyperService,
+};
+use rustls::{
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    server::WebPkiClientVerifier,
+    RootCertStore,
+};
+use sd_notify::NotifyState;
+use socket2::{SockRef, TcpKeepalive};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::{
+    net::{TcpListener, TcpSocket, TcpStream, UnixListener},
+    task::JoinSet,
+};
+use tower::ServiceExt;
+
+use crate::{acme, client_ip::UnixPeer, HttpVersion, ServerConfig, SocketOptions, TlsConfig};
+
+enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+/// Serves `app` until one of the listeners fails. Sockets passed by systemd
+/// socket activation take the place of the configured ones; once listening,
+/// readiness and watchdog keep-alives are reported when running under systemd.
+/// With TLS configured, TCP listeners speak HTTPS only. TCP peers are exposed
+/// to the app as `ConnectInfo<SocketAddr>`; Unix socket peers are marked so
+/// their forwarding headers are believed.
+pub async fn serve(app: Router, config: &ServerConfig) -> io::Result<()> {
+    let http = config.http;
+    let tls = match &config.tls {
+        Some(tls) => Some(load_tls(tls, http).await?),
+        None => None,
+    };
+
+    let listeners = match activated_listeners()? {
+        listeners if !listeners.is_empty() => listeners,
+        _ => bind_listeners(config).await?,
+    };
+
+    let socket = config.socket;
+    let mut tasks = JoinSet::new();
+    for listener in listeners {
+        let app = app.clone();
+        let tls = tls.clone();
+        tasks.spawn(async move {
+            match (listener, tls) {
+                (Listener::Tcp(listener), Some(tls)) => {
+                    // The protocol is negotiated through ALPN, see `restrict_alpn`.
+                    let acceptor = RustlsAcceptor::new(tls).acceptor(Tuned(socket));
+                    axum_server::from_tcp(listener.into_std()?)
+                        .acceptor(acceptor)
+                        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                        .await
+                }
+                (Listener::Tcp(listener), None) => loop {
+                    match listener.accept().await {
+                        Ok((stream, peer)) => {
+                            tune(&stream, socket);
+                            serve_connection(stream, app.clone(), http, ConnectInfo(peer))
+                        }
+                        Err(err) => accept_failed(err).await,
+                    }
+                },
+                (Listener::Unix(listener), _) => loop {
+                    match listener.accept().await {
+                        Ok((socket, _)) => serve_connection(socket, app.clone(), http, UnixPeer),
+                        Err(err) => accept_failed(err).await,
+                    }
+                },
+            }
+        });
+    }
+
+    sd_notify::notify(&[NotifyState::Ready])?;
+    if let Some(timeout) = sd_notify::watchdog_enabled() {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(timeout / 2);
+            loop {
+                interval.tick().await;
+                if let Err(err) = sd_notify::notify(&[NotifyState::Watchdog]) {
+                    tracing::warn!("watchdog notification failed: {err}");
+                }
+            }
+        });
+    }
+
+    while let Some(result) = tasks.join_next().await {
+        result.map_err(io::Error::other)??;
+    }
+    Ok(())
+}
+
+/// Serves the admin routes over plain HTTP on `addr`, which should only be
+/// reachable by operators, e.g. a loopback address.
+pub async fn serve_admin(app: Router, addr: SocketAddr) -> io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    tracing::info!("admin routes listening on {addr}");
+    loop {
+        match listener.accept().await {
+            Ok((socket, peer)) => {
+                serve_connection(socket, app.clone(), HttpVersion::Auto, ConnectInfo(peer))
+            }
+            Err(err) => accept_failed(err).await,
+        }
+    }
+}
+
+/// Loads the certificate and key, then keeps polling them so renewed
+/// certificates are picked up without a restart. The client CA, when
+/// configured, is reloaded along with them. With ACME configured, the files
+/// are obtained first when missing or about to expire.
+async fn load_tls(config: &TlsConfig, http: HttpVersion) -> io::Result<RustlsConfig> {
+    // Several rustls providers can be compiled in; ring is the one we ship.
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    if let Some(acme) = &config.acme {
+        acme::provision(config, acme).await?;
+    }
+
+    let rustls_config = match &config.client_ca_path {
+        Some(ca_path) => RustlsConfig::from_config(Arc::new(mutual_tls_config(config, ca_path)?)),
+        None => RustlsConfig::from_pem_file(&config.cert_path, &config.key_path).await?,
+    };
+    restrict_alpn(&rustls_config, http);
+
+    let reloaded = rustls_config.clone();
+    let config = config.clone();
+    tokio::spawn(async move {
+        let mut last_modified = modified(&config);
+        let mut interval = tokio::time::interval(Duration::from_secs(config.reload_secs));
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let current = modified(&config);
+            if current == last_modified {
+                continue;
+            }
+            let result = match &config.client_ca_path {
+                Some(ca_path) => mutual_tls_config(&config, ca_path)
+                    .map(|inner| reloaded.reload_from_config(Arc::new(inner))),
+                None => {
+                    reloaded
+                        .reload_from_pem_file(&config.cert_path, &config.key_path)
+                        .await
+                }
+            };
+            match result {
+                Ok(()) => {
+                    restrict_alpn(&reloaded, http);
+                    tracing::info!("reloaded TLS certificate");
+                    last_modified = current;
+                }
+                // Keep serving the previous certificate; a half-written
+                // renewal is retried on the next tick.
+                Err(err) => tracing::warn!("TLS certificate reload failed: {err}"),
+            }
+        }
+    });
+
+    Ok(rustls_config)
+}
+
+/// The rustls config axum-server would build from the PEM files, except that
+/// clients must present a certificate issued by a CA in `ca_path`.
+fn mutual_tls_config(config: &TlsConfig, ca_path: &Path) -> io::Result<rustls::ServerConfig> {
+    let mut roots = RootCertStore::empty();
+    for cert in CertificateDer::pem_file_iter(ca_path).map_err(io::Error::other)? {
+        roots
+            .add(cert.map_err(io::Error::other)?)
+            .map_err(io::Error::other)?;
+    }
+    let verifier = WebPkiClientVerifier::builder(Arc::new(roots))
+        .build()
+        .map_err(io::Error::other)?;
+
+    let certs = CertificateDer::pem_file_iter(&config.cert_path)
+        .map_err(io::Error::other)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(io::Error::other)?;
+    let key = PrivateKeyDer::from_pem_file(&config.key_path).map_err(io::Error::other)?;
+
+    let mut inner = rustls::ServerConfig::builder()
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(certs, key)
+        .map_err(io::Error::other)?;
+    inner.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(inner)
+}
+
+/// axum-server always offers both `h2` and `http/1.1`; narrow that down so
+/// clients don't negotiate a protocol the connection builder rejects.
+fn restrict_alpn(rustls_config: &RustlsConfig, http: HttpVersion) {
+    let protocol: &[u8] = match http {
+        HttpVersion::Auto => return,
+        HttpVersion::Http1 => b"http/1.1",
+        HttpVersion::Http2 => b"h2",
+    };
+    let mut inner = (*rustls_config.get_inner()).clone();
+    inner.alpn_protocols = vec![protocol.to_vec()];
+    rustls_config.reload_from_config(Arc::new(inner));
+}
+
+fn modified(config: &TlsConfig) -> Vec<Option<SystemTime>> {
+    [&config.cert_path, &config.key_path]
+        .into_iter()
+        .chain(&config.client_ca_path)
+        .map(|path| {
+            std::fs::metadata(path)
+                .and_then(|meta| meta.modified())
+                .ok()
+        })
+        .collect()
+}
+
+/// Listeners inherited through `LISTEN_FDS`, in the order systemd passed them.
+fn activated_listeners() -> io::Result<Vec<Listener>> {
+    sd_notify::listen_fds()?
+        .map(|fd| {
+            let listener = inherited_listener(fd)?;
+            tracing::info!("listening on socket passed by systemd (fd {fd})");
+            Ok(listener)
+        })
+        .collect()
+}
+
+fn inherited_listener(fd: RawFd) -> io::Result<Listener> {
+    // SAFETY: systemd hands over ownership of the descriptors it passes, and
+    // `listen_fds` only yields each of them once.
+    let unix = unsafe { std::os::unix::net::UnixListener::from_raw_fd(fd) };
+    unix.set_nonblocking(true)?;
+    if unix.local_addr().is_ok() {
+        return UnixListener::from_std(unix).map(Listener::Unix);
+    }
+
+    // SAFETY: the descriptor was just released by the Unix listener above.
+    let tcp = unsafe { std::net::TcpListener::from_raw_fd(unix.into_raw_fd()) };
+    TcpListener::from_std(tcp).map(Listener::Tcp)
+}
+
+async fn bind_listeners(config: &ServerConfig) -> io::Result<Vec<Listener>> {
+    let mut listeners = Vec::new();
+
+    for &addr in &config.listen_addrs {
+        // A dual-stack socket would also claim the port on IPv4.
+        let only_v6 = config
+            .listen_addrs
+            .iter()
+            .any(|other| other.is_ipv4() && other.port() == addr.port());
+        listeners.push(Listener::Tcp(bind_tcp(addr, config.socket, only_v6)?));
+        tracing::info!("listening on {addr}");
+    }
+
+    if let Some(path) = &config.unix_socket {
+        listeners.push(Listener::Unix(bind_unix(path, config.unix_socket_mode)?));
+        tracing::info!("listening on {}", path.display());
+    }
+
+    Ok(listeners)
+}
+
+/// What `TcpListener::bind` does, with a configurable backlog and optionally
+/// `SO_REUSEPORT`. IPv6 sockets accept IPv4 connections unless `only_v6`,
+/// whatever the system default.
+fn bind_tcp(addr: SocketAddr, options: SocketOptions, only_v6: bool) -> io::Result<TcpListener> {
+    let socket = match addr {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => {
+            let socket = TcpSocket::new_v6()?;
+            SockRef::from(&socket).set_only_v6(only_v6)?;
+            socket
+        }
+    };
+    socket.set_reuseaddr(true)?;
+    if options.reuseport {
+        socket.set_reuseport(true)?;
+    }
+    socket.bind(addr)?;
+    socket.listen(options.backlog)
+}
+
+/// Applies the per-connection options. A failure only costs the tuning, so
+/// the connection is served regardless.
+fn tune(stream: &TcpStream, options: SocketOptions) {
+    let result = stream
+        .set_nodelay(options.nodelay)
+        .and_then(|()| match options.keepalive_secs {
+            Some(secs) => SockRef::from(stream)
+                .set_tcp_keepalive(&TcpKeepalive::new().with_time(Duration::from_secs(secs))),
+            None => Ok(()),
+        });
+    if let Err(err) = result {
+        tracing::debug!("setting socket options failed: {err}");
+    }
+}
+
+/// Runs [`tune`] on connections accepted by axum-server, before the TLS
+/// handshake.
+#[derive(Debug, Clone, Copy)]
+struct Tuned(SocketOptions);
+
+impl<S> Accept<TcpStream, S> for Tuned {
+    type Stream = TcpStream;
+    type Service = S;
+    type Future = std::future::Ready<io::Result<(TcpStream, S)>>;
+
+    fn accept(&self, stream: TcpStream, service: S) -> Self::Future {
+        tune(&stream, self.0);
+        std::future::ready(Ok((stream, service)))
+    }
+}
+
+fn bind_unix(path: &Path, mode: u32) -> io::Result<UnixListener> {
+    // A socket file left behind by a previous run would make bind fail.
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+        _ => {}
+    }
+    let listener = UnixListener::bind(path)?;
+    std::fs::set_permissions(path, Permissions::from_mode(mode))?;
+    Ok(listener)
+}
+
+/// Accept errors such as running out of file descriptors are transient, so
+/// back off instead of shutting the listener down.
+async fn accept_failed(err: io::Error) {
+    tracing::warn!("accepting connection failed: {err}");
+    tokio::time::sleep(Duration::from_secs(1)).await;
+}
+
+fn serve_connection<I, E>(socket: I, app: Router, http: HttpVersion, extension: E)
+where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    E: Clone + Send + Sync + 'static,
+{
+    let io = TokioIo::new(socket);
+    // Inserted per request rather than layered onto the router, which would
+    // rebuild its whole middleware stack for every connection.
+    let app = app.map_request(move |mut request: Request<Incoming>| {
+        request.extensions_mut().insert(extension.clone());
+        request
+    });
+    let service = TowerToHyperService::new(app);
+    tokio::spawn(async move {
+        // The auto builder ignores `http1_only`/`http2_only` once upgrades
+        // (needed for WebSockets) are enabled, hence the dedicated builders.
+        let result = match http {
+            HttpVersion::Auto => {
+                auto::Builder::new(TokioExecutor::new())
+                    .serve_connection_with_upgrades(io, service)
+                    .await
+            }
+            HttpVersion::Http1 => http1::Builder::new()
+                .serve_connection(io, service)
+                .with_upgrades()
+                .await
+                .map_err(Into::into),
+            HttpVersion::Http2 => http2::Builder::new(TokioExecutor::new())
+                .serve_connection(io, service)
+                .await
+                .map_err(Into::into),
+        };
+        if let Err(err) = result {
+            tracing::debug!("connection failed: {err}");
+        }
+    });
+}
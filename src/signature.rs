@@ -0,0 +1,192 @@
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use ring::hmac;
+use tokio::sync::Mutex;
+
+use crate::HmacConfig;
+
+/// Verifies `X-Signature` headers: hex HMAC-SHA256 over
+/// `<METHOD>\n<path and query>\n<X-Signature-Timestamp>`.
+#[derive(Debug)]
+pub(crate) struct Signatures {
+    key: hmac::Key,
+    window_secs: u64,
+    /// Signatures accepted within the window, by their timestamp, so a
+    /// captured request can't be sent again.
+    seen: Mutex<HashMap<Vec<u8>, u64>>,
+}
+
+impl Signatures {
+    pub(crate) fn new(config: &HmacConfig) -> Signatures {
+        Signatures {
+            key: hmac::Key::new(hmac::HMAC_SHA256, config.secret.as_bytes()),
+            window_secs: config.window_secs,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) async fn verify(
+        &self,
+        method: &str,
+        path_and_query: &str,
+        timestamp: &str,
+        signature: &str,
+    ) -> Result<(), &'static str> {
+        let sent_at: u64 = timestamp.parse().map_err(|_| "malformed timestamp")?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        if now.abs_diff(sent_at) > self.window_secs {
+            return Err("timestamp outside the accepted window");
+        }
+
+        let signature = decode_hex(signature).ok_or("malformed signature")?;
+        let message = format!("{method}\n{path_and_query}\n{timestamp}");
+        hmac::verify(&self.key, message.as_bytes(), &signature)
+            .map_err(|_| "signature mismatch")?;
+
+        let mut seen = self.seen.lock().await;
+        seen.retain(|_, seen_at| now.abs_diff(*seen_at) <= self.window_secs);
+        if seen.insert(signature, sent_at).is_some() {
+            return Err("signature already used");
+        }
+        Ok(())
+    }
+}
+
+/// `None` on non-hex characters or an odd length.
+fn decode_hex(raw: &str) -> Option<Vec<u8>> {
+    (0..raw.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(raw.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "signing-secret";
+    const WINDOW_SECS: u64 = 300;
+
+    fn signatures() -> Signatures {
+        Signatures::new(&HmacConfig {
+            secret: SECRET.to_string(),
+            window_secs: WINDOW_SECS,
+        })
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    fn sign(secret: &str, path_and_query: &str, timestamp: u64) -> String {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+        let message = format!("GET\n{path_and_query}\n{timestamp}");
+        hmac::sign(&key, message.as_bytes())
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn valid_signature_is_accepted() {
+        let timestamp = now();
+        let signature = sign(SECRET, "/v1/current", timestamp);
+        let result = signatures()
+            .verify("GET", "/v1/current", &timestamp.to_string(), &signature)
+            .await;
+        assert_eq!(result, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn mismatched_signatures_are_rejected() {
+        let signatures = signatures();
+        let timestamp = now();
+        let wrong_secret = sign("other-secret", "/v1/current", timestamp);
+        let other_path = sign(SECRET, "/v1/current", timestamp);
+        for (path, signature) in [
+            ("/v1/current", wrong_secret.as_str()),
+            ("/v1/forecast", other_path.as_str()),
+        ] {
+            let result = signatures
+                .verify("GET", path, &timestamp.to_string(), signature)
+                .await;
+            assert_eq!(result, Err("signature mismatch"));
+        }
+        let result = signatures
+            .verify("POST", "/v1/current", &timestamp.to_string(), &other_path)
+            .await;
+        assert_eq!(result, Err("signature mismatch"));
+    }
+
+    #[tokio::test]
+    async fn replayed_signature_is_rejected() {
+        let signatures = signatures();
+        let timestamp = now();
+        let signature = sign(SECRET, "/v1/current", timestamp);
+        let timestamp = timestamp.to_string();
+        assert_eq!(
+            signatures
+                .verify("GET", "/v1/current", &timestamp, &signature)
+                .await,
+            Ok(())
+        );
+        assert_eq!(
+            signatures
+                .verify("GET", "/v1/current", &timestamp, &signature)
+                .await,
+            Err("signature already used")
+        );
+    }
+
+    #[tokio::test]
+    async fn clock_skew_within_the_window_is_tolerated() {
+        let signatures = signatures();
+        for timestamp in [now() - WINDOW_SECS + 10, now() + WINDOW_SECS - 10] {
+            let signature = sign(SECRET, "/v1/current", timestamp);
+            let result = signatures
+                .verify("GET", "/v1/current", &timestamp.to_string(), &signature)
+                .await;
+            assert_eq!(result, Ok(()));
+        }
+    }
+
+    #[tokio::test]
+    async fn timestamps_outside_the_window_are_rejected() {
+        let signatures = signatures();
+        for timestamp in [now() - WINDOW_SECS - 10, now() + WINDOW_SECS + 10] {
+            let signature = sign(SECRET, "/v1/current", timestamp);
+            let result = signatures
+                .verify("GET", "/v1/current", &timestamp.to_string(), &signature)
+                .await;
+            assert_eq!(result, Err("timestamp outside the accepted window"));
+        }
+    }
+
+    #[tokio::test]
+    async fn malformed_input_is_rejected() {
+        let signatures = signatures();
+        let timestamp = now().to_string();
+        assert_eq!(
+            signatures
+                .verify("GET", "/v1/current", "yesterday", "00")
+                .await,
+            Err("malformed timestamp")
+        );
+        assert_eq!(
+            signatures
+                .verify("GET", "/v1/current", &timestamp, "not hex")
+                .await,
+            Err("malformed signature")
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use std::{io, time::Duration};
+
+use base64::{prelude::BASE64_STANDARD, Engine};
+use chrono::Utc;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
+use tokio_native_tls::{native_tls, TlsConnector};
+
+use crate::{SmtpConfig, SmtpTls};
+
+/// Covers connecting, the handshake and the whole transaction.
+const SESSION_TIMEOUT: Duration = Duration::from_secs(60);
+/// Base64 body lines; RFC 2045 allows at most 76 characters.
+const LINE_LENGTH: usize = 76;
+
+/// A plain-text message.
+pub(crate) struct Message<'a> {
+    pub(crate) from: &'a str,
+    pub(crate) to: &'a [String],
+    pub(crate) subject: &'a str,
+    pub(crate) body: &'a str,
+}
+
+/// Delivers `message` through the server in `config`, authenticating with
+/// `AUTH PLAIN` when a username is set.
+pub(crate) async fn send(config: &SmtpConfig, message: &Message<'_>) -> io::Result<()> {
+    tokio::time::timeout(SESSION_TIMEOUT, session(config, message))
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "SMTP session timed out"))?
+}
+
+async fn session(config: &SmtpConfig, message: &Message<'_>) -> io::Result<()> {
+    let tcp = TcpStream::connect((config.host.as_str(), config.port)).await?;
+    // EHLO wants our name; an address literal needs no DNS.
+    let client = match tcp.local_addr()?.ip() {
+        std::net::IpAddr::V4(ip) => format!("[{ip}]"),
+        std::net::IpAddr::V6(ip) => format!("[IPv6:{ip}]"),
+    };
+    match config.tls {
+        SmtpTls::Implicit => {
+            let mut connection = Connection::new(upgrade(config, tcp).await?);
+            connection.reply(&[220]).await?;
+            connection
+                .command(&format!("EHLO {client}"), &[250])
+                .await?;
+            connection.deliver(config, message).await
+        }
+        SmtpTls::StartTls => {
+            let mut connection = Connection::new(tcp);
+            connection.reply(&[220]).await?;
+            connection
+                .command(&format!("EHLO {client}"), &[250])
+                .await?;
+            connection.command("STARTTLS", &[220]).await?;
+            let tls = upgrade(config, connection.stream.into_inner()).await?;
+            let mut connection = Connection::new(tls);
+            // The server forgets everything said before the handshake.
+            connection
+                .command(&format!("EHLO {client}"), &[250])
+                .await?;
+            connection.deliver(config, message).await
+        }
+        SmtpTls::None => {
+            let mut connection = Connection::new(tcp);
+            connection.reply(&[220]).await?;
+            connection
+                .command(&format!("EHLO {client}"), &[250])
+                .await?;
+            connection.deliver(config, message).await
+        }
+    }
+}
+
+async fn upgrade(
+    config: &SmtpConfig,
+    tcp: TcpStream,
+) -> io::Result<tokio_native_tls::TlsStream<TcpStream>> {
+    let connector = native_tls::TlsConnector::new().map_err(io::Error::other)?;
+    TlsConnector::from(connector)
+        .connect(&config.host, tcp)
+        .await
+        .map_err(io::Error::other)
+}
+
+struct Connection<S> {
+    stream: BufReader<S>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Connection<S> {
+    fn new(stream: S) -> Self {
+        Connection {
+            stream: BufReader::new(stream),
+        }
+    }
+
+    async fn deliver(&mut self, config: &SmtpConfig, message: &Message<'_>) -> io::Result<()> {
+        if let Some(username) = &config.username {
+            let password = config.password.as_deref().unwrap_or_default();
+            let credentials = BASE64_STANDARD.encode(format!("\0{username}\0{password}"));
+            self.command(&format!("AUTH PLAIN {credentials}"), &[235])
+                .await?;
+        }
+        self.command(&format!("MAIL FROM:<{}>", message.from), &[250])
+            .await?;
+        for recipient in message.to {
+            self.command(&format!("RCPT TO:<{recipient}>"), &[250, 251])
+                .await?;
+        }
+        self.command("DATA", &[354]).await?;
+        self.stream.write_all(message.to_mime().as_bytes()).await?;
+        self.command(".", &[250]).await?;
+        // The message is accepted; a failed goodbye changes nothing.
+        let _ = self.command("QUIT", &[221]).await;
+        Ok(())
+    }
+
+    async fn command(&mut self, line: &str, expected: &[u16]) -> io::Result<String> {
+        self.stream
+            .write_all(format!("{line}\r\n").as_bytes())
+            .await?;
+        self.stream.flush().await?;
+        self.reply(expected).await
+    }
+
+    /// Reads a possibly multiline reply and fails unless its code is one of
+    /// `expected`.
+    async fn reply(&mut self, expected: &[u16]) -> io::Result<String> {
+        let mut reply = String::new();
+        loop {
+            let mut line = String::new();
+            if self.stream.read_line(&mut line).await? == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "SMTP server closed the connection",
+                ));
+            }
+            reply.push_str(&line);
+            // `250-...` continues, `250 ...` ends the reply.
+            if line.as_bytes().get(3) != Some(&b'-') {
+                break;
+            }
+        }
+        let code = reply.get(..3).and_then(|code| code.parse().ok());
+        match code {
+            Some(code) if expected.contains(&code) => Ok(reply),
+            _ => Err(io::Error::other(format!(
+                "unexpected SMTP reply: {}",
+                reply.trim_end()
+            ))),
+        }
+    }
+}
+
+impl Message<'_> {
+    /// Headers and the base64 body, which keeps non-ASCII text intact and
+    /// never starts a line with the `.` that would end `DATA`.
+    fn to_mime(&self) -> String {
+        let body = BASE64_STANDARD.encode(self.body);
+        let lines: Vec<&str> = body
+            .as_bytes()
+            .chunks(LINE_LENGTH)
+            .map(|chunk| std::str::from_utf8(chunk).unwrap_or_default())
+            .collect();
+        format!(
+            "From: {}\r\nTo: {}\r\nSubject: =?UTF-8?B?{}?=\r\nDate: {}\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: base64\r\n\r\n{}\r\n",
+            self.from,
+            self.to.join(", "),
+            BASE64_STANDARD.encode(self.subject),
+            Utc::now().to_rfc2822(),
+            lines.join("\r\n"),
+        )
+    }
+}
@@ -0,0 +1,484 @@
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    hash::{DefaultHasher, Hash, Hasher},
+    sync::Arc,
+    time::Instant,
+};
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts},
+    middleware::Next,
+    response::Response,
+};
+use base64::{prelude::BASE64_STANDARD, Engine};
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::Value;
+use subtle::ConstantTimeEq;
+use tokio::sync::Mutex;
+
+use crate::{
+    cache::CachedEntry,
+    constants::{
+        API_KEY_HEADER, API_PREFIX, HEALTH_PATH, SIGNATURE_HEADER, SIGNATURE_TIMESTAMP_HEADER,
+    },
+    jwt::Jwt,
+    signature::Signatures,
+    AppConfig, AppError, BasicAuthConfig, Result, TenantConfig,
+};
+
+/// Tenants indexed by their key, with the bearer token validation, Basic auth
+/// login and request signing accepted instead of a key.
+#[derive(Debug)]
+pub(crate) struct Tenants {
+    by_key: HashMap<String, Tenant>,
+    jwt: Option<Jwt>,
+    basic_auth: Option<BasicAuthConfig>,
+    signatures: Option<Signatures>,
+    exempt_health: bool,
+}
+
+#[derive(Debug)]
+struct Tenant {
+    name: String,
+    config: TenantConfig,
+    bucket: Mutex<TokenBucket>,
+    usage: Mutex<Usage>,
+}
+
+/// Requests admitted on the current UTC day.
+#[derive(Debug)]
+struct Usage {
+    day: NaiveDate,
+    requests: u32,
+    last_seen: Option<DateTime<Utc>>,
+}
+
+/// A tenant's standing as reported by `/admin/clients`.
+#[derive(Debug, Serialize)]
+pub(crate) struct ClientUsage {
+    name: String,
+    requests_today: u32,
+    daily_quota: Option<u32>,
+    /// `None` for tenants without a quota.
+    remaining_today: Option<u32>,
+    /// RFC 3339, `None` when the key hasn't been used since startup.
+    last_seen: Option<String>,
+}
+
+/// The stations a request may read: its tenant's `stations`, attached by
+/// [`authorize`]. Every station when empty, as for token holders, signed
+/// requests, the Basic auth user and proxies without tenants.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Stations(Arc<[String]>);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Stations {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        _state: &S,
+    ) -> std::result::Result<Self, Infallible> {
+        Ok(parts
+            .extensions
+            .get::<Stations>()
+            .cloned()
+            .unwrap_or_default())
+    }
+}
+
+impl Stations {
+    /// The allowed station ids; empty when every station is.
+    pub(crate) fn ids(&self) -> &[String] {
+        &self.0
+    }
+
+    pub(crate) fn allows(&self, station: &str) -> bool {
+        self.0.is_empty() || self.0.iter().any(|allowed| allowed == station)
+    }
+
+    /// `current` with only the observations of allowed stations, or `None`
+    /// when none are left.
+    pub(crate) fn observations(&self, mut current: Value) -> Option<Value> {
+        if self.0.is_empty() {
+            return Some(current);
+        }
+        let observations = current.get_mut("observations")?.as_array_mut()?;
+        observations.retain(|observation| self.allows(station_id(observation)));
+        (!observations.is_empty()).then_some(current)
+    }
+
+    /// Like [`Stations::observations`], refusing the request when none are
+    /// left.
+    pub(crate) fn current(&self, current: Value) -> Result<Value> {
+        let refused = AppError::Forbidden(format!("station {}", station_ids(&current)));
+        self.observations(current).ok_or(refused)
+    }
+
+    /// [`Stations::current`] of a cached entry. The content hash is mixed
+    /// with the allowed stations, so representations and `ETag`s of
+    /// differently filtered payloads don't collide.
+    pub(crate) fn entry(&self, entry: CachedEntry) -> Result<CachedEntry> {
+        if self.0.is_empty() {
+            return Ok(entry);
+        }
+        let mut hasher = DefaultHasher::new();
+        (entry.content_hash, &self.0).hash(&mut hasher);
+        Ok(CachedEntry {
+            value: self.current(entry.value)?,
+            fetched_at: entry.fetched_at,
+            content_hash: hasher.finish(),
+        })
+    }
+
+    /// Refuses the request unless the first observation of `current`, which
+    /// the in-memory history follows, is of an allowed station.
+    #[cfg(feature = "history")]
+    pub(crate) fn first(&self, current: &Value) -> Result<()> {
+        let station = current
+            .get("observations")
+            .and_then(|observations| observations.get(0))
+            .map_or("", station_id);
+        if self.allows(station) {
+            Ok(())
+        } else {
+            Err(AppError::Forbidden(format!("station {station}")))
+        }
+    }
+}
+
+fn station_id(observation: &Value) -> &str {
+    observation
+        .get("stationID")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+}
+
+fn station_ids(current: &Value) -> String {
+    current
+        .get("observations")
+        .and_then(Value::as_array)
+        .map(|observations| {
+            observations
+                .iter()
+                .map(station_id)
+                .collect::<Vec<_>>()
+                .join(", ")
+        })
+        .unwrap_or_default()
+}
+
+/// Refills continuously, so a tenant can burst up to a minute's worth of
+/// requests after being idle.
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl Tenants {
+    pub(crate) fn new(config: &AppConfig, client: &Client) -> Tenants {
+        let by_key = config
+            .tenants
+            .iter()
+            .map(|(name, config)| {
+                let tenant = Tenant {
+                    name: name.clone(),
+                    config: config.clone(),
+                    bucket: Mutex::new(TokenBucket {
+                        tokens: config.rate_limit.unwrap_or_default().into(),
+                        updated_at: Instant::now(),
+                    }),
+                    usage: Mutex::new(Usage {
+                        day: Utc::now().date_naive(),
+                        requests: 0,
+                        last_seen: None,
+                    }),
+                };
+                (config.key.clone(), tenant)
+            })
+            .collect();
+
+        Tenants {
+            by_key,
+            jwt: config.jwt.as_ref().map(|jwt| Jwt::new(jwt, client)),
+            basic_auth: config.basic_auth.clone(),
+            signatures: config.hmac.as_ref().map(Signatures::new),
+            exempt_health: config.auth_exempt_health,
+        }
+    }
+
+    /// Today's usage of every tenant, by name.
+    pub(crate) async fn usage(&self) -> Vec<ClientUsage> {
+        let mut clients = Vec::with_capacity(self.by_key.len());
+        for tenant in self.by_key.values() {
+            let mut usage = tenant.usage.lock().await;
+            usage.roll_over(Utc::now().date_naive());
+            let daily_quota = tenant.config.daily_quota;
+            clients.push(ClientUsage {
+                name: tenant.name.clone(),
+                requests_today: usage.requests,
+                daily_quota,
+                remaining_today: daily_quota.map(|quota| quota.saturating_sub(usage.requests)),
+                last_seen: usage.last_seen.map(|at| at.to_rfc3339()),
+            });
+        }
+        clients.sort_by(|a, b| a.name.cmp(&b.name));
+        clients
+    }
+}
+
+impl Usage {
+    fn roll_over(&mut self, today: NaiveDate) {
+        if self.day != today {
+            self.day = today;
+            self.requests = 0;
+        }
+    }
+}
+
+/// The path with the `/v1` prefix removed, as endpoints are configured.
+fn unversioned(path: &str) -> &str {
+    path.strip_prefix(API_PREFIX)
+        .filter(|rest| rest.starts_with('/'))
+        .unwrap_or(path)
+}
+
+impl Tenant {
+    fn allows_endpoint(&self, path: &str) -> bool {
+        let path = unversioned(path);
+        self.config.endpoints.is_empty()
+            || self.config.endpoints.iter().any(|endpoint| {
+                path.strip_prefix(endpoint.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+            })
+    }
+
+    /// Counts a request against today's quota and the rate limit. The quota
+    /// is checked first, so requests refused for it don't drain the bucket.
+    async fn admit(&self) -> Result<()> {
+        let now = Utc::now();
+        let mut usage = self.usage.lock().await;
+        usage.roll_over(now.date_naive());
+        usage.last_seen = Some(now);
+
+        if self
+            .config
+            .daily_quota
+            .is_some_and(|quota| usage.requests >= quota)
+        {
+            let midnight = now
+                .date_naive()
+                .checked_add_days(Days::new(1))
+                .and_then(|day| day.and_hms_opt(0, 0, 0))
+                .map(|midnight| midnight.and_utc());
+            let retry_after = midnight.map_or(0, |midnight| (midnight - now).num_seconds());
+            return Err(AppError::QuotaExceeded(retry_after.max(1) as u64));
+        }
+        self.take().await.map_err(AppError::RateLimited)?;
+        usage.requests += 1;
+        Ok(())
+    }
+
+    /// Takes one request from the bucket, or returns the seconds until the
+    /// next one is available.
+    async fn take(&self) -> std::result::Result<(), u64> {
+        let Some(per_minute) = self.config.rate_limit else {
+            return Ok(());
+        };
+        let capacity = f64::from(per_minute);
+        let per_sec = capacity / 60.0;
+
+        let mut bucket = self.bucket.lock().await;
+        let now = Instant::now();
+        let refilled =
+            bucket.tokens + now.duration_since(bucket.updated_at).as_secs_f64() * per_sec;
+        bucket.tokens = refilled.min(capacity);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else if per_sec > 0.0 {
+            Err(((1.0 - bucket.tokens) / per_sec).ceil() as u64)
+        } else {
+            Err(60)
+        }
+    }
+}
+
+/// Rejects requests without a tenant key, valid bearer token, Basic auth
+/// login or request signature, outside the tenant's endpoints, or over its
+/// daily quota or rate limit, and attaches the tenant's [`Stations`] for the
+/// handlers to filter by. Token holders aren't restricted beyond what the
+/// identity provider grants, and the Basic auth user and signing scripts not
+/// at all. Health checks pass untouched when exempted, so probes don't need
+/// credentials or count against a limit.
+pub(crate) async fn authorize(
+    State(tenants): State<Arc<Tenants>>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response> {
+    if tenants.exempt_health && unversioned(request.uri().path()) == HEALTH_PATH {
+        return Ok(next.run(request).await);
+    }
+
+    if let Some(token) = bearer_token(&request) {
+        let jwt = tenants.jwt.as_ref().ok_or(AppError::Unauthorized)?;
+        let subject = jwt.verify(token).await.map_err(AppError::InvalidToken)?;
+        tracing::debug!(
+            "request by token subject {}",
+            subject.as_deref().unwrap_or("-")
+        );
+        return Ok(next.run(request).await);
+    }
+
+    if let Some(signature) = header_str(&request, SIGNATURE_HEADER) {
+        let signatures = tenants.signatures.as_ref().ok_or(AppError::Unauthorized)?;
+        let timestamp = header_str(&request, SIGNATURE_TIMESTAMP_HEADER).unwrap_or_default();
+        let path_and_query = request.uri().path_and_query().map_or("/", |pq| pq.as_str());
+        signatures
+            .verify(
+                request.method().as_str(),
+                path_and_query,
+                timestamp,
+                signature,
+            )
+            .await
+            .map_err(|reason| AppError::InvalidSignature(reason.to_string()))?;
+        return Ok(next.run(request).await);
+    }
+
+    if let Some((username, password)) = basic_credentials(&request) {
+        let basic_auth = tenants.basic_auth.as_ref().ok_or(AppError::Unauthorized)?;
+        if !basic_auth_matches(basic_auth, &username, &password) {
+            return Err(AppError::BasicAuthRequired);
+        }
+        return Ok(next.run(request).await);
+    }
+
+    let Some(key) = request.headers().get(API_KEY_HEADER) else {
+        return Err(if tenants.basic_auth.is_some() {
+            AppError::BasicAuthRequired
+        } else {
+            AppError::Unauthorized
+        });
+    };
+    let tenant = key
+        .to_str()
+        .ok()
+        .and_then(|key| tenants.by_key.get(key))
+        .ok_or(AppError::Unauthorized)?;
+
+    let path = request.uri().path();
+    if !tenant.allows_endpoint(path) {
+        return Err(AppError::Forbidden(path.to_string()));
+    }
+    tenant.admit().await?;
+    request
+        .extensions_mut()
+        .insert(Stations(tenant.config.stations.as_slice().into()));
+
+    tracing::debug!("request by tenant {}", tenant.name);
+    Ok(next.run(request).await)
+}
+
+fn header_str<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+    request.headers().get(name)?.to_str().ok()
+}
+
+fn bearer_token(request: &Request) -> Option<&str> {
+    header_str(request, header::AUTHORIZATION.as_str())?.strip_prefix("Bearer ")
+}
+
+fn basic_credentials(request: &Request) -> Option<(String, String)> {
+    let encoded = request
+        .headers()
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(BASE64_STANDARD.decode(encoded).ok()?).ok()?;
+    let (username, password) = decoded.split_once(':')?;
+    Some((username.to_string(), password.to_string()))
+}
+
+/// Compares both fields in constant time, without short-circuiting on the
+/// username, so response timing doesn't reveal how much of a guess matched.
+fn basic_auth_matches(config: &BasicAuthConfig, username: &str, password: &str) -> bool {
+    let username = config.username.as_bytes().ct_eq(username.as_bytes());
+    let password = config.password.as_bytes().ct_eq(password.as_bytes());
+    (username & password).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn stations(ids: &[&str]) -> Stations {
+        Stations(ids.iter().map(|id| id.to_string()).collect())
+    }
+
+    fn current() -> Value {
+        json!({
+            "observations": [
+                { "stationID": "KHOME1", "epoch": 1 },
+                { "stationID": "KNEIGHBOR2", "epoch": 1 },
+            ],
+        })
+    }
+
+    #[test]
+    fn keeps_only_allowed_observations() {
+        let filtered = stations(&["KNEIGHBOR2"]).current(current()).unwrap();
+        assert_eq!(
+            filtered["observations"],
+            json!([{ "stationID": "KNEIGHBOR2", "epoch": 1 }])
+        );
+    }
+
+    #[test]
+    fn unrestricted_requests_see_every_observation() {
+        assert_eq!(Stations::default().current(current()).unwrap(), current());
+    }
+
+    #[test]
+    fn refuses_when_no_observation_is_left() {
+        let err = stations(&["KELSEWHERE3"]).current(current()).unwrap_err();
+        assert!(matches!(err, AppError::Forbidden(_)), "{err:?}");
+        assert_eq!(
+            err.to_string(),
+            "station KHOME1, KNEIGHBOR2 is not allowed for this API key"
+        );
+    }
+
+    #[test]
+    fn filtered_entries_get_their_own_hash() {
+        let entry = CachedEntry::new(current());
+        let home = stations(&["KHOME1"]).entry(entry.clone()).unwrap();
+        let neighbor = stations(&["KNEIGHBOR2"]).entry(entry.clone()).unwrap();
+        assert_ne!(home.content_hash, entry.content_hash);
+        assert_ne!(home.content_hash, neighbor.content_hash);
+        assert_eq!(
+            Stations::default()
+                .entry(entry.clone())
+                .unwrap()
+                .content_hash,
+            entry.content_hash
+        );
+    }
+
+    #[cfg(feature = "history")]
+    #[test]
+    fn history_follows_the_first_observation() {
+        assert!(stations(&["KHOME1"]).first(&current()).is_ok());
+        assert!(stations(&["KNEIGHBOR2"]).first(&current()).is_err());
+    }
+}
@@ -0,0 +1,53 @@
+use chrono::{DateTime, SecondsFormat, TimeZone};
+use chrono_tz::Tz;
+use serde_json::Value;
+
+/// Rewrites every `epoch` value and every `…Utc` field (epoch seconds or
+/// RFC 3339 strings, alone or in arrays) to an ISO-8601 string in `tz`, e.g.
+/// `2024-05-01T14:00:00+02:00`. Values that are not timestamps are kept.
+pub fn localize(value: &mut Value, tz: Tz) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if key == "epoch" || key.ends_with("Utc") {
+                    localize_timestamps(value, tz);
+                } else {
+                    localize(value, tz);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(|value| localize(value, tz)),
+        _ => {}
+    }
+}
+
+fn localize_timestamps(value: &mut Value, tz: Tz) {
+    let local = match value {
+        Value::Array(values) => {
+            values
+                .iter_mut()
+                .for_each(|value| localize_timestamps(value, tz));
+            return;
+        }
+        Value::Number(epoch) => epoch.as_i64().and_then(|epoch| format_epoch(epoch, tz)),
+        Value::String(time) => DateTime::parse_from_rfc3339(time)
+            .ok()
+            .map(|time| format_local(&time.with_timezone(&tz))),
+        _ => None,
+    };
+
+    if let Some(local) = local {
+        *value = Value::String(local);
+    }
+}
+
+/// Formats epoch seconds as an ISO-8601 string in `tz`.
+pub fn format_epoch(epoch: i64, tz: Tz) -> Option<String> {
+    tz.timestamp_opt(epoch, 0)
+        .single()
+        .map(|time| format_local(&time))
+}
+
+fn format_local(time: &DateTime<Tz>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, false)
+}
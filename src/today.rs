@@ -0,0 +1,205 @@
+use std::collections::VecDeque;
+
+use chrono::{NaiveDate, TimeZone};
+use chrono_tz::Tz;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::timezone;
+
+/// Long enough to cover "today" in any timezone relative to the station.
+const RETENTION_SECS: i64 = 48 * 3600;
+const MAX_SAMPLES: usize = 4096;
+
+/// Recent `/current` observations, keyed by observation epoch, from which
+/// today's extremes are summarized.
+#[derive(Debug, Default)]
+pub struct DailyHistory {
+    samples: VecDeque<Sample>,
+}
+
+#[derive(Debug)]
+struct Sample {
+    epoch: i64,
+    local_time: String,
+    temp: Option<f64>,
+    gust: Option<f64>,
+    /// Upstream `precipTotal`, accumulated since the station's local midnight.
+    rain: Option<f64>,
+}
+
+/// Extremes seen across today's `/current` refreshes.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailySummary {
+    date: Option<String>,
+    pub(crate) samples: u32,
+    pub(crate) min_temp: Option<Extreme>,
+    pub(crate) max_temp: Option<Extreme>,
+    pub(crate) peak_gust: Option<Extreme>,
+    pub(crate) rain_total: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Extreme {
+    pub(crate) value: f64,
+    pub(crate) at: String,
+}
+
+impl DailyHistory {
+    /// Records the first observation of a refreshed `/current` payload.
+    pub fn record(&mut self, current: &Value) {
+        let Some(observation) = current.get("observations").and_then(|obs| obs.get(0)) else {
+            return;
+        };
+        let epoch = observation.get("epoch").and_then(Value::as_i64);
+        let local_time = observation.get("obsTimeLocal").and_then(Value::as_str);
+        let (Some(epoch), Some(local_time)) = (epoch, local_time) else {
+            return;
+        };
+
+        // Refreshes often return the same observation again.
+        if self.samples.back().is_some_and(|last| last.epoch >= epoch) {
+            return;
+        }
+
+        let metric = |key: &str| {
+            observation
+                .get("metric")
+                .and_then(|metric| metric.get(key))
+                .and_then(Value::as_f64)
+        };
+        self.samples.push_back(Sample {
+            epoch,
+            local_time: local_time.to_string(),
+            temp: metric("temp"),
+            gust: metric("windGust"),
+            rain: metric("precipTotal"),
+        });
+        while self
+            .samples
+            .front()
+            .is_some_and(|oldest| epoch - oldest.epoch > RETENTION_SECS)
+            || self.samples.len() > MAX_SAMPLES
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Summarizes the day of the latest observation. Without `tz` the day is
+    /// taken from the station's `obsTimeLocal`; with it, day boundaries are
+    /// midnight in `tz` and extreme times are reported in `tz`.
+    pub fn summary(&self, tz: Option<Tz>) -> DailySummary {
+        match tz {
+            None => self.station_summary(),
+            Some(tz) => self.zoned_summary(tz),
+        }
+    }
+
+    fn station_summary(&self) -> DailySummary {
+        let date = |sample: &Sample| {
+            sample
+                .local_time
+                .split(' ')
+                .next()
+                .unwrap_or(&sample.local_time)
+                .to_string()
+        };
+        let Some(today) = self.samples.back().map(date) else {
+            return DailySummary::default();
+        };
+
+        let mut summary = DailySummary {
+            date: Some(today.clone()),
+            ..DailySummary::default()
+        };
+        for sample in self.samples.iter().filter(|sample| date(sample) == today) {
+            summary.add(sample, &sample.local_time);
+            // `precipTotal` already accumulates since the station's midnight.
+            if let Some(rain) = sample.rain {
+                summary.rain_total = Some(summary.rain_total.map_or(rain, |total| total.max(rain)));
+            }
+        }
+        summary
+    }
+
+    fn zoned_summary(&self, tz: Tz) -> DailySummary {
+        let today = self
+            .samples
+            .back()
+            .and_then(|sample| zoned_date(sample, tz));
+        match today {
+            Some(today) => self.day_summary(today, tz),
+            None => DailySummary::default(),
+        }
+    }
+
+    /// Summarizes `day`, from midnight to midnight in `tz`, with extreme
+    /// times reported in `tz`. Days more than a day before the latest
+    /// observation have been forgotten.
+    pub fn day_summary(&self, day: NaiveDate, tz: Tz) -> DailySummary {
+        let date = |sample: &Sample| zoned_date(sample, tz);
+        let mut summary = DailySummary {
+            date: Some(day.to_string()),
+            ..DailySummary::default()
+        };
+        let mut previous_rain = None;
+        for sample in &self.samples {
+            let rain = sample.rain;
+            if date(sample) != Some(day) {
+                previous_rain = rain.or(previous_rain);
+                continue;
+            }
+
+            let at = timezone::format_epoch(sample.epoch, tz).unwrap_or_default();
+            summary.add(sample, &at);
+            // The upstream total resets at the station's midnight, which need
+            // not match `tz`, so sum the increments between samples instead.
+            // The first sample has no baseline and contributes nothing.
+            if let (Some(previous), Some(rain)) = (previous_rain, rain) {
+                let increment = if rain >= previous {
+                    rain - previous
+                } else {
+                    rain
+                };
+                summary.rain_total = Some(summary.rain_total.unwrap_or_default() + increment);
+            } else if rain.is_some() && summary.rain_total.is_none() {
+                summary.rain_total = Some(0.0);
+            }
+            previous_rain = rain.or(previous_rain);
+        }
+        summary.rain_total = summary
+            .rain_total
+            .map(|total| (total * 100.0).round() / 100.0);
+        summary
+    }
+}
+
+fn zoned_date(sample: &Sample, tz: Tz) -> Option<NaiveDate> {
+    Some(tz.timestamp_opt(sample.epoch, 0).single()?.date_naive())
+}
+
+impl DailySummary {
+    fn add(&mut self, sample: &Sample, at: &str) {
+        self.samples += 1;
+        if let Some(temp) = sample.temp {
+            update(&mut self.min_temp, temp, at, |new, old| new < old);
+            update(&mut self.max_temp, temp, at, |new, old| new > old);
+        }
+        if let Some(gust) = sample.gust {
+            update(&mut self.peak_gust, gust, at, |new, old| new > old);
+        }
+    }
+}
+
+fn update(extreme: &mut Option<Extreme>, value: f64, at: &str, replaces: fn(f64, f64) -> bool) {
+    if extreme
+        .as_ref()
+        .is_none_or(|current| replaces(value, current.value))
+    {
+        *extreme = Some(Extreme {
+            value,
+            at: at.to_string(),
+        });
+    }
+}
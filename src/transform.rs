@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::units::Units;
+
+/// A site-specific rewrite of a route's payload, listed under
+/// `[[routes."<path>"]]` in `TRANSFORMERS_FILE`. Transformers run in order
+/// after unit conversion and before the client's other query parameters.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "transformer", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Transformer {
+    /// Renames object keys at any depth, `from = "to"`.
+    Rename { fields: HashMap<String, String> },
+    /// Default unit system for the route; the `units` query parameter wins.
+    Units { units: Units },
+    /// Removes the station's `lat` and `lon`.
+    RedactCoordinates,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct TransformersFile {
+    #[serde(default)]
+    pub(crate) routes: HashMap<String, Vec<Transformer>>,
+}
+
+/// The route's default unit system. Conversion round-trips through the typed
+/// models, so it has to happen before keys are renamed or removed.
+pub(crate) fn units(transformers: &[Transformer]) -> Option<Units> {
+    transformers
+        .iter()
+        .rev()
+        .find_map(|transformer| match transformer {
+            Transformer::Units { units } => Some(*units),
+            _ => None,
+        })
+}
+
+pub(crate) fn apply(value: &mut Value, transformers: &[Transformer]) {
+    for transformer in transformers {
+        match transformer {
+            Transformer::Rename { fields } => rename(value, fields),
+            Transformer::Units { .. } => {}
+            Transformer::RedactCoordinates => remove_keys(value, &["lat", "lon"]),
+        }
+    }
+}
+
+fn rename(value: &mut Value, fields: &HashMap<String, String>) {
+    match value {
+        Value::Object(object) => {
+            *object = std::mem::take(object)
+                .into_iter()
+                .map(|(key, mut nested)| {
+                    rename(&mut nested, fields);
+                    (fields.get(&key).cloned().unwrap_or(key), nested)
+                })
+                .collect();
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| rename(item, fields)),
+        _ => {}
+    }
+}
+
+fn remove_keys(value: &mut Value, keys: &[&str]) {
+    match value {
+        Value::Object(object) => {
+            object.retain(|key, _| !keys.contains(&key.as_str()));
+            object
+                .values_mut()
+                .for_each(|nested| remove_keys(nested, keys));
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| remove_keys(item, keys)),
+        _ => {}
+    }
+}
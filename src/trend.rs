@@ -0,0 +1,108 @@
+use std::collections::VecDeque;
+
+use serde::Serialize;
+use serde_json::Value;
+
+const HOUR_SECS: i64 = 3600;
+/// Samples older than this are dropped; a little over three hours so the 3h
+/// change can still find a baseline sample.
+const RETENTION_SECS: i64 = 3 * HOUR_SECS + 15 * 60;
+const MAX_SAMPLES: usize = 1024;
+/// A change smaller than this over three hours counts as steady (hPa).
+const STEADY_THRESHOLD_HPA: f64 = 1.0;
+
+/// Recent pressure readings from `/current` refreshes, keyed by observation epoch.
+#[derive(Debug, Default)]
+pub struct PressureHistory {
+    samples: VecDeque<(i64, f64)>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PressureTrend {
+    pressure: Option<f64>,
+    change_1h: Option<f64>,
+    change_3h: Option<f64>,
+    tendency: Option<Tendency>,
+    samples: usize,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tendency {
+    Rising,
+    Steady,
+    Falling,
+}
+
+impl PressureHistory {
+    pub fn record(&mut self, current: &Value) {
+        let Some(observation) = current.get("observations").and_then(|obs| obs.get(0)) else {
+            return;
+        };
+        let epoch = observation.get("epoch").and_then(Value::as_i64);
+        let pressure = observation
+            .get("metric")
+            .and_then(|metric| metric.get("pressure"))
+            .and_then(Value::as_f64);
+        let (Some(epoch), Some(pressure)) = (epoch, pressure) else {
+            return;
+        };
+
+        // Refreshes often return the same observation again.
+        if self.samples.back().is_some_and(|(last, _)| *last >= epoch) {
+            return;
+        }
+
+        self.samples.push_back((epoch, pressure));
+        while self
+            .samples
+            .front()
+            .is_some_and(|(oldest, _)| epoch - oldest > RETENTION_SECS)
+            || self.samples.len() > MAX_SAMPLES
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    pub fn trend(&self) -> PressureTrend {
+        let change_1h = self.change_over(HOUR_SECS);
+        let change_3h = self.change_over(3 * HOUR_SECS);
+
+        // Without three hours of history, extrapolate the hourly change.
+        let tendency = change_3h
+            .or(change_1h.map(|change| change * 3.0))
+            .map(|change| {
+                if change >= STEADY_THRESHOLD_HPA {
+                    Tendency::Rising
+                } else if change <= -STEADY_THRESHOLD_HPA {
+                    Tendency::Falling
+                } else {
+                    Tendency::Steady
+                }
+            });
+
+        PressureTrend {
+            pressure: self.samples.back().map(|(_, pressure)| *pressure),
+            change_1h,
+            change_3h,
+            tendency,
+            samples: self.samples.len(),
+        }
+    }
+
+    /// Difference between the latest sample and the newest sample at least
+    /// `window` seconds older than it. Requires the baseline to be no more than
+    /// a quarter window further back, so a long gap is not mistaken for a trend.
+    fn change_over(&self, window: i64) -> Option<f64> {
+        let (latest_epoch, latest) = *self.samples.back()?;
+        let (baseline_epoch, baseline) = self
+            .samples
+            .iter()
+            .rev()
+            .find(|(epoch, _)| latest_epoch - epoch >= window)?;
+
+        (latest_epoch - baseline_epoch <= window + window / 4)
+            .then(|| ((latest - baseline) * 10.0).round() / 10.0)
+    }
+}
@@ -0,0 +1,142 @@
+use serde::Deserialize;
+use serde_json::{Number, Value};
+use utoipa::ToSchema;
+
+use crate::models::{CurrentConditions, DailyForecast, Measurements};
+
+/// Unit systems using the same codes as the weather.com API (`m`, `e`, `h`).
+/// Upstream data is always fetched in metric and converted on the way out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+pub enum Units {
+    #[serde(rename = "m", alias = "metric")]
+    Metric,
+    #[serde(rename = "e", alias = "imperial")]
+    Imperial,
+    #[serde(rename = "h", alias = "hybrid")]
+    Hybrid,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Quantity {
+    Temperature,
+    Speed,
+    Pressure,
+    Precipitation,
+    Snow,
+    Elevation,
+}
+
+impl Units {
+    /// Returns `None` when the quantity is already expressed in metric for this unit system.
+    fn convert(self, quantity: Quantity, metric: f64) -> Option<f64> {
+        let (converted, decimals) = match (self, quantity) {
+            (Units::Imperial, Quantity::Temperature) => (metric * 9.0 / 5.0 + 32.0, 1),
+            (Units::Imperial | Units::Hybrid, Quantity::Speed) => (metric / 1.609_344, 1),
+            (Units::Imperial, Quantity::Pressure) => (metric * 0.029_529_983, 2),
+            (Units::Imperial, Quantity::Precipitation) => (metric / 25.4, 2),
+            (Units::Imperial, Quantity::Snow) => (metric / 2.54, 1),
+            (Units::Imperial, Quantity::Elevation) => (metric * 3.280_84, 0),
+            _ => return None,
+        };
+        Some(round(converted, decimals))
+    }
+
+    fn convert_number(self, quantity: Quantity, number: &mut Option<Number>) {
+        if let Some(converted) = number
+            .as_ref()
+            .and_then(Number::as_f64)
+            .and_then(|metric| self.convert(quantity, metric))
+            .and_then(Number::from_f64)
+        {
+            *number = Some(converted);
+        }
+    }
+
+    fn convert_series(self, quantity: Quantity, series: &mut [Option<Number>]) {
+        series
+            .iter_mut()
+            .for_each(|number| self.convert_number(quantity, number));
+    }
+}
+
+impl Units {
+    fn convert_measurements(self, measurements: &mut Measurements) {
+        let fields = [
+            (&mut measurements.temp, Quantity::Temperature),
+            (&mut measurements.heat_index, Quantity::Temperature),
+            (&mut measurements.dewpt, Quantity::Temperature),
+            (&mut measurements.wind_chill, Quantity::Temperature),
+            (&mut measurements.apparent_temp, Quantity::Temperature),
+            (&mut measurements.wind_speed, Quantity::Speed),
+            (&mut measurements.wind_gust, Quantity::Speed),
+            (&mut measurements.pressure, Quantity::Pressure),
+            (&mut measurements.precip_rate, Quantity::Precipitation),
+            (&mut measurements.precip_total, Quantity::Precipitation),
+            (&mut measurements.elev, Quantity::Elevation),
+        ];
+        for (number, quantity) in fields {
+            self.convert_number(quantity, number);
+        }
+    }
+}
+
+/// Converts a metric `/v2/pws/observations/current` payload, moving each
+/// observation's `metric` object to the key upstream would use for `units`.
+/// Payloads that don't match [`CurrentConditions`] are left unchanged.
+pub fn convert_current(value: &mut Value, units: Units) {
+    if units == Units::Metric {
+        return;
+    }
+    let Ok(mut current) = CurrentConditions::deserialize(&*value) else {
+        return;
+    };
+
+    for observation in &mut current.observations {
+        if let Some(mut measurements) = observation.metric.take() {
+            units.convert_measurements(&mut measurements);
+            match units {
+                Units::Imperial => observation.imperial = Some(measurements),
+                Units::Hybrid => observation.uk_hybrid = Some(measurements),
+                Units::Metric => observation.metric = Some(measurements),
+            }
+        }
+    }
+
+    if let Ok(converted) = serde_json::to_value(&current) {
+        *value = converted;
+    }
+}
+
+/// Converts a metric `/v3/wx/forecast/daily` payload. Payloads that don't
+/// match [`DailyForecast`] are left unchanged.
+pub fn convert_forecast(value: &mut Value, units: Units) {
+    if units == Units::Metric {
+        return;
+    }
+    let Ok(mut forecast) = DailyForecast::deserialize(&*value) else {
+        return;
+    };
+
+    units.convert_series(Quantity::Temperature, &mut forecast.temperature_max);
+    units.convert_series(Quantity::Temperature, &mut forecast.temperature_min);
+    units.convert_series(Quantity::Precipitation, &mut forecast.qpf);
+    units.convert_series(Quantity::Snow, &mut forecast.qpf_snow);
+
+    for daypart in &mut forecast.daypart {
+        units.convert_series(Quantity::Temperature, &mut daypart.temperature);
+        units.convert_series(Quantity::Temperature, &mut daypart.temperature_heat_index);
+        units.convert_series(Quantity::Temperature, &mut daypart.temperature_wind_chill);
+        units.convert_series(Quantity::Speed, &mut daypart.wind_speed);
+        units.convert_series(Quantity::Precipitation, &mut daypart.qpf);
+        units.convert_series(Quantity::Snow, &mut daypart.qpf_snow);
+    }
+
+    if let Ok(converted) = serde_json::to_value(&forecast) {
+        *value = converted;
+    }
+}
+
+fn round(value: f64, decimals: i32) -> f64 {
+    let factor = 10f64.powi(decimals);
+    (value * factor).round() / factor
+}
@@ -0,0 +1,241 @@
+use std::{collections::HashMap, time::Duration};
+
+use axum::{
+    extract::{Query, RawQuery, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Form, Json, Router,
+};
+use chrono::{NaiveDateTime, Utc};
+use serde_json::{json, Value};
+use subtle::ConstantTimeEq;
+
+use crate::{
+    cache, calibration,
+    constants::{ECOWITT_PATH, UPLOAD_PATH},
+    derived, quality,
+    tenants::Stations,
+    AppError, AppState, Result, UploadConfig,
+};
+
+const FORWARD_URL: &str =
+    "https://weatherstation.wunderground.com/weatherstation/updateweatherstation.php";
+/// Rapid-fire uploads (`realtime=1`) go to a separate host.
+const RAPID_FIRE_URL: &str =
+    "https://rtupdate.wunderground.com/weatherstation/updateweatherstation.php";
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(10);
+/// Stations send this for a sensor they don't have or couldn't read.
+const MISSING: f64 = -9999.0;
+/// Ecowitt fields named differently from their Wunderground counterparts;
+/// the rest share names.
+const ECOWITT_FIELDS: [(&str, &str); 2] = [("baromrelin", "baromin"), ("rainratein", "rainin")];
+
+/// The Wunderground station upload endpoint and the Ecowitt custom server
+/// endpoint, for pointing a station at the proxy. Stations can't send API
+/// keys, so uploads are checked against `UPLOAD_PASSWORD` or
+/// `ECOWITT_PASSKEY` instead of the tenant credentials.
+pub(crate) fn router(state: AppState) -> Router {
+    Router::new()
+        .route(UPLOAD_PATH, get(update))
+        // Gateways are set up with and without the trailing slash.
+        .route(ECOWITT_PATH, post(ecowitt))
+        .route(&format!("{ECOWITT_PATH}/"), post(ecowitt))
+        .with_state(state)
+}
+
+/// Answers like Wunderground does, so station firmware is satisfied.
+async fn update(
+    State(state): State<AppState>,
+    Query(params): Query<HashMap<String, String>>,
+    RawQuery(query): RawQuery,
+) -> Result<Response> {
+    let config = state
+        .config
+        .upload
+        .as_ref()
+        .ok_or(AppError::NotConfigured("UPLOAD_PASSWORD"))?;
+    // Firmware differs in the case of the parameter names.
+    let params: HashMap<String, String> = params
+        .into_iter()
+        .map(|(name, value)| (name.to_lowercase(), value))
+        .collect();
+    if !authorized(config, &params) {
+        return Ok((
+            StatusCode::UNAUTHORIZED,
+            "INVALIDPASSWORDID|Password or key and/or id are incorrect\n",
+        )
+            .into_response());
+    }
+    let Some(mut observation) = observation(&params) else {
+        return Err(AppError::InvalidQuery(
+            "dateutc must be `now` or `YYYY-MM-DD HH:MM:SS`".to_string(),
+        ));
+    };
+    calibration::apply(&state.config.calibration, &mut observation);
+    quality::check(&state, &mut observation).await;
+    *state.local_observation.write().await = Some(observation);
+    cache::publish_local(&state).await;
+
+    if config.forward {
+        let url = if params.get("realtime").is_some_and(|flag| flag == "1") {
+            RAPID_FIRE_URL
+        } else {
+            FORWARD_URL
+        };
+        tokio::spawn(forward(
+            state.client.clone(),
+            format!("{url}?{}", query.unwrap_or_default()),
+        ));
+    }
+    Ok("success\n".into_response())
+}
+
+/// Ecowitt gateways post their readings as a form in imperial units. The
+/// gateway ignores the answer.
+async fn ecowitt(
+    State(state): State<AppState>,
+    Form(fields): Form<HashMap<String, String>>,
+) -> Result<StatusCode> {
+    let config = state
+        .config
+        .ecowitt
+        .as_ref()
+        .ok_or(AppError::NotConfigured("ECOWITT_ENABLED"))?;
+    let passkey = fields
+        .get("PASSKEY")
+        .map_or(&[][..], |passkey| passkey.as_bytes());
+    if !bool::from(config.passkey.as_bytes().ct_eq(passkey)) {
+        return Ok(StatusCode::UNAUTHORIZED);
+    }
+    let mut params: HashMap<String, String> = fields
+        .into_iter()
+        .map(|(name, value)| {
+            let name = name.to_lowercase();
+            match ECOWITT_FIELDS.iter().find(|(ecowitt, _)| *ecowitt == name) {
+                Some((_, wunderground)) => (wunderground.to_string(), value),
+                None => (name, value),
+            }
+        })
+        .collect();
+    params.insert("id".to_string(), config.station_id.clone());
+    if let Some(station_type) = params.get("stationtype").cloned() {
+        params.insert("softwaretype".to_string(), station_type);
+    }
+    let mut observation = observation(&params).ok_or_else(|| {
+        AppError::InvalidQuery("dateutc must be `now` or `YYYY-MM-DD HH:MM:SS`".to_string())
+    })?;
+    calibration::apply(&state.config.calibration, &mut observation);
+    quality::check(&state, &mut observation).await;
+    *state.local_observation.write().await = Some(observation);
+    cache::publish_local(&state).await;
+    Ok(StatusCode::OK)
+}
+
+fn authorized(config: &UploadConfig, params: &HashMap<String, String>) -> bool {
+    let id_matches = config
+        .station_id
+        .as_ref()
+        .is_none_or(|station_id| params.get("id") == Some(station_id));
+    let password = params
+        .get("password")
+        .map_or(&[][..], |password| password.as_bytes());
+    id_matches && bool::from(config.password.as_bytes().ct_eq(password))
+}
+
+/// Relays the upload as received; Wunderground's answer only matters for the
+/// log.
+async fn forward(client: reqwest::Client, url: String) {
+    let response = client
+        .get(&url)
+        .timeout(FORWARD_TIMEOUT)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+    if let Err(err) = response {
+        tracing::warn!("forwarding station upload failed: {err}");
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/current/local",
+    responses(
+        (status = 200, description = "The latest reading uploaded by the local station, through the Wunderground upload protocol or an Ecowitt gateway, shaped like `/current` with metric values; the dew point is computed when not reported; upstream is not contacted", body = Object),
+        (status = 404, description = "Neither receiver is configured, or no reading has been uploaded yet", body = String, content_type = "text/plain"),
+    )
+)]
+pub(crate) async fn current_local(
+    State(state): State<AppState>,
+    stations: Stations,
+) -> Result<Json<Value>> {
+    if state.config.upload.is_none() && state.config.ecowitt.is_none() {
+        return Err(AppError::NotConfigured(
+            "UPLOAD_PASSWORD or ECOWITT_ENABLED",
+        ));
+    }
+    state
+        .local_observation
+        .read()
+        .await
+        .clone()
+        .ok_or(AppError::NoUpload)
+        .and_then(|local| stations.current(local))
+        .map(Json)
+}
+
+/// A `/current` payload from the upload parameters, converted from imperial
+/// units. `None` when `dateutc` can't be read.
+fn observation(params: &HashMap<String, String>) -> Option<Value> {
+    let time = match params.get("dateutc").map(String::as_str) {
+        None | Some("now") => Utc::now(),
+        Some(raw) => NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M:%S")
+            .ok()?
+            .and_utc(),
+    };
+    let number = |name: &str| {
+        params
+            .get(name)
+            .and_then(|raw| raw.parse::<f64>().ok())
+            .filter(|value| value.is_finite() && *value != MISSING)
+    };
+    let celsius = |name: &str| number(name).map(|f| tenths((f - 32.0) * 5.0 / 9.0));
+    let kmh = |name: &str| number(name).map(|mph| tenths(mph * 1.609_344));
+    let mm = |name: &str| number(name).map(|inches| hundredths(inches * 25.4));
+    let temp = celsius("tempf");
+    let humidity = number("humidity");
+    let dew_point =
+        celsius("dewptf").or_else(|| Some(tenths(derived::dew_point(temp?, humidity?))));
+
+    Some(json!({
+        "observations": [{
+            "stationID": params.get("id"),
+            "obsTimeUtc": time.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            "epoch": time.timestamp(),
+            "softwareType": params.get("softwaretype"),
+            "solarRadiation": number("solarradiation"),
+            "uv": number("uv"),
+            "winddir": number("winddir"),
+            "humidity": humidity,
+            "metric": {
+                "temp": temp,
+                "heatIndex": celsius("heatindexf").or(temp),
+                "dewpt": dew_point,
+                "windChill": celsius("windchillf").or(temp),
+                "windSpeed": kmh("windspeedmph"),
+                "windGust": kmh("windgustmph"),
+                "pressure": number("baromin").map(|inhg| hundredths(inhg * 33.863_886)),
+                "precipRate": mm("rainin"),
+                "precipTotal": mm("dailyrainin"),
+            },
+        }],
+    }))
+}
+
+fn tenths(value: f64) -> f64 {
+    (value * 10.0).round() / 10.0
+}
+
+fn hundredths(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
@@ -0,0 +1,168 @@
+use serde::Serialize;
+use serde_json::Value;
+
+/// Below this wind speed (km/h) a clear night cools plants and glass well
+/// under the forecast air temperature.
+const CALM_WIND: f64 = 8.0;
+/// Above this wind speed (km/h) mixing keeps surfaces near air temperature.
+const WINDY: f64 = 20.0;
+/// At or below this dew point (°C) no dew forms to release heat, so frost
+/// sets in sooner.
+const DRY_DEW_POINT: f64 = 0.0;
+/// At or above this dew point (°C) sweat and leaves barely cool by
+/// evaporation.
+const HUMID_DEW_POINT: f64 = 21.0;
+
+/// Overnight minimum (°C) at or below which each frost risk starts.
+const FROST_THRESHOLDS: [(f64, Risk); 3] =
+    [(0.0, Risk::High), (2.0, Risk::Moderate), (4.0, Risk::Low)];
+/// Daytime maximum or heat index (°C) at or above which each heat risk
+/// starts.
+const HEAT_THRESHOLDS: [(f64, Risk); 3] = [
+    (35.0, Risk::High),
+    (32.0, Risk::Moderate),
+    (28.0, Risk::Low),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Risk {
+    None,
+    Low,
+    Moderate,
+    High,
+}
+
+impl Risk {
+    fn raised(self) -> Risk {
+        match self {
+            Risk::None => Risk::None,
+            Risk::Low => Risk::Moderate,
+            Risk::Moderate | Risk::High => Risk::High,
+        }
+    }
+
+    fn lowered(self) -> Risk {
+        match self {
+            Risk::None | Risk::Low => Risk::None,
+            Risk::Moderate => Risk::Low,
+            Risk::High => Risk::Moderate,
+        }
+    }
+}
+
+/// Frost and heat risk for the station, from its current observation and
+/// the forecast for its location.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalWarnings {
+    station: Option<String>,
+    observed_at: Option<String>,
+    frost: FrostRisk,
+    heat: HeatRisk,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrostRisk {
+    risk: Risk,
+    overnight_min: Option<f64>,
+    dew_point: Option<f64>,
+    wind_speed: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeatRisk {
+    risk: Risk,
+    forecast_max: Option<f64>,
+    heat_index: Option<f64>,
+    dew_point: Option<f64>,
+}
+
+/// Classifies frost risk from tonight's forecast minimum, raised a level on
+/// calm, dry nights and lowered on windy ones, and heat risk from the higher
+/// of today's forecast maximum and the current heat index, raised a level
+/// when the air is humid. Missing inputs leave the risk at `none`.
+pub fn assess(current: &Value, forecast: Option<&Value>) -> LocalWarnings {
+    let observation = current.get("observations").and_then(|obs| obs.get(0));
+    let text = |key: &str| {
+        observation
+            .and_then(|observation| observation.get(key))
+            .and_then(Value::as_str)
+            .map(String::from)
+    };
+    let metric = |key: &str| {
+        observation
+            .and_then(|observation| observation.get("metric"))
+            .and_then(|metric| metric.get(key))
+            .and_then(Value::as_f64)
+    };
+    let dew_point = metric("dewpt");
+    let wind_speed = metric("windSpeed");
+    let heat_index = metric("heatIndex").or_else(|| metric("temp"));
+
+    let overnight_min = forecast.and_then(|forecast| {
+        daypart_temperature(forecast, "N").or_else(|| first(forecast, "temperatureMin"))
+    });
+    let mut frost = FROST_THRESHOLDS
+        .iter()
+        .find(|(threshold, _)| overnight_min.is_some_and(|min| min <= *threshold))
+        .map_or(Risk::None, |(_, risk)| *risk);
+    if let (Some(dew_point), Some(wind_speed)) = (dew_point, wind_speed) {
+        if wind_speed < CALM_WIND && dew_point <= DRY_DEW_POINT {
+            frost = frost.raised();
+        }
+    }
+    if wind_speed.is_some_and(|wind_speed| wind_speed >= WINDY) {
+        frost = frost.lowered();
+    }
+
+    // Today's daytime part and maximum go null once the day is mostly over.
+    let forecast_max = forecast.and_then(|forecast| {
+        daypart_temperature(forecast, "D").or_else(|| first(forecast, "temperatureMax"))
+    });
+    let hottest = forecast_max.into_iter().chain(heat_index).reduce(f64::max);
+    let mut heat = HEAT_THRESHOLDS
+        .iter()
+        .find(|(threshold, _)| hottest.is_some_and(|hottest| hottest >= *threshold))
+        .map_or(Risk::None, |(_, risk)| *risk);
+    if dew_point.is_some_and(|dew_point| dew_point >= HUMID_DEW_POINT) {
+        heat = heat.raised();
+    }
+
+    LocalWarnings {
+        station: text("stationID"),
+        observed_at: text("obsTimeUtc"),
+        frost: FrostRisk {
+            risk: frost,
+            overnight_min,
+            dew_point,
+            wind_speed,
+        },
+        heat: HeatRisk {
+            risk: heat,
+            forecast_max,
+            heat_index,
+            dew_point,
+        },
+    }
+}
+
+/// The temperature of the first upcoming day (`D`) or night (`N`) part.
+fn daypart_temperature(forecast: &Value, day_or_night: &str) -> Option<f64> {
+    let daypart = forecast.get("daypart")?.get(0)?;
+    let parts = daypart.get("dayOrNight")?.as_array()?;
+    let temperatures = daypart.get("temperature")?.as_array()?;
+    // Only the first day and night are "today" and "tonight".
+    parts
+        .iter()
+        .zip(temperatures)
+        .take(2)
+        .find(|(part, _)| part.as_str() == Some(day_or_night))
+        .and_then(|(_, temperature)| temperature.as_f64())
+}
+
+fn first(forecast: &Value, field: &str) -> Option<f64> {
+    forecast.get(field)?.get(0)?.as_f64()
+}
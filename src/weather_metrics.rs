@@ -0,0 +1,114 @@
+use std::fmt::Write;
+
+use serde_json::Value;
+
+/// Gauge name, help text, and the dot path of the value inside an observation.
+const GAUGES: &[(&str, &str, &str)] = &[
+    ("pws_temperature_celsius", "Air temperature.", "metric.temp"),
+    ("pws_dew_point_celsius", "Dew point.", "metric.dewpt"),
+    ("pws_heat_index_celsius", "Heat index.", "metric.heatIndex"),
+    ("pws_wind_chill_celsius", "Wind chill.", "metric.windChill"),
+    ("pws_humidity_percent", "Relative humidity.", "humidity"),
+    (
+        "pws_wind_speed_kph",
+        "Sustained wind speed.",
+        "metric.windSpeed",
+    ),
+    ("pws_wind_gust_kph", "Wind gust speed.", "metric.windGust"),
+    ("pws_wind_direction_degrees", "Wind direction.", "winddir"),
+    (
+        "pws_pressure_hpa",
+        "Barometric pressure.",
+        "metric.pressure",
+    ),
+    (
+        "pws_precipitation_rate_mm_per_hour",
+        "Precipitation rate.",
+        "metric.precipRate",
+    ),
+    (
+        "pws_precipitation_total_mm",
+        "Precipitation since local midnight.",
+        "metric.precipTotal",
+    ),
+    (
+        "pws_solar_radiation_watts_per_square_meter",
+        "Solar radiation.",
+        "solarRadiation",
+    ),
+    ("pws_uv_index", "UV index.", "uv"),
+    (
+        "pws_observation_timestamp_seconds",
+        "Unix time of the observation.",
+        "epoch",
+    ),
+];
+
+/// A gauge with its value for every station reporting it.
+pub(crate) struct Gauge<'a> {
+    pub(crate) name: &'static str,
+    help: &'static str,
+    pub(crate) samples: Vec<(&'a str, f64)>,
+}
+
+/// The gauges of the observations in a cached `/current` payload. Values the
+/// station does not report are omitted, and gauges no station reports.
+pub(crate) fn gauges(current: &Value) -> Vec<Gauge<'_>> {
+    let observations: Vec<&Value> = current
+        .get("observations")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .collect();
+
+    GAUGES
+        .iter()
+        .map(|(name, help, path)| Gauge {
+            name,
+            help,
+            samples: observations
+                .iter()
+                .filter_map(|observation| {
+                    let station = observation.get("stationID").and_then(Value::as_str)?;
+                    let value = path
+                        .split('.')
+                        .try_fold(*observation, |value, key| value.get(key))?
+                        .as_f64()?;
+                    Some((station, value))
+                })
+                .collect(),
+        })
+        .filter(|gauge| !gauge.samples.is_empty())
+        .collect()
+}
+
+/// Renders the observations of a cached `/current` payload in the Prometheus
+/// text exposition format, one series per station.
+pub fn render(current: &Value) -> String {
+    let mut output = String::new();
+    for Gauge {
+        name,
+        help,
+        samples,
+    } in gauges(current)
+    {
+        let _ = writeln!(output, "# HELP {name} {help}");
+        let _ = writeln!(output, "# TYPE {name} gauge");
+        for (station, value) in samples {
+            let _ = writeln!(
+                output,
+                "{name}{{station=\"{}\"}} {value}",
+                escape_label(station)
+            );
+        }
+    }
+
+    output
+}
+
+pub(crate) fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}